@@ -0,0 +1,127 @@
+//! Offscreen frame capture: record exactly what a channel renders, for documentation, archival,
+//! or restreaming. `Show::render` reads the default framebuffer back with `glReadPixels` after
+//! drawing each frame and hands the raw RGBA bytes off to a dedicated encoder thread over a
+//! channel (see `spawn`), so PNG encoding or disk I/O never blocks the render loop.
+
+use anyhow::Result;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use tunnels_lib::Timestamp;
+
+/// Where to send captured frames and how to encode them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CaptureConfig {
+    /// Write one PNG per frame into `directory`, named `frame_{index:08}_{time_us}.png` so
+    /// frames sort in capture order and still carry their server timestamp.
+    PngSequence { directory: String },
+    /// Write raw RGBA8 frames to `path` if set, or to stdout otherwise - the format an external
+    /// encoder (e.g. ffmpeg's `-f rawvideo` input) expects. Each frame is preceded by an 8-byte
+    /// little-endian timestamp (microseconds since show start) so a downstream tool can align
+    /// captures from different channels without a separate sidecar file.
+    RawPipe { path: Option<String> },
+}
+
+/// One captured frame, read back from the default framebuffer right after it was drawn.
+pub struct CaptureFrame {
+    /// Server timestamp of the snapshot this frame was rendered from, so captures can be aligned
+    /// with audio or other channels' captures after the fact.
+    pub time: Timestamp,
+    pub width: u32,
+    pub height: u32,
+    /// Bottom-up RGBA8 pixel data, straight out of `glReadPixels`.
+    pub rgba: Vec<u8>,
+}
+
+pub type CaptureHandle = Sender<CaptureFrame>;
+
+/// Spawn the encoder thread and return a handle `Show::render` can feed frames to. The thread
+/// runs until the handle (and every clone of it) is dropped, or until a write fails.
+pub fn spawn(cfg: CaptureConfig) -> Result<CaptureHandle> {
+    let mut sink = make_sink(&cfg)?;
+    let (tx, rx) = mpsc::channel::<CaptureFrame>();
+    thread::Builder::new()
+        .name("capture_encoder".to_string())
+        .spawn(move || {
+            let mut index = 0u64;
+            for frame in rx {
+                if let Err(e) = sink.write(index, &frame) {
+                    error!("Frame capture write error, stopping capture: {e}");
+                    return;
+                }
+                index += 1;
+            }
+            info!("Frame capture encoder shutting down.");
+        })?;
+    Ok(tx)
+}
+
+/// One frame's worth of encoder output, abstracting over the PNG-sequence and raw-pipe modes.
+trait FrameSink: Send {
+    fn write(&mut self, index: u64, frame: &CaptureFrame) -> Result<()>;
+}
+
+struct PngSequenceSink {
+    directory: String,
+}
+
+impl FrameSink for PngSequenceSink {
+    fn write(&mut self, index: u64, frame: &CaptureFrame) -> Result<()> {
+        let path = format!(
+            "{}/frame_{:08}_{}.png",
+            self.directory, index, frame.time.0
+        );
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, frame.width, frame.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&flip_rows(frame))?;
+        Ok(())
+    }
+}
+
+struct RawPipeSink {
+    out: Box<dyn Write + Send>,
+}
+
+impl FrameSink for RawPipeSink {
+    fn write(&mut self, _index: u64, frame: &CaptureFrame) -> Result<()> {
+        self.out.write_all(&frame.time.0.to_le_bytes())?;
+        self.out.write_all(&flip_rows(frame))?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn make_sink(cfg: &CaptureConfig) -> Result<Box<dyn FrameSink>> {
+    match cfg {
+        CaptureConfig::PngSequence { directory } => {
+            fs::create_dir_all(directory)?;
+            Ok(Box::new(PngSequenceSink {
+                directory: directory.clone(),
+            }))
+        }
+        CaptureConfig::RawPipe { path } => {
+            let out: Box<dyn Write + Send> = match path {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stdout()),
+            };
+            Ok(Box::new(RawPipeSink { out }))
+        }
+    }
+}
+
+/// `glReadPixels` returns rows bottom-to-top; flip back to the top-down order PNG and raw video
+/// consumers expect.
+fn flip_rows(frame: &CaptureFrame) -> Vec<u8> {
+    let stride = (frame.width * 4) as usize;
+    let mut flipped = vec![0u8; frame.rgba.len()];
+    for (dst_row, src_row) in frame.rgba.chunks(stride).rev().enumerate() {
+        flipped[dst_row * stride..(dst_row + 1) * stride].copy_from_slice(src_row);
+    }
+    flipped
+}
@@ -1,20 +1,33 @@
 //! Loading and parsing client configurations.
-use crate::draw::{Transform, TransformDirection};
-use anyhow::{anyhow, Result};
+use crate::capture::CaptureConfig;
+use crate::clock_source::ClockSource;
+use crate::draw::{GeometryTransform, Transform, TransformDirection};
+use crate::render_backend::OutputTarget;
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::cmp;
 use std::fs::File;
 use std::io::Read;
 use std::time::Duration;
-use yaml_rust::YamlLoader;
+use tunnels_lib::multicast::MulticastConfig;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SnapshotManagement {
     /// Always render the latest snapshot.
     Single,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Configuration for `crate::preview::PreviewShow`, a single window that tiles several video
+/// channels into a grid instead of one window per channel.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviewConfig {
+    /// Video channels to monitor, in row-major tile order (left to right, top to bottom).
+    pub channels: Vec<u64>,
+    /// Number of tiles per row; the tile count divided by this (rounded up) gives the row count.
+    pub columns: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
     /// Hostname of the machine running the controller.
     pub server_hostname: String,
@@ -36,14 +49,32 @@ pub struct ClientConfig {
     pub critical_size: f64,
     /// Used to rescale unit-scale lineweights to the current resolution.
     pub thickness_scale: f64,
-    /// Computed pixel x-offset of the drawing coordinate system.
-    pub x_center: f64,
-    /// Computed pixel y-offset of the drawing coordinate system.
-    pub y_center: f64,
-    /// Geometric transformation to optionally apply to the entire image.
-    pub transformation: Option<Transform>,
+    /// Independently-framed outputs to draw the same show into every frame, e.g. one per
+    /// physical projector in a multi-surface install. A single-output client just has one entry
+    /// here, centered on the window.
+    pub targets: Vec<OutputTarget>,
     /// Log at debug level?  This option is ignored when running in remote mode.
     pub log_level_debug: bool,
+    /// If set, receive this channel's snapshots over UDP multicast (see
+    /// `tunnels::multicast_send`) instead of the zmq/shm transport.
+    pub multicast: Option<MulticastConfig>,
+    /// If true, only redraw when a new snapshot has actually arrived, rather than re-rasterizing
+    /// the same content on every render tick. Disable this on machines with broken vsync that
+    /// rely on the free-running draw loop to stay smooth.
+    pub redraw_on_change: bool,
+    /// How far behind the host's clock to hold the playback head, giving the snapshot jitter
+    /// buffer (see `crate::snapshot_manager`) room to absorb network jitter and reordering before
+    /// a late frame forces a stutter. Zero renders the newest snapshot available with no
+    /// buffering, which is fine on a quiet local network but will stutter on a lossy one.
+    pub target_delay: Duration,
+    /// If set, record every rendered frame to disk or a pipe; see `crate::capture`.
+    pub capture: Option<CaptureConfig>,
+    /// If set, run in tiled multi-channel preview mode instead of showing `video_channel` alone;
+    /// see `crate::preview`.
+    pub preview: Option<PreviewConfig>,
+    /// Which clock backend to use as the source of truth for "what time is it on the host";
+    /// see `crate::clock_source`. Defaults to the builtin zmq REQ/REP exchange.
+    pub clock_source: ClockSource,
 }
 
 impl ClientConfig {
@@ -56,7 +87,14 @@ impl ClientConfig {
         fullscreen: bool,
         capture_mouse: bool,
         transformation: Option<Transform>,
+        geometry: Vec<GeometryTransform>,
         log_level_debug: bool,
+        multicast: Option<MulticastConfig>,
+        redraw_on_change: bool,
+        target_delay: Duration,
+        capture: Option<CaptureConfig>,
+        preview: Option<PreviewConfig>,
+        clock_source: ClockSource,
     ) -> ClientConfig {
         let (x_resolution, y_resolution) = resolution;
 
@@ -71,11 +109,15 @@ impl ClientConfig {
             capture_mouse,
             critical_size: f64::from(cmp::min(x_resolution, y_resolution)),
             thickness_scale: 0.5,
-            x_center: f64::from(x_resolution / 2),
-            y_center: f64::from(y_resolution / 2),
-            transformation,
+            targets: vec![OutputTarget::centered(resolution, transformation, geometry)],
             log_level_debug,
             snapshot_management: SnapshotManagement::Single,
+            multicast,
+            redraw_on_change,
+            target_delay,
+            capture,
+            preview,
+            clock_source,
         }
     }
 
@@ -86,25 +128,9 @@ impl ClientConfig {
         let mut config_file = File::open(config_path)?;
         let mut config_file_string = String::new();
         config_file.read_to_string(&mut config_file_string)?;
-        let docs = YamlLoader::load_from_str(&config_file_string)?;
-        let cfg = &docs[0];
-        let x_resolution = cfg["x_resolution"]
-            .as_i64()
-            .ok_or(anyhow!("Bad x resolution."))? as u32;
-        let y_resolution = cfg["y_resolution"]
-            .as_i64()
-            .ok_or(anyhow!("Bad y resolution."))? as u32;
-        let host = cfg["server_hostname"]
-            .as_str()
-            .ok_or(anyhow!("Hostname missing."))?
-            .trim()
-            .to_string();
-
-        let flag = |name: &str, missing: &'static str| -> Result<bool> {
-            cfg[name].as_bool().ok_or(anyhow!(missing))
-        };
+        let raw: RawClientConfig = serde_yaml::from_str(&config_file_string)?;
 
-        let transformation = if flag("flip_horizontal", "Bad horizontal flip flag.")? {
+        let transformation = if raw.flip_horizontal {
             Some(Transform::Flip(TransformDirection::Horizontal))
         } else {
             None
@@ -112,14 +138,72 @@ impl ClientConfig {
 
         Ok(ClientConfig::new(
             video_channel,
-            host,
-            (x_resolution, y_resolution),
-            flag("fullscreen", "Bad fullscreen flag.")?,
-            flag("capture_mouse", "Bad mouse capture flag.")?,
+            raw.server_hostname.trim().to_string(),
+            (raw.x_resolution, raw.y_resolution),
+            raw.fullscreen,
+            raw.capture_mouse,
             transformation,
-            flag("log_level_debug", "Bad log level flag.")?,
+            raw.geometry,
+            raw.log_level_debug,
+            raw.multicast,
+            raw.redraw_on_change,
+            Duration::from_millis(raw.target_delay_ms),
+            raw.capture,
+            raw.preview,
+            raw.clock_source,
         ))
     }
 }
 
+/// On-disk representation of a client config: just what a human edits by hand. `ClientConfig`
+/// augments this with the `critical_size`/`thickness_scale`/per-target center fields derived
+/// from it once `geometry` and the rest of the transform pipeline are resolved, which a config
+/// file never sets directly.
+#[derive(Debug, Serialize, Deserialize)]
+struct RawClientConfig {
+    server_hostname: String,
+    x_resolution: u32,
+    y_resolution: u32,
+    fullscreen: bool,
+    capture_mouse: bool,
+    #[serde(default)]
+    flip_horizontal: bool,
+    /// Coordinate-space corrections applied, in order, to this client's single output - see
+    /// `GeometryTransform`. Multi-output installs still need to be assembled via
+    /// `ClientConfig::new`/`targets` directly.
+    #[serde(default)]
+    geometry: Vec<GeometryTransform>,
+    #[serde(default)]
+    log_level_debug: bool,
+    /// If set, join this group/port instead of the zmq/shm transport to receive this client's
+    /// video channel. Must match the server's `--multicast` render transport configuration.
+    #[serde(default)]
+    multicast: Option<MulticastConfig>,
+    /// If true, only redraw when a new snapshot has actually arrived. Defaults to on; set to
+    /// false on machines with broken vsync that rely on the free-running draw loop.
+    #[serde(default = "default_redraw_on_change")]
+    redraw_on_change: bool,
+    /// Milliseconds behind the host's clock to hold the playback head; see
+    /// `ClientConfig::target_delay`.
+    #[serde(default = "default_target_delay_ms")]
+    target_delay_ms: u64,
+    /// If set, record every rendered frame; see `crate::capture::CaptureConfig`.
+    #[serde(default)]
+    capture: Option<CaptureConfig>,
+    /// If set, run in tiled multi-channel preview mode; see `crate::preview::PreviewConfig`.
+    #[serde(default)]
+    preview: Option<PreviewConfig>,
+    /// Which clock backend to use; see `ClientConfig::clock_source`. Defaults to `Builtin`.
+    #[serde(default)]
+    clock_source: ClockSource,
+}
+
+fn default_redraw_on_change() -> bool {
+    true
+}
+
+fn default_target_delay_ms() -> u64 {
+    100
+}
+
 pub type Resolution = (u32, u32);
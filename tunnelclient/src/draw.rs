@@ -1,10 +1,7 @@
 use std::sync::Arc;
 
 use crate::config::ClientConfig;
-use crate::constants::TWOPI;
-use graphics::types::Color;
-use graphics::{rectangle, CircleArc, Graphics, Transformed};
-use piston_window::Context;
+use crate::render_backend::{ArcInstance, OutputTarget, RenderBackend};
 use serde::{Deserialize, Serialize};
 use tunnels_lib::ArcSegment;
 use tunnels_lib::Snapshot;
@@ -21,117 +18,158 @@ pub enum TransformDirection {
 pub enum Transform {
     /// Flip the image in the specified direction.
     Flip(TransformDirection),
-    // /// Mirror the image in the specified direction.
-    //Mirror(TransformDirection),
+    /// Reflect one half of the frame onto the other across the center axis, by drawing each arc
+    /// segment a second time with the relevant coordinate and rotation negated.
+    Mirror(TransformDirection),
+    /// Replicate each arc segment around the screen center into `folds` evenly spaced wedges,
+    /// alternating a mirror reflection every other fold for seamless symmetry.
+    Kaleidoscope { folds: u32 },
 }
 
-pub trait Draw<G: Graphics> {
-    /// Given a context and gl instance, draw this entity to the screen.
-    fn draw(&self, c: &Context, gl: &mut G, cfg: &ClientConfig);
+/// One coordinate-space correction applied, in order, when mapping a segment's normalized
+/// position onto an output target's drawing surface - for example compensating for an angled or
+/// mirrored projector. Unlike `Transform::Mirror`/`Kaleidoscope`, which submit extra copies of a
+/// segment, these only ever move an existing point, so an `OutputTarget` carries an ordered list
+/// of them (`OutputTarget::geometry`) rather than at most one.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum GeometryTransform {
+    FlipHorizontal,
+    FlipVertical,
+    /// Rotate clockwise by a multiple of 90 degrees.
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    /// Scale about the origin; 1.0 on an axis leaves it unchanged.
+    Scale { x: f64, y: f64 },
+    /// Shift by this many normalized units along each axis.
+    Translate { x: f64, y: f64 },
+    /// Warp the normalized unit square so its four corners land at the given positions, to
+    /// correct an off-axis (keystoned) projector. Corners are given in the same normalized space
+    /// as a segment's own coordinates.
+    Keystone {
+        top_left: (f64, f64),
+        top_right: (f64, f64),
+        bottom_right: (f64, f64),
+        bottom_left: (f64, f64),
+    },
 }
 
-impl<T, G> Draw<G> for Vec<T>
-where
-    G: Graphics,
-    T: Draw<G>,
-{
-    fn draw(&self, c: &Context, gl: &mut G, cfg: &ClientConfig) {
-        for e in self {
-            e.draw(c, gl, cfg);
+impl GeometryTransform {
+    fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        match *self {
+            Self::FlipHorizontal => (-x, y),
+            Self::FlipVertical => (x, -y),
+            Self::Rotate90 => (-y, x),
+            Self::Rotate180 => (-x, -y),
+            Self::Rotate270 => (y, -x),
+            Self::Scale { x: sx, y: sy } => (x * sx, y * sy),
+            Self::Translate { x: dx, y: dy } => (x + dx, y + dy),
+            Self::Keystone {
+                top_left,
+                top_right,
+                bottom_right,
+                bottom_left,
+            } => {
+                // Segment coordinates are centered on the screen, so shift into the [0, 1]
+                // bilinear interpolation parameters the corners are indexed by.
+                let u = x + 0.5;
+                let v = y + 0.5;
+                let top = lerp_point(top_left, top_right, u);
+                let bottom = lerp_point(bottom_left, bottom_right, u);
+                lerp_point(top, bottom, v)
+            }
         }
     }
 }
 
-impl<T, G> Draw<G> for Arc<T>
-where
-    G: Graphics,
-    T: Draw<G>,
-{
-    fn draw(&self, c: &Context, gl: &mut G, cfg: &ClientConfig) {
-        (**self).draw(c, gl, cfg);
-    }
+fn lerp_point(a: (f64, f64), b: (f64, f64), t: f64) -> (f64, f64) {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
 }
 
-#[inline]
-fn color_from_rgb(r: f64, g: f64, b: f64, a: f64) -> Color {
-    [r as f32, g as f32, b as f32, a as f32]
+/// Apply a sequence of geometry corrections to a normalized point, in the given order.
+pub fn apply_geometry(x: f64, y: f64, transforms: &[GeometryTransform]) -> (f64, f64) {
+    transforms.iter().fold((x, y), |(x, y), t| t.apply(x, y))
 }
 
-/// Convert HSV to a Piston RGB color.
-#[inline]
-fn hsv_to_rgb(hue: f64, sat: f64, val: f64, alpha: f64) -> Color {
-    if sat == 0.0 {
-        color_from_rgb(val, val, val, alpha)
-    } else {
-        let var_h = if hue == 1.0 { 0.0 } else { hue * 6.0 };
+const TWOPI: f64 = 2.0 * std::f64::consts::PI;
 
-        let var_i = var_h.floor();
-        let var_1 = val * (1.0 - sat);
-        let var_2 = val * (1.0 - sat * (var_h - var_i));
-        let var_3 = val * (1.0 - sat * (1.0 - (var_h - var_i)));
-
-        match var_i as i64 {
-            0 => color_from_rgb(val, var_3, var_1, alpha),
-            1 => color_from_rgb(var_2, val, var_1, alpha),
-            2 => color_from_rgb(var_1, val, var_3, alpha),
-            3 => color_from_rgb(var_1, var_2, val, alpha),
-            4 => color_from_rgb(var_3, var_1, val, alpha),
-            _ => color_from_rgb(val, var_1, var_2, alpha),
-        }
+/// Reflect a segment's position and rotation across the given axis, leaving its shape otherwise
+/// unchanged.
+fn mirror_segment(segment: &ArcSegment, direction: TransformDirection) -> ArcSegment {
+    let mut mirrored = segment.clone();
+    match direction {
+        TransformDirection::Horizontal => mirrored.x = -mirrored.x,
+        TransformDirection::Vertical => mirrored.y = -mirrored.y,
     }
+    mirrored.rot_angle = -mirrored.rot_angle;
+    mirrored
 }
 
-impl<G: Graphics> Draw<G> for ArcSegment {
-    fn draw(&self, c: &Context, gl: &mut G, cfg: &ClientConfig) {
-        let thickness = self.thickness * cfg.critical_size * cfg.thickness_scale / 2.0;
+/// Rotate a segment's position about the screen center by `k` of `folds` full turns, mirroring
+/// every other fold to produce seamless wedge symmetry.
+fn kaleidoscope_fold(segment: &ArcSegment, k: u32, folds: u32) -> ArcSegment {
+    let turn = f64::from(k) / f64::from(folds);
+    let (sin, cos) = (turn * TWOPI).sin_cos();
+    let mut folded = segment.clone();
+    folded.x = segment.x * cos - segment.y * sin;
+    folded.y = segment.x * sin + segment.y * cos;
+    folded.rot_angle += turn;
+    if k % 2 == 1 {
+        folded = mirror_segment(&folded, TransformDirection::Horizontal);
+    }
+    folded
+}
 
-        let (val, alpha) = if cfg.alpha_blend {
-            (self.val, self.level)
-        } else {
-            (self.val * self.level, 1.0)
-        };
+pub trait Draw<B: RenderBackend> {
+    /// Submit this entity's drawing instructions to the backend, framed for one output target.
+    fn draw(&self, backend: &mut B, cfg: &ClientConfig, target: &OutputTarget);
+}
 
-        let color = hsv_to_rgb(self.hue, self.sat, val, alpha);
+impl<T, B> Draw<B> for Vec<T>
+where
+    B: RenderBackend,
+    T: Draw<B>,
+{
+    fn draw(&self, backend: &mut B, cfg: &ClientConfig, target: &OutputTarget) {
+        for e in self {
+            e.draw(backend, cfg, target);
+        }
+    }
+}
 
-        let (x, y) = {
-            let (x0, y0) = match cfg.transformation {
-                None => (self.x, self.y),
-                Some(Transform::Flip(TransformDirection::Horizontal)) => (-1.0 * self.x, self.y),
-                Some(Transform::Flip(TransformDirection::Vertical)) => (self.x, -1.0 * self.y),
-            };
-            let x = x0 * f64::from(cfg.x_resolution) + cfg.x_center;
-            let y = y0 * f64::from(cfg.y_resolution) + cfg.y_center;
-            (x, y)
-        };
+impl<T, B> Draw<B> for Arc<T>
+where
+    B: RenderBackend,
+    T: Draw<B>,
+{
+    fn draw(&self, backend: &mut B, cfg: &ClientConfig, target: &OutputTarget) {
+        (**self).draw(backend, cfg, target);
+    }
+}
 
-        let transform = {
-            let t = c.transform.trans(x, y).rot_rad(self.rot_angle * TWOPI);
-            match cfg.transformation {
-                None => t,
-                Some(Transform::Flip(TransformDirection::Horizontal)) => t.flip_h(),
-                Some(Transform::Flip(TransformDirection::Vertical)) => t.flip_v(),
+impl<B: RenderBackend> Draw<B> for ArcSegment {
+    fn draw(&self, backend: &mut B, cfg: &ClientConfig, target: &OutputTarget) {
+        match target.transformation {
+            Some(Transform::Mirror(direction)) => {
+                backend.submit_arc(&ArcInstance::from_segment(self, cfg, target));
+                let mirrored = mirror_segment(self, direction);
+                backend.submit_arc(&ArcInstance::from_segment(&mirrored, cfg, target));
             }
-        };
-
-        let x_size = self.rad_x * cfg.critical_size;
-        let y_size = self.rad_y * cfg.critical_size;
-
-        let bound = rectangle::centered([0.0, 0.0, x_size, y_size]);
-
-        let start = self.start * TWOPI;
-        let stop = self.stop * TWOPI;
-
-        CircleArc::new(color, thickness, start, stop).draw(
-            bound,
-            &Default::default(),
-            transform,
-            gl,
-        );
+            Some(Transform::Kaleidoscope { folds }) => {
+                for k in 0..folds {
+                    let folded = kaleidoscope_fold(self, k, folds);
+                    backend.submit_arc(&ArcInstance::from_segment(&folded, cfg, target));
+                }
+            }
+            _ => {
+                backend.submit_arc(&ArcInstance::from_segment(self, cfg, target));
+            }
+        }
     }
 }
 
-impl<G: Graphics> Draw<G> for Snapshot {
-    fn draw(&self, c: &Context, gl: &mut G, cfg: &ClientConfig) {
-        self.layers.draw(c, gl, cfg);
+impl<B: RenderBackend> Draw<B> for Snapshot {
+    fn draw(&self, backend: &mut B, cfg: &ClientConfig, target: &OutputTarget) {
+        self.layers.draw(backend, cfg, target);
     }
 }
@@ -0,0 +1,113 @@
+//! Capture recent log records in a bounded ring buffer so they can be fetched remotely.
+//! `run_remote` previously only logged `Show::new` and runtime failures locally via `error!`,
+//! with no way for the administrator to see a failure on a machine without SSHing into it.
+//! Installing `RingLogger` as the global logger keeps every record printing to stdout as before,
+//! but also retains the most recent ones so `RemoteCommand::FetchLog` can hand them back.
+
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Instant;
+use tunnels_lib::Timestamp;
+
+/// Maximum number of log records retained for remote fetch.
+const CAPACITY: usize = 512;
+
+/// A serializable stand-in for `log::Level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl From<Level> for LogLevel {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Error => LogLevel::Error,
+            Level::Warn => LogLevel::Warn,
+            Level::Info => LogLevel::Info,
+            Level::Debug => LogLevel::Debug,
+            Level::Trace => LogLevel::Trace,
+        }
+    }
+}
+
+/// A single buffered log record, timestamped relative to when this client's logger was
+/// installed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    pub timestamp: Timestamp,
+    pub message: String,
+}
+
+/// Global logger that prints every record to stdout, as `SimpleLogger` did, and also retains the
+/// most recent `CAPACITY` of them in a ring buffer for remote retrieval.
+struct RingLogger {
+    start: Instant,
+    buffer: Mutex<VecDeque<LogRecord>>,
+}
+
+impl Log for RingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        // Filtering is handled globally via `log::set_max_level`.
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let timestamp = Timestamp::since(self.start);
+        println!(
+            "{} {} [{}] {}",
+            timestamp,
+            record.level(),
+            record.target(),
+            record.args()
+        );
+
+        let mut buffer = self.buffer.lock().unwrap();
+        if buffer.len() == CAPACITY {
+            buffer.pop_front();
+        }
+        buffer.push_back(LogRecord {
+            level: record.level().into(),
+            timestamp,
+            message: record.args().to_string(),
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+lazy_static! {
+    static ref LOGGER: RingLogger = RingLogger {
+        start: Instant::now(),
+        buffer: Mutex::new(VecDeque::with_capacity(CAPACITY)),
+    };
+}
+
+/// Install the ring-buffered logger as the global logger, at the given level.
+/// Panics if a global logger has already been installed.
+pub fn init(level: LevelFilter) {
+    log::set_logger(&*LOGGER).expect("A logger was already installed.");
+    log::set_max_level(level);
+}
+
+/// Return every buffered record with a timestamp strictly greater than `since`, oldest first.
+pub fn records_since(since: Timestamp) -> Vec<LogRecord> {
+    LOGGER
+        .buffer
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|r| r.timestamp > since)
+        .cloned()
+        .collect()
+}
@@ -1,14 +1,21 @@
-mod constants {
-    use std::f64::consts::PI;
-    pub const TWOPI: f64 = 2.0 * PI;
-}
-
+mod capture;
+mod clock_source;
 mod config;
 mod draw;
+mod log_buffer;
 mod remote;
+mod render_backend;
+#[cfg(feature = "render-piston")]
+mod render_piston;
+#[cfg(feature = "render-wgpu")]
+mod render_wgpu;
+mod preview;
 mod show;
+mod snapshot_manager;
+mod timesync;
 
 use crate::config::ClientConfig;
+use crate::preview::PreviewShow;
 use crate::remote::{administrate, run_remote};
 use crate::show::Show;
 use simplelog::{Config as LogConfig, LevelFilter, SimpleLogger};
@@ -20,18 +27,35 @@ fn main() {
     // Check if running in remote mode.
     let first_arg = env::args().nth(1).expect(
         "First argument must be 'remote' to run in remote mode, \
-        'admin' to run the client administrator,
-         or the integer virtual video channel to listen to.",
+        'admin' to run the client administrator, 'preview' to run a tiled multi-channel \
+         preview window, or the integer virtual video channel to listen to.",
     );
 
     let ctx = Context::new();
 
     if first_arg == "remote" {
-        init_logger(LevelFilter::Info);
+        // Running headless with an administrator on the other end of the wire: install the
+        // ring-buffered logger so a failed or misbehaving show is diagnosable via
+        // `Administrator::fetch_log` instead of requiring someone to SSH in and tail stdout.
+        log_buffer::init(LevelFilter::Info);
         run_remote(ctx);
     } else if first_arg == "admin" {
         init_logger(LevelFilter::Info);
         administrate();
+    } else if first_arg == "preview" {
+        // The preview window doesn't show a single `video_channel`; pass a placeholder and pull
+        // the real channel list from `cfg.preview` instead.
+        let config_path = env::args().nth(2).expect("No config path arg provided.");
+        let cfg = ClientConfig::load(0, &config_path).expect("Failed to load config");
+        init_logger(if cfg.log_level_debug {
+            LevelFilter::Debug
+        } else {
+            LevelFilter::Info
+        });
+
+        let mut preview =
+            PreviewShow::new(cfg, ctx, RunFlag::default()).expect("Failed to initialize preview");
+        preview.run();
     } else {
         let video_channel: u64 = first_arg
             .parse()
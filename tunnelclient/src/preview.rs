@@ -0,0 +1,210 @@
+//! Tiled multi-channel preview window: monitor several video channels from a single window
+//! instead of launching one full `Show` process per channel. Each tile runs its own snapshot
+//! receiver thread and jitter buffer (see `crate::snapshot_manager`), exactly as a standalone
+//! `Show` would, but all tiles share one host timesync estimate and one GL canvas, with each
+//! tile's layers drawn into its own slice of the canvas via `crate::render_backend::OutputTarget`.
+
+use crate::config::ClientConfig;
+use crate::render_backend::{OutputTarget, RenderBackend};
+use crate::render_piston::PistonBackend;
+use crate::show::receive_snapshots;
+use crate::snapshot_manager::{
+    SnapshotFetchResult, SnapshotManager, SnapshotManagerHandle, VecDequeSnapshotManager,
+};
+use crate::timesync::{self, Synchronizer};
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use opengl_graphics::{GlGraphics, OpenGL};
+use piston_window::prelude::*;
+use sdl2_window::Sdl2Window;
+use std::cmp;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tunnels_lib::{RunFlag, Timestamp};
+use zmq::Context;
+
+/// One grid cell: a single channel's snapshot feed, a config scoped to this tile's slice of the
+/// canvas, and the output target that positions its drawing within the shared window.
+struct Tile {
+    video_channel: u64,
+    snapshot_manager: SnapshotManagerHandle,
+    cfg: ClientConfig,
+    target: OutputTarget,
+}
+
+/// A single window that tiles several video channels into a grid, for a lighting operator
+/// monitoring a multi-channel show from one surface rather than one fullscreen `Show` per
+/// channel.
+pub struct PreviewShow {
+    gl: GlGraphics,
+    window: PistonWindow<Sdl2Window>,
+    run_flag: RunFlag,
+    tiles: Vec<Tile>,
+    target_delay: Duration,
+    /// Smoothed estimate of the current time on the host, shared by every tile so they all play
+    /// back at the same point in the show, mirroring `Show::synchronizer`.
+    synchronizer: Arc<Mutex<Synchronizer>>,
+    last_sync_update: Instant,
+}
+
+impl PreviewShow {
+    pub fn new(cfg: ClientConfig, ctx: Context, run_flag: RunFlag) -> Result<Self> {
+        let preview = cfg
+            .preview
+            .clone()
+            .ok_or_else(|| anyhow!("preview mode requires a `preview` section in the client config"))?;
+        if preview.channels.is_empty() {
+            return Err(anyhow!("preview.channels must list at least one video channel"));
+        }
+        if preview.columns == 0 {
+            return Err(anyhow!("preview.columns must be at least 1"));
+        }
+        info!("Running preview of channels {:?}.", preview.channels);
+
+        let rows = (preview.channels.len() as u32 + preview.columns - 1) / preview.columns;
+        let tile_x_resolution = cfg.x_resolution / preview.columns;
+        let tile_y_resolution = cfg.y_resolution / rows;
+        let tile_critical_size = f64::from(cmp::min(tile_x_resolution, tile_y_resolution));
+
+        let tiles = preview
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(i, &video_channel)| -> Result<Tile> {
+                let col = i as u32 % preview.columns;
+                let row = i as u32 / preview.columns;
+
+                let snapshot_manager: SnapshotManagerHandle =
+                    Arc::new(Mutex::new(Box::new(VecDequeSnapshotManager::default())));
+                let mut receive_cfg = cfg.clone();
+                receive_cfg.video_channel = video_channel;
+                receive_snapshots(&ctx, &receive_cfg, snapshot_manager.clone(), run_flag.clone())?;
+
+                let mut tile_cfg = receive_cfg;
+                tile_cfg.x_resolution = tile_x_resolution;
+                tile_cfg.y_resolution = tile_y_resolution;
+                tile_cfg.critical_size = tile_critical_size;
+
+                let target = OutputTarget {
+                    x_resolution: tile_x_resolution,
+                    y_resolution: tile_y_resolution,
+                    x_center: f64::from(col * tile_x_resolution + tile_x_resolution / 2),
+                    y_center: f64::from(row * tile_y_resolution + tile_y_resolution / 2),
+                    transformation: None,
+                    geometry: Vec::new(),
+                };
+
+                Ok(Tile {
+                    video_channel,
+                    snapshot_manager,
+                    cfg: tile_cfg,
+                    target,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let ts_client = timesync::Client::new(&cfg.server_hostname, ctx)
+            .map_err(|e| anyhow!("failed to connect timesync client: {e}"))?;
+        let synchronizer = timesync::synchronize_rapid_then_refine(ts_client)
+            .map_err(|e| anyhow!("failed to perform initial timesync: {e}"))?;
+
+        let opengl = OpenGL::V3_2;
+        let mut window: PistonWindow<Sdl2Window> = WindowSettings::new(
+            format!(
+                "tunnelclient: preview ({} channels)",
+                preview.channels.len()
+            ),
+            [cfg.x_resolution, cfg.y_resolution],
+        )
+        .graphics_api(opengl)
+        .exit_on_esc(true)
+        .vsync(true)
+        .samples(4)
+        .fullscreen(cfg.fullscreen)
+        .build()
+        .map_err(|err| anyhow!("{err}"))?;
+
+        window.set_capture_cursor(cfg.capture_mouse);
+        window.set_max_fps(120);
+
+        Ok(PreviewShow {
+            gl: GlGraphics::new(opengl),
+            window,
+            run_flag,
+            tiles,
+            target_delay: cfg.target_delay,
+            synchronizer,
+            last_sync_update: Instant::now(),
+        })
+    }
+
+    /// Run the preview's event loop.
+    pub fn run(&mut self) {
+        while self.step() {}
+    }
+
+    /// Process a single iteration of the event loop, rendering a frame if one is due. Returns
+    /// false once the preview should exit, either because the window was closed or because the
+    /// run flag was tripped.
+    pub fn step(&mut self) -> bool {
+        let Some(e) = self.window.next() else {
+            self.run_flag.stop();
+            return false;
+        };
+
+        if !self.run_flag.should_run() {
+            info!("Quit flag tripped, ending preview.");
+            return false;
+        }
+
+        if let Some(r) = e.render_args() {
+            self.render(&r);
+        }
+        true
+    }
+
+    /// Render one frame of every tile into its slice of the shared canvas. Unlike `Show::render`,
+    /// there's no `redraw_on_change` skip here: with several independent channels in one window,
+    /// some tile is essentially always due for a redraw, so the dirty check would rarely save a
+    /// frame while adding a per-tile "last drawn" bookkeeping cost to every tile.
+    fn render(&mut self, args: &RenderArgs) {
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_sync_update).as_secs_f64();
+        self.last_sync_update = now;
+        let synced_now = {
+            let mut synchronizer = self.synchronizer.lock().unwrap();
+            synchronizer.update(dt);
+            synchronizer.now()
+        };
+        let target_time = synced_now - Timestamp::from_duration(self.target_delay);
+
+        let tiles = &self.tiles;
+        self.gl.draw(args.viewport(), |c, gl| {
+            let mut backend = PistonBackend::new(c, gl);
+            backend.begin_frame();
+            for tile in tiles {
+                let result = {
+                    let mut manager = tile.snapshot_manager.lock().unwrap();
+                    let result = manager.get(target_time);
+                    manager.update();
+                    result
+                };
+                let snapshot = match result {
+                    SnapshotFetchResult::Good(s)
+                    | SnapshotFetchResult::MissingNewer(s)
+                    | SnapshotFetchResult::MissingOlder(s) => s,
+                    SnapshotFetchResult::NoData => continue,
+                    SnapshotFetchResult::Error(_) => {
+                        warn!(
+                            "Channel {}: jitter buffer could not bracket the requested playback time.",
+                            tile.video_channel
+                        );
+                        continue;
+                    }
+                };
+                snapshot.layers.draw(&mut backend, &tile.cfg, &tile.target);
+            }
+            backend.end_frame();
+        });
+    }
+}
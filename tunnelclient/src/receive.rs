@@ -1,18 +1,66 @@
 //! 0mq communication and deserialization.
 
-use log::error;
+use log::{error, warn};
 
 use anyhow::Result;
-use tunnels_lib::Snapshot;
+use tunnels_lib::{Snapshot, Timestamp};
 
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::sync::mpsc::{channel, Receiver};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 use zero_configure::pub_sub::Receiver as SubReceiver;
 
 use crate::timesync::Synchronizer;
 
+/// How long the playout buffer holds a snapshot before releasing it, to absorb network jitter
+/// and let reordering fall out of the buffer for free. Bigger values smooth more jitter at the
+/// cost of more fixed latency between the show controller and what ends up on screen.
+const TARGET_LATENCY: Duration = Duration::from_millis(75);
+
+/// How often the worker wakes up to check whether anything in the playout buffer is due for
+/// release, while also polling for newly-arrived snapshots.
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+/// A snapshot held in the playout buffer, ordered by release time rather than arrival order.
+struct Scheduled {
+    release_time: Timestamp,
+    snapshot: Snapshot,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.release_time == other.release_time
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap; reverse the comparison so the earliest release time is
+        // always on top.
+        other.release_time.cmp(&self.release_time)
+    }
+}
+
 /// Spawn a thread and pass SUB messages onto a channel.
+///
+/// Incoming snapshots are held in a playout buffer keyed by `snapshot.time` rather than being
+/// forwarded the instant they arrive, so out-of-order or jittery network delivery doesn't
+/// produce uneven playback; reordering falls out of the heap for free. A snapshot is released
+/// once the synchronized host clock reaches `snapshot.time + TARGET_LATENCY`. Snapshots whose
+/// release time has already passed on arrival are dropped and counted instead of being forwarded
+/// late.
+///
 /// This will run until the returned channel is dropped.
 pub fn receive_async(
     mut receiver: SubReceiver<Snapshot>,
@@ -22,22 +70,43 @@ pub fn receive_async(
     thread::Builder::new()
         .name("subscribe_receiver".to_string())
         .spawn(move || {
+            let mut buffer: BinaryHeap<Scheduled> = BinaryHeap::new();
+            let mut dropped_late = 0u64;
             loop {
-                // blocking receive
-                match receiver.receive_msg(true) {
+                match receiver.receive_msg(false) {
                     Ok(Some(msg)) => {
                         let current_time = timesync.lock().unwrap().now();
-                        println!("received snapshot; delay: {}", current_time - msg.time);
-                        // post message to queue
-                        // if a send fails, the other side has hung up and we should quit
-                        match tx.send(msg) {
-                            Ok(_) => continue,
-                            Err(_) => break,
+                        let release_time = msg.time + Timestamp::from_duration(TARGET_LATENCY);
+                        if release_time < current_time {
+                            dropped_late += 1;
+                            warn!(
+                                "dropped a snapshot that arrived {} late ({dropped_late} dropped so far)",
+                                current_time - release_time,
+                            );
+                        } else {
+                            buffer.push(Scheduled {
+                                release_time,
+                                snapshot: msg,
+                            });
                         }
                     }
-                    Ok(None) => continue, // Odd case, given that we should have blocked.
+                    Ok(None) => (),
                     Err(e) => error!("receive error: {e}"),
                 }
+
+                // Release everything in the buffer whose time has come, oldest first.
+                while let Some(next) = buffer.peek() {
+                    if next.release_time > timesync.lock().unwrap().now() {
+                        break;
+                    }
+                    let due = buffer.pop().unwrap().snapshot;
+                    // if a send fails, the other side has hung up and we should quit
+                    if tx.send(due).is_err() {
+                        return;
+                    }
+                }
+
+                thread::sleep(POLL_INTERVAL);
             }
         })?;
     Ok(rx)
@@ -1,25 +1,31 @@
 //! Enable remote control of a tunnel render slave over the network.
 //! Advertise this slave for control over DNS-SD, handling requests on a 0mq socket.
-//! Very basic control; every message received is a full configuration struct, and the receipt of
-//! a message completely tears down an existing show and brings up a new one using the new
-//! parameters.
+//! Every message received is either a full configuration struct, which completely tears down an
+//! existing show and brings up a new one using the new parameters, or a targeted command that is
+//! routed to the already-running show so it can apply the change live without a restart.
+//! A look change can also be staged ahead of a future apply rather than applied immediately,
+//! which `Administrator::broadcast` uses to flip a whole flock of clients over at once.
 //! Also provide the tools needed for simple remote administration.
 
 use crate::config::{ClientConfig, Resolution};
 use crate::draw::{Transform, TransformDirection};
-use crate::show::Show;
+use crate::log_buffer::{self, LogRecord};
+use crate::show::{Look, Show, ShowCommand};
 use hostname;
 use lazy_static::lazy_static;
 use log::{error, info};
 use regex::Regex;
 use rmp_serde::decode::from_read;
 use rmp_serde::encode::write;
+use serde::{Deserialize, Serialize};
+use simple_error::bail;
+use std::collections::HashMap;
 use std::error::Error;
 use std::io::{stdin, stdout, Write};
 use std::sync::mpsc::{channel, Sender};
 use std::thread;
-use std::time::Duration;
-use tunnels_lib::RunFlag;
+use std::time::{Duration, Instant};
+use tunnels_lib::{RunFlag, Timestamp};
 use zero_configure::{run_service_req_rep, Controller};
 use zmq::Context;
 
@@ -28,6 +34,46 @@ const PORT: u16 = 15000;
 
 // --- client remote control ---
 
+/// A command sent from the administrator to a running (or not-yet-running) client.
+/// `FullConfig` preserves the original teardown-and-rebuild semantics; every other variant is
+/// applied directly to the running show's event loop without dropping its OpenGL state.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum RemoteCommand {
+    /// Tear down any running show and start a fresh one with this configuration.
+    FullConfig(ClientConfig),
+    /// Change the window/render resolution of the running show.
+    SetResolution(Resolution),
+    /// Change the geometric transform applied to the running show.
+    SetTransform(Option<Transform>),
+    /// Change the render delay of the running show.
+    SetRenderDelay(Duration),
+    /// Toggle fullscreen on the running show's window.
+    SetFullscreen(bool),
+    /// Stop the running show, if any, without starting a new one.
+    Stop,
+    /// Phase one of a synchronized fleet-wide look change (see `Administrator::broadcast`):
+    /// stage this look without disturbing the currently-running show. `apply_after` is relative
+    /// to receipt of this message rather than to when the controller sent it, so that every
+    /// client in the barrier converges on roughly the same wall-clock moment regardless of its
+    /// individual round-trip latency to the controller.
+    StageLook { look: Look, apply_after: Duration },
+    /// Phase one abort path: discard a previously staged look without applying it, e.g. because
+    /// some other client in the barrier failed to ACK in time.
+    AbortStagedLook,
+    /// Fetch every buffered log record more recent than `since`, so a failed or misbehaving show
+    /// can be diagnosed without SSHing into the client machine. Served regardless of whether a
+    /// show is currently running.
+    FetchLog { since: Timestamp },
+}
+
+/// What the remote service thread sends to the show-hosting thread.
+enum RemoteEvent {
+    /// Start up a brand new show with this configuration.
+    NewShow(ClientConfig, RunFlag),
+    /// Apply a live command to whatever show is currently running.
+    Command(ShowCommand),
+}
+
 /// Run this client as a remotely configurable service.
 /// The show starts up in the main thread to ensure we don't end up with issues trying to pass
 /// OpenGL resources between threads.
@@ -35,7 +81,7 @@ const PORT: u16 = 15000;
 /// channel.
 /// Panics if the remote service thread fails to spawn.
 pub fn run_remote(ctx: Context) {
-    // Create a channel to wait on config requests.
+    // Create a channel to wait on config requests and live commands.
     let (send, recv) = channel();
 
     // Spawn a thread to receive config requests.
@@ -47,23 +93,45 @@ pub fn run_remote(ctx: Context) {
         })
         .expect("Failed to spawn remote service thread");
 
-    loop {
+    'shows: loop {
         info!("Waiting for show configuration.");
-        // Wait on a config from the remote service.
-        let (config, run_flag) = recv.recv().expect("Remote service thread hung up.");
+        // Wait on a config from the remote service, discarding any live commands that arrive
+        // while there's no show running to apply them to.
+        let (config, run_flag) = loop {
+            match recv.recv().expect("Remote service thread hung up.") {
+                RemoteEvent::NewShow(config, run_flag) => break (config, run_flag),
+                RemoteEvent::Command(_) => {
+                    info!("Ignoring live command; no show is currently running.");
+                }
+            }
+        };
 
         info!("Starting a new show with configuration: {:?}", config);
         // Start up a fresh show.
         match Show::new(config, ctx.clone(), run_flag) {
             Ok(mut show) => {
                 info!("Show initialized, starting event loop.");
-                // Run the show until the remote thread tells us to quit.
-                show.run();
+                // Run the show until the remote thread tells us to quit, applying any live
+                // commands that arrive in the meantime.
+                loop {
+                    while let Ok(event) = recv.try_recv() {
+                        match event {
+                            RemoteEvent::Command(cmd) => show.apply_command(cmd),
+                            RemoteEvent::NewShow(..) => {
+                                info!("Full reconfiguration requested; tearing down show.");
+                                continue 'shows;
+                            }
+                        }
+                    }
+                    if !show.step() {
+                        break;
+                    }
+                }
                 info!("Show exited.");
             }
 
-            // TODO: enable some kind of remote logging so we can collect these messages at the
-            // controller.
+            // Logged locally via `error!` and also retained in this client's log ring buffer, so
+            // the administrator can pull it with `Administrator::fetch_log` without SSHing in.
             Err(e) => error!("Failed to initialize show: {}", e),
         }
     }
@@ -72,14 +140,18 @@ pub fn run_remote(ctx: Context) {
 /// Run the remote discovery and configuration service, passing config states and cancellation
 /// flags back to the main thread.
 /// Panics if the service completes with an error.
-pub fn run_remote_service(ctx: Context, sender: Sender<(ClientConfig, RunFlag)>) {
+pub fn run_remote_service(ctx: Context, sender: Sender<RemoteEvent>) {
     // Run flag for currently-executing show, if there is one.
     let mut running_flag: Option<RunFlag> = None;
 
-    run_service_req_rep(ctx, SERVICE_NAME, PORT, |request_buffer| {
-        // Attempt to deserialize this request buffer as a client configuration.
+    // This service currently runs for the life of the process; there's no external trigger to
+    // stop it early, but `run_service_req_rep` still needs a flag to poll against.
+    let service_run = RunFlag::new();
+
+    run_service_req_rep(ctx, SERVICE_NAME, PORT, service_run, |request_buffer| {
+        // Attempt to deserialize this request buffer as a remote command.
         match deserialize_config(request_buffer) {
-            Ok(config) => {
+            Ok(RemoteCommand::FullConfig(config)) => {
                 // If there's currently a show running, pull the run flag out and stop it.
                 let show_stop_message = if let Some(ref mut flag) = running_flag {
                     flag.stop();
@@ -93,7 +165,7 @@ pub fn run_remote_service(ctx: Context, sender: Sender<(ClientConfig, RunFlag)>)
                 running_flag = Some(new_run_flag.clone());
 
                 // Send the config and flag back to the show thread.
-                if let Err(e) = sender.send((config, new_run_flag)) {
+                if let Err(e) = sender.send(RemoteEvent::NewShow(config, new_run_flag)) {
                     format!(
                         "{}\nError trying to start new show: {}.",
                         show_stop_message, e
@@ -102,20 +174,71 @@ pub fn run_remote_service(ctx: Context, sender: Sender<(ClientConfig, RunFlag)>)
                     // everything is OK
                     format!("{}\nStarting a new show.", show_stop_message)
                 }
+                .into_bytes()
+            }
+            Ok(RemoteCommand::Stop) => {
+                if let Some(ref mut flag) = running_flag {
+                    flag.stop();
+                    running_flag = None;
+                    "Stopped the running show.".to_string()
+                } else {
+                    "No show was running.".to_string()
+                }
+                .into_bytes()
+            }
+            // Served regardless of whether a show is currently running, since this is about the
+            // client process's own log history rather than the show itself.
+            Ok(RemoteCommand::FetchLog { since }) => {
+                let records = log_buffer::records_since(since);
+                let mut serialized = Vec::new();
+                if let Err(e) = write(&mut serialized, &records) {
+                    return format!("Failed to serialize log records: {}", e).into_bytes();
+                }
+                serialized
+            }
+            Ok(other) => {
+                let cmd = match other {
+                    RemoteCommand::SetResolution(r) => ShowCommand::SetResolution(r),
+                    RemoteCommand::SetTransform(t) => ShowCommand::SetTransform(t),
+                    RemoteCommand::SetRenderDelay(d) => ShowCommand::SetRenderDelay(d),
+                    RemoteCommand::SetFullscreen(f) => ShowCommand::SetFullscreen(f),
+                    RemoteCommand::StageLook { look, apply_after } => {
+                        ShowCommand::StageLook(look, Instant::now() + apply_after)
+                    }
+                    RemoteCommand::AbortStagedLook => ShowCommand::AbortStagedLook,
+                    RemoteCommand::FullConfig(_)
+                    | RemoteCommand::Stop
+                    | RemoteCommand::FetchLog { .. } => unreachable!(),
+                };
+                if running_flag.is_none() {
+                    "No show is currently running to apply this command to.".to_string()
+                } else if let Err(e) = sender.send(RemoteEvent::Command(cmd)) {
+                    format!("Error trying to apply live command: {}.", e)
+                } else {
+                    "Applied live command.".to_string()
+                }
+                .into_bytes()
             }
-            Err(e) => format!("Could not parse request as a show configuration:\n{}", e),
+            Err(e) => format!("Could not parse request as a remote command:\n{}", e).into_bytes(),
         }
-        .into_bytes()
     })
     .expect("Remote configuration service crashed")
 }
 
-fn deserialize_config(buffer: &[u8]) -> Result<ClientConfig, String> {
+fn deserialize_config(buffer: &[u8]) -> Result<RemoteCommand, String> {
     from_read(buffer).map_err(|e| e.to_string())
 }
 
 // --- remote administration ---
 
+/// How long to wait for a single client to ACK a staged look before giving up on the barrier.
+const BROADCAST_ACK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How far into the future (from each client's receipt of the stage message) to schedule the
+/// synchronized apply, giving every client in the barrier time to receive and prepare the look
+/// before any of them needs to flip.
+const BROADCAST_APPLY_LEAD: Duration = Duration::from_millis(500);
+
 /// Provide an API for administering a flock of tunnel clients.
 pub struct Administrator {
     /// zero_configure service controller.
@@ -137,20 +260,91 @@ impl Administrator {
     /// Command a particular client to run using the provided configuration.
     /// If the client is available, returns the string response from sending the config.
     /// Returns Err if the specified client doesn't exist.
+    /// This always tears down any show currently running on the client.
     pub fn run_with_config(
         &self,
         client: &str,
         config: ClientConfig,
     ) -> Result<String, Box<dyn Error>> {
-        // Serialize the config.
+        self.send_command(client, RemoteCommand::FullConfig(config))
+    }
+
+    /// Send a live command to a particular client's already-running show.
+    /// If the client is available, returns the string response from sending the command.
+    /// Returns Err if the specified client doesn't exist.
+    pub fn send_command(
+        &self,
+        client: &str,
+        command: RemoteCommand,
+    ) -> Result<String, Box<dyn Error>> {
+        // Serialize the command.
         let mut serialized = Vec::new();
-        write(&mut serialized, &config)?;
+        write(&mut serialized, &command)?;
 
         // Send the serialized command.
         let response = self.controller.send(client, &serialized)?;
         // Parse the string response.
         Ok(String::from_utf8(response)?)
     }
+
+    /// Stage `look` on every currently discovered client and have them flip to it together.
+    /// Phase one sends `StageLook` to each client in turn and waits for its ACK; if every client
+    /// acks within `BROADCAST_ACK_TIMEOUT`, they will all flip to the look at roughly the same
+    /// moment, `BROADCAST_APPLY_LEAD` after each received its stage message. If any client fails
+    /// to ACK in time, the barrier is aborted: every client that did ack is told to discard its
+    /// staged look, and this returns the failure that triggered the abort.
+    pub fn broadcast(&self, look: Look) -> Result<(), Box<dyn Error>> {
+        let clients = self.clients();
+        let mut acked = Vec::new();
+        let mut failure = None;
+
+        for client in &clients {
+            let command = RemoteCommand::StageLook {
+                look,
+                apply_after: BROADCAST_APPLY_LEAD,
+            };
+            let mut serialized = Vec::new();
+            write(&mut serialized, &command)?;
+            match self
+                .controller
+                .send_with_timeout(client, &serialized, BROADCAST_ACK_TIMEOUT)
+            {
+                Ok(_) => acked.push(client.clone()),
+                Err(e) => {
+                    failure = Some(format!(
+                        "client '{}' failed to ack staged look: {}",
+                        client, e
+                    ));
+                    break;
+                }
+            }
+        }
+
+        if let Some(failure) = failure {
+            for client in &acked {
+                if let Err(e) = self.send_command(client, RemoteCommand::AbortStagedLook) {
+                    error!("Failed to abort staged look on client '{}': {}", client, e);
+                }
+            }
+            bail!("Aborted synchronized look broadcast: {}", failure);
+        }
+
+        Ok(())
+    }
+
+    /// Fetch every log record a client has buffered since `since`, so a failed or misbehaving
+    /// show can be diagnosed without SSHing into that machine. Pass `Timestamp(i64::MIN)` to
+    /// fetch everything the client currently has buffered.
+    pub fn fetch_log(
+        &self,
+        client: &str,
+        since: Timestamp,
+    ) -> Result<Vec<LogRecord>, Box<dyn Error>> {
+        let mut serialized = Vec::new();
+        write(&mut serialized, &RemoteCommand::FetchLog { since })?;
+        let response = self.controller.send(client, &serialized)?;
+        Ok(from_read(&response[..])?)
+    }
 }
 
 /// Read a single line from stdin and return it as a string.
@@ -308,6 +502,28 @@ where
     )
 }
 
+/// Interactive series of user prompts, producing a look to broadcast to the whole flock.
+fn configure_look() -> Look {
+    let resolution = prompt(
+        "Specify display resolution (widthxheight or heightp for 16:9)",
+        parse_resolution,
+    );
+    let fullscreen = prompt_y_n("Fullscreen");
+    let transformation = if prompt_y_n("Flip horizontal") {
+        Some(Transform::Flip(TransformDirection::Horizontal))
+    } else {
+        None
+    };
+    let render_delay = prompt("Client render delay in seconds (default 0.015)", parse_f64);
+
+    Look {
+        resolution,
+        transformation,
+        render_delay: Duration::from_secs_f64(render_delay),
+        fullscreen,
+    }
+}
+
 /// Slightly janky interactive command line utility for administering a fleet of tunnel clients.
 pub fn administrate() {
     let host = hostname::get()
@@ -322,9 +538,15 @@ pub fn administrate() {
 
     let usage = "list    List the available clients.
 conf    Configure a client.
+bcast   Broadcast a synchronized look to every client.
+log     Fetch recent log records from a client.
 quit    Quit.";
     println!("Administrator started.");
 
+    // Remember how far into each client's log we've already read, so repeated `log` commands
+    // only print what's new since the last fetch.
+    let mut log_cursor: HashMap<String, Timestamp> = HashMap::new();
+
     let parse_client_name = |name: &str| -> Result<String, String> {
         let clients = admin.clients();
         if clients.iter().any(|client| name == client) {
@@ -356,6 +578,34 @@ quit    Quit.";
                     }
                 }
             }
+            "bcast" | "b" => {
+                let look = configure_look();
+                match admin.broadcast(look) {
+                    Ok(()) => println!("Look applied across the flock."),
+                    Err(e) => println!("Broadcast failed: {}", e),
+                }
+            }
+            "log" | "g" => {
+                let client_name = prompt("Enter client name", &parse_client_name);
+                let since = *log_cursor.get(&client_name).unwrap_or(&Timestamp(i64::MIN));
+                match admin.fetch_log(&client_name, since) {
+                    Ok(records) if records.is_empty() => {
+                        println!("No new log records for '{}'.", client_name);
+                    }
+                    Ok(records) => {
+                        for record in &records {
+                            println!(
+                                "[{}] {:?}: {}",
+                                record.timestamp, record.level, record.message
+                            );
+                        }
+                        if let Some(last) = records.last() {
+                            log_cursor.insert(client_name, last.timestamp);
+                        }
+                    }
+                    Err(e) => println!("Failed to fetch log from '{}': {}", client_name, e),
+                }
+            }
             "quit" | "q" => {
                 break;
             }
@@ -0,0 +1,164 @@
+//! Abstracts the drawing surface an `ArcSegment`/`Snapshot` gets rendered onto, so the rest of
+//! the client doesn't need to know whether it's ultimately a Piston `Graphics` implementor or a
+//! wgpu surface. Which concrete backend is compiled in is selected via the `render-piston` /
+//! `render-wgpu` cargo features (see `render_piston`/`render_wgpu`), mirroring how other projects
+//! expose `opengl_renderer`/`wgpu_renderer` backends behind a common trait.
+//!
+//! The color and transform math that both backends must agree on - HSV-to-RGB conversion, the
+//! alpha-vs-multiply `val`/`level` blend, and mapping a segment's normalized coordinates through
+//! the client's resolution/transform config - lives here as backend-agnostic code, so a Piston
+//! frame and a wgpu frame of the same snapshot are pixel-identical.
+use crate::config::ClientConfig;
+use crate::draw::{apply_geometry, GeometryTransform, Transform, TransformDirection};
+use serde::{Deserialize, Serialize};
+use tunnels_lib::ArcSegment;
+
+/// One independently-framed output of the show: its own transform, resolution and screen center,
+/// so a client can fan the same `Snapshot` out to several physical outputs (e.g. one per
+/// projector in a multi-surface install) without running multiple client processes.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OutputTarget {
+    /// Used to rescale unit-scale coordinates onto this target's pixel space.
+    pub x_resolution: u32,
+    pub y_resolution: u32,
+    /// Computed pixel x-offset of this target's drawing coordinate system.
+    pub x_center: f64,
+    /// Computed pixel y-offset of this target's drawing coordinate system.
+    pub y_center: f64,
+    /// Geometric transformation to optionally apply to this target's framing.
+    pub transformation: Option<Transform>,
+    /// Coordinate-space corrections (rotation, scale, translation, keystone, ...) applied in
+    /// order on top of `transformation`, for example to correct an off-axis projector. See
+    /// `crate::draw::GeometryTransform`.
+    pub geometry: Vec<GeometryTransform>,
+}
+
+impl OutputTarget {
+    /// Build a target centered in the middle of the given resolution, the common case for a
+    /// single-output client.
+    pub fn centered(
+        resolution: (u32, u32),
+        transformation: Option<Transform>,
+        geometry: Vec<GeometryTransform>,
+    ) -> Self {
+        let (x_resolution, y_resolution) = resolution;
+        Self {
+            x_resolution,
+            y_resolution,
+            x_center: f64::from(x_resolution / 2),
+            y_center: f64::from(y_resolution / 2),
+            transformation,
+            geometry,
+        }
+    }
+}
+
+/// One arc segment's worth of draw data, fully resolved against one output target's resolution
+/// and transform. Every backend consumes the same `ArcInstance`s, so none of them re-derive this
+/// math themselves.
+#[derive(Copy, Clone, Debug)]
+pub struct ArcInstance {
+    /// Center of the arc, in window pixel coordinates.
+    pub center: (f64, f64),
+    /// Rotation of the arc, in radians.
+    pub rotation: f64,
+    pub rad_x: f64,
+    pub rad_y: f64,
+    pub thickness: f64,
+    /// RGBA, each channel in 0.0..=1.0.
+    pub color: [f32; 4],
+    /// Arc start angle, in radians.
+    pub start: f64,
+    /// Arc stop angle, in radians.
+    pub stop: f64,
+}
+
+impl ArcInstance {
+    const TWOPI: f64 = 2.0 * std::f64::consts::PI;
+
+    /// Derive this segment's fully-resolved instance data, applying the client's critical size,
+    /// alpha/multiply blending configuration, and the given target's resolution, center offset
+    /// and transform.
+    pub fn from_segment(segment: &ArcSegment, cfg: &ClientConfig, target: &OutputTarget) -> Self {
+        let thickness = segment.thickness * cfg.critical_size * cfg.thickness_scale / 2.0;
+
+        let (val, alpha) = if cfg.alpha_blend {
+            (segment.val, segment.level)
+        } else {
+            (segment.val * segment.level, 1.0)
+        };
+        let color = hsv_to_rgb(segment.hue, segment.sat, val, alpha);
+
+        // Mirror and Kaleidoscope are applied upstream, in `Draw<B> for ArcSegment`, by
+        // submitting extra already-transformed copies of the segment; only Flip (a whole-image
+        // transform with no extra copies) needs to be resolved here. `geometry` then applies on
+        // top, in order, to correct for the target's physical placement.
+        let (x0, y0) = match target.transformation {
+            Some(Transform::Flip(TransformDirection::Horizontal)) => (-1.0 * segment.x, segment.y),
+            Some(Transform::Flip(TransformDirection::Vertical)) => (segment.x, -1.0 * segment.y),
+            None | Some(Transform::Mirror(_)) | Some(Transform::Kaleidoscope { .. }) => {
+                (segment.x, segment.y)
+            }
+        };
+        let (x0, y0) = apply_geometry(x0, y0, &target.geometry);
+        let center = (
+            x0 * f64::from(target.x_resolution) + target.x_center,
+            y0 * f64::from(target.y_resolution) + target.y_center,
+        );
+
+        Self {
+            center,
+            rotation: segment.rot_angle * Self::TWOPI,
+            rad_x: segment.rad_x * cfg.critical_size,
+            rad_y: segment.rad_y * cfg.critical_size,
+            thickness,
+            color,
+            start: segment.start * Self::TWOPI,
+            stop: segment.stop * Self::TWOPI,
+        }
+    }
+}
+
+/// Convert an HSV color to RGBA, alpha channel passed through unchanged.
+pub fn hsv_to_rgb(hue: f64, sat: f64, val: f64, alpha: f64) -> [f32; 4] {
+    #[inline]
+    fn rgba(r: f64, g: f64, b: f64, a: f64) -> [f32; 4] {
+        [r as f32, g as f32, b as f32, a as f32]
+    }
+
+    if sat == 0.0 {
+        rgba(val, val, val, alpha)
+    } else {
+        let var_h = if hue == 1.0 { 0.0 } else { hue * 6.0 };
+
+        let var_i = var_h.floor();
+        let var_1 = val * (1.0 - sat);
+        let var_2 = val * (1.0 - sat * (var_h - var_i));
+        let var_3 = val * (1.0 - sat * (1.0 - (var_h - var_i)));
+
+        match var_i as i64 {
+            0 => rgba(val, var_3, var_1, alpha),
+            1 => rgba(var_2, val, var_1, alpha),
+            2 => rgba(var_1, val, var_3, alpha),
+            3 => rgba(var_1, var_2, val, alpha),
+            4 => rgba(var_3, var_1, val, alpha),
+            _ => rgba(val, var_1, var_2, alpha),
+        }
+    }
+}
+
+/// A drawing surface that can accept a frame's worth of arc segments, abstracting over the
+/// specific graphics API used to put pixels on screen.
+pub trait RenderBackend {
+    /// Begin a new frame, clearing the surface to black.
+    fn begin_frame(&mut self);
+
+    /// Set the flip transform applied to everything submitted for the rest of this frame.
+    fn set_transform(&mut self, transform: Option<Transform>);
+
+    /// Submit one arc segment's instance data to be drawn as part of the current frame.
+    fn submit_arc(&mut self, instance: &ArcInstance);
+
+    /// Flush all arcs submitted since `begin_frame` to the surface.
+    fn end_frame(&mut self);
+}
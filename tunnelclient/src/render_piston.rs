@@ -0,0 +1,63 @@
+//! Piston/OpenGL `RenderBackend`: immediate-mode, issuing one `CircleArc` draw call per arc
+//! segment submitted. This is the original drawing path, now expressed in terms of
+//! `RenderBackend` rather than being the only way `Draw` knows how to put pixels on screen.
+use graphics::{clear, rectangle, CircleArc, Context, Graphics, Transformed};
+
+use crate::draw::{Transform, TransformDirection};
+use crate::render_backend::{ArcInstance, RenderBackend};
+
+pub struct PistonBackend<'g, G: Graphics> {
+    context: Context,
+    gl: &'g mut G,
+    transform: Option<Transform>,
+}
+
+impl<'g, G: Graphics> PistonBackend<'g, G> {
+    pub fn new(context: Context, gl: &'g mut G) -> Self {
+        Self {
+            context,
+            gl,
+            transform: None,
+        }
+    }
+}
+
+impl<'g, G: Graphics> RenderBackend for PistonBackend<'g, G> {
+    fn begin_frame(&mut self) {
+        clear([0.0, 0.0, 0.0, 1.0], self.gl);
+    }
+
+    fn set_transform(&mut self, transform: Option<Transform>) {
+        self.transform = transform;
+    }
+
+    fn submit_arc(&mut self, instance: &ArcInstance) {
+        let transform = {
+            let t = self
+                .context
+                .transform
+                .trans(instance.center.0, instance.center.1)
+                .rot_rad(instance.rotation);
+            match self.transform {
+                Some(Transform::Flip(TransformDirection::Horizontal)) => t.flip_h(),
+                Some(Transform::Flip(TransformDirection::Vertical)) => t.flip_v(),
+                // Mirror/Kaleidoscope are resolved into extra plain instances upstream.
+                None | Some(Transform::Mirror(_)) | Some(Transform::Kaleidoscope { .. }) => t,
+            }
+        };
+
+        let bound = rectangle::centered([0.0, 0.0, instance.rad_x, instance.rad_y]);
+
+        CircleArc::new(instance.color, instance.thickness, instance.start, instance.stop).draw(
+            bound,
+            &Default::default(),
+            transform,
+            self.gl,
+        );
+    }
+
+    fn end_frame(&mut self) {
+        // Piston's immediate-mode draw calls have already landed on `gl` by the time
+        // `submit_arc` returns; nothing to flush.
+    }
+}
@@ -0,0 +1,196 @@
+//! wgpu `RenderBackend`: batches every `ArcSegment` submitted during a frame into a single
+//! instance buffer and issues one instanced draw call per frame, rather than Piston's one draw
+//! call per segment. This is the main win for large looks with thousands of arcs.
+//!
+//! Each instance carries center, rotation, radii, thickness, HSV-derived RGBA color and arc
+//! start/stop angle; the vertex shader expands a shared unit quad per instance and the fragment
+//! shader clips it down to the arc using `start`/`stop`, so the geometry cost stays flat as arc
+//! count grows.
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::draw::{Transform, TransformDirection};
+use crate::render_backend::{ArcInstance, RenderBackend};
+
+/// GPU-layout instance data for one arc segment. Mirrors `ArcInstance`, but with the flip
+/// transform already folded in and angles/position in the units the shader expects.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct ArcInstanceRaw {
+    center: [f32; 2],
+    rotation: f32,
+    rad: [f32; 2],
+    thickness: f32,
+    color: [f32; 4],
+    start: f32,
+    stop: f32,
+    _pad: [f32; 2],
+}
+
+impl ArcInstanceRaw {
+    fn from_instance(instance: &ArcInstance, transform: Option<Transform>) -> Self {
+        let (cx, cy) = instance.center;
+        let (cx, cy, rotation) = match transform {
+            // Mirroring the center position alone isn't enough to produce an arc that looks
+            // reflected once it's rotated; the rotation direction has to flip too.
+            Some(Transform::Flip(TransformDirection::Horizontal)) => (-cx, cy, -instance.rotation),
+            Some(Transform::Flip(TransformDirection::Vertical)) => (cx, -cy, -instance.rotation),
+            // Mirror/Kaleidoscope are resolved into extra plain instances upstream.
+            None | Some(Transform::Mirror(_)) | Some(Transform::Kaleidoscope { .. }) => {
+                (cx, cy, instance.rotation)
+            }
+        };
+        Self {
+            center: [cx as f32, cy as f32],
+            rotation: rotation as f32,
+            rad: [instance.rad_x as f32, instance.rad_y as f32],
+            thickness: instance.thickness as f32,
+            color: instance.color,
+            start: instance.start as f32,
+            stop: instance.stop as f32,
+            _pad: [0.0, 0.0],
+        }
+    }
+}
+
+/// Batches arcs for one frame and flushes them as a single instanced draw call.
+pub struct WgpuBackend {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    target: Option<wgpu::TextureView>,
+    instances: Vec<ArcInstanceRaw>,
+    transform: Option<Transform>,
+}
+
+impl WgpuBackend {
+    pub fn new(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        surface_format: wgpu::TextureFormat,
+    ) -> Self {
+        let pipeline = Self::build_pipeline(&device, surface_format);
+        Self {
+            device,
+            queue,
+            pipeline,
+            target: None,
+            instances: Vec::new(),
+            transform: None,
+        }
+    }
+
+    /// Point this backend at the surface texture view to draw into for the upcoming frame. Must
+    /// be called before `begin_frame` every frame.
+    pub fn set_target(&mut self, target: wgpu::TextureView) {
+        self.target = Some(target);
+    }
+
+    fn build_pipeline(device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("arc_instance_shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("arc_instance.wgsl").into()),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("arc_instance_layout"),
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("arc_instance_pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<ArcInstanceRaw>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x2, // center
+                        1 => Float32,   // rotation
+                        2 => Float32x2, // rad
+                        3 => Float32,   // thickness
+                        4 => Float32x4, // color
+                        5 => Float32,   // start
+                        6 => Float32,   // stop
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+}
+
+impl RenderBackend for WgpuBackend {
+    fn begin_frame(&mut self) {
+        self.instances.clear();
+    }
+
+    fn set_transform(&mut self, transform: Option<Transform>) {
+        self.transform = transform;
+    }
+
+    fn submit_arc(&mut self, instance: &ArcInstance) {
+        self.instances
+            .push(ArcInstanceRaw::from_instance(instance, self.transform));
+    }
+
+    fn end_frame(&mut self) {
+        let Some(target) = self.target.take() else {
+            return;
+        };
+
+        let instance_buffer = (!self.instances.is_empty()).then(|| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("arc_instance_buffer"),
+                    contents: bytemuck::cast_slice(&self.instances),
+                    usage: wgpu::BufferUsages::VERTEX,
+                })
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("arc_instance_encoder"),
+            });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("arc_instance_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            if let Some(instance_buffer) = &instance_buffer {
+                pass.set_pipeline(&self.pipeline);
+                pass.set_vertex_buffer(0, instance_buffer.slice(..));
+                // Four vertices per instance, expanded into the arc's bounding quad in the shader.
+                pass.draw(0..4, 0..self.instances.len() as u32);
+            }
+        }
+        self.queue.submit(Some(encoder.finish()));
+    }
+}
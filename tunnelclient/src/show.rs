@@ -1,20 +1,66 @@
-use crate::config::ClientConfig;
-use crate::draw::Draw;
+use crate::capture::{self, CaptureFrame, CaptureHandle};
+use crate::clock_source::{self, HostClock};
+use crate::config::{ClientConfig, Resolution};
+use crate::draw::{Draw, Transform};
+use crate::render_backend::{OutputTarget, RenderBackend};
+use crate::render_piston::PistonBackend;
+use crate::snapshot_manager::{
+    SnapshotFetchResult, SnapshotManager, SnapshotManagerHandle, VecDequeSnapshotManager,
+};
 use anyhow::{anyhow, Result};
-use graphics::clear;
-use log::{error, info};
+use gl;
+use log::{error, info, warn};
 use opengl_graphics::{GlGraphics, OpenGL};
 use piston_window::prelude::*;
 use sdl2_window::Sdl2Window;
+use serde::{Deserialize, Serialize};
+use std::net::UdpSocket;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
+use tunnels_lib::multicast::Reassembler;
+use tunnels_lib::shm;
 use tunnels_lib::RunFlag;
-use tunnels_lib::Snapshot;
+use tunnels_lib::{wire, Snapshot, Timestamp};
 use zero_configure::pub_sub::Receiver;
 use zmq::Context;
 
-pub type SnapshotManagerHandle = Arc<Mutex<Option<SnapshotHandle>>>;
-pub type SnapshotHandle = Arc<Snapshot>;
+/// Idle poll rate used when `redraw_on_change` is set: slow enough to noticeably cut GPU/CPU use
+/// on a channel showing static content, but fast enough that a new snapshot still shows up
+/// promptly. A true Piston "lazy" event loop would stop ticking entirely between window-system
+/// events, which would also stop us from ever noticing a snapshot arriving on a background
+/// thread, so this just lowers the tick rate instead of eliminating it.
+const IDLE_POLL_FPS: u64 = 30;
+
+/// The subset of a client's configuration that can be changed live, bundled together so that a
+/// fleet-wide "look" change can be staged and flipped atomically rather than one field at a time.
+/// Mirrors the fields covered individually by `ShowCommand`'s `SetXxx` variants.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Look {
+    pub resolution: Resolution,
+    pub transformation: Option<Transform>,
+    pub render_delay: Duration,
+    pub fullscreen: bool,
+}
+
+/// A live reconfiguration to apply to a running show without tearing it down.
+#[derive(Debug)]
+pub enum ShowCommand {
+    SetResolution(Resolution),
+    SetTransform(Option<Transform>),
+    SetRenderDelay(Duration),
+    SetFullscreen(bool),
+    /// Stage a look for synchronized apply, replacing any look already staged. The show keeps
+    /// rendering with its current configuration until `apply_at` arrives.
+    StageLook(Look, Instant),
+    /// Flip to the currently staged look, if any. Fired locally by the show's event loop once
+    /// `apply_at` arrives; see `Administrator::broadcast` for how the whole flock is kept in
+    /// sync without a second round-trip over the network.
+    ApplyStagedLook,
+    /// Discard the currently staged look without applying it, e.g. because some other client in
+    /// the synchronized-apply barrier failed to acknowledge its own staged look in time.
+    AbortStagedLook,
+}
 
 /// Top-level structure that owns all of the show data.
 pub struct Show {
@@ -23,6 +69,21 @@ pub struct Show {
     cfg: ClientConfig,
     run_flag: RunFlag,
     window: PistonWindow<Sdl2Window>,
+    /// A look staged for synchronized apply, and the local instant at which to flip to it.
+    staged_look: Option<(Look, Instant)>,
+    /// Smoothed estimate of the current time on the host, used to pick a playback time that sits
+    /// `cfg.target_delay` behind the host's clock. Backed by whichever backend `cfg.clock_source`
+    /// selects; see `crate::clock_source`.
+    host_clock: Arc<Mutex<Box<dyn HostClock>>>,
+    /// Local instant `host_clock` was last advanced, so `render` can pass it the correct
+    /// elapsed-time step regardless of how irregularly render ticks arrive.
+    last_sync_update: Instant,
+    /// Timestamp of the last snapshot actually drawn, so `render` can skip re-rasterizing
+    /// identical content when `cfg.redraw_on_change` is set.
+    last_drawn_time: Option<Timestamp>,
+    /// Handle to the frame capture encoder thread, if `cfg.capture` is set. Cleared if the
+    /// encoder thread exits, so a failed capture doesn't also take down the show.
+    capture: Option<CaptureHandle>,
 }
 
 impl Show {
@@ -30,9 +91,18 @@ impl Show {
         info!("Running on video channel {}.", cfg.video_channel);
 
         // Set up snapshot reception and management.
-        let snapshot_manager = Arc::new(Mutex::new(None));
+        let snapshot_manager: SnapshotManagerHandle =
+            Arc::new(Mutex::new(Box::new(VecDequeSnapshotManager::default())));
         receive_snapshots(&ctx, &cfg, snapshot_manager.clone(), run_flag.clone())?;
 
+        // Build whichever host clock backend `cfg.clock_source` selects. The builtin backend gets
+        // a rough estimate locked in immediately, then keeps refining it in the background so
+        // `render` always has a recent estimate to query.
+        let host_clock = clock_source::build(&cfg.clock_source, &cfg.server_hostname, ctx.clone())
+            .map_err(|e| anyhow!("failed to initialize host clock: {e}"))?;
+
+        let capture = cfg.capture.clone().map(capture::spawn).transpose()?;
+
         let opengl = OpenGL::V3_2;
 
         // Create the window.
@@ -55,6 +125,11 @@ impl Show {
         // timesteps to update args; since we only use this for interpolating
         // timesync, it isn't a big deal.
         window.set_max_fps(120);
+        if cfg.redraw_on_change {
+            // Idle at a lower tick rate rather than free-running at 120fps; `render` still only
+            // actually draws when a new snapshot has arrived.
+            window.set_max_fps(IDLE_POLL_FPS);
+        }
 
         Ok(Show {
             gl: GlGraphics::new(opengl),
@@ -62,49 +137,269 @@ impl Show {
             cfg,
             run_flag,
             window,
+            staged_look: None,
+            host_clock,
+            last_sync_update: Instant::now(),
+            last_drawn_time: None,
+            capture,
         })
     }
 
     /// Run the show's event loop.
     pub fn run(&mut self) {
-        // Run the event loop.
-        while let Some(e) = self.window.next() {
-            if !self.run_flag.should_run() {
-                info!("Quit flag tripped, ending show.");
-                break;
-            }
+        while self.step() {}
+    }
 
-            if let Some(r) = e.render_args() {
-                self.render(&r);
+    /// Process a single iteration of the event loop, rendering a frame if one is due.
+    /// Returns false once the show should exit, either because the window was closed or because
+    /// the run flag was tripped.
+    pub fn step(&mut self) -> bool {
+        let Some(e) = self.window.next() else {
+            // If the window is closed, the event loop will exit normally.  Flip the run flag to
+            // stop to ensure all of the services close down and we don't leak a timesync thread.
+            // TODO: hold onto the join handle for the timesync service?
+            self.run_flag.stop();
+            return false;
+        };
+
+        if !self.run_flag.should_run() {
+            info!("Quit flag tripped, ending show.");
+            return false;
+        }
+
+        // If a look has been staged for synchronized apply and its moment has arrived, flip to
+        // it now, before rendering this frame.
+        if matches!(self.staged_look, Some((_, apply_at)) if Instant::now() >= apply_at) {
+            self.apply_command(ShowCommand::ApplyStagedLook);
+        }
+
+        if let Some(r) = e.render_args() {
+            self.render(&r);
+        }
+        true
+    }
+
+    /// Apply a live reconfiguration to this show without tearing it down.
+    pub fn apply_command(&mut self, command: ShowCommand) {
+        match command {
+            ShowCommand::SetResolution(resolution) => {
+                info!("Applying live resolution change: {:?}", resolution);
+                let (x_resolution, y_resolution) = resolution;
+                self.window.window.set_size([x_resolution, y_resolution]);
+                self.cfg.x_resolution = x_resolution;
+                self.cfg.y_resolution = y_resolution;
+                self.cfg.critical_size =
+                    f64::from(std::cmp::min(x_resolution, y_resolution));
+                if let [target] = self.cfg.targets.as_mut_slice() {
+                    // The common single-output case: the one target tracks the window.
+                    *target = OutputTarget::centered(
+                        resolution,
+                        target.transformation,
+                        target.geometry.clone(),
+                    );
+                } else {
+                    warn!("Multiple output targets configured; leaving their framing untouched.");
+                }
+            }
+            ShowCommand::SetTransform(transform) => {
+                info!("Applying live transform change: {:?}", transform);
+                for target in &mut self.cfg.targets {
+                    target.transformation = transform;
+                }
+            }
+            ShowCommand::SetRenderDelay(delay) => {
+                info!("Applying live render delay change: {:?}", delay);
+                self.cfg.render_delay = delay;
+            }
+            ShowCommand::SetFullscreen(fullscreen) => {
+                info!("Applying live fullscreen change: {}", fullscreen);
+                self.window.window.set_fullscreen(fullscreen);
+                self.cfg.fullscreen = fullscreen;
+            }
+            ShowCommand::StageLook(look, apply_at) => {
+                info!("Staging a look for synchronized apply.");
+                self.staged_look = Some((look, apply_at));
+            }
+            ShowCommand::ApplyStagedLook => match self.staged_look.take() {
+                Some((look, _)) => {
+                    info!("Applying staged look.");
+                    self.apply_look(look);
+                }
+                None => warn!("No staged look to apply."),
+            },
+            ShowCommand::AbortStagedLook => {
+                if self.staged_look.take().is_some() {
+                    info!("Aborted staged look.");
+                } else {
+                    warn!("No staged look to abort.");
+                }
             }
         }
+    }
 
-        // If the window is closed, the event loop will exit normally.  Flip the run flag to stop
-        // to ensure all of the services close down and we don't leak a timesync thread.
-        // TODO: hold onto the join handle for the timesync service?
-        self.run_flag.stop();
+    /// Apply every field of a staged look as individual live reconfigurations.
+    fn apply_look(&mut self, look: Look) {
+        self.apply_command(ShowCommand::SetResolution(look.resolution));
+        self.apply_command(ShowCommand::SetTransform(look.transformation));
+        self.apply_command(ShowCommand::SetRenderDelay(look.render_delay));
+        self.apply_command(ShowCommand::SetFullscreen(look.fullscreen));
     }
 
-    /// Render a frame to the window.
+    /// Render a frame to the window. Selects the snapshot whose timestamp sits
+    /// `cfg.target_delay` behind the host's clock, giving the jitter buffer in
+    /// `snapshot_manager` room to absorb network jitter and reordering before a late frame would
+    /// otherwise force a stutter. If `cfg.redraw_on_change` is set and the selected snapshot is
+    /// unchanged since the last frame we actually drew, skips the `gl.draw` call and buffer swap
+    /// entirely, since re-rasterizing identical content wastes GPU and power on idle channels.
     fn render(&mut self, args: &RenderArgs) {
-        let Some(snapshot) = self.snapshot_manager.lock().unwrap().clone() else {
-            return;
+        let now = Instant::now();
+        let dt = now.duration_since(self.last_sync_update).as_secs_f64();
+        self.last_sync_update = now;
+        let synced_now = {
+            let mut host_clock = self.host_clock.lock().unwrap();
+            host_clock.update(dt);
+            host_clock.now()
         };
+        let target_time = synced_now - Timestamp::from_duration(self.cfg.target_delay);
 
-        self.gl.draw(args.viewport(), |c, gl| {
-            // Clear the screen.
-            clear([0.0, 0.0, 0.0, 1.0], gl);
+        let result = {
+            let mut manager = self.snapshot_manager.lock().unwrap();
+            let result = manager.get(target_time);
+            manager.update();
+            result
+        };
+        let snapshot = match result {
+            SnapshotFetchResult::Good(s)
+            | SnapshotFetchResult::MissingNewer(s)
+            | SnapshotFetchResult::MissingOlder(s) => s,
+            SnapshotFetchResult::NoData => return,
+            SnapshotFetchResult::Error(_) => {
+                warn!("Snapshot jitter buffer could not bracket the requested playback time.");
+                return;
+            }
+        };
+
+        if self.cfg.redraw_on_change && self.last_drawn_time == Some(snapshot.time) {
+            return;
+        }
+        self.last_drawn_time = Some(snapshot.time);
 
-            // Draw everything.
-            snapshot.layers.draw(&c, gl, &self.cfg);
+        let cfg = &self.cfg;
+        self.gl.draw(args.viewport(), |c, gl| {
+            let mut backend = PistonBackend::new(c, gl);
+            backend.begin_frame();
+            // Draw the same snapshot once per configured output target, e.g. once per
+            // physical projector sharing this window's canvas.
+            for target in &cfg.targets {
+                backend.set_transform(target.transformation);
+                snapshot.layers.draw(&mut backend, cfg, target);
+            }
+            backend.end_frame();
         });
+
+        if let Some(capture) = &self.capture {
+            let frame = read_framebuffer(args.viewport(), snapshot.time);
+            if capture.send(frame).is_err() {
+                warn!("Capture encoder thread exited; disabling capture for the rest of this run.");
+                self.capture = None;
+            }
+        }
+    }
+}
+
+/// Read the currently-bound (default) framebuffer back into an RGBA8 buffer, right after it was
+/// drawn into and before the window swaps buffers.
+fn read_framebuffer(viewport: Viewport, time: Timestamp) -> CaptureFrame {
+    let [width, height] = viewport.draw_size;
+    let mut rgba = vec![0u8; (width * height * 4) as usize];
+    unsafe {
+        gl::ReadPixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            rgba.as_mut_ptr() as *mut std::ffi::c_void,
+        );
+    }
+    CaptureFrame {
+        time,
+        width,
+        height,
+        rgba,
     }
 }
 
 /// Spawn a thread to receive snapshots.
 /// Inject them into the provided manager.
 /// The thread runs until the run flag is tripped.
-fn receive_snapshots(
+/// If `cfg.multicast` is set, joins the configured multicast group instead of using either of the
+/// other transports, since that's an explicit choice to match a server running the multicast
+/// render transport. Otherwise, if the server resolves to this machine and has already created
+/// this channel's shared-memory ring, reads from it directly instead of over the network;
+/// otherwise falls back to the zmq PUB/SUB transport, as does a localhost server whose ring
+/// doesn't exist yet (e.g. not started).
+pub(crate) fn receive_snapshots(
+    ctx: &Context,
+    cfg: &ClientConfig,
+    snapshot_manager: SnapshotManagerHandle,
+    run_flag: RunFlag,
+) -> Result<()> {
+    if let Some(multicast) = cfg.multicast {
+        return receive_snapshots_multicast(
+            multicast,
+            cfg.video_channel as usize,
+            snapshot_manager,
+            run_flag,
+        );
+    }
+    if shm::is_localhost(&cfg.server_hostname) {
+        match shm::ShmReader::open(cfg.video_channel as u8) {
+            Ok(reader) => return receive_snapshots_shm(reader, snapshot_manager, run_flag),
+            Err(e) => info!(
+                "No local shared-memory snapshot ring for channel {} yet ({e}); \
+                 falling back to the network transport.",
+                cfg.video_channel
+            ),
+        }
+    }
+    receive_snapshots_zmq(ctx, cfg, snapshot_manager, run_flag)
+}
+
+/// Poll a local shared-memory ring for the newest snapshot, the fast path for a client running
+/// on the same host as the server, skipping the zmq socket and its per-frame copies entirely.
+fn receive_snapshots_shm(
+    reader: shm::ShmReader,
+    snapshot_manager: SnapshotManagerHandle,
+    run_flag: RunFlag,
+) -> Result<()> {
+    thread::Builder::new()
+        .name("snapshot_receiver_shm".to_string())
+        .spawn(move || {
+            let mut last_seq = None;
+            while run_flag.should_run() {
+                match reader.read_latest(last_seq) {
+                    Some((seq, payload)) => {
+                        last_seq = Some(seq);
+                        match rmp_serde::from_slice::<Snapshot>(&payload) {
+                            Ok(snapshot) => {
+                                snapshot_manager.lock().unwrap().insert_snapshot(snapshot)
+                            }
+                            Err(e) => error!("shared-memory snapshot decode error: {e}"),
+                        }
+                    }
+                    None => thread::sleep(Duration::from_micros(500)),
+                }
+            }
+            info!("Snapshot receiver shutting down.");
+        })?;
+    Ok(())
+}
+
+/// Receive snapshots over the zmq PUB/SUB transport, the path used for any client that isn't on
+/// the same host as the server.
+fn receive_snapshots_zmq(
     ctx: &Context,
     cfg: &ClientConfig,
     snapshot_manager: SnapshotManagerHandle,
@@ -124,12 +419,80 @@ fn receive_snapshots(
                 break;
             }
             match receiver.receive_msg(true) {
-                Ok(Some(msg)) => {
-                    *snapshot_manager.lock().unwrap() = Some(Arc::new(msg));
-                }
+                Ok(Some(msg)) => snapshot_manager.lock().unwrap().insert_snapshot(msg),
                 Ok(None) => continue,
                 Err(e) => error!("receive error: {e}"),
             }
         })?;
     Ok(())
 }
+
+/// Receive snapshots over UDP multicast, the path used when `cfg.multicast` is set, matching a
+/// server running `tunnels::multicast_send` as its render transport. Datagrams are reassembled
+/// with a `Reassembler` before being decoded, since (unlike the zmq or shm transports) nothing
+/// else frames a complete snapshot's worth of bytes for us.
+fn receive_snapshots_multicast(
+    multicast: tunnels_lib::multicast::MulticastConfig,
+    video_channel: usize,
+    snapshot_manager: SnapshotManagerHandle,
+    run_flag: RunFlag,
+) -> Result<()> {
+    let (group, port) = multicast.channel_addr(video_channel);
+    let socket = UdpSocket::bind(("0.0.0.0", port))
+        .map_err(|e| anyhow!("failed to bind multicast receive socket on port {port}: {e}"))?;
+    socket
+        .join_multicast_v4(&group, &std::net::Ipv4Addr::UNSPECIFIED)
+        .map_err(|e| anyhow!("failed to join multicast group {group}: {e}"))?;
+    socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+
+    thread::Builder::new()
+        .name("snapshot_receiver_multicast".to_string())
+        .spawn(move || {
+            let mut reassembler = Reassembler::new();
+            let mut buf = [0u8; 65536];
+            while run_flag.should_run() {
+                match socket.recv(&mut buf) {
+                    Ok(len) => {
+                        if let Some(payload) = reassembler.receive(&buf[..len]) {
+                            if multicast.compact {
+                                decode_compact_multicast_frame(&payload, &snapshot_manager);
+                            } else {
+                                match rmp_serde::from_slice::<Snapshot>(&payload) {
+                                    Ok(snapshot) => {
+                                        snapshot_manager.lock().unwrap().insert_snapshot(snapshot)
+                                    }
+                                    Err(e) => error!("multicast snapshot decode error: {e}"),
+                                }
+                            }
+                        }
+                    }
+                    Err(e)
+                        if e.kind() == std::io::ErrorKind::WouldBlock
+                            || e.kind() == std::io::ErrorKind::TimedOut => {}
+                    Err(e) => error!("multicast receive error: {e}"),
+                }
+            }
+            info!("Snapshot receiver shutting down.");
+        })?;
+    Ok(())
+}
+
+/// Decode one reassembled multicast datagram sent in `tunnels_lib::wire`'s compact format (see
+/// `tunnels::multicast_send`'s `FRAME_TAG_KEYFRAME`/`FRAME_TAG_DELTA`-tagged encoder) and insert
+/// it into `snapshot_manager`. A delta frame received before this channel's first keyframe (e.g.
+/// a client that joined mid-stream) is logged and dropped; it'll catch up at the next keyframe.
+fn decode_compact_multicast_frame(payload: &[u8], snapshot_manager: &SnapshotManagerHandle) {
+    let Some((&tag, body)) = payload.split_first() else {
+        error!("multicast snapshot decode error: empty datagram");
+        return;
+    };
+    let mut manager = snapshot_manager.lock().unwrap();
+    let result = if tag == wire::FRAME_TAG_KEYFRAME {
+        wire::decode(body).map(|snapshot| manager.insert_snapshot(snapshot))
+    } else {
+        manager.insert_encoded_delta(body)
+    };
+    if let Err(e) = result {
+        error!("multicast snapshot decode error: {e}");
+    }
+}
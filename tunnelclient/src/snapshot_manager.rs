@@ -3,8 +3,13 @@
 
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
+use tunnels_lib::number::{Lerp, Phase, UnipolarFloat};
 use tunnels_lib::Timestamp;
-use tunnels_lib::{LayerCollection, Snapshot};
+use tunnels_lib::{modulo, wire, ArcSegment, LayerCollection, Snapshot};
+
+/// How far past the newest available snapshot we'll dead-reckon extrapolate before giving up
+/// and holding the last frame, in microseconds.
+const DEFAULT_MAX_EXTRAPOLATION: Timestamp = Timestamp(250_000);
 
 pub type SnapshotManagerHandle = Arc<Mutex<Box<dyn SnapshotManager>>>;
 
@@ -15,6 +20,17 @@ pub trait SnapshotManager: Send {
     fn update(&mut self);
     fn peek_front(&self) -> Option<&Snapshot>;
     fn get(&mut self, time: Timestamp) -> SnapshotFetchResult;
+
+    /// Decode a delta-encoded snapshot (see `tunnels_lib::wire::encode_delta`) against the
+    /// snapshot returned by `peek_front` and insert the result.
+    fn insert_encoded_delta(&mut self, buf: &[u8]) -> Result<(), wire::DecodeError> {
+        let snapshot = {
+            let reference = self.peek_front().ok_or(wire::DecodeError::NoReference)?;
+            wire::decode_delta(buf, reference)?
+        };
+        self.insert_snapshot(snapshot);
+        Ok(())
+    }
 }
 
 /// Maintain a single snapshot, use whatever is newest.
@@ -42,11 +58,40 @@ impl SnapshotManager for SingleSnapshotManager {
 }
 
 /// Handle receiving and maintaining a collection of snapshots.
-/// Provide interpolated snapshots on request.
-#[derive(Default)]
+/// Provide interpolated snapshots on request, dead-reckoning extrapolating past the newest
+/// snapshot when we're lagging fresh data, if enabled.
 pub struct VecDequeSnapshotManager {
     snapshots: VecDeque<SnapshotHandle>, // Ordered queue of snapshots; latest is snapshots.front()
     oldest_relevant_snapshot_time: Timestamp,
+    /// If true, dead-reckon extrapolate from the two most recent snapshots when we're lagging
+    /// fresh data, rather than holding the last frame.
+    extrapolate: bool,
+    /// The longest we'll extrapolate past the newest snapshot before giving up and holding it.
+    max_extrapolation: Timestamp,
+}
+
+impl Default for VecDequeSnapshotManager {
+    fn default() -> Self {
+        Self {
+            snapshots: VecDeque::new(),
+            oldest_relevant_snapshot_time: Timestamp(0),
+            extrapolate: false,
+            max_extrapolation: DEFAULT_MAX_EXTRAPOLATION,
+        }
+    }
+}
+
+impl VecDequeSnapshotManager {
+    /// Enable or disable dead-reckoning extrapolation when we're lagging fresh snapshot data.
+    pub fn set_extrapolate(&mut self, extrapolate: bool) {
+        self.extrapolate = extrapolate;
+    }
+
+    /// Set how far past the newest available snapshot we'll dead-reckon extrapolate before
+    /// giving up and holding the last frame.
+    pub fn set_max_extrapolation(&mut self, max_extrapolation: Timestamp) {
+        self.max_extrapolation = max_extrapolation;
+    }
 }
 
 pub enum SnapshotFetchResult {
@@ -138,18 +183,25 @@ impl SnapshotManager for VecDequeSnapshotManager {
                 }
             }
             _ => {
-                // If we're lagging on snapshots, just draw the most recent one.
-                if let Some(s) = snaps.front() {
-                    if s.time < time {
-                        self.oldest_relevant_snapshot_time = s.time;
-                        return SnapshotFetchResult::MissingNewer(s.clone());
+                // If we're lagging on snapshots, either dead-reckon extrapolate forward from the
+                // two most recent ones, or just hold the most recent one.
+                if let Some(newest) = snaps.front() {
+                    if newest.time < time {
+                        self.oldest_relevant_snapshot_time = newest.time;
+                        let elapsed = time - newest.time;
+                        if self.extrapolate && elapsed <= self.max_extrapolation {
+                            return SnapshotFetchResult::MissingNewer(extrapolate(
+                                &snaps[1], newest, time,
+                            ));
+                        }
+                        return SnapshotFetchResult::MissingNewer(newest.clone());
                     }
                 }
                 // Find the two snapshots that bracket the requested timestamp.
                 for (newer, older) in snaps.iter().zip(snaps.iter().skip(1)) {
                     if time <= newer.time && time >= older.time {
                         self.oldest_relevant_snapshot_time = older.time;
-                        return SnapshotFetchResult::Good(newer.clone());
+                        return SnapshotFetchResult::Good(interpolate(older, newer, time));
                     }
                 }
                 SnapshotFetchResult::Error(Vec::from(snaps.clone()))
@@ -158,6 +210,153 @@ impl SnapshotManager for VecDequeSnapshotManager {
     }
 }
 
+/// Interpolate between bracketing snapshots `older` and `newer` at `time`, producing a fresh,
+/// owned snapshot. Layers (and the arc segments within them) are paired up by index; if the two
+/// snapshots disagree on layer or segment counts, the unmatched tail is taken verbatim from
+/// `newer`.
+fn interpolate(older: &Snapshot, newer: &Snapshot, time: Timestamp) -> SnapshotHandle {
+    let span = (newer.time - older.time).0;
+    let t = if span <= 0 {
+        UnipolarFloat::ONE
+    } else {
+        UnipolarFloat::new((time - older.time).0 as f64 / span as f64)
+    };
+
+    let layers = older
+        .layers
+        .iter()
+        .zip(newer.layers.iter())
+        .map(|(o, n)| Arc::new(interpolate_segments(o, n, t)))
+        .chain(newer.layers.iter().skip(older.layers.len()).cloned())
+        .collect();
+
+    Arc::new(Snapshot {
+        frame_number: newer.frame_number,
+        time,
+        layers,
+    })
+}
+
+/// Pair up arc segments by index and interpolate each pair. Any segments in `newer` past the end
+/// of `older` are passed through untouched.
+fn interpolate_segments(
+    older: &[ArcSegment],
+    newer: &[ArcSegment],
+    t: UnipolarFloat,
+) -> Vec<ArcSegment> {
+    older
+        .iter()
+        .zip(newer.iter())
+        .map(|(o, n)| interpolate_segment(o, n, t))
+        .chain(newer.iter().skip(older.len()).cloned())
+        .collect()
+}
+
+/// Linearly interpolate every field of an arc segment via `Lerp`. `hue` is interpolated as a
+/// `Phase` to take the shorter way around the hue circle rather than running monotonically from
+/// 0 to 1; the remaining fields are plain floats (`x`/`y`/`rad_x`/`rad_y`/`start`/`stop`/
+/// `rot_angle` are unconstrained, so a `stop` of 1.0 stays a full turn, and
+/// `level`/`thickness`/`sat`/`val` are `UnipolarFloat`, though clamping makes no difference here
+/// since both endpoints are already in range).
+fn interpolate_segment(older: &ArcSegment, newer: &ArcSegment, t: UnipolarFloat) -> ArcSegment {
+    ArcSegment {
+        level: UnipolarFloat::new(older.level)
+            .lerp(UnipolarFloat::new(newer.level), t)
+            .val(),
+        thickness: UnipolarFloat::new(older.thickness)
+            .lerp(UnipolarFloat::new(newer.thickness), t)
+            .val(),
+        hue: Phase::new(older.hue).lerp(Phase::new(newer.hue), t).val(),
+        sat: UnipolarFloat::new(older.sat)
+            .lerp(UnipolarFloat::new(newer.sat), t)
+            .val(),
+        val: UnipolarFloat::new(older.val)
+            .lerp(UnipolarFloat::new(newer.val), t)
+            .val(),
+        x: older.x.lerp(newer.x, t),
+        y: older.y.lerp(newer.y, t),
+        rad_x: older.rad_x.lerp(newer.rad_x, t),
+        rad_y: older.rad_y.lerp(newer.rad_y, t),
+        start: older.start.lerp(newer.start, t),
+        stop: older.stop.lerp(newer.stop, t),
+        rot_angle: older.rot_angle.lerp(newer.rot_angle, t),
+    }
+}
+
+/// Dead-reckon extrapolate forward from the two most recent snapshots, `second` and `newest`, to
+/// `time`, which must be newer than `newest`. This is the producer-side analogue of the
+/// `pop_latest`/`peek_clock` pattern in the moa `ClockedQueue`, where the consumer reasons about
+/// the newest available timestamp rather than stalling. Layers and segments are paired by index,
+/// as in `interpolate`; any segments in `newest` with no counterpart in `second` are passed
+/// through untouched, since we have no prior sample to estimate their velocity from.
+fn extrapolate(second: &Snapshot, newest: &Snapshot, time: Timestamp) -> SnapshotHandle {
+    let dt = (newest.time - second.time).0;
+    let forward = (time - newest.time).0 as f64;
+
+    let layers = second
+        .layers
+        .iter()
+        .zip(newest.layers.iter())
+        .map(|(s, n)| Arc::new(extrapolate_segments(s, n, dt, forward)))
+        .chain(newest.layers.iter().skip(second.layers.len()).cloned())
+        .collect();
+
+    Arc::new(Snapshot {
+        frame_number: newest.frame_number,
+        time,
+        layers,
+    })
+}
+
+fn extrapolate_segments(
+    second: &[ArcSegment],
+    newest: &[ArcSegment],
+    dt: i64,
+    forward: f64,
+) -> Vec<ArcSegment> {
+    second
+        .iter()
+        .zip(newest.iter())
+        .map(|(s, n)| extrapolate_segment(s, n, dt, forward))
+        .chain(newest.iter().skip(second.len()).cloned())
+        .collect()
+}
+
+/// Project an arc segment's fields forward by estimating a per-field velocity from `second` to
+/// `newest` and applying it over `forward` additional microseconds. `level`, `thickness`, `sat`,
+/// and `val` are clamped back to their valid range after projection, since dead reckoning can
+/// easily overshoot it; `hue` and `rot_angle` wrap instead, since they're angles.
+fn extrapolate_segment(
+    second: &ArcSegment,
+    newest: &ArcSegment,
+    dt: i64,
+    forward: f64,
+) -> ArcSegment {
+    if dt <= 0 {
+        // No time elapsed between the two most recent snapshots, so there's no velocity to
+        // estimate; just hold.
+        return newest.clone();
+    }
+    let project = |from: f64, to: f64| -> f64 {
+        let v = (to - from) / (dt as f64);
+        to + v * forward
+    };
+    ArcSegment {
+        level: project(second.level, newest.level).clamp(0.0, 1.0),
+        thickness: project(second.thickness, newest.thickness).clamp(0.0, 1.0),
+        hue: modulo(project(second.hue, newest.hue), 1.0),
+        sat: project(second.sat, newest.sat).clamp(0.0, 1.0),
+        val: project(second.val, newest.val).clamp(0.0, 1.0),
+        x: project(second.x, newest.x),
+        y: project(second.y, newest.y),
+        rad_x: project(second.rad_x, newest.rad_x),
+        rad_y: project(second.rad_y, newest.rad_y),
+        start: project(second.start, newest.start),
+        stop: project(second.stop, newest.stop),
+        rot_angle: modulo(project(second.rot_angle, newest.rot_angle), 1.0),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use tunnels_lib::{ArcSegment, Snapshot};
@@ -292,4 +491,214 @@ mod tests {
             panic!();
         }
     }
+
+    /// Build an arc segment with every field set to `v`, except hue which is set separately so
+    /// wraparound behavior can be exercised independent of the other fields.
+    fn mkarc(v: f64, hue: f64) -> ArcSegment {
+        ArcSegment {
+            level: v,
+            thickness: v,
+            hue,
+            sat: v,
+            val: v,
+            x: v,
+            y: v,
+            rad_x: v,
+            rad_y: v,
+            start: v,
+            stop: v,
+            rot_angle: v,
+        }
+    }
+
+    fn get_only_segment(f: &LayerCollection) -> &ArcSegment {
+        &f[0][0]
+    }
+
+    #[test]
+    fn test_interpolate_exact_hits() {
+        let mut sm = VecDequeSnapshotManager::default();
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.0, 0.0));
+        let snap1 = mksnapshot_with_arc(1, Timestamp(10000), mkarc(1.0, 0.5));
+        sm.insert_snapshot(snap0.as_ref().clone());
+        sm.insert_snapshot(snap1.as_ref().clone());
+
+        let older = match sm.get(Timestamp(0)) {
+            SnapshotFetchResult::Good(f) => f,
+            _ => panic!(),
+        };
+        assert_eq!(get_only_segment(&older.layers), &mkarc(0.0, 0.0));
+
+        let newer = match sm.get(Timestamp(10000)) {
+            SnapshotFetchResult::Good(f) => f,
+            _ => panic!(),
+        };
+        assert_eq!(get_only_segment(&newer.layers), &mkarc(1.0, 0.5));
+    }
+
+    #[test]
+    fn test_interpolate_midpoint() {
+        let mut sm = VecDequeSnapshotManager::default();
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.0, 0.2));
+        let snap1 = mksnapshot_with_arc(1, Timestamp(10000), mkarc(1.0, 0.4));
+        sm.insert_snapshot(snap0.as_ref().clone());
+        sm.insert_snapshot(snap1.as_ref().clone());
+
+        let result = match sm.get(Timestamp(5000)) {
+            SnapshotFetchResult::Good(f) => f,
+            _ => panic!(),
+        };
+        assert_eq!(get_only_segment(&result.layers), &mkarc(0.5, 0.3));
+    }
+
+    #[test]
+    fn test_interpolate_hue_wraparound() {
+        let mut sm = VecDequeSnapshotManager::default();
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.0, 0.95));
+        let snap1 = mksnapshot_with_arc(1, Timestamp(10000), mkarc(0.0, 0.05));
+        sm.insert_snapshot(snap0.as_ref().clone());
+        sm.insert_snapshot(snap1.as_ref().clone());
+
+        let result = match sm.get(Timestamp(5000)) {
+            SnapshotFetchResult::Good(f) => f,
+            _ => panic!(),
+        };
+        // The short way around from 0.95 to 0.05 passes through 0.0, so the midpoint should land
+        // there rather than at 0.5, which is what a naive (non-wrapping) lerp would produce.
+        let hue = get_only_segment(&result.layers).hue;
+        assert!((hue - 0.0).abs() < 1e-9 || (hue - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_interpolate_mismatched_layer_counts() {
+        let mut sm = VecDequeSnapshotManager::default();
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.0, 0.0));
+        let extra_layer = mkarc(1.0, 0.0);
+        let snap1 = {
+            let mut s = mksnapshot_with_arc(1, Timestamp(10000), mkarc(1.0, 0.0))
+                .as_ref()
+                .clone();
+            s.layers.push(Arc::new(vec![extra_layer.clone()]));
+            s
+        };
+        sm.insert_snapshot(snap0.as_ref().clone());
+        sm.insert_snapshot(snap1.clone());
+
+        let result = match sm.get(Timestamp(5000)) {
+            SnapshotFetchResult::Good(f) => f,
+            _ => panic!(),
+        };
+        let layers = &result.layers;
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0][0], mkarc(0.5, 0.0));
+        // The unmatched second layer has no counterpart in the older snapshot, so it's passed
+        // through from the newer snapshot untouched.
+        assert_eq!(layers[1][0], extra_layer);
+    }
+
+    #[test]
+    fn test_interpolate_mismatched_segment_counts() {
+        let mut sm = VecDequeSnapshotManager::default();
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.0, 0.0));
+        let extra_segment = mkarc(1.0, 0.0);
+        let snap1 = {
+            let mut s = mksnapshot_with_arc(1, Timestamp(10000), mkarc(1.0, 0.0))
+                .as_ref()
+                .clone();
+            s.layers[0] = Arc::new(vec![mkarc(1.0, 0.0), extra_segment.clone()]);
+            s
+        };
+        sm.insert_snapshot(snap0.as_ref().clone());
+        sm.insert_snapshot(snap1.clone());
+
+        let result = match sm.get(Timestamp(5000)) {
+            SnapshotFetchResult::Good(f) => f,
+            _ => panic!(),
+        };
+        let layer = &result.layers[0];
+        assert_eq!(layer.len(), 2);
+        assert_eq!(layer[0], mkarc(0.5, 0.0));
+        // The unmatched second segment has no counterpart in the older snapshot's layer, so it's
+        // passed through from the newer snapshot untouched.
+        assert_eq!(layer[1], extra_segment);
+    }
+
+    #[test]
+    fn test_lagging_without_extrapolation_holds() {
+        let mut sm = VecDequeSnapshotManager::default();
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.0, 0.0));
+        let snap1 = mksnapshot_with_arc(1, Timestamp(10000), mkarc(1.0, 0.0));
+        sm.insert_snapshot(snap0.as_ref().clone());
+        sm.insert_snapshot(snap1.as_ref().clone());
+
+        if let SnapshotFetchResult::MissingNewer(f) = sm.get(Timestamp(15000)) {
+            assert_eq!(snap1, f);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_extrapolation_projects_forward_and_clamps() {
+        let mut sm = VecDequeSnapshotManager::default();
+        sm.set_extrapolate(true);
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.0, 0.0));
+        let snap1 = mksnapshot_with_arc(1, Timestamp(10000), mkarc(1.0, 0.0));
+        sm.insert_snapshot(snap0.as_ref().clone());
+        sm.insert_snapshot(snap1.as_ref().clone());
+
+        let result = match sm.get(Timestamp(15000)) {
+            SnapshotFetchResult::MissingNewer(f) => f,
+            _ => panic!(),
+        };
+        let segment = get_only_segment(&result.layers);
+        // x has no valid-range clamp, so it overshoots past 1.0 the way the velocity predicts.
+        assert!((segment.x - 1.5).abs() < 1e-9);
+        // level is clamped back into its valid range after projection.
+        assert_eq!(segment.level, 1.0);
+    }
+
+    #[test]
+    fn test_extrapolation_past_horizon_holds() {
+        let mut sm = VecDequeSnapshotManager::default();
+        sm.set_extrapolate(true);
+        sm.set_max_extrapolation(Timestamp(1000));
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.0, 0.0));
+        let snap1 = mksnapshot_with_arc(1, Timestamp(10000), mkarc(1.0, 0.0));
+        sm.insert_snapshot(snap0.as_ref().clone());
+        sm.insert_snapshot(snap1.as_ref().clone());
+
+        if let SnapshotFetchResult::MissingNewer(f) = sm.get(Timestamp(15000)) {
+            assert_eq!(snap1, f);
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_insert_encoded_delta() {
+        let mut sm = VecDequeSnapshotManager::default();
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.5, 0.25));
+        sm.insert_snapshot(snap0.as_ref().clone());
+
+        let snap1 = mksnapshot_with_arc(1, Timestamp(10000), mkarc(0.5, 0.25));
+        let delta = wire::encode_delta(&snap1, &snap0);
+        sm.insert_encoded_delta(&delta).unwrap();
+
+        match sm.get(Timestamp(10000)) {
+            SnapshotFetchResult::Good(f) => assert_eq!(f.frame_number, 1),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_insert_encoded_delta_without_reference_errors() {
+        let mut sm = VecDequeSnapshotManager::default();
+        let snap0 = mksnapshot_with_arc(0, Timestamp(0), mkarc(0.5, 0.25));
+        let delta = wire::encode_delta(&snap0, &snap0);
+        assert_eq!(
+            sm.insert_encoded_delta(&delta),
+            Err(wire::DecodeError::NoReference)
+        );
+    }
 }
@@ -1,12 +1,22 @@
 //! Synchronize time between the master and this client.
 //! Using this simple technique:
 //! http://www.mine-control.com/zack/timesync/timesync.html
+//!
+//! Orphaned: this module predates `crate::timesync`, which now covers the same job (a zmq
+//! REQ/REP exchange disciplining a local clock estimate against the show host) with a
+//! crossfade-smoothed `Synchronizer` instead of this module's slew-limited servo, and is what
+//! `Show`/`clock_source` actually build on. There's no `mod sntp_service;` in `main.rs`, so
+//! nothing here compiles into the client binary; it's kept only as a reference for the
+//! servo/Theil-Sen approach, not as a backend to wire in alongside `timesync`.
 
 use receive::{Receive};
 use serde::Deserialize;
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Instant, Duration, SystemTime};
 use std::thread::sleep;
-use stats::{mean, stddev};
+use stats::stddev;
+use tunnels_lib::RunFlag;
 use zmq;
 use zmq::{Context, Socket, DONTWAIT};
 
@@ -67,19 +77,130 @@ struct SntpMeasurement {
     timestamp: Timestamp
 }
 
+/// Feedback gain applied to each new measurement's error, trading responsiveness against jitter
+/// rejection. Borrowed from the servo-loop approach ARTIQ's WRPLL uses to keep a local oscillator
+/// locked to a reference: a small fraction of the error is folded in on every update rather than
+/// snapping straight to the new measurement.
+const KP: f64 = 0.1;
+
+/// Maximum correction applied per second of elapsed time between updates, so a single noisy
+/// measurement can't jump `now_as_timestamp()` discontinuously and make an in-progress animation
+/// skip. The estimate instead slews towards the true offset at no more than this rate.
+const MAX_SLEW_PER_SEC: f64 = 0.005; // 5 ms/s
+
+/// How often the background servo re-measures the remote clock and corrects the estimate.
+const SERVO_POLL_PERIOD: Duration = Duration::from_secs(5);
+
 #[derive(Debug)]
 pub struct SntpSync {
     ref_time: Instant,
-    host_ref_time: Timestamp
+    host_ref_time: Timestamp,
+    /// Estimated rate of the remote clock relative to ours (1.0 == ticking at the same rate), fit
+    /// alongside `host_ref_time` so `now_as_timestamp()` doesn't drift linearly between
+    /// measurements just because the two crystals run a few tens of ppm apart.
+    skew: f64,
 }
 
 impl SntpSync {
     /// Return our estimate of what time it is now on the host.
     /// This is in milliseconds.
     pub fn now_as_timestamp(&self) -> Timestamp {
-        let time_secs = self.host_ref_time + duration_to_f64(self.ref_time.elapsed());
+        let time_secs = self.host_ref_time + self.skew * duration_to_f64(self.ref_time.elapsed());
         time_secs * 1000.0
     }
+
+    /// Our estimate of what the remote clock reads at `at`, per the current estimate. Used both
+    /// to report `now_as_timestamp()` and as the prediction a fresh measurement is compared
+    /// against when disciplining the estimate.
+    fn predicted_remote(&self, at: Instant) -> Timestamp {
+        self.host_ref_time + self.skew * duration_to_f64(at.duration_since(self.ref_time))
+    }
+
+    /// Fold a single fresh measurement into the estimate: `measured_remote_estimate` is the
+    /// remote clock's reading at local instant `measured_at`. The instantaneous error between
+    /// that measurement and what we would have predicted is scaled by `KP` and clamped to a
+    /// bounded slew rate before being applied, so `now_as_timestamp()` stays monotonic and never
+    /// skips even when a measurement is noisy.
+    pub fn update(&mut self, measured_at: Instant, measured_remote_estimate: Timestamp) {
+        let error = measured_remote_estimate - self.predicted_remote(measured_at);
+        let since_last_update = duration_to_f64(measured_at.duration_since(self.ref_time)).max(0.0);
+        let max_correction = MAX_SLEW_PER_SEC * since_last_update.max(1.0);
+        let correction = (KP * error).clamp(-max_correction, max_correction);
+
+        // Re-anchor the reference instant to this measurement so the next update's elapsed time,
+        // and every `now_as_timestamp()` call in between, is measured from the freshest estimate.
+        self.host_ref_time = self.predicted_remote(measured_at) + correction;
+        self.ref_time = measured_at;
+    }
+}
+
+/// Thread-safe handle to an [`SntpSync`] that a background [`SntpServo`] keeps disciplined, so the
+/// owning show thread can query the smoothed estimate at any time without racing the servo's own
+/// updates.
+#[derive(Clone)]
+pub struct SntpSyncHandle(Arc<Mutex<SntpSync>>);
+
+impl SntpSyncHandle {
+    /// Return our current best estimate of what time it is now on the host, in milliseconds.
+    pub fn now_as_timestamp(&self) -> Timestamp {
+        self.0.lock().unwrap().now_as_timestamp()
+    }
+}
+
+/// Keeps an [`SntpSync`] estimate locked to the remote clock for the lifetime of a show, rather
+/// than letting the one-shot `synchronize()` burst freeze an estimate that slowly drifts from the
+/// truth as the local `Instant` and the remote clock diverge. Mirrors `TimesyncServer`'s
+/// `RunFlag`/`JoinHandle` lifecycle: runs in a background thread until dropped.
+pub struct SntpServo {
+    join_handle: Option<thread::JoinHandle<()>>,
+    run: RunFlag,
+}
+
+impl SntpServo {
+    /// Run the usual startup burst via `synchronize`, then spawn a background thread that keeps
+    /// re-measuring the remote clock at `SERVO_POLL_PERIOD` and feeding each measurement through
+    /// `SntpSync::update`. Returns a handle the show thread can query safely alongside the servo
+    /// itself, which disciplines the estimate until dropped.
+    pub fn start(host: &str, poll_period: Duration, n_meas: usize) -> (SntpSyncHandle, Self) {
+        let sync = Arc::new(Mutex::new(synchronize(host, poll_period, n_meas)));
+        let handle = SntpSyncHandle(sync.clone());
+
+        let run = RunFlag::new();
+        let run_local = run.clone();
+        let host = host.to_string();
+
+        let join_handle = thread::Builder::new()
+            .name("sntp_servo".to_string())
+            .spawn(move || {
+                let mut ctx = Context::new();
+                let mut client = SntpClient::new(&host, SNTP_PORT, &mut ctx);
+                while run.should_run() {
+                    sleep(SERVO_POLL_PERIOD);
+                    if !run.should_run() {
+                        return;
+                    }
+                    let m = client.take_measurement();
+                    let measured_at = m.sent + m.round_trip / 2;
+                    sync.lock().unwrap().update(measured_at, m.timestamp);
+                }
+            })
+            .expect("failed to spawn sntp_servo thread");
+
+        (
+            handle,
+            SntpServo {
+                join_handle: Some(join_handle),
+                run: run_local,
+            },
+        )
+    }
+}
+
+impl Drop for SntpServo {
+    fn drop(&mut self) {
+        self.run.stop();
+        self.join_handle.take().unwrap().join().unwrap();
+    }
 }
 
 /// Get the offset between this machine's system clock and the host's.
@@ -108,16 +229,69 @@ pub fn synchronize(host: &str, poll_period: Duration, n_meas: usize) -> SntpSync
         panic!("Ony got {} synchronization samples.", measurements.len());
     }
 
-    // Estimate the remote clock time that corresponds to our reference time.
-    let remote_time_estimates =
-        measurements.iter()
+    // Each measurement is a point (t_i, r_i): t_i is seconds elapsed since reference_time at the
+    // midpoint of the round trip, r_i is the remote timestamp read back at that point.
+    let points: Vec<(f64, Timestamp)> = measurements
+        .iter()
         .map(|m| {
-            let delta = (m.sent + m.round_trip / 2).duration_since(reference_time);
-            m.timestamp - duration_to_f64(delta)
-        });
-    // Take the average of these estaimtes, and we're done
-    let best_remote_time_estimate = mean(remote_time_estimates);
-    SntpSync{ref_time: reference_time, host_ref_time: best_remote_time_estimate}
+            let t = duration_to_f64((m.sent + m.round_trip / 2).duration_since(reference_time));
+            (t, m.timestamp)
+        })
+        .collect();
+    let (skew, host_ref_time) =
+        theil_sen(&points).expect("measurements is non-empty, checked above");
+    SntpSync {
+        ref_time: reference_time,
+        host_ref_time,
+        skew,
+    }
+}
+
+/// Estimate the remote clock's offset and skew relative to `reference_time` from a set of
+/// `(elapsed, remote_timestamp)` points, modeling `remote = offset + skew * elapsed`. Uses a
+/// Theil-Sen estimator in the spirit of the "median edge" idea behind ARTIQ's DDMTD deglitcher:
+/// `skew` is the median of the pairwise slopes between every two points, and `offset` is the
+/// median of each point's residual once that slope is removed. This is insensitive to the
+/// occasional badly asymmetric-latency outlier that throws off a least-squares fit, and needs no
+/// stddev threshold tuning of its own - at the cost of being O(n^2), which is fine since `n_meas`
+/// is always small.
+///
+/// Returns `None` if `points` is empty. If no two points have distinct enough `t` values to form
+/// a slope from (e.g. `n_meas <= 1`, or simultaneous measurements), `skew` falls back to `1.0` -
+/// clocks assumed to tick at the same rate, the same assumption this estimator replaced, rather
+/// than panicking for lack of enough information to do better.
+fn theil_sen(points: &[(f64, Timestamp)]) -> Option<(f64, Timestamp)> {
+    if points.is_empty() {
+        return None;
+    }
+    let mut slopes = Vec::with_capacity(points.len() * points.len().saturating_sub(1) / 2);
+    for i in 0..points.len() {
+        for j in (i + 1)..points.len() {
+            let (t_i, r_i) = points[i];
+            let (t_j, r_j) = points[j];
+            if (t_j - t_i).abs() > f64::EPSILON {
+                slopes.push((r_j - r_i) / (t_j - t_i));
+            }
+        }
+    }
+    let skew = median(&mut slopes).unwrap_or(1.0);
+    let mut residuals: Vec<Timestamp> = points.iter().map(|&(t, r)| r - skew * t).collect();
+    let offset = median(&mut residuals).expect("points is non-empty, so residuals is non-empty");
+    Some((skew, offset))
+}
+
+/// The median of `values`, which is sorted in place. Returns `None` for an empty slice.
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mid = values.len() / 2;
+    Some(if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    })
 }
 
 #[test]
@@ -1,21 +1,61 @@
 //! Synchronize time between the master and this client.
-//! Using this simple technique:
+//! Using a continuous NTP-style four-timestamp exchange:
 //! http://www.mine-control.com/zack/timesync/timesync.html
+//!
+//! Each poll stamps the client's send time (t0) and reply arrival time (t3), and reads the
+//! host's receive time (t1) and transmit time (t2) out of the reply. From those four timestamps
+//! we can estimate both the clock offset and the round-trip delay for that single sample:
+//!   offset = ((t1 - t0) + (t2 - t3)) / 2
+//!   round_trip_delay = (t3 - t0) - (t2 - t1)
+//! Individual samples are noisy due to network jitter, so we keep a sliding window of the last
+//! few samples and use the offset from whichever sample had the *minimum* round-trip delay, on
+//! the theory that the least-delayed sample is the least likely to have been skewed by queuing.
+//! Samples whose delay is far above the window's median are discarded outright rather than being
+//! given a chance to win that selection, and a sample whose offset has jumped well outside the
+//! window's spread (e.g. the local machine slept and its monotonic clock stalled) reseeds the
+//! window from scratch instead of being blended in or filtered out.
+//!
+//! A reply too short to hold both host timestamps is treated as coming from a server still on
+//! the older single-timestamp protocol, and falls back to the symmetric round-trip assumption.
 
 use interpolation::lerp;
+use log::warn;
 use simple_error::bail;
-use stats::{mean, stddev};
+use std::collections::VecDeque;
 use std::error::Error;
 use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::thread::sleep;
 use std::time::{Duration, Instant};
-use tunnels_lib::{number::UnipolarFloat, Timestamp};
+use tunnels_lib::{number::UnipolarFloat, Timestamp, TimesyncReply};
 use zero_configure::msgpack::Receive;
 use zmq;
 use zmq::{Context, Socket, DONTWAIT};
 
 const PORT: u64 = 8989;
 
+/// Number of recent samples to retain when selecting the minimum-delay offset estimate.
+const WINDOW_SIZE: usize = 8;
+
+/// Minimum number of samples already in the window before outlier rejection and step detection
+/// kick in; with fewer than this, there isn't enough history to tell a genuine outlier or step
+/// from ordinary jitter.
+const MIN_WINDOW_FOR_FILTERING: usize = 3;
+
+/// Discard a sample outright if its round-trip delay is more than this many times the window's
+/// current median delay, on the theory that a delay that much worse than its neighbors reflects a
+/// transient network hiccup (a queued packet, a GC pause on the host) rather than being
+/// informative about the offset - and letting it into the window risks it winning the
+/// minimum-delay selection on some later poll once fresher samples have aged out.
+const OUTLIER_DELAY_FACTOR: i64 = 3;
+
+/// Treat a sample as a clock step rather than noise if its offset differs from the window's
+/// current best estimate by more than this much, and reseed the window with just that sample.
+/// Sized well above ordinary jitter but well below the kind of jump caused by the local machine
+/// suspending and resuming, which stalls our monotonic clock while the host's keeps running.
+const STEP_THRESHOLD: Timestamp = Timestamp(500_000);
+
 /// Provide estimates of the offset between this host's monotonic clock and the server's.
 pub struct Client {
     socket: Socket,
@@ -23,6 +63,10 @@ pub struct Client {
     pub poll_period: Duration,
     /// Make this many measurements in each determination of the time offset.
     pub n_meas: usize,
+    /// Local reference instant that all of our stamped timestamps are measured relative to.
+    reference_time: Instant,
+    /// Sliding window of the most recent offset/delay samples.
+    window: VecDeque<Sample>,
 }
 
 impl Client {
@@ -36,6 +80,8 @@ impl Client {
             socket,
             poll_period: Duration::from_millis(500),
             n_meas: 10,
+            reference_time: Instant::now(),
+            window: VecDeque::with_capacity(WINDOW_SIZE),
         })
     }
 
@@ -44,64 +90,165 @@ impl Client {
         self.poll_period * self.n_meas as u32
     }
 
-    /// Take a time delay measurement.
-    fn measure(&mut self) -> Result<Measurement, Box<dyn Error>> {
-        let now = Instant::now();
+    /// Perform a single timing exchange with the host and record the resulting sample.
+    fn poll(&mut self) -> Result<Sample, Box<dyn Error>> {
+        let t0 = Timestamp::since(self.reference_time);
         self.socket.send(&[][..], 0)?;
         let buf = match self.receive_buffer(true) {
             Some(buf) => buf,
             None => bail!("Unable to receive a response from timesync server."),
         };
-        let elapsed = now.elapsed();
-        let timestamp: Timestamp = self.deserialize_msg(buf)?;
-        Ok(Measurement {
-            sent: now,
-            round_trip: elapsed,
-            timestamp,
-        })
+        let t3 = Timestamp::since(self.reference_time);
+
+        // A server running the current protocol replies with a `TimesyncReply`, letting us run
+        // the full four-timestamp calculation below. A server that hasn't yet been upgraded to
+        // stamp its own receive/transmit times replies with a bare `Timestamp` (its transmit time
+        // only); fall back to the old two-timestamp (Cristian's algorithm) estimate in that case
+        // so a client can still sync against an older host during a rolling upgrade.
+        let sample = match self.deserialize_msg::<TimesyncReply>(buf.clone()) {
+            Ok(reply) => {
+                let offset =
+                    Timestamp(((reply.receive_time - t0) + (reply.transmit_time - t3)).0 / 2);
+                let round_trip_delay = (t3 - t0) - (reply.transmit_time - reply.receive_time);
+                Sample {
+                    offset,
+                    round_trip_delay,
+                }
+            }
+            Err(_) => {
+                let legacy_timestamp: Timestamp = self.deserialize_msg(buf)?;
+                let round_trip_delay = t3 - t0;
+                let offset = legacy_timestamp + Timestamp(round_trip_delay.0 / 2) - t3;
+                Sample {
+                    offset,
+                    round_trip_delay,
+                }
+            }
+        };
+
+        let has_filtering_history = self.window.len() >= MIN_WINDOW_FOR_FILTERING;
+        if let Some(current_best) = self.best_sample().filter(|_| has_filtering_history) {
+            let step = (sample.offset - current_best.offset).0.abs();
+            if step > STEP_THRESHOLD.0 {
+                warn!(
+                    "Timesync: offset jumped by {step}us from the window's current best \
+                     estimate, reseeding synchronization window.",
+                );
+                self.window.clear();
+            } else if self.is_outlier(&sample) {
+                warn!(
+                    "Timesync: discarding sample with round-trip delay {}us, more than {}x the \
+                     window's median delay.",
+                    sample.round_trip_delay.0, OUTLIER_DELAY_FACTOR,
+                );
+                return Ok(sample);
+            }
+        }
+
+        if self.window.len() == WINDOW_SIZE {
+            self.window.pop_front();
+        }
+        self.window.push_back(sample);
+
+        Ok(sample)
+    }
+
+    /// The sample in the current window with the minimum round-trip delay, or `None` if the
+    /// window is empty.
+    fn best_sample(&self) -> Option<Sample> {
+        self.window.iter().min_by_key(|s| s.round_trip_delay).copied()
     }
 
-    /// Get the offset between this machine's system clock and the host's.
+    /// The median round-trip delay across the current window, used as the outlier-rejection
+    /// baseline. Panics if the window is empty; only called once `MIN_WINDOW_FOR_FILTERING`
+    /// samples are present.
+    fn median_delay(&self) -> Timestamp {
+        let mut delays: Vec<Timestamp> = self.window.iter().map(|s| s.round_trip_delay).collect();
+        delays.sort();
+        delays[delays.len() / 2]
+    }
+
+    /// True if `sample`'s round-trip delay is more than `OUTLIER_DELAY_FACTOR` times the window's
+    /// current median delay.
+    fn is_outlier(&self, sample: &Sample) -> bool {
+        sample.round_trip_delay.0 > self.median_delay().0 * OUTLIER_DELAY_FACTOR
+    }
+
+    /// Get the offset between this machine's system clock and the host's, taking the estimate
+    /// from whichever sample in the sliding window had the minimum round-trip delay.
     pub fn synchronize(&mut self) -> Result<Timesync, Box<dyn Error>> {
-        let reference_time = Instant::now();
-        // Take a bunch of measurements, sleeping in between.
-        let mut measurements = Vec::with_capacity(self.n_meas);
         for _ in 0..self.n_meas {
-            measurements.push(self.measure()?);
+            self.poll()?;
             sleep(self.poll_period);
         }
+        self.best_sync()
+    }
 
-        // Sort the measurements by round-trip time and remove outliers.
-        measurements.sort_by_key(|m| m.round_trip);
-        let median_delay = measurements[(self.n_meas / 2) as usize].round_trip;
-        let stddev = Duration::from_secs_f64(stddev(
-            measurements.iter().map(|m| m.round_trip.as_secs_f64()),
-        ));
-        let cutoff = median_delay + stddev;
-
-        measurements.retain(|m| m.round_trip < cutoff);
-
-        if measurements.len() < self.n_meas / 2 {
-            bail!(format!(
-                "Only got {} usable synchronization samples.",
-                measurements.len()
-            ));
+    /// Fire a short burst of `RAPID_BURST_SIZE` back-to-back probes, with no inter-sample sleep,
+    /// to get a usable offset within tens of milliseconds instead of waiting out the full
+    /// `n_meas`-sample, `poll_period`-spaced window `synchronize` takes. Still picks the
+    /// minimum-round-trip-delay sample out of the burst, just over far fewer samples than a full
+    /// `synchronize` pass, so a client joining mid-show can start rendering immediately and let
+    /// `synchronize` keep refining the estimate afterwards.
+    pub fn synchronize_rapid(&mut self) -> Result<Timesync, Box<dyn Error>> {
+        for _ in 0..RAPID_BURST_SIZE {
+            self.poll()?;
         }
+        self.best_sync()
+    }
+
+    /// Return an estimate of how long `synchronize_rapid` will take, for UI to show "locking…"
+    /// for the right duration instead of assuming the full `synchronization_duration`.
+    pub fn rapid_synchronization_duration(&self) -> Duration {
+        RAPID_PROBE_ESTIMATE * RAPID_BURST_SIZE as u32
+    }
 
-        // Estimate the remote clock time that corresponds to our reference time.
-        let remote_time_estimates = measurements.iter().map(|m| {
-            let delta = (m.sent + m.round_trip / 2).duration_since(reference_time);
-            m.timestamp - Timestamp::from_duration(delta)
-        });
-        // Take the average of these estimates, and we're done
-        let best_remote_time_estimate = Timestamp(mean(remote_time_estimates) as i64);
+    /// Resolve the current window down to a single offset estimate, taken from whichever sample
+    /// had the minimum round-trip delay.
+    fn best_sync(&self) -> Result<Timesync, Box<dyn Error>> {
+        let best = self
+            .best_sample()
+            .ok_or("No synchronization samples available.")?;
+
+        // `best.offset` is the host-minus-ours offset estimated relative to `reference_time`, so
+        // it is itself the host's absolute clock value at that instant (local elapsed is zero
+        // there).
         Ok(Timesync {
-            ref_time: reference_time,
-            host_ref_time: best_remote_time_estimate,
+            ref_time: self.reference_time,
+            host_ref_time: best.offset,
         })
     }
 }
 
+/// Number of back-to-back probes fired by `Client::synchronize_rapid`.
+const RAPID_BURST_SIZE: usize = 4;
+
+/// Rough per-probe round-trip estimate used to report `rapid_synchronization_duration`, since
+/// rapid mode has no `poll_period` to size the estimate from.
+const RAPID_PROBE_ESTIMATE: Duration = Duration::from_millis(10);
+
+/// Get a `Synchronizer` up and running quickly for a client joining mid-show: fire a rapid burst
+/// to produce an initial estimate within tens of milliseconds, then keep refining it in the
+/// background with full, slower, outlier-filtered `synchronize` passes, feeding each one into the
+/// `Synchronizer` via `update_current` as it arrives.
+pub fn synchronize_rapid_then_refine(
+    mut client: Client,
+) -> Result<Arc<Mutex<Synchronizer>>, Box<dyn Error>> {
+    let rapid = client.synchronize_rapid()?;
+    let synchronizer = Arc::new(Mutex::new(Synchronizer::new(rapid)));
+    let refine_synchronizer = synchronizer.clone();
+    thread::spawn(move || loop {
+        match client.synchronize() {
+            Ok(sync) => refine_synchronizer.lock().unwrap().update_current(sync),
+            Err(e) => {
+                warn!("Background timesync refinement failed, giving up: {e}");
+                return;
+            }
+        }
+    });
+    Ok(synchronizer)
+}
+
 impl Receive for Client {
     fn receive_buffer(&mut self, block: bool) -> Option<Vec<u8>> {
         let flag = if block { 0 } else { DONTWAIT };
@@ -113,11 +260,13 @@ impl Receive for Client {
     }
 }
 
-#[derive(Debug)]
-struct Measurement {
-    sent: Instant,
-    round_trip: Duration,
-    timestamp: Timestamp,
+/// A single offset/delay estimate produced by one timing exchange.
+#[derive(Copy, Clone, Debug)]
+struct Sample {
+    /// Estimated offset between the host's clock and ours (host - ours).
+    offset: Timestamp,
+    /// Estimated round-trip network delay for this exchange.
+    round_trip_delay: Timestamp,
 }
 
 #[derive(Debug, Clone)]
@@ -127,12 +276,25 @@ pub struct Timesync {
 }
 
 impl Timesync {
-    /// Return an estimate of what time it is now on the host.
+    /// Return an estimate of what time it is now on the host, assuming no clock skew.
     pub fn now(&self) -> Timestamp {
-        self.host_ref_time + Timestamp::from_duration(self.ref_time.elapsed())
+        self.now_with_skew(0.0)
+    }
+
+    /// Return an estimate of what time it is now on the host, modeling the host clock as
+    /// running at rate `1.0 + skew` relative to ours (e.g. `skew = 1e-5` is a host clock running
+    /// 10 ppm fast). This keeps the estimate from drifting linearly between measurements when
+    /// the host and local clocks simply tick at different rates.
+    pub fn now_with_skew(&self, skew: f64) -> Timestamp {
+        let elapsed = self.ref_time.elapsed().as_secs_f64() * (1.0 + skew);
+        self.host_ref_time + Timestamp::from_duration(Duration::from_secs_f64(elapsed.max(0.0)))
     }
 }
 
+/// Number of recent (ref_time_elapsed, host_offset) measurement pairs kept for fitting the
+/// host clock's skew relative to ours.
+const SKEW_WINDOW_SIZE: usize = 16;
+
 /// Provide smoothed estimates of the current time on the host.
 /// Ensures that we don't suddenly draw a jerk when we update our estimate of the host time offset.
 #[derive(Debug, Clone)]
@@ -143,25 +305,53 @@ pub struct Synchronizer {
     current: Timesync,
     /// Linear interpolation parameter.
     alpha: UnipolarFloat,
+    /// Recent (seconds since the client's reference instant, estimated host offset in seconds)
+    /// pairs produced by successive `update_current` calls, used to fit clock skew.
+    skew_samples: VecDeque<(f64, f64)>,
+    /// Estimated fractional clock skew of the host relative to ours, fit as the slope of
+    /// `skew_samples`. Zero until at least two samples have been recorded.
+    skew: f64,
 }
 
 impl Synchronizer {
     /// Instantiate a new synchronizer from an initial time estimate on the host.
     pub fn new(sync: Timesync) -> Self {
-        Synchronizer {
+        let mut synchronizer = Synchronizer {
             last: sync.clone(),
-            current: sync,
+            current: sync.clone(),
             alpha: UnipolarFloat::ONE,
-        }
+            skew_samples: VecDeque::with_capacity(SKEW_WINDOW_SIZE),
+            skew: 0.0,
+        };
+        synchronizer.record_skew_sample(&sync);
+        synchronizer
     }
 
     /// Update the current estimate and reset the interpolation parameter to 0.
     pub fn update_current(&mut self, sync: Timesync) {
+        self.record_skew_sample(&sync);
         mem::swap(&mut self.last, &mut self.current);
         self.current = sync;
         self.alpha = UnipolarFloat::ZERO;
     }
 
+    /// Fold a fresh measurement into the skew-fitting window and refit the skew estimate.
+    fn record_skew_sample(&mut self, sync: &Timesync) {
+        let elapsed = sync.ref_time.elapsed().as_secs_f64();
+        let offset = sync.host_ref_time.0 as f64 / 1e6;
+        if self.skew_samples.len() == SKEW_WINDOW_SIZE {
+            self.skew_samples.pop_front();
+        }
+        self.skew_samples.push_back((elapsed, offset));
+        self.skew = fit_skew(&self.skew_samples);
+    }
+
+    /// The most recently fit clock skew of the host relative to ours, as a fractional rate
+    /// (e.g. `1e-5` == 10 ppm fast). Exposed for logging.
+    pub fn skew(&self) -> f64 {
+        self.skew
+    }
+
     /// Update the interpolation parameter during state update.
     /// Sole argument is the update interval in seconds.
     /// Smooth the host time update over one second by advancing alpha by dt and clamping to 1.0.
@@ -169,18 +359,43 @@ impl Synchronizer {
         self.alpha += dt;
     }
 
-    /// Get a (possibly interpolated) estimate of the time on the host.
+    /// Get a (possibly interpolated) estimate of the time on the host, rate-corrected by the
+    /// fitted clock skew so the crossfade blends two skew-corrected predictions rather than
+    /// jumping when the slope is updated.
     pub fn now(&mut self) -> Timestamp {
-        let current = self.current.now();
+        let current = self.current.now_with_skew(self.skew);
         if self.alpha == 1.0 {
             current
         } else {
-            let old = self.last.now();
+            let old = self.last.now_with_skew(self.skew);
             Timestamp(lerp(&old.0, &current.0, &self.alpha.val()))
         }
     }
 }
 
+/// Fit a least-squares line through `(elapsed, offset)` samples and return its slope, the
+/// estimated clock skew. Returns 0 if there are too few samples, or they don't span enough
+/// elapsed time to fit a slope from.
+fn fit_skew(samples: &VecDeque<(f64, f64)>) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let n = samples.len() as f64;
+    let mean_x = samples.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = samples.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let mut covariance = 0.0;
+    let mut variance = 0.0;
+    for (x, y) in samples {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance += (x - mean_x).powi(2);
+    }
+    if variance < f64::EPSILON {
+        0.0
+    } else {
+        covariance / variance
+    }
+}
+
 // This test requires the remote timesync service to be running.
 #[test]
 #[ignore]
@@ -1,3 +1,4 @@
+use crate::audio::AudioEnvelopes;
 use crate::clock::Clock;
 use crate::clock::ControllableClock;
 use crate::clock::Ticks;
@@ -14,7 +15,7 @@ use std::time::Duration;
 use tunnels_lib::number::{BipolarFloat, Phase, UnipolarFloat};
 use tunnels_lib::smooth::Smoother;
 
-#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum Waveform {
     Sine,
     Triangle,
@@ -22,6 +23,11 @@ pub enum Waveform {
     Sawtooth,
     Noise,
     Constant,
+    /// A user-authored periodic shape, read from this animation's `wavetable`.
+    Wavetable,
+    /// Stepped pseudo-random noise from a linear-feedback shift register, for a crunchier,
+    /// Game-Boy-style alternative to the continuous `Noise` field.
+    LfsrNoise,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -42,10 +48,68 @@ pub struct Animation {
     internal_clock: Clock,
     clock_source: Option<ClockIdx>,
     use_audio_size: bool,
+    /// User-authored table of samples for `Waveform::Wavetable`, read as one period over
+    /// `phase in [0, 1)` with linear interpolation between neighboring samples.
+    wavetable: Vec<f64>,
+    /// If true, `Waveform::LfsrNoise` folds feedback into bit 6 as well as bit 14, shortening
+    /// its period from 15 bits to 7 for a more tonal/rattly texture.
+    lfsr_narrow: bool,
+    /// Index of a sibling animation (in whatever collection drives a beam) whose value
+    /// frequency-modulates this animation's phase, mirroring an FM operator stack. Only
+    /// lower-indexed animations may be used as modulators, to rule out cycles.
+    modulator: Option<usize>,
+    /// How strongly the modulator's value offsets this animation's temporal phase.
+    mod_depth: UnipolarFloat,
+    /// State of the `Waveform::LfsrNoise` shift register. Not serialized, since resuming a shift
+    /// register mid-stream isn't meaningful; reseeded deterministically from `ticks` on load.
+    #[serde(skip)]
+    lfsr_reg: u16,
+    /// Tick count as of the last time the LFSR was stepped, so it advances by exactly one step
+    /// per elapsed tick regardless of how many frames that tick spans.
+    #[serde(skip)]
+    lfsr_prev_ticks: Ticks,
+    /// A waveform switch not yet applied, held until the driving clock's phase next crosses
+    /// zero so it doesn't snap the animated value mid-cycle.
+    #[serde(skip)]
+    pending_waveform: Option<Waveform>,
+    /// An invert switch not yet applied; see `pending_waveform`.
+    #[serde(skip)]
+    pending_invert: Option<bool>,
+    /// A large periodicity jump not yet applied; see `pending_waveform`.
+    #[serde(skip)]
+    pending_n_periods: Option<u16>,
+    /// Phase observed on the previous update, used to detect the clock wrapping past zero in
+    /// order to commit `pending_waveform`/`pending_invert`/`pending_n_periods`.
+    #[serde(skip)]
+    crossover_prev_phase: Phase,
+    /// If true, shape the animation's size with `envelope` each time the driving clock's
+    /// downbeat retriggers it, instead of using a constant size.
+    envelope_enabled: bool,
+    envelope: Envelope,
+    /// Current output level of the envelope, in [0, 1]. Transient state, not serialized.
+    #[serde(skip)]
+    envelope_level: f64,
+    /// Time elapsed since the envelope was last retriggered by a downbeat.
+    #[serde(skip)]
+    envelope_elapsed: Duration,
+    /// Phase observed on the previous update, used to detect the clock wrapping past zero.
+    #[serde(skip)]
+    envelope_prev_phase: Phase,
     #[serde(skip, default = "get_simplex_gen")]
     simplex_gen: &'static Simplex,
 }
 
+/// An attack/decay/sustain/release envelope, retriggered every time its driving clock wraps
+/// past its downbeat. Segments use an exponential rather than linear curve, which reads as far
+/// more natural/percussive than a linear ramp for the "forest of peaks" noise-pulse look.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub attack: Duration,
+    pub decay: Duration,
+    pub sustain: UnipolarFloat,
+    pub release: Duration,
+}
+
 fn get_simplex_gen() -> &'static Simplex {
     static SIMPLEX_GEN: LazyLock<Simplex> = LazyLock::new(Default::default);
 
@@ -70,6 +134,26 @@ impl Default for Animation {
             internal_clock: Clock::new(),
             clock_source: None,
             use_audio_size: false,
+            wavetable: Vec::new(),
+            lfsr_narrow: false,
+            lfsr_reg: 0,
+            lfsr_prev_ticks: 0,
+            pending_waveform: None,
+            pending_invert: None,
+            pending_n_periods: None,
+            crossover_prev_phase: Phase::ZERO,
+            modulator: None,
+            mod_depth: UnipolarFloat::ZERO,
+            envelope_enabled: false,
+            envelope: Envelope {
+                attack: Duration::from_millis(10),
+                decay: Duration::from_millis(150),
+                sustain: UnipolarFloat::new(0.5),
+                release: Duration::from_millis(300),
+            },
+            envelope_level: 0.0,
+            envelope_elapsed: Duration::ZERO,
+            envelope_prev_phase: Phase::ZERO,
             simplex_gen: get_simplex_gen(),
         }
     }
@@ -78,6 +162,10 @@ impl Default for Animation {
 impl Animation {
     const SMOOTH_SMOOTH_TIME: Duration = Duration::from_millis(100);
 
+    /// `NPeriods` changes larger than this jump are deferred to the next phase crossover, the
+    /// same as a waveform or invert switch, since they're visually just as discontinuous.
+    const N_PERIODS_JUMP_THRESHOLD: u16 = 1;
+
     /// Return true if this animation has nonzero size.
     fn active(&self) -> bool {
         self.size > 0.0
@@ -107,11 +195,125 @@ impl Animation {
         self.internal_clock.rate_coarse = speed.val() * ControllableClock::RATE_SCALE;
     }
 
-    pub fn update_state(&mut self, delta_t: Duration, audio_envelope: UnipolarFloat) {
+    pub fn update_state<E: EmitStateChange>(
+        &mut self,
+        delta_t: Duration,
+        audio_envelope: UnipolarFloat,
+        external_clocks: &impl ClockStore,
+        emitter: &mut E,
+    ) {
         if self.active() {
             self.internal_clock.update_state(delta_t, audio_envelope);
             self.smoothing.update_state(delta_t);
         }
+        if matches!(self.waveform, Waveform::LfsrNoise) {
+            self.advance_lfsr(external_clocks);
+        }
+        if self.envelope_enabled {
+            self.update_envelope(delta_t, external_clocks);
+        }
+        self.commit_pending_at_crossover(external_clocks, emitter);
+    }
+
+    /// Commit any deferred waveform/invert/periodicity switch once the driving clock's phase
+    /// next crosses zero, so a discontinuous change doesn't snap the animated value mid-cycle
+    /// and pop across every beam using this animation.
+    fn commit_pending_at_crossover<E: EmitStateChange>(
+        &mut self,
+        external_clocks: &impl ClockStore,
+        emitter: &mut E,
+    ) {
+        let phase = self.phase(external_clocks);
+        let crossed_zero = phase < self.crossover_prev_phase;
+        self.crossover_prev_phase = phase;
+        if !crossed_zero {
+            return;
+        }
+        if let Some(v) = self.pending_waveform.take() {
+            self.waveform = v;
+            emitter.emit_animation_state_change(StateChange::Waveform(v));
+        }
+        if let Some(v) = self.pending_invert.take() {
+            self.invert = v;
+            emitter.emit_animation_state_change(StateChange::Invert(v));
+        }
+        if let Some(v) = self.pending_n_periods.take() {
+            self.n_periods = v;
+            emitter.emit_animation_state_change(StateChange::NPeriods(v));
+        }
+    }
+
+    /// Advance the LFSR noise register by one step per elapsed tick of the driving clock. Since
+    /// the register's bit state isn't serialized, reseed it deterministically from the current
+    /// tick count the first time this runs after load, so shows stay reproducible.
+    fn advance_lfsr(&mut self, external_clocks: &impl ClockStore) {
+        let ticks = self.ticks(external_clocks);
+        if self.lfsr_reg == 0 {
+            // 0 is not a valid LFSR state (it would never produce feedback), so it doubles as
+            // the "uninitialized" sentinel left behind by `#[serde(skip)]`.
+            self.lfsr_reg = (ticks as u16) | 1;
+            self.lfsr_prev_ticks = ticks;
+            return;
+        }
+        let steps = ticks.saturating_sub(self.lfsr_prev_ticks).unsigned_abs();
+        // Cap the catch-up to one full period; further steps would just retread the same cycle.
+        for _ in 0..steps.min(1 << 15) {
+            self.lfsr_reg = Self::step_lfsr(self.lfsr_reg, self.lfsr_narrow);
+        }
+        self.lfsr_prev_ticks = ticks;
+    }
+
+    /// Advance a 15-bit Fibonacci LFSR by one step. In "narrow" mode, the feedback bit is also
+    /// copied into bit 6, shortening the period to 7 bits for a more tonal/rattly texture.
+    fn step_lfsr(reg: u16, narrow: bool) -> u16 {
+        let feedback = (reg ^ (reg >> 1)) & 1;
+        let mut next = (reg >> 1) | (feedback << 14);
+        if narrow {
+            next = (next & !(1 << 6)) | (feedback << 6);
+        }
+        next
+    }
+
+    /// Advance the ADSR envelope by one frame, retriggering it if the driving clock's phase has
+    /// just wrapped past its downbeat.
+    fn update_envelope(&mut self, delta_t: Duration, external_clocks: &impl ClockStore) {
+        let phase = self.phase(external_clocks);
+        if phase < self.envelope_prev_phase {
+            self.envelope_elapsed = Duration::ZERO;
+        }
+        self.envelope_prev_phase = phase;
+
+        // Gate the note off once phase has advanced past the "on" portion of the cycle.
+        let gate_open = self.duty_cycle > 0.0 && phase <= self.duty_cycle;
+
+        self.envelope_level = if !gate_open {
+            Self::exp_approach(self.envelope_level, 0.0, delta_t, self.envelope.release)
+        } else if self.envelope_elapsed < self.envelope.attack {
+            Self::exp_approach(self.envelope_level, 1.0, delta_t, self.envelope.attack)
+        } else {
+            Self::exp_approach(
+                self.envelope_level,
+                self.envelope.sustain.val(),
+                delta_t,
+                self.envelope.decay,
+            )
+        };
+
+        self.envelope_elapsed += delta_t;
+    }
+
+    /// Exponentially approach `target` from `current`, settling to within -60dB of it over
+    /// `time_to_settle`. Expressed as a per-frame gain derived from a dB-per-second rate, rather
+    /// than a linear ramp, since exponential segments read as much more natural/percussive.
+    fn exp_approach(current: f64, target: f64, delta_t: Duration, time_to_settle: Duration) -> f64 {
+        if time_to_settle.is_zero() {
+            return target;
+        }
+        const SETTLE_DB: f64 = -60.0;
+        let db_per_sec = SETTLE_DB / time_to_settle.as_secs_f64();
+        let gain_per_sec = 10f64.powf(db_per_sec / 20.0);
+        let gain = gain_per_sec.powf(delta_t.as_secs_f64());
+        target + (current - target) * gain
     }
 
     pub fn get_value(
@@ -119,7 +321,8 @@ impl Animation {
         spatial_phase_offset: Phase,
         offset_index: usize,
         external_clocks: &impl ClockStore,
-        audio_envelope: UnipolarFloat,
+        audio_envelopes: &AudioEnvelopes,
+        modulator_values: &[f64],
     ) -> f64 {
         if !self.active() {
             return 0.;
@@ -127,18 +330,26 @@ impl Animation {
 
         let mut result = self.size.val()
             * match self.waveform {
-                Waveform::Sine => {
-                    waveforms::sine(&self.waveform_args(spatial_phase_offset, external_clocks))
-                }
-                Waveform::Square => {
-                    waveforms::square(&self.waveform_args(spatial_phase_offset, external_clocks))
-                }
-                Waveform::Sawtooth => {
-                    waveforms::sawtooth(&self.waveform_args(spatial_phase_offset, external_clocks))
-                }
-                Waveform::Triangle => {
-                    waveforms::triangle(&self.waveform_args(spatial_phase_offset, external_clocks))
-                }
+                Waveform::Sine => waveforms::sine(&self.waveform_args(
+                    spatial_phase_offset,
+                    external_clocks,
+                    modulator_values,
+                )),
+                Waveform::Square => waveforms::square(&self.waveform_args(
+                    spatial_phase_offset,
+                    external_clocks,
+                    modulator_values,
+                )),
+                Waveform::Sawtooth => waveforms::sawtooth(&self.waveform_args(
+                    spatial_phase_offset,
+                    external_clocks,
+                    modulator_values,
+                )),
+                Waveform::Triangle => waveforms::triangle(&self.waveform_args(
+                    spatial_phase_offset,
+                    external_clocks,
+                    modulator_values,
+                )),
                 Waveform::Noise => {
                     // Handle duty cycle - this is a bit odd compared to waveforms,
                     // since noise isn't periodic. Rather than trying to compress
@@ -187,17 +398,43 @@ impl Animation {
                     val
                 }
                 Waveform::Constant => 1.0,
+                Waveform::LfsrNoise => {
+                    // Gate the note off for a portion of each cycle, the same way the Simplex
+                    // noise path does, since this is likewise not a periodic waveshape that can
+                    // be compressed to fit the duty cycle.
+                    let spatial_phase = spatial_phase_offset.val() * self.n_periods as f64;
+                    let temporal_phase = self.phase(external_clocks).val();
+                    if Phase::new(spatial_phase + temporal_phase) > self.duty_cycle
+                        || self.duty_cycle == 0.0
+                    {
+                        return 0.0;
+                    }
+                    1.0 - (self.lfsr_reg & 1) as f64
+                }
+                Waveform::Wavetable => waveforms::wavetable(
+                    &self.waveform_args(spatial_phase_offset, external_clocks, modulator_values),
+                    &self.wavetable,
+                ),
             };
 
         // scale this animation by submaster level if using external clock
         let mut use_audio_size = self.use_audio_size;
+        let mut audio_envelope_gain = audio_envelopes.wideband;
         if let Some(id) = self.clock_source {
             result *= external_clocks.submaster_level(id).val();
-            use_audio_size = use_audio_size || external_clocks.use_audio_size(id);
+            if external_clocks.use_audio_size(id) {
+                use_audio_size = true;
+                // Use the source clock's response curve and selected band, so quiet passages
+                // still produce visible movement under the perceptual curve.
+                audio_envelope_gain = external_clocks.scale_audio_envelope(id, audio_envelopes);
+            }
         }
         // scale this animation by audio envelope if set
         if use_audio_size {
-            result *= audio_envelope.val();
+            result *= audio_envelope_gain.val();
+        }
+        if self.envelope_enabled {
+            result *= self.envelope_level;
         }
         if self.invert {
             -result
@@ -211,10 +448,17 @@ impl Animation {
         &self,
         spatial_phase_offset: Phase,
         external_clocks: &impl ClockStore,
+        modulator_values: &[f64],
     ) -> WaveformArgs {
+        let mut phase_temporal = self.phase(external_clocks);
+        if let Some(modulator) = self.modulator {
+            if let Some(mod_value) = modulator_values.get(modulator) {
+                phase_temporal = phase_temporal + mod_value * self.mod_depth.val();
+            }
+        }
         WaveformArgs {
             phase_spatial: spatial_phase_offset * (self.n_periods as f64),
-            phase_temporal: self.phase(external_clocks),
+            phase_temporal,
             smoothing: self.smoothing.val(),
             duty_cycle: self.duty_cycle,
             pulse: self.pulse,
@@ -237,6 +481,17 @@ impl Animation {
         emitter.emit_animation_state_change(ClockSource(self.clock_source));
         emitter.emit_animation_state_change(UseAudioSize(self.use_audio_size));
         emitter.emit_animation_state_change(UseAudioSpeed(self.internal_clock.use_audio));
+        emitter.emit_animation_state_change(EnvelopeEnable(self.envelope_enabled));
+        emitter.emit_animation_state_change(Attack(self.envelope.attack));
+        emitter.emit_animation_state_change(Decay(self.envelope.decay));
+        emitter.emit_animation_state_change(Sustain(self.envelope.sustain));
+        emitter.emit_animation_state_change(Release(self.envelope.release));
+        emitter.emit_animation_state_change(Wavetable(
+            self.wavetable.iter().map(|v| UnipolarFloat::new(*v)).collect(),
+        ));
+        emitter.emit_animation_state_change(NoiseWidthNarrow(self.lfsr_narrow));
+        emitter.emit_animation_state_change(Modulator(self.modulator));
+        emitter.emit_animation_state_change(ModDepth(self.mod_depth));
     }
 
     /// Handle a control event.
@@ -267,8 +522,8 @@ impl Animation {
                 emitter.emit_animation_state_change(StateChange::Standing(self.standing));
             }
             ToggleInvert => {
-                self.invert = !self.invert;
-                emitter.emit_animation_state_change(StateChange::Invert(self.invert));
+                let new_invert = !self.pending_invert.unwrap_or(self.invert);
+                self.handle_state_change(StateChange::Invert(new_invert), emitter);
             }
             ToggleUseAudioSize => {
                 self.use_audio_size = !self.use_audio_size;
@@ -280,30 +535,124 @@ impl Animation {
                     self.internal_clock.use_audio,
                 ));
             }
+            ToggleEnvelopeEnable => {
+                self.envelope_enabled = !self.envelope_enabled;
+                emitter
+                    .emit_animation_state_change(StateChange::EnvelopeEnable(self.envelope_enabled));
+            }
+            ToggleNoiseWidth => {
+                self.lfsr_narrow = !self.lfsr_narrow;
+                emitter.emit_animation_state_change(StateChange::NoiseWidthNarrow(
+                    self.lfsr_narrow,
+                ));
+            }
         }
     }
 
+    /// Handle a state change. Waveform/invert switches and large periodicity jumps are
+    /// discontinuous, so rather than applying (and emitting) immediately, they're stashed as
+    /// pending and committed by `commit_pending_at_crossover` the next time the driving clock's
+    /// phase crosses zero.
     fn handle_state_change<E: EmitStateChange>(&mut self, sc: StateChange, emitter: &mut E) {
         use StateChange::*;
-        match sc {
-            Waveform(v) => self.waveform = v,
-            Pulse(v) => self.pulse = v,
-            Standing(v) => self.standing = v,
-            Invert(v) => self.invert = v,
-            NPeriods(v) => self.n_periods = v,
-            Speed(v) => self.set_clock_speed(v),
-            Size(v) => self.size = v,
-            DutyCycle(v) => self.duty_cycle = v,
-            Smoothing(v) => self.smoothing.set_target(v),
-            ClockSource(v) => self.clock_source = v,
-            UseAudioSize(v) => self.use_audio_size = v,
-            UseAudioSpeed(v) => self.internal_clock.use_audio = v,
+        let emit = match sc {
+            Waveform(v) => {
+                self.pending_waveform = Some(v);
+                false
+            }
+            Invert(v) => {
+                self.pending_invert = Some(v);
+                false
+            }
+            NPeriods(v) => {
+                if self.n_periods.abs_diff(v) > Self::N_PERIODS_JUMP_THRESHOLD {
+                    self.pending_n_periods = Some(v);
+                    false
+                } else {
+                    self.n_periods = v;
+                    true
+                }
+            }
+            Pulse(v) => {
+                self.pulse = v;
+                true
+            }
+            Standing(v) => {
+                self.standing = v;
+                true
+            }
+            Speed(v) => {
+                self.set_clock_speed(v);
+                true
+            }
+            Size(v) => {
+                self.size = v;
+                true
+            }
+            DutyCycle(v) => {
+                self.duty_cycle = v;
+                true
+            }
+            Smoothing(v) => {
+                self.smoothing.set_target(v);
+                true
+            }
+            ClockSource(v) => {
+                self.clock_source = v;
+                true
+            }
+            UseAudioSize(v) => {
+                self.use_audio_size = v;
+                true
+            }
+            UseAudioSpeed(v) => {
+                self.internal_clock.use_audio = v;
+                true
+            }
+            EnvelopeEnable(v) => {
+                self.envelope_enabled = v;
+                true
+            }
+            Attack(v) => {
+                self.envelope.attack = v;
+                true
+            }
+            Decay(v) => {
+                self.envelope.decay = v;
+                true
+            }
+            Sustain(v) => {
+                self.envelope.sustain = v;
+                true
+            }
+            Release(v) => {
+                self.envelope.release = v;
+                true
+            }
+            Wavetable(ref v) => {
+                self.wavetable = v.iter().map(|s| s.val()).collect();
+                true
+            }
+            NoiseWidthNarrow(v) => {
+                self.lfsr_narrow = v;
+                true
+            }
+            Modulator(v) => {
+                self.modulator = v;
+                true
+            }
+            ModDepth(v) => {
+                self.mod_depth = v;
+                true
+            }
         };
-        emitter.emit_animation_state_change(sc);
+        if emit {
+            emitter.emit_animation_state_change(sc);
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum StateChange {
     Waveform(Waveform),
     Pulse(bool),
@@ -317,6 +666,15 @@ pub enum StateChange {
     ClockSource(Option<ClockIdx>),
     UseAudioSize(bool),
     UseAudioSpeed(bool),
+    EnvelopeEnable(bool),
+    Attack(Duration),
+    Decay(Duration),
+    Sustain(UnipolarFloat),
+    Release(Duration),
+    Wavetable(Vec<UnipolarFloat>),
+    NoiseWidthNarrow(bool),
+    Modulator(Option<usize>),
+    ModDepth(UnipolarFloat),
 }
 
 #[derive(Debug, Clone)]
@@ -332,6 +690,8 @@ pub enum ControlMessage {
     ToggleInvert,
     ToggleUseAudioSize,
     ToggleUseAudioSpeed,
+    ToggleEnvelopeEnable,
+    ToggleNoiseWidth,
 }
 
 pub trait EmitStateChange {
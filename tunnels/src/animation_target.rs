@@ -6,7 +6,7 @@
 use serde::{Deserialize, Serialize};
 
 /// Tunnel parameters that can be targeted by animations.
-#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+#[derive(Copy, Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub enum AnimationTarget {
     Rotation,
     Thickness,
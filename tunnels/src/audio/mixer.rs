@@ -0,0 +1,216 @@
+//! Mix several named audio input sources (e.g. a mic plus a line feed) into a single summed
+//! stream before it reaches the shared `Processor`, rather than limiting the show to a single
+//! cpal device.
+//!
+//! Each source writes its resampled mono samples into its own circular buffer from its device's
+//! reconnecting input thread, and a dedicated mixer thread wakes on a fixed tick, pulls the next
+//! block of samples from every source, sums them with per-source gain applied, and hands the
+//! result to the `Processor`. Samples carry no timestamps: blocks from different sources are
+//! lined up only by arrival order in each buffer, not by capture time. A source that falls
+//! behind or disconnects simply runs its buffer dry and contributes silence to that tick's mix
+//! rather than stalling the others.
+use audio_processor_traits::AtomicF32;
+use log::info;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use super::processor::{Processor, ProcessorSettings, ProcessorSettingsInner};
+use super::reconnect::ReconnectingInput;
+use super::resampler::Resampler;
+
+/// Sample rate the mixer operates at internally. Every source is resampled to this rate as it
+/// arrives (see `Source::new`), regardless of its device's native rate, so two sources running at
+/// different rates still sum in lockstep instead of drifting apart.
+const MIXER_SAMPLE_RATE: u32 = ProcessorSettingsInner::TARGET_SAMPLE_RATE;
+
+/// How often the mixer thread wakes to pull, sum, and process a block of samples from every
+/// source.
+const MIX_TICK: Duration = Duration::from_millis(5);
+
+/// Cap each source's ring buffer to about one second of audio at `MIXER_SAMPLE_RATE`, so a
+/// stalled or disconnected source can't grow its buffer unbounded.
+const RING_BUFFER_CAPACITY: usize = MIXER_SAMPLE_RATE as usize;
+
+/// Default gain applied to a newly-added source.
+const DEFAULT_SOURCE_GAIN: f32 = 1.0;
+
+/// A circular buffer of downmixed mono samples that a device's input callback writes into, and
+/// that the mixer thread reads aligned blocks out of.
+#[derive(Default)]
+struct RingBuffer(Mutex<VecDeque<f32>>);
+
+impl RingBuffer {
+    fn push(&self, samples: impl Iterator<Item = f32>) {
+        let mut buf = self.0.lock().unwrap();
+        for sample in samples {
+            if buf.len() >= RING_BUFFER_CAPACITY {
+                buf.pop_front();
+            }
+            buf.push_back(sample);
+        }
+    }
+
+    /// Pull up to `count` samples, oldest first, filling any shortfall with silence so a source
+    /// that has fallen behind or disconnected never stalls the mix.
+    fn pull(&self, count: usize) -> Vec<f32> {
+        let mut buf = self.0.lock().unwrap();
+        let available = count.min(buf.len());
+        let mut out: Vec<f32> = buf.drain(..available).collect();
+        out.resize(count, 0.0);
+        out
+    }
+}
+
+/// One named input source feeding the mixer.
+struct Source {
+    name: String,
+    input: ReconnectingInput,
+    buffer: Arc<RingBuffer>,
+    gain: Arc<AtomicF32>,
+}
+
+impl Source {
+    fn new(name: String) -> Self {
+        let buffer = Arc::new(RingBuffer::default());
+        let sink = buffer.clone();
+        // The device may reopen at a different rate after a reconnect, so the resampler is
+        // (re)built lazily from the first buffer of each connection rather than once up front.
+        let mut resampler: Option<(u32, Resampler)> = None;
+        Self {
+            input: ReconnectingInput::new(
+                name.clone(),
+                Box::new(move |interleaved_buffer, channel_count, device_sample_rate| {
+                    if !matches!(resampler, Some((rate, _)) if rate == device_sample_rate) {
+                        resampler = Some((
+                            device_sample_rate,
+                            Resampler::new(device_sample_rate as f64, MIXER_SAMPLE_RATE as f64),
+                        ));
+                    }
+                    let mono: Vec<f32> = downmix_to_mono(interleaved_buffer, channel_count).collect();
+                    let mut resampled = Vec::with_capacity(mono.len());
+                    resampler.as_mut().unwrap().1.process(&mono, &mut resampled);
+                    sink.push(resampled.into_iter());
+                }),
+            ),
+            name,
+            buffer,
+            gain: Arc::new(AtomicF32::new(DEFAULT_SOURCE_GAIN)),
+        }
+    }
+}
+
+/// Average an interleaved multi-channel buffer down to one mono sample per frame.
+fn downmix_to_mono(interleaved_buffer: &[f32], channel_count: usize) -> impl Iterator<Item = f32> + '_ {
+    let channel_count = channel_count.max(1);
+    interleaved_buffer
+        .chunks(channel_count)
+        .map(move |frame| frame.iter().sum::<f32>() / channel_count as f32)
+}
+
+type StopMixer = Box<dyn FnOnce()>;
+
+/// Mixes any number of named audio input sources into the envelope-derivation `Processor`.
+pub struct AudioMixer {
+    sources: Arc<Mutex<Vec<Source>>>,
+    stop: Option<StopMixer>,
+}
+
+impl AudioMixer {
+    /// Build a mixer with the given initial set of named input devices, feeding the shared
+    /// `processor_settings` handle that `AudioInput` reads envelope/speed/tempo state from.
+    pub fn new(device_names: Vec<String>, processor_settings: ProcessorSettings) -> Self {
+        let sources: Vec<Source> = device_names.into_iter().map(Source::new).collect();
+        let sources = Arc::new(Mutex::new(sources));
+
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        let thread_stop_flag = stop_flag.clone();
+        let thread_sources = sources.clone();
+
+        let block_size = (MIXER_SAMPLE_RATE as f64 * MIX_TICK.as_secs_f64()).round() as usize;
+
+        let mix_thread = thread::spawn(move || {
+            let mut processor = Processor::new(processor_settings, MIXER_SAMPLE_RATE, 1);
+            let mut mixed = vec![0.0; block_size];
+            while !thread_stop_flag.load(Ordering::Relaxed) {
+                thread::sleep(MIX_TICK);
+
+                mixed.iter_mut().for_each(|s| *s = 0.0);
+                for source in thread_sources.lock().unwrap().iter() {
+                    let gain = source.gain.get();
+                    for (mixed_sample, source_sample) in
+                        mixed.iter_mut().zip(source.buffer.pull(block_size))
+                    {
+                        *mixed_sample += source_sample * gain;
+                    }
+                }
+                processor.process(&mixed);
+            }
+        });
+
+        Self {
+            sources,
+            stop: Some(Box::new(move || {
+                stop_flag.store(true, Ordering::Relaxed);
+                mix_thread.join().expect("Joining audio mixer thread failed");
+            })),
+        }
+    }
+
+    /// Add a new named input device to the mix, at default gain.
+    pub fn add_source(&mut self, device_name: String) {
+        info!("Adding audio mixer source {device_name}.");
+        self.sources.lock().unwrap().push(Source::new(device_name));
+    }
+
+    /// Remove a named input device from the mix, if present.
+    pub fn remove_source(&mut self, device_name: &str) {
+        self.sources
+            .lock()
+            .unwrap()
+            .retain(|s| s.name != device_name);
+    }
+
+    /// Set the gain applied to a named source's contribution to the mix, if present.
+    pub fn set_gain(&mut self, device_name: &str, gain: f32) {
+        if let Some(source) = self
+            .sources
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|s| s.name == device_name)
+        {
+            source.gain.set(gain);
+        }
+    }
+
+    /// The names and gains of every source currently in the mix, in add order.
+    pub fn sources(&self) -> Vec<(String, f32)> {
+        self.sources
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|s| (s.name.clone(), s.gain.get()))
+            .collect()
+    }
+
+    /// The name and current connection state of every source currently in the mix, in add order.
+    pub fn connection_states(&self) -> Vec<(String, bool)> {
+        self.sources
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|s| (s.name.clone(), s.input.connected()))
+            .collect()
+    }
+}
+
+impl Drop for AudioMixer {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop()
+        }
+    }
+}
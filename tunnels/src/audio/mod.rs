@@ -2,31 +2,74 @@ use crate::master_ui::EmitStateChange as EmitShowStateChange;
 use crate::transient_indicator::TransientIndicator;
 use cpal::traits::{DeviceTrait, HostTrait};
 use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::error::Error;
-use std::time::Duration;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
 use tunnels_lib::number::UnipolarFloat;
+use tunnels_lib::prompt::{prompt_bool, prompt_indexed_value};
 
+pub use self::processor::N_BANDS;
+use self::mixer::AudioMixer;
 use self::processor::ProcessorSettings;
-use self::reconnect::ReconnectingInput;
 
+mod mixer;
 mod processor;
 mod reconnect;
+mod resampler;
 
 pub struct AudioInput {
-    _input: Option<ReconnectingInput>,
+    /// The degenerate zero-source case (no configured device) leaves this `None` and skips
+    /// spinning up a mixer thread at all.
+    mixer: Option<AudioMixer>,
     processor_settings: ProcessorSettings,
     /// Locally-stored value of the envelope.
     envelope_value: UnipolarFloat,
+    /// Locally-stored value of the onset-rate "speed" signal.
+    speed_value: UnipolarFloat,
+    /// Locally-stored value of the true-peak detector, gain-scaled like `envelope_value`. Unlike
+    /// the envelope this is not clamped to the unipolar range, since an inter-sample over by
+    /// definition exceeds full scale.
+    true_peak_value: f32,
+    /// Locally-stored values of the spectral filterbank's per-band envelopes.
+    band_envelope_values: [UnipolarFloat; N_BANDS],
     /// Should we send monitor updates?
     monitor: bool,
     /// Envelope gain factor.
     gain: f64,
     /// Transient envelope clip indicator.
     clip_indicator: TransientIndicator,
+    /// Most recent raw tempo estimate read from the processor, used to detect when a new
+    /// estimate has arrived so we don't keep re-smoothing an unchanged value every tick.
+    last_raw_tempo_bpm: f32,
+    /// When we last accepted a new tempo estimate, to rate-limit how often the tempo we hand
+    /// off to clocks can change.
+    last_tempo_update: Option<Instant>,
+    /// Smoothed tempo estimate, in BPM, handed off to clocks with audio tempo follow enabled.
+    /// `None` until the first tempo estimate is accepted.
+    tempo_bpm: Option<f64>,
+    /// Last-reported connection state of each named source, so `update_state` only emits
+    /// `StateChange::DeviceConnected` on an actual transition rather than every tick.
+    last_connection_states: HashMap<String, bool>,
+    /// Picks beat onsets out of the processor's spectral-flux onset function and estimates tempo
+    /// from their spacing, independent of `tempo_bpm` (see [`BeatDetector`]).
+    beat_detector: BeatDetector,
+    /// Latest tempo estimate from `beat_detector`, in BPM. `None` until enough onsets have been
+    /// seen to trust an estimate.
+    beat_bpm: Option<f64>,
 }
 
 impl AudioInput {
     const CLIP_INDICATOR_DURATION: Duration = Duration::from_millis(100);
+
+    /// Minimum time between tempo updates handed off to clocks, so a burst of onsets can't make
+    /// the driven rate jitter faster than a human would notice as a deliberate tempo change.
+    const TEMPO_UPDATE_INTERVAL: Duration = Duration::from_millis(200);
+
+    /// Weight given to a fresh tempo estimate when folding it into the smoothed value; keeps a
+    /// single outlier detection from snapping the driven rate around.
+    const TEMPO_SMOOTHING: f64 = 0.3;
     /// Get the names of all available input audio devices.
     pub fn devices() -> Result<Vec<String>, Box<dyn Error>> {
         let host = cpal::default_host();
@@ -38,36 +81,54 @@ impl AudioInput {
 
     fn offline() -> Self {
         Self {
-            _input: None,
+            mixer: None,
             processor_settings: ProcessorSettings::default(),
             envelope_value: UnipolarFloat::ZERO,
+            speed_value: UnipolarFloat::ZERO,
+            true_peak_value: 0.0,
+            band_envelope_values: [UnipolarFloat::ZERO; N_BANDS],
             monitor: false,
             gain: 1.0,
             clip_indicator: TransientIndicator::new(Self::CLIP_INDICATOR_DURATION),
+            last_raw_tempo_bpm: 0.0,
+            last_tempo_update: None,
+            tempo_bpm: None,
+            last_connection_states: HashMap::new(),
+            beat_detector: BeatDetector::new(),
+            beat_bpm: None,
         }
     }
 
-    pub fn new(device_name: Option<String>) -> Result<Self, Box<dyn Error>> {
-        let device_name = match device_name {
-            None => {
-                return Ok(Self::offline());
-            }
-            Some(d) => d,
-        };
+    /// Mix the named input devices (e.g. a mic plus a line feed) into a single derived envelope.
+    /// An empty list is the degenerate zero-source case and leaves audio input offline, matching
+    /// prior single-device behavior when no device was configured.
+    pub fn new(device_names: Vec<String>) -> Result<Self, Box<dyn Error>> {
+        if device_names.is_empty() {
+            return Ok(Self::offline());
+        }
 
-        info!("Using audio input device {}.", device_name);
+        info!("Using audio input devices {}.", device_names.join(", "));
 
         let processor_settings = ProcessorSettings::default();
 
-        let input = ReconnectingInput::new(device_name, processor_settings.clone());
+        let mixer = AudioMixer::new(device_names, processor_settings.clone());
 
         Ok(Self {
-            _input: Some(input),
+            mixer: Some(mixer),
             processor_settings,
             envelope_value: UnipolarFloat::ZERO,
+            speed_value: UnipolarFloat::ZERO,
+            true_peak_value: 0.0,
+            band_envelope_values: [UnipolarFloat::ZERO; N_BANDS],
             monitor: false,
             gain: 1.0,
             clip_indicator: TransientIndicator::new(Self::CLIP_INDICATOR_DURATION),
+            last_raw_tempo_bpm: 0.0,
+            last_tempo_update: None,
+            tempo_bpm: None,
+            last_connection_states: HashMap::new(),
+            beat_detector: BeatDetector::new(),
+            beat_bpm: None,
         })
     }
 
@@ -76,13 +137,109 @@ impl AudioInput {
     pub fn update_state<E: EmitStateChange>(&mut self, delta_t: Duration, emitter: &mut E) {
         let raw_envelope = self.processor_settings.envelope.get() as f64;
         let scaled_envelope = raw_envelope * self.gain;
-        let clipping = scaled_envelope > 1.0;
+        self.true_peak_value = self.processor_settings.true_peak.get() * self.gain as f32;
+        // A true-peak over catches brief inter-sample clipping the (RMS-style) envelope smooths
+        // away, so the clip indicator should fire on whichever signal is currently higher.
+        let clipping = scaled_envelope.max(self.true_peak_value as f64) > 1.0;
         self.envelope_value = UnipolarFloat::new(scaled_envelope);
+        self.speed_value = UnipolarFloat::new(self.processor_settings.speed.get() as f64);
+        for (band, value) in self.band_envelope_values.iter_mut().enumerate() {
+            *value = UnipolarFloat::new(self.processor_settings.band_envelopes[band].get() as f64);
+        }
         if self.monitor {
             emitter.emit_audio_state_change(StateChange::EnvelopeValue(self.envelope_value));
+            emitter.emit_audio_state_change(StateChange::TruePeak(self.true_peak_value));
             if let Some(clip_state) = self.clip_indicator.update_state(delta_t, clipping) {
                 emitter.emit_audio_state_change(StateChange::IsClipping(clip_state));
             }
+            for (band, value) in self.band_envelope_values.iter().enumerate() {
+                emitter.emit_audio_state_change(StateChange::BandEnvelope(band, *value));
+            }
+        }
+        if self.update_tempo() && self.monitor {
+            emitter.emit_audio_state_change(StateChange::DetectedTempo(
+                self.tempo_bpm.unwrap_or(0.0),
+            ));
+        }
+        self.update_connection_states(emitter);
+        self.update_beats(emitter);
+    }
+
+    /// Drain the processor's spectral-flux onset readings accumulated since the last tick,
+    /// running each through the beat detector's adaptive-threshold peak picker, and emit a
+    /// `StateChange::Beat` pulse for every accepted onset, plus an updated
+    /// `StateChange::BeatTempo` whenever the detector has enough intervals for an estimate,
+    /// regardless of whether monitoring is enabled, so the master timing layer can phase-lock to
+    /// live music at all times.
+    fn update_beats<E: EmitStateChange>(&mut self, emitter: &mut E) {
+        for (timestamp, flux) in self.processor_settings.onset_flux.drain() {
+            if let Some(bpm) = self.beat_detector.process(timestamp, flux) {
+                emitter.emit_audio_state_change(StateChange::Beat);
+                if let Some(bpm) = bpm {
+                    self.beat_bpm = Some(bpm as f64);
+                    emitter.emit_audio_state_change(StateChange::BeatTempo(bpm as f64));
+                }
+            }
+        }
+    }
+
+    /// Compare each source's current connection state against what was last reported, emitting
+    /// `StateChange::DeviceConnected` for any source whose connectivity changed (a disconnect or
+    /// a recovery), regardless of whether monitoring is enabled, so the UI can always show when
+    /// audio input drops out.
+    fn update_connection_states<E: EmitStateChange>(&mut self, emitter: &mut E) {
+        let Some(mixer) = &self.mixer else {
+            return;
+        };
+        for (device_name, connected) in mixer.connection_states() {
+            let changed = match self.last_connection_states.get(&device_name) {
+                Some(&last) => last != connected,
+                None => true,
+            };
+            if changed {
+                self.last_connection_states
+                    .insert(device_name.clone(), connected);
+                emitter.emit_audio_state_change(StateChange::DeviceConnected(
+                    device_name,
+                    connected,
+                ));
+            }
+        }
+    }
+
+    /// Pull the latest raw tempo estimate out of the processor, if any, and fold it into the
+    /// smoothed tempo estimate handed off to clocks, rate-limited so a burst of onsets can't
+    /// make the driven rate jitter. Returns true if the smoothed estimate changed.
+    fn update_tempo(&mut self) -> bool {
+        let raw = self.processor_settings.detected_tempo_bpm.get();
+        if raw <= 0.0 || raw == self.last_raw_tempo_bpm {
+            return false;
+        }
+        self.last_raw_tempo_bpm = raw;
+
+        let now = Instant::now();
+        if matches!(self.last_tempo_update, Some(t) if now.duration_since(t) < Self::TEMPO_UPDATE_INTERVAL)
+        {
+            return false;
+        }
+        self.last_tempo_update = Some(now);
+
+        let raw = raw as f64;
+        self.tempo_bpm = Some(match self.tempo_bpm {
+            Some(prev) => prev + Self::TEMPO_SMOOTHING * (raw - prev),
+            None => raw,
+        });
+        true
+    }
+
+    /// The envelope mode currently selected, read back from the processor's atomic flags.
+    fn envelope_mode(&self) -> EnvelopeMode {
+        if self.processor_settings.envelope_loudness.load(Ordering::Relaxed) {
+            EnvelopeMode::Loudness
+        } else if self.processor_settings.envelope_gated.load(Ordering::Relaxed) {
+            EnvelopeMode::Gated
+        } else {
+            EnvelopeMode::Follower
         }
     }
 
@@ -90,6 +247,7 @@ impl AudioInput {
     pub fn emit_state<E: EmitStateChange>(&self, emitter: &mut E) {
         use StateChange::*;
         emitter.emit_audio_state_change(EnvelopeValue(self.envelope_value));
+        emitter.emit_audio_state_change(TruePeak(self.true_peak_value));
         emitter.emit_audio_state_change(Monitor(self.monitor));
         emitter.emit_audio_state_change(FilterCutoff(self.processor_settings.filter_cutoff.get()));
         emitter.emit_audio_state_change(EnvelopeAttack(Duration::from_secs_f32(
@@ -98,8 +256,45 @@ impl AudioInput {
         emitter.emit_audio_state_change(EnvelopeRelease(Duration::from_secs_f32(
             self.processor_settings.envelope_release.get(),
         )));
+        emitter.emit_audio_state_change(EnvelopeDecay(Duration::from_secs_f32(
+            self.processor_settings.envelope_decay.get(),
+        )));
+        emitter.emit_audio_state_change(SustainLevel(UnipolarFloat::new(
+            self.processor_settings.sustain_level.get() as f64,
+        )));
+        emitter.emit_audio_state_change(EnvelopeMode(self.envelope_mode()));
+        emitter.emit_audio_state_change(LoudnessReferenceRange(
+            self.processor_settings.loudness_reference_min.get(),
+            self.processor_settings.loudness_reference_max.get(),
+        ));
         emitter.emit_audio_state_change(Gain(self.gain));
         emitter.emit_audio_state_change(IsClipping(self.clip_indicator.state()));
+        for (band, value) in self.band_envelope_values.iter().enumerate() {
+            emitter.emit_audio_state_change(BandEnvelope(band, *value));
+            emitter.emit_audio_state_change(BandFrequency(
+                band,
+                self.processor_settings.band_freq[band].get(),
+            ));
+            emitter.emit_audio_state_change(BandQ(band, self.processor_settings.band_q[band].get()));
+            emitter.emit_audio_state_change(BandGain(
+                band,
+                self.processor_settings.band_gain[band].get(),
+            ));
+            emitter.emit_audio_state_change(BandAttack(
+                band,
+                Duration::from_secs_f32(self.processor_settings.band_attack[band].get()),
+            ));
+            emitter.emit_audio_state_change(BandRelease(
+                band,
+                Duration::from_secs_f32(self.processor_settings.band_release[band].get()),
+            ));
+        }
+        emitter.emit_audio_state_change(DetectedTempo(self.tempo_bpm.unwrap_or(0.0)));
+        emitter.emit_audio_state_change(BeatTempo(self.beat_bpm.unwrap_or(0.0)));
+        for (device_name, gain) in self.sources() {
+            emitter.emit_audio_state_change(SourceAdded(device_name.clone()));
+            emitter.emit_audio_state_change(SourceGain(device_name, gain));
+        }
     }
 
     /// Handle a control event.
@@ -123,7 +318,33 @@ impl AudioInput {
                 self.clip_indicator.reset();
                 self.emit_state(emitter);
             }
+            ToggleEnvelopeMode => {
+                let next = match self.envelope_mode() {
+                    EnvelopeMode::Follower => EnvelopeMode::Gated,
+                    EnvelopeMode::Gated => EnvelopeMode::Loudness,
+                    EnvelopeMode::Loudness => EnvelopeMode::Follower,
+                };
+                self.handle_state_change(StateChange::EnvelopeMode(next), emitter);
+            }
             Set(sc) => self.handle_state_change(sc, emitter),
+            AddSource(device_name) => {
+                if let Some(mixer) = &mut self.mixer {
+                    mixer.add_source(device_name.clone());
+                } else {
+                    self.mixer = Some(AudioMixer::new(
+                        vec![device_name.clone()],
+                        self.processor_settings.clone(),
+                    ));
+                }
+                emitter.emit_audio_state_change(StateChange::SourceAdded(device_name));
+            }
+            RemoveSource(device_name) => {
+                if let Some(mixer) = &mut self.mixer {
+                    mixer.remove_source(&device_name);
+                }
+                self.last_connection_states.remove(&device_name);
+                emitter.emit_audio_state_change(StateChange::SourceRemoved(device_name));
+            }
         }
     }
 
@@ -144,6 +365,24 @@ impl AudioInput {
                 .processor_settings
                 .envelope_release
                 .set(v.as_secs_f32()),
+            EnvelopeDecay(v) => self.processor_settings.envelope_decay.set(v.as_secs_f32()),
+            SustainLevel(v) => self.processor_settings.sustain_level.set(v.val() as f32),
+            EnvelopeMode(v) => {
+                self.processor_settings
+                    .envelope_gated
+                    .store(v == EnvelopeMode::Gated, Ordering::Relaxed);
+                self.processor_settings
+                    .envelope_loudness
+                    .store(v == EnvelopeMode::Loudness, Ordering::Relaxed);
+            }
+            LoudnessReferenceRange(min, max) => {
+                if max <= min {
+                    warn!("Invalid loudness reference range {}..{} (max <= min).", min, max);
+                    return;
+                }
+                self.processor_settings.loudness_reference_min.set(min);
+                self.processor_settings.loudness_reference_max.set(max);
+            }
             Gain(v) => {
                 if v < 0. {
                     warn!("Invalid audio envelope gain {} (< 0).", v);
@@ -155,6 +394,58 @@ impl AudioInput {
             IsClipping(_) => {
                 return; // output only
             }
+            BandEnvelope(_, _) => {
+                return; // output only
+            }
+            BandFrequency(band, v) => {
+                if v <= 0. || band >= N_BANDS {
+                    warn!("Invalid band crossover frequency {} for band {}.", v, band);
+                    return;
+                }
+                self.processor_settings.band_freq[band].set(v);
+            }
+            BandQ(band, v) => {
+                if v <= 0. || band >= N_BANDS {
+                    warn!("Invalid band Q {} for band {}.", v, band);
+                    return;
+                }
+                self.processor_settings.band_q[band].set(v);
+            }
+            BandGain(band, v) => {
+                if v < 0. || band >= N_BANDS {
+                    warn!("Invalid band gain {} for band {}.", v, band);
+                    return;
+                }
+                self.processor_settings.band_gain[band].set(v);
+            }
+            BandAttack(band, v) => {
+                if band >= N_BANDS {
+                    return;
+                }
+                self.processor_settings.band_attack[band].set(v.as_secs_f32());
+            }
+            BandRelease(band, v) => {
+                if band >= N_BANDS {
+                    return;
+                }
+                self.processor_settings.band_release[band].set(v.as_secs_f32());
+            }
+            DetectedTempo(_) => {
+                return; // output only
+            }
+            SourceGain(ref device_name, v) => {
+                if v < 0. {
+                    warn!("Invalid audio mixer source gain {} (< 0).", v);
+                    return;
+                }
+                if let Some(mixer) = &mut self.mixer {
+                    mixer.set_gain(device_name, v);
+                }
+            }
+            SourceAdded(_) | SourceRemoved(_) | DeviceConnected(_, _) | TruePeak(_) | Beat
+            | BeatTempo(_) => {
+                return; // output only
+            }
         };
         emitter.emit_audio_state_change(sc);
     }
@@ -163,23 +454,269 @@ impl AudioInput {
     pub fn envelope(&self) -> UnipolarFloat {
         self.envelope_value
     }
+
+    /// Return the current value of the onset-rate "speed" signal, for clocks that want to track
+    /// tempo rather than amplitude.
+    pub fn speed(&self) -> UnipolarFloat {
+        self.speed_value
+    }
+
+    /// Bundle the current wideband and per-band envelope values together, so a clock can select
+    /// which one drives its submaster size modulation.
+    pub fn audio_envelopes(&self) -> AudioEnvelopes {
+        AudioEnvelopes {
+            wideband: self.envelope_value,
+            bands: self.band_envelope_values,
+        }
+    }
+
+    /// Return the current smoothed tempo estimate, in BPM, for clocks that have opted into audio
+    /// tempo follow. `None` until the onset detector has produced its first stable estimate.
+    pub fn tempo_bpm(&self) -> Option<f64> {
+        self.tempo_bpm
+    }
+
+    /// Return the current tempo estimate from the beat detector's onset histogram, in BPM.
+    /// Independent of `tempo_bpm`, which is derived from the energy-based `TempoDetector` instead
+    /// of discrete beat onsets. `None` until enough onsets have been seen to trust an estimate.
+    pub fn beat_bpm(&self) -> Option<f64> {
+        self.beat_bpm
+    }
+
+    /// The names and gains of every source currently mixed into the envelope, in add order.
+    pub fn sources(&self) -> Vec<(String, f32)> {
+        match &self.mixer {
+            Some(mixer) => mixer.sources(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Picks discrete beat onsets out of the processor's per-block spectral-flux onset function with
+/// a threshold-with-adaptive-mean peak picker, then folds the resulting inter-onset intervals
+/// into a tempo estimate. Deliberately independent of `processor::TempoDetector`, which tracks
+/// tempo from raw buffer energy rather than real onsets, so the two can disagree and a consumer
+/// can pick whichever tracks a given source better.
+struct BeatDetector {
+    /// Recent (timestamp, flux) readings within `STATS_WINDOW`, used to compute the rolling mean
+    /// and standard deviation the peak picker thresholds against.
+    flux_history: VecDeque<(Instant, f32)>,
+    /// When the last onset was accepted, for refractory gating and interval measurement.
+    last_onset: Option<Instant>,
+    /// Inter-onset intervals accepted so far, oldest first, each already folded into
+    /// `MIN_BPM..=MAX_BPM` to correct for octave errors (e.g. a half-time backbeat).
+    intervals: VecDeque<Duration>,
+}
+
+impl BeatDetector {
+    /// Trailing window the rolling mean/standard deviation of the onset function is computed
+    /// over.
+    const STATS_WINDOW: Duration = Duration::from_secs(1);
+
+    /// Minimum time between accepted onsets, so a single transient's decay can't multi-trigger.
+    const REFRACTORY: Duration = Duration::from_millis(100);
+
+    /// An onset is accepted when the flux reading exceeds the rolling mean by this many standard
+    /// deviations.
+    const THRESHOLD_K: f32 = 1.5;
+
+    /// How many recent inter-onset intervals feed the tempo histogram.
+    const INTERVAL_HISTORY: usize = 16;
+
+    /// Require at least this many intervals before trusting the histogram's dominant bin.
+    const MIN_INTERVALS_FOR_ESTIMATE: usize = 4;
+
+    /// Reject/fold tempo estimates to within this range, covering the large majority of dance and
+    /// popular music tempos while still being narrow enough to disambiguate octave errors.
+    const MIN_BPM: f32 = 60.0;
+    const MAX_BPM: f32 = 180.0;
+
+    fn new() -> Self {
+        Self {
+            flux_history: VecDeque::new(),
+            last_onset: None,
+            intervals: VecDeque::new(),
+        }
+    }
+
+    /// Feed one block's onset-flux reading. Returns `None` if it wasn't accepted as an onset, or
+    /// `Some(bpm)` if it was, where `bpm` is an updated tempo estimate once enough intervals have
+    /// accumulated to trust the histogram's dominant bin.
+    fn process(&mut self, now: Instant, flux: f32) -> Option<Option<f32>> {
+        // Compute the rolling mean/standard deviation from history *before* this reading is
+        // folded in, so a strong transient is judged against what came before it rather than
+        // against a threshold it has already dragged upward.
+        let n = self.flux_history.len() as f32;
+        let threshold = if n == 0.0 {
+            0.0
+        } else {
+            let mean = self.flux_history.iter().map(|(_, v)| *v).sum::<f32>() / n;
+            let variance = self
+                .flux_history
+                .iter()
+                .map(|(_, v)| (v - mean).powi(2))
+                .sum::<f32>()
+                / n;
+            mean + Self::THRESHOLD_K * variance.sqrt()
+        };
+
+        self.flux_history.push_back((now, flux));
+        while matches!(self.flux_history.front(), Some((t, _)) if now.duration_since(*t) > Self::STATS_WINDOW)
+        {
+            self.flux_history.pop_front();
+        }
+
+        let refractory =
+            matches!(self.last_onset, Some(t) if now.duration_since(t) < Self::REFRACTORY);
+        if refractory || n < 1.0 || flux <= threshold {
+            return None;
+        }
+
+        let interval = self.last_onset.map(|t| now.duration_since(t));
+        self.last_onset = Some(now);
+        Some(interval.and_then(|interval| self.record_interval(interval)))
+    }
+
+    /// Fold a raw inter-onset interval into the `MIN_BPM..=MAX_BPM` range by repeated
+    /// doubling/halving (correcting for e.g. a listener or algorithm locking onto every other
+    /// beat), then return the dominant bin of the resulting tempo histogram once there's enough
+    /// history to trust it.
+    fn record_interval(&mut self, interval: Duration) -> Option<f32> {
+        if interval.is_zero() {
+            return None;
+        }
+        let mut bpm = 60.0 / interval.as_secs_f32();
+        while bpm < Self::MIN_BPM {
+            bpm *= 2.0;
+        }
+        while bpm > Self::MAX_BPM {
+            bpm /= 2.0;
+        }
+
+        self.intervals.push_back(Duration::from_secs_f32(60.0 / bpm));
+        if self.intervals.len() > Self::INTERVAL_HISTORY {
+            self.intervals.pop_front();
+        }
+        if self.intervals.len() < Self::MIN_INTERVALS_FOR_ESTIMATE {
+            return None;
+        }
+
+        // Bucket each folded interval's tempo into 1-BPM-wide bins and report the dominant bin's
+        // mean, so a handful of stray onsets can't swing the estimate the way a plain mean would.
+        let mut bins: HashMap<i32, Vec<f32>> = HashMap::new();
+        for interval in &self.intervals {
+            let bpm = 60.0 / interval.as_secs_f32();
+            bins.entry(bpm.round() as i32).or_default().push(bpm);
+        }
+        bins.into_values()
+            .max_by_key(|bin| bin.len())
+            .map(|bin| bin.iter().sum::<f32>() / bin.len() as f32)
+    }
 }
 
-#[derive(Debug)]
+/// Which algorithm derives the output envelope from the followed input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EnvelopeMode {
+    /// Track the filtered envelope follower output directly.
+    Follower,
+    /// Run the followed envelope through a four-stage ADSR generator, gated by a threshold
+    /// crossing, producing percussive, snappy modulation shapes a plain follower cannot.
+    Gated,
+    /// Report K-weighted perceptual loudness (EBU R128 short-term LUFS) mapped onto a
+    /// configurable reference range, for a brightness/intensity driver that isn't dominated by
+    /// bass transients the way a raw envelope is.
+    Loudness,
+}
+
+/// Which audio envelope signal a clock's `use_audio_size` submaster modulation draws from: the
+/// overall wideband envelope, or one band of the spectral filterbank.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AudioEnvelopeSource {
+    #[default]
+    Wideband,
+    Band(usize),
+}
+
+/// A bundle of the wideband and per-band envelope values produced by the spectral filterbank in
+/// a single frame, so a clock can select which one drives its submaster size modulation without
+/// every consumer needing its own handle onto the audio input.
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize)]
+pub struct AudioEnvelopes {
+    pub wideband: UnipolarFloat,
+    pub bands: [UnipolarFloat; N_BANDS],
+}
+
+impl AudioEnvelopes {
+    /// Select the envelope value requested by `source`, falling back to the wideband envelope if
+    /// the requested band is out of range.
+    pub fn select(&self, source: AudioEnvelopeSource) -> UnipolarFloat {
+        match source {
+            AudioEnvelopeSource::Wideband => self.wideband,
+            AudioEnvelopeSource::Band(i) => self.bands.get(i).copied().unwrap_or(self.wideband),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum StateChange {
     Monitor(bool),
     EnvelopeValue(UnipolarFloat),
     FilterCutoff(f32),
     EnvelopeAttack(Duration),
     EnvelopeRelease(Duration),
+    EnvelopeDecay(Duration),
+    SustainLevel(UnipolarFloat),
+    EnvelopeMode(EnvelopeMode),
+    /// Reference range (min, max), in LUFS, mapped onto the unipolar loudness envelope output.
+    LoudnessReferenceRange(f32, f32),
     Gain(f64),
     IsClipping(bool),
+    /// Outgoing only, no effect as control. Gain-scaled true-peak reading from the most recently
+    /// processed buffer, not clamped to the unipolar range since an inter-sample over by
+    /// definition exceeds full scale.
+    TruePeak(f32),
+    /// Outgoing only, no effect as control. The envelope level of one band of the spectral
+    /// filterbank.
+    BandEnvelope(usize, UnipolarFloat),
+    /// Center frequency (Hz) of one band's bandpass crossover.
+    BandFrequency(usize, f32),
+    /// Q of one band's bandpass crossover.
+    BandQ(usize, f32),
+    /// Output gain applied to one band's envelope.
+    BandGain(usize, f32),
+    /// Attack time of one band's envelope follower, independent of the wideband envelope's.
+    BandAttack(usize, Duration),
+    /// Release time of one band's envelope follower, independent of the wideband envelope's.
+    BandRelease(usize, Duration),
+    /// Outgoing only, no effect as control. The latest smoothed tempo estimate from the onset
+    /// detector, in BPM.
+    DetectedTempo(f64),
+    /// The gain applied to a named mixer source's contribution to the summed envelope input.
+    SourceGain(String, f32),
+    /// Outgoing only, no effect as control. A named source was added to the mixer.
+    SourceAdded(String),
+    /// Outgoing only, no effect as control. A named source was removed from the mixer.
+    SourceRemoved(String),
+    /// Outgoing only, no effect as control. A named source's device connection came up or went
+    /// down, e.g. a USB interface glitch or its recovery.
+    DeviceConnected(String, bool),
+    /// Outgoing only, no effect as control. A discrete beat onset was detected, for UI elements
+    /// (or a future phase-locking consumer) that want a pulse rather than a continuous envelope.
+    Beat,
+    /// Outgoing only, no effect as control. The beat detector's current tempo estimate, in BPM,
+    /// independent of `DetectedTempo`.
+    BeatTempo(f64),
 }
 
 pub enum ControlMessage {
     Set(StateChange),
     ToggleMonitor,
     ResetParameters,
+    ToggleEnvelopeMode,
+    /// Add a named input device to the mixer, starting audio input if it was previously offline.
+    AddSource(String),
+    /// Remove a named input device from the mixer.
+    RemoveSource(String),
 }
 
 pub trait EmitStateChange {
@@ -192,3 +729,19 @@ impl<T: EmitShowStateChange> EmitStateChange for T {
         self.emit(ShowStateChange::Audio(sc))
     }
 }
+
+/// Prompt the user to select zero or more audio input devices to mix into the envelope.
+pub fn prompt_audio() -> Result<Vec<String>, Box<dyn Error>> {
+    let devices = AudioInput::devices()?;
+    let mut selected = Vec::new();
+    if devices.is_empty() || !prompt_bool("Use an audio input device?")? {
+        return Ok(selected);
+    }
+    loop {
+        selected.push(prompt_indexed_value("Audio input device:", &devices)?);
+        if !prompt_bool("Add another audio input device?")? {
+            break;
+        }
+    }
+    Ok(selected)
+}
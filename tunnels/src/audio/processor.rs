@@ -5,26 +5,182 @@ use audio_processor_traits::AudioProcessorSettings;
 use audio_processor_traits::{simple_processor::MonoAudioProcessor, AtomicF32, AudioContext};
 use augmented_dsp_filters::rbj::{FilterProcessor, FilterType};
 use log::debug;
-use std::sync::Arc;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+/// Center frequency of the K-weighting cascade's high-shelf stage (RLB pre-filter per
+/// ITU-R BS.1770 / EBU R128), approximating the ear's increased sensitivity above ~1.5 kHz.
+const K_WEIGHT_SHELF_FREQ: f32 = 1500.0;
+
+/// Boost applied by the K-weighting high-shelf stage, in dB.
+const K_WEIGHT_SHELF_GAIN_DB: f32 = 4.0;
+
+/// Cutoff of the K-weighting cascade's second stage, a high-pass approximating the "revised
+/// low-frequency B" (RLB) curve's bass rolloff.
+const K_WEIGHT_HIGHPASS_FREQ: f32 = 38.0;
+
+/// EBU R128's "momentary" loudness integration window.
+const LOUDNESS_MOMENTARY_WINDOW: Duration = Duration::from_millis(400);
+
+/// EBU R128's "short-term" loudness integration window; used as the window the loudness
+/// envelope mode reports, since it's stable enough to drive a continuous modulation signal
+/// without the momentary window's faster jitter.
+const LOUDNESS_SHORT_TERM_WINDOW: Duration = Duration::from_secs(3);
+
+/// Default reference range (LUFS) mapped onto the unipolar envelope output: the low end reads
+/// as silence, the high end as full scale.
+const DEFAULT_LOUDNESS_REFERENCE_MIN: f32 = -40.0;
+const DEFAULT_LOUDNESS_REFERENCE_MAX: f32 = 0.0;
+
+/// How far back we look when counting onsets to estimate the "speed" signal.
+const ONSET_WINDOW: Duration = Duration::from_secs(2);
+
+/// A rise in the envelope larger than this between consecutive process calls counts as an onset.
+const ONSET_THRESHOLD: f32 = 0.05;
+
+/// Treat this many onsets per `ONSET_WINDOW` (roughly a 4 Hz beat) as maximum speed, normalizing
+/// the onset count seen in the window onto the unipolar range.
+const MAX_ONSETS_PER_WINDOW: f32 = 8.0;
+
+/// How far back the tempo detector's energy history reaches when computing the local mean that
+/// onsets are judged against.
+const TEMPO_ENERGY_WINDOW: Duration = Duration::from_secs(1);
+
+/// Minimum time between accepted tempo onsets, to avoid double-triggering on a single transient.
+const TEMPO_ONSET_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// An onset is flagged when instantaneous energy exceeds this multiple of the local mean energy.
+const TEMPO_ONSET_RATIO: f32 = 1.4;
+
+/// How many recent inter-onset intervals the tempo detector keeps around to take a running
+/// median over, so a handful of stray early/late hits can't swing the tempo estimate.
+const TEMPO_INTERVAL_HISTORY: usize = 8;
+
+/// Reject a median interval implying a tempo outside this range as spurious.
+const TEMPO_MIN_BPM: f32 = 40.0;
+const TEMPO_MAX_BPM: f32 = 220.0;
+
+/// Number of bands in the spectral envelope filterbank.
+pub const N_BANDS: usize = 4;
+
+/// Oversampling factor applied by the true-peak detector before taking the inter-sample maximum.
+const TRUE_PEAK_UPSAMPLE_FACTOR: usize = 4;
+
+/// Number of taps per polyphase phase in the true-peak upsampling filter.
+const TRUE_PEAK_TAPS_PER_PHASE: usize = 12;
+
+/// Center frequency (Hz) and Q of each band's RBJ bandpass filter, in ascending order, roughly
+/// separating bass, low-mid, high-mid and high content (e.g. bass drum vs. hi-hats) so separate
+/// clocks can be driven by separate spectral content.
+const BAND_FREQS: [(f32, f32); N_BANDS] = [(80., 1.0), (300., 1.0), (1500., 1.0), (6000., 1.0)];
+
+/// Cap the number of onset-flux readings the ring holds before the oldest are dropped, so a
+/// consumer that stops polling `ProcessorSettingsInner::onset_flux` can't grow it unbounded.
+const ONSET_FLUX_RING_CAPACITY: usize = 256;
+
+/// A ring of recent (timestamp, spectral-flux) onset-function readings, one per processed block,
+/// that `AudioInput`'s beat detector polls and drains on each `update_state` tick.
+#[derive(Default)]
+pub struct OnsetFluxRing(Mutex<VecDeque<(Instant, f32)>>);
+
+impl OnsetFluxRing {
+    fn push(&self, now: Instant, flux: f32) {
+        let mut ring = self.0.lock().unwrap();
+        if ring.len() >= ONSET_FLUX_RING_CAPACITY {
+            ring.pop_front();
+        }
+        ring.push_back((now, flux));
+    }
+
+    /// Drain every reading accumulated since the last drain, oldest first.
+    pub fn drain(&self) -> Vec<(Instant, f32)> {
+        self.0.lock().unwrap().drain(..).collect()
+    }
+}
+
+impl Clone for OnsetFluxRing {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().unwrap().clone()))
+    }
+}
+
 #[derive(Clone)]
 pub struct ProcessorSettingsInner {
     pub envelope: AtomicF32,
+    /// Onset-rate "speed" signal, on the unipolar range, for clocks that want to track tempo
+    /// rather than amplitude.
+    pub speed: AtomicF32,
     pub filter_cutoff: AtomicF32,    // Hz
     pub envelope_attack: AtomicF32,  // sec
     pub envelope_release: AtomicF32, // sec
+    /// Decay time of the gated ADSR envelope mode, from the attack peak down to `sustain_level`.
+    pub envelope_decay: AtomicF32, // sec
+    /// Sustain level of the gated ADSR envelope mode, held while the gate stays open.
+    pub sustain_level: AtomicF32,
+    /// If true, replace the raw envelope follower output with the gated ADSR generator's output.
+    pub envelope_gated: AtomicBool,
+    /// If true, replace the raw envelope follower output with the K-weighted loudness output,
+    /// taking priority over `envelope_gated` if both are somehow set.
+    pub envelope_loudness: AtomicBool,
+    /// Low end of the LUFS range mapped onto the unipolar loudness envelope output.
+    pub loudness_reference_min: AtomicF32,
+    /// High end of the LUFS range mapped onto the unipolar loudness envelope output.
+    pub loudness_reference_max: AtomicF32,
+    /// Per-band envelope levels from the spectral filterbank, in ascending frequency order.
+    pub band_envelopes: [AtomicF32; N_BANDS],
+    /// Per-band bandpass center frequency (Hz), user-adjustable so crossovers can be retuned to a
+    /// show's material rather than living with the fixed defaults.
+    pub band_freq: [AtomicF32; N_BANDS],
+    /// Per-band bandpass Q.
+    pub band_q: [AtomicF32; N_BANDS],
+    /// Per-band output gain, applied after that band's envelope follower.
+    pub band_gain: [AtomicF32; N_BANDS],
+    /// Per-band envelope follower attack time (sec), independent of the wideband envelope's.
+    pub band_attack: [AtomicF32; N_BANDS],
+    /// Per-band envelope follower release time (sec), independent of the wideband envelope's.
+    pub band_release: [AtomicF32; N_BANDS],
+    /// Most recent tempo estimate from the onset-based tempo detector, in BPM. Zero means no
+    /// tempo has been detected yet.
+    pub detected_tempo_bpm: AtomicF32,
+    /// Peak absolute sample value found by 4x-oversampling the most recently processed buffer,
+    /// catching brief inter-sample overs that the envelope follower smooths away.
+    pub true_peak: AtomicF32,
+    /// Per-block spectral-flux onset function, one reading per processed buffer, polled by
+    /// `AudioInput`'s beat detector to phase-lock the master clock to live music.
+    pub onset_flux: OnsetFluxRing,
 }
 
 impl ProcessorSettingsInner {
+    /// Fixed internal analysis rate that incoming audio is resampled to before reaching
+    /// `Processor`, regardless of whatever rate the device actually negotiates. Keeps filter
+    /// cutoffs, envelope timing constants, and onset thresholds device-independent.
+    pub const TARGET_SAMPLE_RATE: u32 = 44_100;
+
     const DEFAULT_FILTER_CUTOFF: f32 = 200.;
     const DEFAULT_ENVELOPE_ATTACK: f32 = 0.01;
     const DEFAULT_ENVELOPE_RELEASE: f32 = 0.1;
+    const DEFAULT_ENVELOPE_DECAY: f32 = 0.1;
+    const DEFAULT_SUSTAIN_LEVEL: f32 = 0.5;
 
     pub fn reset_defaults(&self) {
         self.filter_cutoff.set(Self::DEFAULT_FILTER_CUTOFF);
         self.envelope_attack.set(Self::DEFAULT_ENVELOPE_ATTACK);
         self.envelope_release.set(Self::DEFAULT_ENVELOPE_RELEASE);
+        self.envelope_decay.set(Self::DEFAULT_ENVELOPE_DECAY);
+        self.sustain_level.set(Self::DEFAULT_SUSTAIN_LEVEL);
+        self.loudness_reference_min
+            .set(DEFAULT_LOUDNESS_REFERENCE_MIN);
+        self.loudness_reference_max
+            .set(DEFAULT_LOUDNESS_REFERENCE_MAX);
+        for band in 0..N_BANDS {
+            self.band_freq[band].set(BAND_FREQS[band].0);
+            self.band_q[band].set(BAND_FREQS[band].1);
+            self.band_gain[band].set(1.0);
+            self.band_attack[band].set(Self::DEFAULT_ENVELOPE_ATTACK);
+            self.band_release[band].set(Self::DEFAULT_ENVELOPE_RELEASE);
+        }
     }
 }
 
@@ -32,13 +188,360 @@ impl Default for ProcessorSettingsInner {
     fn default() -> Self {
         Self {
             envelope: AtomicF32::new(0.0),
+            speed: AtomicF32::new(0.0),
             filter_cutoff: AtomicF32::new(Self::DEFAULT_FILTER_CUTOFF),
             envelope_attack: AtomicF32::new(Self::DEFAULT_ENVELOPE_ATTACK),
             envelope_release: AtomicF32::new(Self::DEFAULT_ENVELOPE_RELEASE),
+            envelope_decay: AtomicF32::new(Self::DEFAULT_ENVELOPE_DECAY),
+            sustain_level: AtomicF32::new(Self::DEFAULT_SUSTAIN_LEVEL),
+            envelope_gated: AtomicBool::new(false),
+            envelope_loudness: AtomicBool::new(false),
+            loudness_reference_min: AtomicF32::new(DEFAULT_LOUDNESS_REFERENCE_MIN),
+            loudness_reference_max: AtomicF32::new(DEFAULT_LOUDNESS_REFERENCE_MAX),
+            band_envelopes: std::array::from_fn(|_| AtomicF32::new(0.0)),
+            band_freq: std::array::from_fn(|i| AtomicF32::new(BAND_FREQS[i].0)),
+            band_q: std::array::from_fn(|i| AtomicF32::new(BAND_FREQS[i].1)),
+            band_gain: std::array::from_fn(|_| AtomicF32::new(1.0)),
+            band_attack: std::array::from_fn(|_| AtomicF32::new(Self::DEFAULT_ENVELOPE_ATTACK)),
+            band_release: std::array::from_fn(|_| AtomicF32::new(Self::DEFAULT_ENVELOPE_RELEASE)),
+            detected_tempo_bpm: AtomicF32::new(0.0),
+            true_peak: AtomicF32::new(0.0),
+            onset_flux: OnsetFluxRing::default(),
+        }
+    }
+}
+
+/// A stage in a four-stage ADSR (attack/decay/sustain/release) envelope generator.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum AdsrStage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// Drives a percussive, gated envelope shape from a gate signal, modeled on the ADSR generators
+/// in FM/PSG synth chips, rather than tracking the input amplitude directly.
+struct AdsrGenerator {
+    stage: AdsrStage,
+    level: f32,
+}
+
+impl AdsrGenerator {
+    /// The followed envelope must rise past this level to be considered a gate-open edge.
+    const GATE_THRESHOLD: f32 = 0.05;
+
+    fn new() -> Self {
+        Self {
+            stage: AdsrStage::Idle,
+            level: 0.0,
+        }
+    }
+
+    /// Advance the generator by `dt` seconds given the current gate state and ADSR timing
+    /// parameters (attack/decay/release in seconds, sustain as a 0..1 level), returning the new
+    /// output level.
+    fn process(&mut self, gate_open: bool, dt: f32, attack: f32, decay: f32, sustain: f32, release: f32) -> f32 {
+        if gate_open {
+            // A rising edge retriggers the envelope, even mid-release, restarting from the
+            // current level rather than snapping back to zero.
+            if matches!(self.stage, AdsrStage::Idle | AdsrStage::Release) {
+                self.stage = if attack <= 0.0 {
+                    AdsrStage::Decay
+                } else {
+                    AdsrStage::Attack
+                };
+            }
+        } else if !matches!(self.stage, AdsrStage::Idle | AdsrStage::Release) {
+            self.stage = AdsrStage::Release;
+        }
+
+        match self.stage {
+            AdsrStage::Idle => self.level = 0.0,
+            AdsrStage::Attack => {
+                self.level = Self::ramp_toward(self.level, 1.0, attack, dt);
+                if self.level >= 1.0 {
+                    // Jump straight into Decay on the same sample if attack is effectively zero.
+                    self.stage = AdsrStage::Decay;
+                }
+            }
+            AdsrStage::Decay => {
+                self.level = Self::ramp_toward(self.level, sustain, decay, dt);
+                if (self.level - sustain).abs() <= f32::EPSILON {
+                    self.stage = AdsrStage::Sustain;
+                }
+            }
+            AdsrStage::Sustain => self.level = sustain,
+            AdsrStage::Release => {
+                self.level = Self::ramp_toward(self.level, 0.0, release, dt);
+                if self.level <= 0.0 {
+                    self.stage = AdsrStage::Idle;
+                }
+            }
+        }
+        self.level
+    }
+
+    /// Step `current` toward `target` at a rate of `1.0 / time` per second, snapping straight to
+    /// `target` if `time` is effectively zero.
+    fn ramp_toward(current: f32, target: f32, time: f32, dt: f32) -> f32 {
+        if time <= 0.0 {
+            return target;
+        }
+        let step = dt / time;
+        if current < target {
+            (current + step).min(target)
+        } else {
+            (current - step).max(target)
+        }
+    }
+}
+
+/// Detects rhythmic onsets directly from buffer energy and derives a tempo estimate from the
+/// interval between them. This is deliberately independent of the envelope-derivative onset
+/// counting that drives the "speed" signal above: that one is tuned to produce a continuous
+/// unipolar knob, while this one is tuned to produce a stable BPM a clock can lock its rate to.
+struct TempoDetector {
+    /// Instantaneous energy of recent buffers, oldest first, spanning roughly
+    /// `TEMPO_ENERGY_WINDOW`.
+    energy_history: VecDeque<(Instant, f32)>,
+    /// When the last accepted onset happened, for debouncing and interval measurement.
+    last_onset: Option<Instant>,
+    /// Interval between the last several accepted onsets, oldest first.
+    intervals: VecDeque<Duration>,
+}
+
+impl TempoDetector {
+    fn new() -> Self {
+        Self {
+            energy_history: VecDeque::new(),
+            last_onset: None,
+            intervals: VecDeque::new(),
+        }
+    }
+
+    /// Process one buffer's instantaneous energy (mean of squared samples), returning a new
+    /// median-filtered tempo estimate in BPM if this energy reading completed an onset and we
+    /// now have enough intervals to trust the estimate.
+    fn process(&mut self, energy: f32) -> Option<f32> {
+        let now = Instant::now();
+        let local_mean = if self.energy_history.is_empty() {
+            energy
+        } else {
+            self.energy_history.iter().map(|(_, e)| *e).sum::<f32>()
+                / self.energy_history.len() as f32
+        };
+
+        self.energy_history.push_back((now, energy));
+        while matches!(self.energy_history.front(), Some((t, _)) if now.duration_since(*t) > TEMPO_ENERGY_WINDOW)
+        {
+            self.energy_history.pop_front();
+        }
+
+        let debounced =
+            matches!(self.last_onset, Some(t) if now.duration_since(t) < TEMPO_ONSET_DEBOUNCE);
+        if debounced || local_mean <= 0.0 || energy <= TEMPO_ONSET_RATIO * local_mean {
+            return None;
+        }
+
+        let interval = self.last_onset.map(|t| now.duration_since(t));
+        self.last_onset = Some(now);
+        let interval = interval?;
+
+        self.intervals.push_back(interval);
+        if self.intervals.len() > TEMPO_INTERVAL_HISTORY {
+            self.intervals.pop_front();
+        }
+        // Require a few intervals before trusting the median, so a single stray onset can't
+        // produce a tempo estimate on its own.
+        if self.intervals.len() < 3 {
+            return None;
+        }
+
+        let mut sorted: Vec<Duration> = self.intervals.iter().copied().collect();
+        sorted.sort();
+        let median = sorted[sorted.len() / 2];
+        let bpm = 60.0 / median.as_secs_f32();
+        if !(TEMPO_MIN_BPM..=TEMPO_MAX_BPM).contains(&bpm) {
+            return None;
         }
+        Some(bpm)
     }
 }
 
+/// Tracks K-weighted mean-square energy over a sliding window (EBU R128's "momentary" or
+/// "short-term" integration), converting to LUFS on demand.
+struct LoudnessWindow {
+    window: Duration,
+    /// Per-block (timestamp, weighted mean square, sample count), oldest first.
+    blocks: VecDeque<(Instant, f32, usize)>,
+}
+
+impl LoudnessWindow {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            blocks: VecDeque::new(),
+        }
+    }
+
+    /// Fold in one block's K-weighted mean square energy, then return the integrated loudness in
+    /// LUFS over the trailing window.
+    fn push(&mut self, mean_square: f32, sample_count: usize) -> f32 {
+        let now = Instant::now();
+        self.blocks.push_back((now, mean_square, sample_count));
+        while matches!(self.blocks.front(), Some((t, _, _)) if now.duration_since(*t) > self.window)
+        {
+            self.blocks.pop_front();
+        }
+
+        let (weighted_sum, total_samples) = self
+            .blocks
+            .iter()
+            .fold((0.0, 0usize), |(sum, count), (_, ms, n)| {
+                (sum + ms * *n as f32, count + n)
+            });
+        if total_samples == 0 {
+            return f32::NEG_INFINITY;
+        }
+        let mean_square = weighted_sum / total_samples as f32;
+        // ITU-R BS.1770's -0.691 dB offset accounts for the K-weighting filter's passband gain.
+        -0.691 + 10.0 * mean_square.max(f32::MIN_POSITIVE).log10()
+    }
+}
+
+/// Per-channel K-weighting cascade (high-shelf then RLB high-pass) feeding a [`LoudnessWindow`],
+/// implementing a simplified ITU-R BS.1770 / EBU R128 loudness meter.
+struct LoudnessMeter {
+    shelf_filters: Vec<FilterProcessor<f32>>,
+    highpass_filters: Vec<FilterProcessor<f32>>,
+    momentary: LoudnessWindow,
+    short_term: LoudnessWindow,
+}
+
+impl LoudnessMeter {
+    fn new(context: &mut AudioContext, channel_count: usize) -> Self {
+        let mut shelf_filters = Vec::with_capacity(channel_count);
+        let mut highpass_filters = Vec::with_capacity(channel_count);
+        for _ in 0..channel_count {
+            let mut shelf = FilterProcessor::new(FilterType::HighShelf);
+            shelf.set_cutoff(K_WEIGHT_SHELF_FREQ);
+            shelf.set_gain_db(K_WEIGHT_SHELF_GAIN_DB);
+            shelf.m_prepare(context);
+            shelf_filters.push(shelf);
+
+            let mut highpass = FilterProcessor::new(FilterType::HighPass);
+            highpass.set_cutoff(K_WEIGHT_HIGHPASS_FREQ);
+            highpass.m_prepare(context);
+            highpass_filters.push(highpass);
+        }
+        Self {
+            shelf_filters,
+            highpass_filters,
+            momentary: LoudnessWindow::new(LOUDNESS_MOMENTARY_WINDOW),
+            short_term: LoudnessWindow::new(LOUDNESS_SHORT_TERM_WINDOW),
+        }
+    }
+
+    /// K-weight one channel's sample in place, for folding into the block's mean square.
+    fn k_weight(&mut self, context: &mut AudioContext, channel: usize, sample: f32) -> f32 {
+        let shelved = self.shelf_filters[channel].m_process(context, sample);
+        self.highpass_filters[channel].m_process(context, shelved)
+    }
+
+    /// Fold one block's accumulated K-weighted mean square into both integration windows,
+    /// returning the short-term LUFS value the loudness envelope mode reports.
+    fn integrate(&mut self, mean_square: f32, sample_count: usize) -> f32 {
+        self.momentary.push(mean_square, sample_count);
+        self.short_term.push(mean_square, sample_count)
+    }
+}
+
+/// Detects inter-sample ("true") peaks per channel by oversampling the raw input
+/// `TRUE_PEAK_UPSAMPLE_FACTOR`x with a short windowed-sinc polyphase FIR and taking the maximum
+/// absolute interpolated sample, catching brief digital overs that a sample-rate peak reading (or
+/// the envelope follower's smoothing) would miss entirely.
+struct TruePeakDetector {
+    /// Polyphase FIR coefficients, `phases[phase][tap]`, one phase per interpolated sub-sample.
+    phases: [[f32; TRUE_PEAK_TAPS_PER_PHASE]; TRUE_PEAK_UPSAMPLE_FACTOR],
+    /// Per-channel history of the last `TRUE_PEAK_TAPS_PER_PHASE` raw samples, oldest first.
+    history: Vec<VecDeque<f32>>,
+}
+
+impl TruePeakDetector {
+    fn new(channel_count: usize) -> Self {
+        Self {
+            phases: Self::build_phases(),
+            history: (0..channel_count)
+                .map(|_| VecDeque::from(vec![0.0; TRUE_PEAK_TAPS_PER_PHASE]))
+                .collect(),
+        }
+    }
+
+    /// Split a windowed-sinc low-pass prototype, sized for `TRUE_PEAK_UPSAMPLE_FACTOR`x
+    /// interpolation, into its polyphase components.
+    fn build_phases() -> [[f32; TRUE_PEAK_TAPS_PER_PHASE]; TRUE_PEAK_UPSAMPLE_FACTOR] {
+        let taps = TRUE_PEAK_UPSAMPLE_FACTOR * TRUE_PEAK_TAPS_PER_PHASE;
+        let center = (taps - 1) as f32 / 2.0;
+        let mut prototype = vec![0.0f32; taps];
+        for (i, h) in prototype.iter_mut().enumerate() {
+            let x = i as f32 - center;
+            let sinc = if x.abs() < 1e-6 {
+                1.0
+            } else {
+                let px = std::f32::consts::PI * x / TRUE_PEAK_UPSAMPLE_FACTOR as f32;
+                px.sin() / px
+            };
+            // Hann window tames the sinc's slow rolloff over this short a kernel.
+            let window =
+                0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (taps - 1) as f32).cos();
+            *h = sinc * window;
+        }
+        // Normalize to unity DC gain across the whole upsampled output; each phase on its own
+        // only sees every `TRUE_PEAK_UPSAMPLE_FACTOR`th tap; the interpolated samples would
+        // otherwise come out `TRUE_PEAK_UPSAMPLE_FACTOR`x too quiet.
+        let sum: f32 = prototype.iter().sum();
+        if sum != 0.0 {
+            for h in prototype.iter_mut() {
+                *h *= TRUE_PEAK_UPSAMPLE_FACTOR as f32 / sum;
+            }
+        }
+
+        std::array::from_fn(|phase| {
+            std::array::from_fn(|tap| prototype[phase + tap * TRUE_PEAK_UPSAMPLE_FACTOR])
+        })
+    }
+
+    /// Feed one raw sample for `channel` and return the peak absolute value among the
+    /// interpolated sub-samples it produces.
+    fn process_sample(&mut self, channel: usize, sample: f32) -> f32 {
+        let history = &mut self.history[channel];
+        history.push_back(sample);
+        history.pop_front();
+
+        let mut peak = 0.0f32;
+        for phase in &self.phases {
+            let interpolated: f32 = phase
+                .iter()
+                .zip(history.iter().rev())
+                .map(|(h, s)| h * s)
+                .sum();
+            peak = peak.max(interpolated.abs());
+        }
+        peak
+    }
+}
+
+/// Map a LUFS reading onto the unipolar range given a reference range, clamping out-of-range
+/// values rather than extrapolating past 0.0/1.0.
+fn loudness_to_unipolar(lufs: f32, reference_min: f32, reference_max: f32) -> f32 {
+    if !lufs.is_finite() || reference_max <= reference_min {
+        return 0.0;
+    }
+    ((lufs - reference_min) / (reference_max - reference_min)).clamp(0.0, 1.0)
+}
+
 pub type ProcessorSettings = Arc<ProcessorSettingsInner>;
 
 pub struct Processor {
@@ -47,9 +550,36 @@ pub struct Processor {
     envelope_attack: f32,
     envelope_release: f32,
     channel_count: usize,
+    sample_rate: f32,
     filters: Vec<FilterProcessor<f32>>,
     envelopes: Vec<EnvelopeFollowerProcessor>,
+    /// Per-band bandpass filters, indexed `[band][channel]`.
+    band_filters: [Vec<FilterProcessor<f32>>; N_BANDS],
+    /// Per-band envelope followers, indexed `[band][channel]`.
+    band_envelopes: [Vec<EnvelopeFollowerProcessor>; N_BANDS],
+    /// Cached per-band crossover frequency, Q, and envelope timing, to detect changes without
+    /// reconfiguring filters/envelopes on every block.
+    band_freq: [f32; N_BANDS],
+    band_q: [f32; N_BANDS],
+    band_attack: [f32; N_BANDS],
+    band_release: [f32; N_BANDS],
     context: AudioContext,
+    /// The mean envelope value as of the previous process call, to detect onsets from its
+    /// derivative.
+    prev_envelope: f32,
+    /// Timestamps of recent onsets, oldest first, used to estimate the "speed" signal.
+    onsets: VecDeque<Instant>,
+    /// Drives the gated ADSR envelope mode.
+    adsr: AdsrGenerator,
+    /// Tracks rhythmic onsets and the tempo implied by their spacing.
+    tempo_detector: TempoDetector,
+    /// Drives the K-weighted loudness envelope mode.
+    loudness_meter: LoudnessMeter,
+    /// Detects inter-sample true peaks ahead of the clip indicator.
+    true_peak_detector: TruePeakDetector,
+    /// Each band's mean envelope as of the previous block, to compute the spectral-flux onset
+    /// function from their positive differences.
+    prev_band_means: [f32; N_BANDS],
 }
 
 impl Processor {
@@ -65,6 +595,10 @@ impl Processor {
 
         let mut filters = vec![];
         let mut envelopes = vec![];
+        let mut band_filters: [Vec<FilterProcessor<f32>>; N_BANDS] =
+            std::array::from_fn(|_| Vec::new());
+        let mut band_envelopes: [Vec<EnvelopeFollowerProcessor>; N_BANDS] =
+            std::array::from_fn(|_| Vec::new());
 
         let filter_cutoff = handle.filter_cutoff.get();
         let envelope_attack = handle.envelope_attack.get();
@@ -83,17 +617,54 @@ impl Processor {
             envelope.m_prepare(&mut context);
 
             envelopes.push(envelope);
+
+            for band in 0..N_BANDS {
+                let mut band_filter = FilterProcessor::new(FilterType::BandPass);
+                band_filter.set_cutoff(handle.band_freq[band].get());
+                band_filter.set_q(handle.band_q[band].get());
+                band_filter.m_prepare(&mut context);
+                band_filters[band].push(band_filter);
+
+                let mut band_envelope = EnvelopeFollowerProcessor::new(
+                    Duration::from_secs_f32(handle.band_attack[band].get()),
+                    Duration::from_secs_f32(handle.band_release[band].get()),
+                );
+                band_envelope.m_prepare(&mut context);
+                band_envelopes[band].push(band_envelope);
+            }
         }
 
+        let band_freq = std::array::from_fn(|band| handle.band_freq[band].get());
+        let band_q = std::array::from_fn(|band| handle.band_q[band].get());
+        let band_attack = std::array::from_fn(|band| handle.band_attack[band].get());
+        let band_release = std::array::from_fn(|band| handle.band_release[band].get());
+
+        let loudness_meter = LoudnessMeter::new(&mut context, settings.input_channels);
+        let true_peak_detector = TruePeakDetector::new(settings.input_channels);
+
         Self {
             filter_cutoff,
             envelope_attack,
             envelope_release,
             settings: handle,
             channel_count: settings.input_channels,
+            sample_rate: settings.sample_rate,
             filters,
             envelopes,
+            band_filters,
+            band_envelopes,
+            band_freq,
+            band_q,
+            band_attack,
+            band_release,
             context,
+            prev_envelope: 0.0,
+            onsets: VecDeque::new(),
+            adsr: AdsrGenerator::new(),
+            tempo_detector: TempoDetector::new(),
+            loudness_meter,
+            true_peak_detector,
+            prev_band_means: [0.0; N_BANDS],
         }
     }
 
@@ -126,6 +697,34 @@ impl Processor {
                 handle.set_release(release);
             }
         }
+
+        for band in 0..N_BANDS {
+            let new_freq = self.settings.band_freq[band].get();
+            let new_q = self.settings.band_q[band].get();
+            if new_freq != self.band_freq[band] || new_q != self.band_q[band] {
+                self.band_freq[band] = new_freq;
+                self.band_q[band] = new_q;
+                for filter in self.band_filters[band].iter_mut() {
+                    filter.set_cutoff(new_freq);
+                    filter.set_q(new_q);
+                }
+            }
+
+            let new_band_attack = self.settings.band_attack[band].get();
+            let new_band_release = self.settings.band_release[band].get();
+            if new_band_attack != self.band_attack[band] || new_band_release != self.band_release[band]
+            {
+                self.band_attack[band] = new_band_attack;
+                self.band_release[band] = new_band_release;
+                let attack = Duration::from_secs_f32(new_band_attack);
+                let release = Duration::from_secs_f32(new_band_release);
+                for band_envelope in self.band_envelopes[band].iter_mut() {
+                    let handle = band_envelope.handle();
+                    handle.set_attack(attack);
+                    handle.set_release(release);
+                }
+            }
+        }
     }
 }
 
@@ -134,14 +733,45 @@ impl Processor {
     pub fn process(&mut self, interleaved_buffer: &[f32]) {
         self.maybe_update_parameters();
 
+        let mut k_weighted_sum_sq = 0.0f32;
+        let mut k_weighted_count = 0usize;
+        let mut true_peak = 0.0f32;
+
         for frame in interleaved_buffer.chunks(self.channel_count) {
-            for (channel_idx, sample) in frame.iter().enumerate() {
-                let sample = self.filters[channel_idx].m_process(&mut self.context, *sample);
+            for (channel_idx, raw_sample) in frame.iter().enumerate() {
+                true_peak = true_peak.max(
+                    self.true_peak_detector
+                        .process_sample(channel_idx, *raw_sample),
+                );
+
+                let filtered = self.filters[channel_idx].m_process(&mut self.context, *raw_sample);
                 let envelope = &mut self.envelopes[channel_idx];
-                envelope.m_process(&mut self.context, sample);
+                envelope.m_process(&mut self.context, filtered);
+
+                // Run each band's bandpass filter on the raw (pre-lowpass) sample, so the
+                // filterbank sees the full spectrum rather than whatever the mono envelope's
+                // lowpass has already attenuated.
+                for band in 0..N_BANDS {
+                    let band_sample = self.band_filters[band][channel_idx]
+                        .m_process(&mut self.context, *raw_sample);
+                    self.band_envelopes[band][channel_idx].m_process(&mut self.context, band_sample);
+                }
+
+                let k_weighted = self
+                    .loudness_meter
+                    .k_weight(&mut self.context, channel_idx, *raw_sample);
+                k_weighted_sum_sq += k_weighted * k_weighted;
+                k_weighted_count += 1;
             }
         }
 
+        let loudness_lufs = if k_weighted_count > 0 {
+            self.loudness_meter
+                .integrate(k_weighted_sum_sq / k_weighted_count as f32, k_weighted_count)
+        } else {
+            f32::NEG_INFINITY
+        };
+
         let mean_envelope = self
             .envelopes
             .iter()
@@ -149,6 +779,66 @@ impl Processor {
             .sum::<f32>()
             / self.channel_count as f32;
 
-        self.settings.envelope.set(mean_envelope);
+        // A rising edge in the envelope steep enough to clear the threshold counts as an onset;
+        // count threshold-crossings within a trailing window to estimate the "speed" signal.
+        if mean_envelope - self.prev_envelope > ONSET_THRESHOLD {
+            self.onsets.push_back(Instant::now());
+        }
+        self.prev_envelope = mean_envelope;
+
+        let now = Instant::now();
+        while matches!(self.onsets.front(), Some(t) if now.duration_since(*t) > ONSET_WINDOW) {
+            self.onsets.pop_front();
+        }
+        let speed = (self.onsets.len() as f32 / MAX_ONSETS_PER_WINDOW).min(1.0);
+
+        let output = if self.settings.envelope_loudness.load(Ordering::Relaxed) {
+            loudness_to_unipolar(
+                loudness_lufs,
+                self.settings.loudness_reference_min.get(),
+                self.settings.loudness_reference_max.get(),
+            )
+        } else if self.settings.envelope_gated.load(Ordering::Relaxed) {
+            let frame_count = interleaved_buffer.len() / self.channel_count.max(1);
+            let dt = frame_count as f32 / self.sample_rate;
+            let gate_open = mean_envelope > AdsrGenerator::GATE_THRESHOLD;
+            self.adsr.process(
+                gate_open,
+                dt,
+                self.envelope_attack,
+                self.settings.envelope_decay.get(),
+                self.settings.sustain_level.get(),
+                self.envelope_release,
+            )
+        } else {
+            mean_envelope
+        };
+
+        self.settings.envelope.set(output);
+        self.settings.speed.set(speed);
+        self.settings.true_peak.set(true_peak);
+
+        let mut onset_flux = 0.0f32;
+        for band in 0..N_BANDS {
+            let band_mean = self.band_envelopes[band]
+                .iter()
+                .map(|envelope| envelope.handle().state())
+                .sum::<f32>()
+                / self.channel_count as f32;
+            let gain = self.settings.band_gain[band].get();
+            self.settings.band_envelopes[band].set(band_mean * gain);
+
+            // Spectral flux: only rises (not falls) in each band's envelope count as onset
+            // energy, so a band decaying after a hit doesn't cancel out another band's attack.
+            onset_flux += (band_mean - self.prev_band_means[band]).max(0.0);
+            self.prev_band_means[band] = band_mean;
+        }
+        self.settings.onset_flux.push(Instant::now(), onset_flux);
+
+        let instantaneous_energy = interleaved_buffer.iter().map(|s| s * s).sum::<f32>()
+            / interleaved_buffer.len().max(1) as f32;
+        if let Some(bpm) = self.tempo_detector.process(instantaneous_energy) {
+            self.settings.detected_tempo_bpm.set(bpm);
+        }
     }
 }
@@ -6,56 +6,103 @@ use cpal::BufferSize;
 use cpal::SupportedBufferSize;
 use cpal::{Device, Stream, StreamError};
 use log::{info, warn};
-use std::sync::mpsc::channel;
+use ringbuf::traits::{Consumer, Producer, Split};
+use ringbuf::HeapRb;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
-use super::processor::{Processor, ProcessorSettings};
+/// Called with each interleaved buffer read from the device, the number of interleaved channels
+/// it contains, and the device's negotiated sample rate in Hz, from a dedicated analysis thread
+/// (not the device's realtime audio callback thread).
+pub type BufferSink = Box<dyn FnMut(&[f32], usize, u32) + Send>;
+
+/// How much audio the lock-free ring between the callback and the analysis thread can hold
+/// before the callback starts dropping the oldest buffered samples to make room for new ones.
+const RING_BUFFER_DURATION: Duration = Duration::from_millis(50);
+
+/// How long the analysis thread sleeps between polls of the ring buffer when it found nothing
+/// to drain on its last pass.
+const ANALYSIS_POLL_INTERVAL: Duration = Duration::from_millis(2);
 
 pub struct ReconnectingInput {
-    stop: Option<StopReconnect>,
+    control: Sender<Cmd>,
+    thread: Option<thread::JoinHandle<()>>,
+    connected: Arc<AtomicBool>,
 }
 
 impl ReconnectingInput {
     /// Create a new self-reconnecting input.
     /// Device disconnection is handled asynchronously and will attempt to
     /// reconnect the device until this struct is dropped.
-    pub fn new(device_name: String, processor_settings: ProcessorSettings) -> Self {
+    ///
+    /// Every buffer read from the device is copied into a lock-free ring by the realtime audio
+    /// callback, then handed to `on_buffer` on a dedicated analysis thread that drains the ring;
+    /// `on_buffer` does not own or run a `Processor` itself, so that multiple inputs can feed a
+    /// single shared `Processor` (see `audio::mixer`).
+    pub fn new(device_name: String, on_buffer: BufferSink) -> Self {
+        let connected = Arc::new(AtomicBool::new(false));
+        let (control, thread) = reconnect(device_name, on_buffer, connected.clone());
         Self {
-            stop: Some(reconnect(device_name, processor_settings)),
+            control,
+            thread: Some(thread),
+            connected,
         }
     }
+
+    /// Switch this input to a different named device. Reuses the same teardown/reopen path as
+    /// an ordinary disconnect, so the stream underneath is swapped out without recreating this
+    /// struct or disturbing whatever `Processor` state `on_buffer` feeds downstream.
+    pub fn set_device(&self, device_name: String) {
+        self.control.send(Cmd::SwitchDevice(device_name)).ok();
+    }
+
+    /// Whether the device is currently open and streaming, as of the last reconnect attempt.
+    pub fn connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
 }
 
 impl Drop for ReconnectingInput {
     fn drop(&mut self) {
-        if let Some(stop) = self.stop.take() {
-            stop()
+        self.control
+            .send(Cmd::Stop)
+            .expect("Sending stop to reconnect thread failed");
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("Joining reconnect thread failed");
         }
     }
 }
 
-type StopReconnect = Box<dyn FnOnce()>;
+enum Cmd {
+    Stop,
+    Disconnected,
+    SwitchDevice(String),
+}
 
 /// Try to reconnect a disconnected audio input this often.
 const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
 
-/// Spawn a thread to handle device disconnection.
-/// Return a closure that can be called to terminate the input stream, blocking
-/// until it completes.
-fn reconnect(device_name: String, processor_settings: ProcessorSettings) -> StopReconnect {
-    enum Cmd {
-        Stop,
-        Disconnected,
-    }
+/// Spawn a thread to handle device disconnection and on-demand device switching.
+/// Return a sender to drive the thread and its join handle. `connected` is updated to reflect
+/// whether the device is currently open, so a caller can surface connection state to the user.
+fn reconnect(
+    device_name: String,
+    on_buffer: BufferSink,
+    connected: Arc<AtomicBool>,
+) -> (Sender<Cmd>, thread::JoinHandle<()>) {
     use Cmd::*;
 
     let (send, recv) = channel::<Cmd>();
     // Load an initial command into the queue to open input.
     send.send(Cmd::Disconnected).unwrap();
     let disconnected_sender = send.clone();
+    let on_buffer = Arc::new(Mutex::new(on_buffer));
 
     let reconnect_thread = thread::spawn(move || {
+        let mut device_name = device_name;
         let mut _input_stream = None;
         for event in recv {
             match event {
@@ -63,15 +110,25 @@ fn reconnect(device_name: String, processor_settings: ProcessorSettings) -> Stop
                     info!("Audio reconnect thread is stopping.");
                     return;
                 }
+                SwitchDevice(name) => {
+                    info!("Switching audio input from {device_name} to {name}.");
+                    device_name = name;
+                    // Drop the existing stream and fall through the same reopen path used for
+                    // an ordinary disconnect.
+                    _input_stream = None;
+                    connected.store(false, Ordering::Relaxed);
+                    disconnected_sender.send(Disconnected).ok();
+                }
                 Disconnected => {
                     // Drop the existing stream.
                     {
                         _input_stream = None;
                     }
+                    connected.store(false, Ordering::Relaxed);
                     // Try to re-open.
                     let sender = disconnected_sender.clone();
                     let reopen_result =
-                        create_input_stream(&device_name, processor_settings.clone(), move || {
+                        create_input_stream(&device_name, on_buffer.clone(), move || {
                             sender.send(Disconnected).ok();
                             warn!("Audio input disconnected.");
                         });
@@ -80,6 +137,7 @@ fn reconnect(device_name: String, processor_settings: ProcessorSettings) -> Stop
                         Ok(input) => {
                             info!("Successfully opened audio input {device_name}.");
                             _input_stream = Some(input);
+                            connected.store(true, Ordering::Relaxed);
                         }
                         Err(e) => {
                             warn!("Unable to reopen audio input {device_name}: {e}.");
@@ -96,13 +154,7 @@ fn reconnect(device_name: String, processor_settings: ProcessorSettings) -> Stop
         }
     });
 
-    Box::new(move || {
-        send.send(Stop)
-            .expect("Sending stop to reconnect thread failed");
-        reconnect_thread
-            .join()
-            .expect("Joining reconnect thread failed");
-    })
+    (send, reconnect_thread)
 }
 
 fn open_audio_device(name: &str) -> Result<Device> {
@@ -131,11 +183,73 @@ fn open_audio_device(name: &str) -> Result<Device> {
     bail!(err_msg);
 }
 
+/// An audio input device available on this host, as surfaced to a device-picker UI.
+#[derive(Debug, Clone)]
+pub struct InputDeviceInfo {
+    pub name: String,
+    /// The device's default input sample rate in Hz.
+    pub default_sample_rate: u32,
+    /// The device's default input channel count.
+    pub default_channels: u16,
+}
+
+/// List every audio input device visible to the default host, along with its default input
+/// config, so a UI can present them and let the user pick one for `ReconnectingInput::set_device`.
+/// A device whose name or default config can't be read is skipped with a warning rather than
+/// failing the whole enumeration.
+pub fn list_input_devices() -> Result<Vec<InputDeviceInfo>> {
+    let host = cpal::default_host();
+    let mut devices = Vec::new();
+    for input in host.input_devices()? {
+        let name = match input.name() {
+            Ok(name) => name,
+            Err(e) => {
+                warn!("Error reading audio input device name: {e}");
+                continue;
+            }
+        };
+        match input.default_input_config() {
+            Ok(config) => devices.push(InputDeviceInfo {
+                name,
+                default_sample_rate: config.sample_rate().0,
+                default_channels: config.channels(),
+            }),
+            Err(e) => {
+                warn!("Error reading default input config for audio device {name}: {e}");
+            }
+        }
+    }
+    Ok(devices)
+}
+
+/// A live cpal `Stream` bundled with the dedicated analysis thread that drains its lock-free
+/// ring buffer. Dropping this stops the device callback first, then signals and joins the
+/// analysis thread, so the pair always tear down together.
+struct InputStream {
+    stream: Option<Stream>,
+    stop: Arc<AtomicBool>,
+    analysis_thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Drop for InputStream {
+    fn drop(&mut self) {
+        // Drop the stream first so the callback stops feeding the ring before we stop draining
+        // it.
+        self.stream.take();
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(analysis_thread) = self.analysis_thread.take() {
+            analysis_thread
+                .join()
+                .expect("Joining audio analysis thread failed");
+        }
+    }
+}
+
 fn create_input_stream<F>(
     device_name: &str,
-    processor_settings: ProcessorSettings,
+    on_buffer: Arc<Mutex<BufferSink>>,
     mut on_disconnect: F,
-) -> Result<Stream>
+) -> Result<InputStream>
 where
     F: FnMut() + Send + 'static,
 {
@@ -173,14 +287,27 @@ where
     let mut config: cpal::StreamConfig = supported.into();
     config.buffer_size = BufferSize::Fixed(frame_count);
 
-    let mut processor = Processor::new(
-        processor_settings,
-        config.sample_rate.0,
-        config.channels as usize,
-    );
+    let channel_count = config.channels as usize;
+    let device_sample_rate = config.sample_rate.0;
+    let sample_rate = device_sample_rate as f64;
 
+    let ring_capacity = ((sample_rate * RING_BUFFER_DURATION.as_secs_f64()).round() as usize
+        * channel_count)
+        .max(channel_count);
+    let (mut producer, mut consumer) = HeapRb::<f32>::new(ring_capacity).split();
+    let dropped_samples = Arc::new(AtomicU64::new(0));
+
+    // The realtime callback: copy samples into the lock-free ring, dropping the oldest
+    // buffered sample to make room on overrun. No allocation, no locking.
+    let callback_dropped_samples = dropped_samples.clone();
     let handle_buffer = move |interleaved_buffer: &[f32], _: &cpal::InputCallbackInfo| {
-        processor.process(interleaved_buffer);
+        for &sample in interleaved_buffer {
+            // `push_overwrite` drops the oldest buffered sample to make room when the ring is
+            // full, rather than dropping the newest (incoming) one.
+            if producer.push_overwrite(sample).is_some() {
+                callback_dropped_samples.fetch_add(1, Ordering::Relaxed);
+            }
+        }
     };
 
     let handle_error = move |err: StreamError| match err {
@@ -192,8 +319,38 @@ where
         }
     };
 
-    let input_stream = device.build_input_stream(&config, handle_buffer, handle_error, None)?;
+    let stream = device.build_input_stream(&config, handle_buffer, handle_error, None)?;
+    stream.play()?;
+
+    // The analysis thread: drain the ring into a reusable scratch buffer and hand it to the
+    // configured sink, off the realtime thread.
+    let stop = Arc::new(AtomicBool::new(false));
+    let analysis_stop = stop.clone();
+    let scratch_capacity = frame_count as usize * channel_count;
+    let analysis_thread = thread::spawn(move || {
+        let mut scratch = vec![0.0f32; scratch_capacity];
+        let mut last_reported_drops = 0u64;
+        loop {
+            if analysis_stop.load(Ordering::Relaxed) {
+                return;
+            }
+            let popped = consumer.pop_slice(&mut scratch);
+            if popped > 0 {
+                (on_buffer.lock().unwrap())(&scratch[..popped], channel_count, device_sample_rate);
+            } else {
+                thread::sleep(ANALYSIS_POLL_INTERVAL);
+            }
+            let total_dropped = dropped_samples.load(Ordering::Relaxed);
+            if total_dropped != last_reported_drops {
+                warn!("Audio input ring buffer overran; dropped {total_dropped} samples so far.");
+                last_reported_drops = total_dropped;
+            }
+        }
+    });
 
-    input_stream.play()?;
-    Ok(input_stream)
+    Ok(InputStream {
+        stream: Some(stream),
+        stop,
+        analysis_thread: Some(analysis_thread),
+    })
 }
@@ -0,0 +1,75 @@
+//! Convert an incoming sample stream at an arbitrary device rate to a fixed internal analysis
+//! rate, mirroring the dedicated resampler stage cubeb-coreaudio uses to decouple device rate
+//! from stream rate. Without this, `Processor`'s filter cutoffs and envelope timing constants
+//! would have to be re-derived for whatever rate a device happens to negotiate (44.1k, 48k,
+//! 96k...), and would drift if a reconnect ever landed on a different rate than before.
+//!
+//! This is a simple per-channel linear interpolator: cheap and alias-prone, since it has no
+//! anti-aliasing lowpass ahead of downsampling. That trades some fidelity for low latency and
+//! simplicity, which is the right call for an envelope-following sidechain rather than a
+//! program audio path. A higher-quality windowed-sinc kernel would be a natural follow-up if
+//! aliasing artifacts ever show up in the derived envelope.
+
+/// Resample a single channel of audio from one sample rate to another via linear interpolation.
+/// Carries just enough state across calls to `process` that a stream can be resampled a block
+/// at a time with no seam at the boundary between blocks.
+pub struct Resampler {
+    /// `input_rate / output_rate`: how many input samples advance per output sample.
+    ratio: f64,
+    /// Fractional read position into the input passed to the next `process` call. A negative
+    /// value means the next output sample still needs to reach back into `last_sample` from the
+    /// previous block.
+    pos: f64,
+    /// The final sample of the previous block, treated as sitting at index `-1` of the current
+    /// block so interpolation can cross the boundary between blocks seamlessly.
+    last_sample: f32,
+}
+
+impl Resampler {
+    /// Build a resampler converting from `input_rate` to `output_rate`, both in Hz.
+    pub fn new(input_rate: f64, output_rate: f64) -> Self {
+        Self {
+            ratio: input_rate / output_rate,
+            pos: 0.0,
+            last_sample: 0.0,
+        }
+    }
+
+    /// Resample one block of input, appending the result to `output`. Leaves any fractional
+    /// remainder in `pos` for the next call rather than rounding it away, so no seams or drift
+    /// accumulate at block boundaries.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        while self.pos < input.len() as f64 {
+            let index = self.pos.floor() as isize;
+            let frac = (self.pos - index as f64) as f32;
+
+            let Some(a) = self.sample_at(input, index) else {
+                break;
+            };
+            let b = match self.sample_at(input, index + 1) {
+                Some(b) => b,
+                // The next input sample hasn't arrived yet; pick this output sample back up
+                // once the following block arrives.
+                None => break,
+            };
+
+            output.push(a + (b - a) * frac);
+            self.pos += self.ratio;
+        }
+
+        if let Some(&last) = input.last() {
+            self.last_sample = last;
+        }
+        self.pos -= input.len() as f64;
+    }
+
+    /// Read input sample `index`, treating index `-1` as the carried-over last sample of the
+    /// previous block. Returns `None` if `index` hasn't arrived yet.
+    fn sample_at(&self, input: &[f32], index: isize) -> Option<f32> {
+        if index < 0 {
+            Some(self.last_sample)
+        } else {
+            input.get(index as usize).copied()
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::audio::AudioEnvelopes;
+use crate::master_ui::EmitStateChange;
 use crate::palette::ColorPalette;
 use crate::position_bank::PositionBank;
 use crate::{clock_bank::ClockBank, look::Look, tunnel::Tunnel};
@@ -18,10 +20,20 @@ pub enum Beam {
 }
 
 impl Beam {
-    pub fn update_state(&mut self, delta_t: Duration, audio_envelope: UnipolarFloat) {
+    /// `audio_envelope` is the scalar already selected by the owning channel (e.g. from one band
+    /// of the spectral filterbank); `audio_envelopes` is the full bundle it was selected from, so
+    /// a nested `Look`'s own channels can each make their own independent band selection.
+    pub fn update_state<E: EmitStateChange>(
+        &mut self,
+        delta_t: Duration,
+        audio_envelope: UnipolarFloat,
+        audio_envelopes: &AudioEnvelopes,
+        external_clocks: &ClockBank,
+        emitter: &mut E,
+    ) {
         match self {
-            Self::Tunnel(t) => t.update_state(delta_t, audio_envelope),
-            Self::Look(l) => l.update_state(delta_t, audio_envelope),
+            Self::Tunnel(t) => t.update_state(delta_t, audio_envelope, external_clocks, emitter),
+            Self::Look(l) => l.update_state(delta_t, audio_envelopes, external_clocks, emitter),
         }
     }
 
@@ -32,7 +44,7 @@ impl Beam {
         external_clocks: &ClockBank,
         color_palette: &ColorPalette,
         positions: &PositionBank,
-        audio_envelope: UnipolarFloat,
+        audio_envelopes: &AudioEnvelopes,
     ) -> Vec<ArcSegment> {
         match self {
             Self::Tunnel(t) => t.render(
@@ -41,16 +53,9 @@ impl Beam {
                 external_clocks,
                 color_palette,
                 positions,
-                audio_envelope,
-            ),
-            Self::Look(l) => l.render(
-                level,
-                mask,
-                external_clocks,
-                color_palette,
-                positions,
-                audio_envelope,
+                audio_envelopes,
             ),
+            Self::Look(l) => l.render(level, mask, external_clocks, color_palette, audio_envelopes),
         }
     }
 }
@@ -48,7 +48,7 @@ impl BeamStore {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
 pub struct BeamStoreAddr {
     pub row: usize,
     pub col: usize,
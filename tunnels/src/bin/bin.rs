@@ -1,19 +1,29 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use clap::Parser;
 use io::Write;
+use serde::{Deserialize, Serialize};
 use simplelog::{Config as LogConfig, LevelFilter, SimpleLogger};
 use std::net::IpAddr;
 use std::net::Ipv4Addr;
 use std::net::SocketAddr;
 use std::time::Duration;
-use std::{env::current_dir, fs::create_dir_all, io, path::PathBuf};
+use std::{
+    env::current_dir,
+    fs::{self, create_dir_all},
+    io,
+    path::{Path, PathBuf},
+};
 use tunnels::audio::prompt_audio;
 use tunnels::midi::list_ports;
 use tunnels::midi::prompt_midi;
+use tunnels::midi::DeviceSpec as MidiDeviceSpec;
 use tunnels::midi_controls::Device as MidiDevice;
 use tunnels::osc::Device as OscDevice;
 use tunnels::osc::DeviceSpec as OscDeviceSpec;
-use tunnels::show::Show;
+use tunnels::show::{RenderTransport, Show};
 use tunnels::test_mode::{all_video_outputs, noise, stress, TestModeSetup};
+use tunnels_lib::mqtt::MqttSinkConfig;
+use tunnels_lib::multicast::MulticastConfig;
 use tunnels_lib::prompt::prompt_bool;
 use tunnels_lib::prompt::prompt_port;
 use tunnels_lib::prompt::read_string;
@@ -22,64 +32,252 @@ use tunnels_lib::prompt::read_string;
 /// essentially this value.
 const RENDER_INTERVAL: Duration = Duration::from_nanos(16666667 / 4);
 
-fn main() -> Result<()> {
-    SimpleLogger::init(LevelFilter::Info, LogConfig::default())?;
-    let (inputs, outputs) = list_ports()?;
+/// A console and show engine for tunnels.
+#[derive(Parser, Debug)]
+struct Cli {
+    /// Path to a show configuration file (TOML, or JSON if the extension is ".json"). When
+    /// given, the show launches from this file with no interactive prompting at all.
+    #[arg(long)]
+    config: Option<PathBuf>,
 
-    let test_mode = prompt_test_mode()?;
+    /// After the show's configuration is assembled, either from `--config` or from the
+    /// interactive prompts below, write it back out to this path (format again selected by
+    /// extension) before starting, so a working rig can be captured and replayed later.
+    #[arg(long)]
+    dump_config: Option<PathBuf>,
 
-    let midi_devices = if test_mode.is_some() {
-        Vec::new()
-    } else {
-        prompt_midi(&inputs, &outputs, MidiDevice::all())?
-    };
+    /// Capture every rendered frame to this directory as a chunked frame recording, for later
+    /// offline replay via `--play-frames`.
+    #[arg(long)]
+    record_frames: Option<PathBuf>,
 
-    let osc_devices = if test_mode.is_some() {
-        Vec::new()
-    } else {
-        prompt_osc()?
-    };
+    /// Replay a frame recording captured via `--record-frames` instead of running a live show.
+    #[arg(long)]
+    play_frames: Option<PathBuf>,
 
-    let audio_input_device = if test_mode.is_some() {
-        None
-    } else {
-        prompt_audio()?
-    };
+    /// When replaying with `--play-frames`, restart from the beginning once the recording is
+    /// exhausted instead of exiting.
+    #[arg(long)]
+    loop_playback: bool,
+}
 
-    let run_clock_service = if test_mode.is_some() {
-        false
-    } else {
-        prompt_bool("Run clock publisher service?")?
-    };
+fn main() -> Result<()> {
+    SimpleLogger::init(LevelFilter::Info, LogConfig::default())?;
+    let cli = Cli::parse();
 
-    let paths = if test_mode.is_some() {
-        LoadSaveConfig {
-            load_path: None,
-            save_path: None,
-        }
-    } else {
-        prompt_load_save()?
+    if let Some(path) = &cli.play_frames {
+        let render_transport = match &cli.config {
+            Some(config_path) => ShowConfig::load(config_path)?.render_transport,
+            None => RenderTransport::Local,
+        };
+        let mut show = Show::new(
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            false,
+            render_transport,
+            None,
+            None,
+            None,
+            None,
+        )?;
+        return show.run_playback(path, cli.loop_playback);
+    }
+
+    let cfg = match &cli.config {
+        Some(path) => ShowConfig::load(path)?,
+        None => ShowConfig::prompt()?,
     };
 
+    if let Some(dump_path) = &cli.dump_config {
+        cfg.dump(dump_path)?;
+    }
+
     let mut show = Show::new(
-        midi_devices,
-        osc_devices,
-        audio_input_device,
-        run_clock_service,
-        paths.save_path,
+        cfg.midi_devices,
+        cfg.osc_devices,
+        cfg.audio_input_devices,
+        cfg.run_clock_service,
+        cfg.render_transport,
+        cfg.save_path,
+        cfg.control_mapping_config_path,
+        cfg.clock_mqtt,
+        cfg.snapshot_mqtt,
     )?;
 
-    if let Some(setup_test) = test_mode {
-        show.test_mode(setup_test);
-    } else if let Some(load_path) = paths.load_path {
-        show.load(&load_path)?;
+    if let Some(mode) = cfg.test_mode {
+        show.test_mode(mode.setup());
+    } else if let Some(load_path) = &cfg.load_path {
+        show.load(load_path)?;
+    }
+
+    if let Some(dir) = cli.record_frames {
+        show.start_frame_recording(dir);
     }
 
     show.run(RENDER_INTERVAL)
 }
 
+/// A fully-specified show configuration, suitable for headless startup with no stdin
+/// interaction via `--config`, or for capturing an interactively-assembled rig via
+/// `--dump-config`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ShowConfig {
+    midi_devices: Vec<MidiDeviceSpec<MidiDevice>>,
+    osc_devices: Vec<OscDeviceSpec>,
+    audio_input_devices: Vec<String>,
+    run_clock_service: bool,
+    render_transport: RenderTransport,
+    test_mode: Option<TestMode>,
+    load_path: Option<PathBuf>,
+    save_path: Option<PathBuf>,
+    /// Path to a custom MIDI control mapping config (TOML, or JSON if the extension is
+    /// ".json"), merged over the built-in device bindings. `None` to use only the built-ins.
+    #[serde(default)]
+    control_mapping_config_path: Option<PathBuf>,
+    /// If set, the clock stream is additionally published to this broker/topic for subscribers
+    /// that would rather not link zmq. `None` to publish only over zmq/DNS-SD.
+    #[serde(default)]
+    clock_mqtt: Option<MqttSinkConfig>,
+    /// If set, each video channel's snapshots are additionally published to
+    /// `{topic}/{video_channel}` on this broker, alongside the existing zmq transport.
+    #[serde(default)]
+    snapshot_mqtt: Option<MqttSinkConfig>,
+}
+
+impl ShowConfig {
+    /// Load and parse a show configuration from `path`, selecting TOML or JSON by extension.
+    fn load(path: &Path) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("could not read config file {}", path.display()))?;
+        if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            serde_json::from_str(&raw)
+                .with_context(|| format!("could not parse JSON config file {}", path.display()))
+        } else {
+            toml::from_str(&raw)
+                .with_context(|| format!("could not parse TOML config file {}", path.display()))
+        }
+    }
+
+    /// Serialize this configuration out to `path`, selecting TOML or JSON by extension.
+    fn dump(&self, path: &Path) -> Result<()> {
+        let rendered = if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            serde_json::to_string_pretty(self).context("could not serialize config to JSON")?
+        } else {
+            toml::to_string_pretty(self).context("could not serialize config to TOML")?
+        };
+        fs::write(path, rendered)
+            .with_context(|| format!("could not write config file {}", path.display()))
+    }
+
+    /// Assemble a show configuration by interactively prompting the user, the fallback path
+    /// when no `--config` file is provided.
+    fn prompt() -> Result<Self> {
+        let (inputs, outputs) = list_ports()?;
+
+        let test_mode = prompt_test_mode()?;
+        let no_interaction = test_mode.is_some();
+
+        let midi_devices = if no_interaction {
+            Vec::new()
+        } else {
+            prompt_midi(&inputs, &outputs, MidiDevice::all())?
+        };
+
+        let osc_devices = if no_interaction {
+            Vec::new()
+        } else {
+            prompt_osc()?
+        };
+
+        let audio_input_devices = if no_interaction {
+            Vec::new()
+        } else {
+            prompt_audio()?
+        };
+
+        let run_clock_service = if no_interaction {
+            false
+        } else {
+            prompt_bool("Run clock publisher service?")?
+        };
+
+        let render_transport = if no_interaction {
+            RenderTransport::Local
+        } else {
+            prompt_render_transport()?
+        };
+
+        let paths = if no_interaction {
+            LoadSaveConfig {
+                load_path: None,
+                save_path: None,
+            }
+        } else {
+            prompt_load_save()?
+        };
+
+        let cfg = Self {
+            midi_devices,
+            osc_devices,
+            audio_input_devices,
+            run_clock_service,
+            render_transport,
+            test_mode,
+            load_path: paths.load_path,
+            save_path: paths.save_path,
+            // No interactive prompt for this yet; custom mappings are only available via
+            // `--config`.
+            control_mapping_config_path: None,
+            // No interactive prompt for these yet; MQTT fan-out is only available via `--config`.
+            clock_mqtt: None,
+            snapshot_mqtt: None,
+        };
+
+        if !no_interaction {
+            cfg.prompt_save()?;
+        }
+
+        Ok(cfg)
+    }
+
+    /// Offer to write this configuration out to a named file, so an operator who just walked
+    /// through the interactive wizard can boot straight into it next time via `--config`
+    /// instead of repeating the prompts.
+    fn prompt_save(&self) -> Result<()> {
+        if !prompt_bool("Save this configuration to a file for reuse?")? {
+            return Ok(());
+        }
+        let mut name = String::new();
+        while name.is_empty() {
+            print!("Save configuration as (e.g. \"my_rig.toml\"): ");
+            io::stdout().flush()?;
+            name = read_string()?;
+        }
+        self.dump(Path::new(&name))
+    }
+}
+
+/// Which built-in test mode to run, if any, in place of interactively assembling a console.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+enum TestMode {
+    VideoOuts,
+    Stress,
+    Noise,
+}
+
+impl TestMode {
+    fn setup(self) -> TestModeSetup {
+        match self {
+            Self::VideoOuts => all_video_outputs,
+            Self::Stress => stress,
+            Self::Noise => noise,
+        }
+    }
+}
+
 /// Prompt the user to optionally configure a test mode.
-fn prompt_test_mode() -> Result<Option<TestModeSetup>> {
+fn prompt_test_mode() -> Result<Option<TestMode>> {
     if !prompt_bool("Output test mode?")? {
         return Ok(None);
     }
@@ -87,9 +285,9 @@ fn prompt_test_mode() -> Result<Option<TestModeSetup>> {
         print!("Select test mode ('video_outs', 'stress', 'noise'): ");
         io::stdout().flush()?;
         match &read_string()?[..] {
-            "video_outs" => break Some(all_video_outputs),
-            "stress" => break Some(stress),
-            "noise" => break Some(noise),
+            "video_outs" => break Some(TestMode::VideoOuts),
+            "stress" => break Some(TestMode::Stress),
+            "noise" => break Some(TestMode::Noise),
             _ => (),
         }
     })
@@ -118,6 +316,43 @@ fn prompt_osc() -> Result<Vec<OscDeviceSpec>> {
     Ok(devices)
 }
 
+/// Prompt the user to select which transport should carry the rendered frame stream.
+fn prompt_render_transport() -> Result<RenderTransport> {
+    Ok(loop {
+        print!("Render transport ('local', 'quic', 'multicast') [local]: ");
+        io::stdout().flush()?;
+        match &read_string()?[..] {
+            "" | "local" => break RenderTransport::Local,
+            "quic" => {
+                let port = prompt_port()?;
+                break RenderTransport::Quic(SocketAddr::new(
+                    IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+                    port,
+                ));
+            }
+            "multicast" => {
+                print!("Multicast group address (e.g. \"239.0.0.1\"): ");
+                io::stdout().flush()?;
+                let group = match read_string()?.parse() {
+                    Ok(group) => group,
+                    Err(e) => {
+                        println!("Invalid multicast group address: {}", e);
+                        continue;
+                    }
+                };
+                let base_port = prompt_port()?;
+                let compact = prompt_bool("Use the compact binary wire format?")?;
+                break RenderTransport::Multicast(MulticastConfig {
+                    group,
+                    base_port,
+                    compact,
+                });
+            }
+            _ => (),
+        }
+    })
+}
+
 struct LoadSaveConfig {
     load_path: Option<PathBuf>,
     save_path: Option<PathBuf>,
@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 use tunnels_lib::number::{BipolarFloat, Phase, UnipolarFloat};
 
+use crate::audio::{AudioEnvelopeSource, AudioEnvelopes, N_BANDS};
 use crate::transient_indicator::TransientIndicator;
 
 /// The number of times a clock has ticked.
@@ -10,8 +11,12 @@ pub type Ticks = i64;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Clock {
-    /// The current phase of this clock.
-    phase: Phase,
+    /// The current phase of this clock, accumulated as a fixed-point fraction of one full turn
+    /// scaled by 2^64, rather than directly as a float. Every update advances this integer
+    /// accumulator by a delta computed fresh from `rate` and elapsed time, rather than
+    /// repeatedly summing float deltas into a float phase, so rounding error can't compound
+    /// across a long show the way it would with pure float accumulation.
+    phase_accum: u64,
     /// The total number of ticks this clock has made.
     ticks: Ticks,
     /// in unit angle per second
@@ -26,7 +31,7 @@ pub struct Clock {
     run: bool,
     /// should this clock reset and tick on the next state update action?
     reset_on_update: bool,
-    /// Should this clock scale its rate during update by the audio envelope?
+    /// Should this clock scale its rate during update by the audio speed signal?
     pub use_audio: bool,
 }
 
@@ -37,9 +42,17 @@ impl Default for Clock {
 }
 
 impl Clock {
+    /// One full turn of phase, expressed in the fixed-point domain that `phase_accum` is
+    /// counted in. `phase_accum` therefore always holds a value in `0..TURN`, i.e. `0..2^64`,
+    /// which we treat as `[0.0, 1.0)` turns.
+    const TURN: u128 = 1u128 << 64;
+
+    /// Femtoseconds per second, for converting `Duration`s into the integer timebase.
+    const FEMTOS_PER_SEC: u128 = 1_000_000_000_000_000;
+
     pub fn new() -> Self {
         Self {
-            phase: Phase::ZERO,
+            phase_accum: 0,
             ticks: 0,
             rate: 0.0,
             ticked: true,
@@ -50,16 +63,15 @@ impl Clock {
         }
     }
 
-    pub fn update_state(&mut self, delta_t: Duration, audio_envelope: UnipolarFloat) {
+    pub fn update_state(&mut self, delta_t: Duration, audio_speed: UnipolarFloat) {
         if self.reset_on_update {
             self.ticked = true;
             self.ticks = 0;
-            // Reset phase to zero or one, depending on sign of rate.
-            self.phase = if self.rate >= 0.0 {
-                Phase::ZERO
-            } else {
-                Phase::ONE
-            };
+            // Reset phase to zero or one, depending on sign of rate. u64::MAX stands in for
+            // Phase::ONE here, since the fixed-point domain can't natively represent an exact
+            // 1.0 turn (it would roll over to 0.0); it's close enough that nothing downstream
+            // can tell the difference.
+            self.phase_accum = if self.rate >= 0.0 { 0 } else { u64::MAX };
             self.reset_on_update = false;
             self.run = true;
             return;
@@ -70,30 +82,47 @@ impl Clock {
         }
 
         let rate_modulation = if self.use_audio {
-            audio_envelope
+            audio_speed
         } else {
             UnipolarFloat::ONE
         };
 
-        let new_angle =
-            self.phase.val() + (self.rate * rate_modulation.val() * delta_t.as_secs_f64());
+        // Do the whole update in femtosecond-resolution integer math so that rounding error
+        // from any single frame can never compound into long-show phase drift: we always
+        // compute this frame's delta fresh from `rate` and elapsed time, rather than repeatedly
+        // summing float deltas into a float phase.
+        let elapsed_femtos = delta_t.as_nanos() as i128 * 1_000;
+        let effective_rate = self.rate * rate_modulation.val();
+        // Round once, after multiplying by the frame's elapsed femtoseconds, rather than
+        // rounding a per-femtosecond increment first and then scaling it up. Rounding first
+        // introduces a fixed, rate-dependent bias that grows linearly with elapsed time instead
+        // of being drift-free, and for a slow enough `effective_rate` (below roughly
+        // FEMTOS_PER_SEC / (2 * TURN) turns/sec) it rounds the per-femto increment to zero
+        // outright, freezing the clock forever regardless of how much time elapses.
+        let delta = (effective_rate * elapsed_femtos as f64 / Self::FEMTOS_PER_SEC as f64
+            * Self::TURN as f64)
+            .round() as i128;
+
+        let total = self.phase_accum as i128 + delta;
+        let turns = total.div_euclid(Self::TURN as i128);
+        let new_accum = total.rem_euclid(Self::TURN as i128) as u64;
 
         // if we're running in one-shot mode, clamp the angle at 1.0
-        if self.one_shot && new_angle >= 1.0 {
-            self.phase = Phase::ONE;
+        if self.one_shot && turns > 0 {
+            self.phase_accum = u64::MAX;
             self.ticked = false;
             self.run = false;
-        } else if self.one_shot && new_angle < 0.0 {
-            self.phase = Phase::ZERO;
+        } else if self.one_shot && turns < 0 {
+            self.phase_accum = 0;
             self.ticked = false;
             self.run = false;
         } else {
             // if the phase just escaped our range, we ticked this frame
-            self.ticked = !(0.0..1.0).contains(&new_angle);
+            self.ticked = turns != 0;
             if self.ticked {
-                self.ticks = self.ticks.wrapping_add(new_angle.div_euclid(1.0) as i64);
+                self.ticks = self.ticks.wrapping_add(turns as i64);
             }
-            self.phase = Phase::new(new_angle);
+            self.phase_accum = new_accum;
         }
     }
 
@@ -104,8 +133,12 @@ impl Clock {
         }
     }
 
+    fn set_run(&mut self, run: bool) {
+        self.run = run;
+    }
+
     pub fn phase(&self) -> Phase {
-        self.phase
+        Phase::new(self.phase_accum as f64 / Self::TURN as f64)
     }
 
     pub fn ticks(&self) -> Ticks {
@@ -113,6 +146,41 @@ impl Clock {
     }
 }
 
+/// A response curve applied when converting a clock's submaster level, and any audio envelope
+/// it scales, from a linear 0..1 control value into a gain. Perceived brightness is roughly
+/// logarithmic, so a plain linear fade feels top-heavy and barely registers at low levels;
+/// `Perceptual` compensates by mapping the control onto a dB dynamic range before converting
+/// back to linear gain.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Curve {
+    /// Use the control value directly as gain.
+    #[default]
+    Linear,
+    /// Map the control onto a dB dynamic range before converting back to linear gain.
+    Perceptual,
+}
+
+impl Curve {
+    /// The bottom of the dB dynamic range that `Perceptual` maps the control onto. 0.0 is
+    /// always mapped onto true black rather than onto this floor's gain value.
+    const MIN_DB: f64 = -40.0;
+
+    /// Apply this curve to a linear 0..1 control value, returning the gain to actually use.
+    pub(crate) fn apply(&self, level: UnipolarFloat) -> UnipolarFloat {
+        match self {
+            Self::Linear => level,
+            Self::Perceptual => {
+                if level <= UnipolarFloat::ZERO {
+                    UnipolarFloat::ZERO
+                } else {
+                    let db = Self::MIN_DB * (1.0 - level.val());
+                    UnipolarFloat::new(10f64.powf(db / 20.0))
+                }
+            }
+        }
+    }
+}
+
 /// A static snapshot of externally-visible ControllableClock state.
 #[derive(Default, Serialize, Deserialize, Debug, Clone)]
 pub struct StaticClock {
@@ -120,6 +188,10 @@ pub struct StaticClock {
     pub ticks: Ticks,
     pub submaster_level: UnipolarFloat,
     pub use_audio_size: bool,
+    pub submaster_curve: Curve,
+    /// Which audio envelope source (wideband or one filterbank band) this clock's submaster
+    /// size modulation draws from.
+    pub audio_envelope_source: AudioEnvelopeSource,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -127,6 +199,11 @@ pub struct StaticClock {
 pub struct ControllableClock {
     clock: Clock,
     sync: TapSync,
+    /// Derives tempo from an external 24 PPQN MIDI clock, if one is connected.
+    midi_sync: MidiClockSync,
+    /// True if this clock is currently being driven by an external MIDI clock,
+    /// rather than running free (internal tempo/tap sync).
+    midi_synced: bool,
     tick_indicator: TransientIndicator,
     /// If true, reset the clock's phase to zero on every tap.
     retrigger: bool,
@@ -134,6 +211,16 @@ pub struct ControllableClock {
     submaster_level: UnipolarFloat,
     /// If true, modulate the submaster level using audio envelope.
     use_audio_size: bool,
+    /// Response curve applied to the submaster level, and to the audio envelope when
+    /// `use_audio_size` is set, when converting them into gain.
+    submaster_curve: Curve,
+    /// Which audio envelope signal drives this clock's submaster size modulation, when
+    /// `use_audio_size` is set: the overall wideband envelope, or one band of the spectral
+    /// filterbank.
+    audio_envelope_source: AudioEnvelopeSource,
+    /// If true, lock this clock's rate to the show's audio-detected tempo rather than manual
+    /// tap/rate control, leaving other clocks free to stay manual.
+    audio_tempo_follow: bool,
 }
 
 impl Default for ControllableClock {
@@ -153,10 +240,15 @@ impl ControllableClock {
         Self {
             clock: Clock::new(),
             sync: TapSync::new(),
+            midi_sync: MidiClockSync::new(),
+            midi_synced: false,
             tick_indicator: TransientIndicator::new(Duration::from_millis(100)),
             retrigger: false,
             submaster_level: UnipolarFloat::ONE,
             use_audio_size: false,
+            submaster_curve: Curve::Linear,
+            audio_envelope_source: AudioEnvelopeSource::Wideband,
+            audio_tempo_follow: false,
         }
     }
 
@@ -170,9 +262,24 @@ impl ControllableClock {
         self.clock.ticks()
     }
 
-    /// Return the current submaster level.
+    /// Return this clock's phase scaled by a rational multiplier, so a
+    /// consumer can run at a musically-related rate (half-time, double-time,
+    /// etc.) without needing a dedicated clock.
+    pub fn phase_at(&self, mult: ClockMultiplier) -> Phase {
+        let (numerator, denominator) = mult.ratio();
+        Phase::new((self.phase().val() * numerator as f64 / denominator as f64).fract())
+    }
+
+    /// Return this clock's tick count scaled by the same rational multiplier
+    /// as `phase_at`.
+    pub fn ticks_at(&self, mult: ClockMultiplier) -> Ticks {
+        let (numerator, denominator) = mult.ratio();
+        (self.ticks() as f64 * numerator as f64 / denominator as f64) as Ticks
+    }
+
+    /// Return the current submaster level, with this clock's response curve applied.
     pub fn submaster_level(&self) -> UnipolarFloat {
-        self.submaster_level
+        self.submaster_curve.apply(self.submaster_level)
     }
 
     /// Return true if we should use audio envelope to scale submaster level.
@@ -183,6 +290,14 @@ impl ControllableClock {
         self.use_audio_size
     }
 
+    /// Select this clock's configured audio envelope source from `envelopes` and scale it by
+    /// this clock's response curve, so quiet passages still produce visible movement under the
+    /// `Perceptual` curve.
+    pub fn scale_audio_envelope(&self, envelopes: &AudioEnvelopes) -> UnipolarFloat {
+        self.submaster_curve
+            .apply(envelopes.select(self.audio_envelope_source))
+    }
+
     /// Get all clock state bundled into a struct.
     pub fn as_static(&self) -> StaticClock {
         StaticClock {
@@ -190,6 +305,8 @@ impl ControllableClock {
             ticks: self.ticks(),
             submaster_level: self.submaster_level(),
             use_audio_size: self.use_audio_size(),
+            submaster_curve: self.submaster_curve,
+            audio_envelope_source: self.audio_envelope_source,
         }
     }
 
@@ -198,10 +315,22 @@ impl ControllableClock {
     pub fn update_state<E: EmitStateChange>(
         &mut self,
         delta_t: Duration,
-        audio_envelope: UnipolarFloat,
+        audio_speed: UnipolarFloat,
+        audio_tempo_bpm: Option<f64>,
         emitter: &mut E,
     ) {
-        self.clock.update_state(delta_t, audio_envelope);
+        if self.audio_tempo_follow {
+            if let Some(bpm) = audio_tempo_bpm {
+                let rate = bpm / 60.0;
+                if rate != self.clock.rate {
+                    self.clock.rate = rate;
+                    emitter.emit_clock_state_change(StateChange::Rate(BipolarFloat::new(
+                        self.clock.rate / ControllableClock::RATE_SCALE,
+                    )));
+                }
+            }
+        }
+        self.clock.update_state(delta_t, audio_speed);
         if let Some(tick_state) = self.tick_indicator.update_state(delta_t, self.clock.ticked) {
             emitter.emit_clock_state_change(StateChange::Ticked(tick_state));
         }
@@ -216,6 +345,10 @@ impl ControllableClock {
         emitter.emit_clock_state_change(Ticked(self.tick_indicator.state()));
         emitter.emit_clock_state_change(UseAudioSpeed(self.clock.use_audio));
         emitter.emit_clock_state_change(UseAudioSize(self.use_audio_size));
+        emitter.emit_clock_state_change(MidiSyncEnabled(self.midi_synced));
+        emitter.emit_clock_state_change(SubmasterCurve(self.submaster_curve));
+        emitter.emit_clock_state_change(AudioEnvelopeSourceChange(self.audio_envelope_source));
+        emitter.emit_clock_state_change(AudioTempoFollow(self.audio_tempo_follow));
     }
 
     /// Handle a control event.
@@ -249,6 +382,65 @@ impl ControllableClock {
                     emitter,
                 );
             }
+            ToggleSubmasterCurve => {
+                let next = match self.submaster_curve {
+                    Curve::Linear => Curve::Perceptual,
+                    Curve::Perceptual => Curve::Linear,
+                };
+                self.handle_state_change(StateChange::SubmasterCurve(next), emitter);
+            }
+            CycleAudioEnvelopeSource => {
+                let next = match self.audio_envelope_source {
+                    AudioEnvelopeSource::Wideband => AudioEnvelopeSource::Band(0),
+                    AudioEnvelopeSource::Band(b) if b + 1 < N_BANDS => {
+                        AudioEnvelopeSource::Band(b + 1)
+                    }
+                    AudioEnvelopeSource::Band(_) => AudioEnvelopeSource::Wideband,
+                };
+                self.handle_state_change(StateChange::AudioEnvelopeSourceChange(next), emitter);
+            }
+            ToggleAudioTempoFollow => {
+                self.handle_state_change(
+                    StateChange::AudioTempoFollow(!self.audio_tempo_follow),
+                    emitter,
+                );
+            }
+            MidiClockPulse { at } => {
+                self.set_midi_synced(true, emitter);
+                if let Some((beat, rate)) = self.midi_sync.pulse(at) {
+                    if let Some(rate) = rate {
+                        self.clock.rate = rate;
+                        emitter.emit_clock_state_change(StateChange::Rate(BipolarFloat::new(
+                            self.clock.rate / ControllableClock::RATE_SCALE,
+                        )));
+                    }
+                    emitter.emit_clock_state_change(StateChange::MidiBeat {
+                        bpm: self.clock.rate.abs() * 60.0,
+                        beat,
+                    });
+                }
+            }
+            MidiStart => {
+                self.midi_sync.reset();
+                self.clock.reset_on_update = true;
+                self.set_midi_synced(true, emitter);
+            }
+            MidiContinue => {
+                self.clock.set_run(true);
+                self.set_midi_synced(true, emitter);
+            }
+            MidiStop => {
+                self.clock.set_run(false);
+            }
+        }
+    }
+
+    /// Update whether this clock is currently slaved to an external MIDI
+    /// clock, emitting a state change if the value actually changed.
+    fn set_midi_synced<E: EmitStateChange>(&mut self, synced: bool, emitter: &mut E) {
+        if self.midi_synced != synced {
+            self.midi_synced = synced;
+            emitter.emit_clock_state_change(StateChange::MidiSyncEnabled(synced));
         }
     }
 
@@ -261,13 +453,52 @@ impl ControllableClock {
             SubmasterLevel(v) => self.submaster_level = v,
             UseAudioSpeed(v) => self.clock.use_audio = v,
             UseAudioSize(v) => self.use_audio_size = v,
+            SubmasterCurve(v) => self.submaster_curve = v,
+            AudioEnvelopeSourceChange(v) => self.audio_envelope_source = v,
+            AudioTempoFollow(v) => self.audio_tempo_follow = v,
             Ticked(_) => (),
+            MidiSyncEnabled(_) => (),
+            MidiBeat { .. } => (),
         };
         emitter.emit_clock_state_change(sc);
     }
 }
 
-#[derive(Debug, Clone)]
+/// A rational multiplier applied to a clock's phase and tick count, so a
+/// single master clock can drive consumers running at different
+/// musically-related rates (e.g. a half-time or double-time layer) without
+/// adding more clocks. Named after the step-sequencer convention where a
+/// whole note is 96 pulses and a sixteenth note is 6, relative to this
+/// clock's own rate standing in for one quarter note (24 pulses).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ClockMultiplier {
+    NinetySixth,
+    ThirtySecond,
+    Sixteenth,
+    Eighth,
+    Quarter,
+    Whole,
+    /// An arbitrary ratio, relative to this clock's native (quarter note) rate.
+    Custom(u32, u32),
+}
+
+impl ClockMultiplier {
+    /// Return the (numerator, denominator) ratio this multiplier scales a
+    /// clock's phase and tick count by.
+    fn ratio(&self) -> (u32, u32) {
+        match *self {
+            Self::NinetySixth => (1, 24),
+            Self::ThirtySecond => (3, 24),
+            Self::Sixteenth => (6, 24),
+            Self::Eighth => (12, 24),
+            Self::Quarter => (24, 24),
+            Self::Whole => (96, 24),
+            Self::Custom(numerator, denominator) => (numerator, denominator),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum StateChange {
     Rate(BipolarFloat),
     Retrigger(bool),
@@ -275,8 +506,25 @@ pub enum StateChange {
     SubmasterLevel(UnipolarFloat),
     UseAudioSize(bool),
     UseAudioSpeed(bool),
+    /// Response curve applied to submaster level and, when `UseAudioSize` is set, to the audio
+    /// envelope.
+    SubmasterCurve(Curve),
+    /// Which audio envelope signal (wideband or one filterbank band) drives this clock's
+    /// submaster size modulation, when `UseAudioSize` is set.
+    AudioEnvelopeSourceChange(AudioEnvelopeSource),
+    /// If true, this clock's rate is locked to the show's audio-detected tempo rather than
+    /// manual tap/rate control.
+    AudioTempoFollow(bool),
     /// Outgoing only, no effect as control.
     Ticked(bool),
+    /// Outgoing only, no effect as control. True if this clock is currently
+    /// slaved to an external MIDI clock rather than running free.
+    MidiSyncEnabled(bool),
+    /// Outgoing only, no effect as control. Current tempo and quarter-note count derived from an
+    /// external MIDI clock, emitted every time a full quarter note's worth of pulses arrives.
+    /// `beat` resets to zero on every MIDI Start, so a consumer can tell where we are in the bar
+    /// rather than just tracking tempo.
+    MidiBeat { bpm: f64, beat: u32 },
 }
 
 #[derive(Debug, Clone)]
@@ -287,6 +535,21 @@ pub enum ControlMessage {
     ToggleRetrigger,
     ToggleUseAudioSize,
     ToggleUseAudioSpeed,
+    /// Toggle the submaster response curve between `Linear` and `Perceptual`.
+    ToggleSubmasterCurve,
+    /// Cycle which audio envelope source (wideband, then each filterbank band in turn) drives
+    /// this clock's submaster size modulation.
+    CycleAudioEnvelopeSource,
+    /// Toggle whether this clock's rate is locked to the show's audio-detected tempo.
+    ToggleAudioTempoFollow,
+    /// A single 24 PPQN MIDI clock pulse (status byte 0xF8) arrived at `at`.
+    MidiClockPulse { at: Instant },
+    /// MIDI start (0xFA): snap phase to zero and begin running, slaved to MIDI clock.
+    MidiStart,
+    /// MIDI continue (0xFB): resume running, slaved to MIDI clock, without resetting phase.
+    MidiContinue,
+    /// MIDI stop (0xFC): stop running.
+    MidiStop,
 }
 
 pub trait EmitStateChange {
@@ -307,6 +570,10 @@ impl TapSync {
     /// start a new one.
     const RESET_THRESHOLD: f64 = 0.1;
 
+    /// Cap the tap buffer to this many of the most recent taps, so the rate estimate tracks
+    /// tempo changes rather than averaging over a whole song.
+    const WINDOW: usize = 8;
+
     pub fn new() -> Self {
         Self {
             taps: Vec::new(),
@@ -324,20 +591,48 @@ impl TapSync {
 
     fn add_tap(&mut self, tap: Instant) {
         self.taps.push(tap);
+        // Only keep the most recent window of taps, so the estimate tracks tempo changes
+        // rather than averaging over the whole buffer.
+        if self.taps.len() > Self::WINDOW {
+            self.taps.remove(0);
+        }
         if self.taps.len() < 2 {
             return;
         }
-        // compute rate if we have at least two taps
-        if let (Some(first), Some(last)) = (self.taps.first(), self.taps.last()) {
-            let period = (*last - *first) / (self.taps.len() as u32 - 1);
-            self.period = Some(period);
-            self.rate = Some(1.0 / period.as_secs_f64());
+        // Fit tap index against tap timestamp with linear least-squares, rather than just
+        // using the first and last tap, so a few noisy intermediate taps don't throw off the
+        // estimate as much as they would with the naive endpoints-only calculation.
+        let first = *self.taps.first().unwrap();
+        let n = self.taps.len();
+        let times: Vec<f64> = self
+            .taps
+            .iter()
+            .map(|t| (*t - first).as_secs_f64())
+            .collect();
+        let x_bar = (n - 1) as f64 / 2.0;
+        let y_bar = times.iter().sum::<f64>() / n as f64;
+
+        let mut numerator = 0.0;
+        let mut denominator = 0.0;
+        for (i, y) in times.iter().enumerate() {
+            let dx = i as f64 - x_bar;
+            numerator += dx * (y - y_bar);
+            denominator += dx * dx;
         }
+        let period = numerator / denominator;
+
+        self.period = Some(Duration::from_secs_f64(period));
+        self.rate = Some(1.0 / period);
     }
 
     /// Process a tap event.  Return our new rate estimate if we have one.
     pub fn tap(&mut self) -> Option<f64> {
-        let tap = Instant::now();
+        self.tap_at(Instant::now())
+    }
+
+    /// Process a tap event that occurred at `tap`. Return our new rate
+    /// estimate if we have one.
+    fn tap_at(&mut self, tap: Instant) -> Option<f64> {
         // if the tap buffer isn't empty, determine elapsed time from the last
         // tap to this one
         match self.period {
@@ -366,3 +661,52 @@ impl TapSync {
         self.rate
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Derive tempo from an external 24 PPQN MIDI clock, smoothing across several
+/// beats the same way `TapSync` smooths taps. Per the MIDI spec, 24 clock
+/// pulses (status byte 0xF8) equal one quarter note, so we count pulses and
+/// feed the tap-sync machinery one "tap" per quarter note, timestamped by the
+/// pulse that completes it.
+struct MidiClockSync {
+    /// Number of pulses seen since the last quarter-note boundary.
+    pulse_count: u8,
+    beat_sync: TapSync,
+    /// Quarter notes seen since the last Start/reset, for consumers that want to know where we
+    /// are in the bar rather than just the current tempo.
+    beat_count: u32,
+}
+
+impl MidiClockSync {
+    /// Per the MIDI spec, 24 clock pulses make up one quarter note.
+    const PULSES_PER_QUARTER_NOTE: u8 = 24;
+
+    fn new() -> Self {
+        Self {
+            pulse_count: 0,
+            beat_sync: TapSync::new(),
+            beat_count: 0,
+        }
+    }
+
+    /// Process a single MIDI clock pulse that occurred at `at`. Once a full quarter note's worth
+    /// of pulses has elapsed, return the updated beat count alongside a new tempo estimate in
+    /// beats (quarter notes) per second, if the tap-sync buffer has enough history for one yet.
+    fn pulse(&mut self, at: Instant) -> Option<(u32, Option<f64>)> {
+        self.pulse_count += 1;
+        if self.pulse_count < Self::PULSES_PER_QUARTER_NOTE {
+            return None;
+        }
+        self.pulse_count = 0;
+        self.beat_count = self.beat_count.wrapping_add(1);
+        Some((self.beat_count, self.beat_sync.tap_at(at)))
+    }
+
+    /// Forget any in-progress pulse count, beat count, and tempo estimate, for example when a
+    /// MIDI start message restarts the clock from a known position.
+    fn reset(&mut self) {
+        self.pulse_count = 0;
+        self.beat_count = 0;
+        self.beat_sync = TapSync::new();
+    }
+}
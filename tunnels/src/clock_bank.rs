@@ -1,6 +1,7 @@
 use std::time::Duration;
 
 use crate::{
+    audio::AudioEnvelopes,
     clock::{
         ControlMessage as ClockControlMessage, ControllableClock,
         EmitStateChange as EmitClockStateChange, StateChange as ClockStateChange, StaticClock,
@@ -30,6 +31,10 @@ pub trait ClockStore {
     /// level directly, to allow clients of the submaster to avoid double-
     /// modulating with audio envelope.
     fn use_audio_size(&self, index: ClockIdx) -> bool;
+
+    /// Select and scale this clock's configured audio envelope source by its submaster
+    /// response curve.
+    fn scale_audio_envelope(&self, index: ClockIdx, envelopes: &AudioEnvelopes) -> UnipolarFloat;
 }
 
 /// how many globally-available clocks?
@@ -80,19 +85,25 @@ impl ClockStore for ClockBank {
     fn use_audio_size(&self, index: ClockIdx) -> bool {
         self.get(index).use_audio_size()
     }
+
+    fn scale_audio_envelope(&self, index: ClockIdx, envelopes: &AudioEnvelopes) -> UnipolarFloat {
+        self.get(index).scale_audio_envelope(envelopes)
+    }
 }
 
 impl ClockBank {
     pub fn update_state<E: EmitStateChange>(
         &mut self,
         delta_t: Duration,
-        audio_envelope: UnipolarFloat,
+        audio_speed: UnipolarFloat,
+        audio_tempo_bpm: Option<f64>,
         emitter: &mut E,
     ) {
         for (i, clock) in self.0.iter_mut().enumerate() {
             clock.update_state(
                 delta_t,
-                audio_envelope,
+                audio_speed,
+                audio_tempo_bpm,
                 &mut ChannelEmitter {
                     channel: ClockIdx(i),
                     emitter,
@@ -159,7 +170,7 @@ pub struct ControlMessage {
     pub msg: ClockControlMessage,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct StateChange {
     pub channel: ClockIdx,
     pub change: ClockStateChange,
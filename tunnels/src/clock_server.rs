@@ -2,14 +2,20 @@
 //! Provide a strongly-typed receiver.
 //! FIXME: would be nice to clean up deserialization to avoid so many allocations.
 
+use std::collections::HashMap;
+
 use anyhow::Result;
 
+use log::error;
 use serde::{Deserialize, Serialize};
+use tunnels_lib::mqtt::{MqttPublisher, MqttSinkConfig};
 use tunnels_lib::number::{Phase, UnipolarFloat};
+use tunnels_lib::ClockReference;
 use zero_configure::pub_sub::{PublisherService, SubscriberService};
 use zmq::Context;
 
 use crate::{
+    audio::AudioEnvelopes,
     clock::StaticClock,
     clock_bank::{ClockIdx, ClockStore, N_CLOCKS},
 };
@@ -17,9 +23,32 @@ use crate::{
 const SERVICE_NAME: &str = "global_show_clocks";
 const PORT: u16 = 9090;
 
-/// Launch clock publisher service.
-pub fn clock_publisher(ctx: &Context) -> Result<ClockPublisher> {
-    PublisherService::new(ctx, SERVICE_NAME, PORT)
+/// Launch clock publisher service, optionally also mirroring every update to an MQTT broker
+/// alongside the zmq/DNS-SD transport, for subscribers that don't want to link zmq at all.
+pub fn clock_publisher(ctx: &Context, mqtt: Option<MqttSinkConfig>) -> Result<ClockPublisherFanOut> {
+    let zmq = PublisherService::new(ctx, SERVICE_NAME, PORT, HashMap::new())?;
+    let mqtt = mqtt.map(|config| MqttPublisher::new(SERVICE_NAME, &config)).transpose()?;
+    Ok(ClockPublisherFanOut { zmq, mqtt })
+}
+
+/// Fans a `SharedClockData` update out to the zmq/DNS-SD transport and, if configured, an
+/// additional MQTT broker. `ClockPublisher` itself is unchanged; this just wraps one alongside
+/// the optional extra sink, so existing callers of `ClockPublisher::send` are unaffected.
+pub struct ClockPublisherFanOut {
+    zmq: ClockPublisher,
+    mqtt: Option<MqttPublisher<SharedClockData>>,
+}
+
+impl ClockPublisherFanOut {
+    pub fn send(&mut self, data: &SharedClockData) -> Result<()> {
+        self.zmq.send(data)?;
+        if let Some(mqtt) = &mut self.mqtt {
+            if let Err(e) = mqtt.publish(data) {
+                error!("failed to publish clock data to MQTT: {e}");
+            }
+        }
+        Ok(())
+    }
 }
 
 /// Launch clock subscriber service.
@@ -31,7 +60,12 @@ pub fn clock_subscriber(ctx: Context) -> ClockSubscriber {
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub struct SharedClockData {
     pub clock_bank: StaticClockBank,
-    pub audio_envelope: UnipolarFloat,
+    pub audio_envelopes: AudioEnvelopes,
+    /// Which clock this host's published frame `Timestamp`s are measured against, so a render
+    /// client can confirm its own synchronization is locked to the same clock rather than
+    /// silently drifting out of alignment after a host restart or clock source switch. `None`
+    /// for a sender that hasn't been updated to set it yet.
+    pub clock_ref: Option<ClockReference>,
 }
 
 pub type ClockPublisher = PublisherService<SharedClockData>;
@@ -53,6 +87,13 @@ impl ClockStore for StaticClockBank {
     fn use_audio_size(&self, index: ClockIdx) -> bool {
         self.get(index).use_audio_size
     }
+
+    fn scale_audio_envelope(&self, index: ClockIdx, envelopes: &AudioEnvelopes) -> UnipolarFloat {
+        let clock = self.get(index);
+        clock
+            .submaster_curve
+            .apply(envelopes.select(clock.audio_envelope_source))
+    }
 }
 
 impl StaticClockBank {
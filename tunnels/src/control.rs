@@ -1,13 +1,22 @@
 use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crate::master_ui::EmitStateChange;
+use crate::beam_store::BeamStoreAddr;
+use crate::control_recorder::Player;
+use crate::keyboard::{self, KeyEvent};
+use crate::master_ui::{AnimationBankSlot, EmitStateChange};
+use crate::step_sequencer::StepIdx;
 use crate::midi_controls::Dispatcher as MidiDispatcher;
 use crate::osc;
 use crate::show::{ControlMessage, StateChange};
+use crate::tunnel::AnimationIdx;
 use crate::{
-    midi::{DeviceSpec as MidiDeviceSpec, Event as MidiEvent},
+    clock::ControlMessage as ClockControlMessage,
+    clock_bank::{ClockIdxExt, ControlMessage as ClockBankControlMessage},
+    midi::{DeviceSpec as MidiDeviceSpec, Event as MidiEvent, RealTimeMessage},
     midi_controls::Device as MidiDevice,
     osc::{Device as OscDevice, DeviceSpec as OscDeviceSpec},
 };
@@ -18,36 +27,195 @@ use rosc::OscMessage;
 pub enum ControlEvent {
     Midi((MidiDevice, MidiEvent)),
     Osc((OscDevice, OscMessage)),
+    /// A MIDI system real-time message, used to slave a clock to an external
+    /// MIDI clock. Unlike `Midi`, these aren't addressed to a specific
+    /// device or control mapping.
+    MidiRealTime(RealTimeMessage),
+    /// A keyboard tap-tempo or Ctrl-chord event from the console's own input surface.
+    Keyboard(KeyEvent),
+}
+
+/// The clock in the bank that is slaved when an external MIDI clock is
+/// present. MIDI real-time messages carry no per-channel addressing, so we
+/// designate a single fixed clock as the MIDI-syncable one.
+const MIDI_CLOCK_SYNC_CHANNEL: ClockIdxExt = ClockIdxExt(0);
+
+/// Map an incoming MIDI real-time message to the show control message that
+/// drives the designated MIDI-synced clock.
+fn map_real_time_to_show_control(msg: RealTimeMessage) -> ControlMessage {
+    let msg = match msg {
+        RealTimeMessage::Clock(at) => ClockControlMessage::MidiClockPulse { at },
+        RealTimeMessage::Start => ClockControlMessage::MidiStart,
+        RealTimeMessage::Continue => ClockControlMessage::MidiContinue,
+        RealTimeMessage::Stop => ClockControlMessage::MidiStop,
+    };
+    ControlMessage::Clock(ClockBankControlMessage {
+        channel: MIDI_CLOCK_SYNC_CHANNEL,
+        msg,
+    })
+}
+
+/// Identifies the logical control that a `StateChange` updates, independent of its value.
+/// Used as the key for coalescing a burst of updates to the same control down to just the most
+/// recent one. Variants that carry an address collapse per-address; everything else collapses
+/// per top-level category, which is coarser but still bounds a fader sweep to one message per
+/// category per tick.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum ControlKey {
+    Tunnel,
+    Animation,
+    AnimationTarget,
+    Mixer,
+    Clock,
+    ColorPalette,
+    Position,
+    Audio,
+    MasterUIChannel,
+    MasterUIAnimation(AnimationIdx),
+    MasterUIBeamButton(BeamStoreAddr),
+    MasterUIAnimationBankSlot(AnimationBankSlot),
+    MasterUIBeamStoreState,
+    MasterUITally,
+    MasterUIStep(StepIdx),
+    MasterUIStepAdvanced(StepIdx),
+    KeyboardBpm,
+    KeyboardMirror(crate::mixer::ChannelIdx),
+}
+
+impl ControlKey {
+    fn for_state_change(sc: &StateChange) -> Self {
+        use crate::master_ui::StateChange as MasterUIStateChange;
+        match sc {
+            StateChange::Tunnel(_) => Self::Tunnel,
+            StateChange::Animation(_) => Self::Animation,
+            StateChange::AnimationTarget(_) => Self::AnimationTarget,
+            StateChange::Mixer(_) => Self::Mixer,
+            StateChange::Clock(_) => Self::Clock,
+            StateChange::ColorPalette(_) => Self::ColorPalette,
+            StateChange::Position(_) => Self::Position,
+            StateChange::Audio(_) => Self::Audio,
+            StateChange::MasterUI(mui_sc) => match mui_sc {
+                MasterUIStateChange::Channel(_) => Self::MasterUIChannel,
+                MasterUIStateChange::Animation(idx) => Self::MasterUIAnimation(*idx),
+                MasterUIStateChange::BeamButton((addr, _)) => Self::MasterUIBeamButton(*addr),
+                MasterUIStateChange::AnimationBankSlot((slot, _)) => {
+                    Self::MasterUIAnimationBankSlot(*slot)
+                }
+                MasterUIStateChange::BeamStoreState(_) => Self::MasterUIBeamStoreState,
+                MasterUIStateChange::Tally(_) => Self::MasterUITally,
+                MasterUIStateChange::Step((idx, _)) => Self::MasterUIStep(*idx),
+                MasterUIStateChange::StepAdvanced((idx, _)) => Self::MasterUIStepAdvanced(*idx),
+            },
+            StateChange::Keyboard(km) => match km {
+                crate::keyboard::StateChange::Bpm(_) => Self::KeyboardBpm,
+                crate::keyboard::StateChange::Mirror((channel, _)) => {
+                    Self::KeyboardMirror(*channel)
+                }
+            },
+        }
+    }
+}
+
+/// Single-slot mailbox per logical control. Queuing a state change for a control that already
+/// has one pending simply overwrites it, so only the most recent value survives to be drained.
+#[derive(Default)]
+struct CoalescingBuffer {
+    pending: HashMap<ControlKey, StateChange>,
+}
+
+impl CoalescingBuffer {
+    fn push(&mut self, sc: StateChange) {
+        self.pending.insert(ControlKey::for_state_change(&sc), sc);
+    }
+
+    /// Remove and return every pending state change, in no particular order.
+    fn drain(&mut self) -> impl Iterator<Item = StateChange> + '_ {
+        self.pending.drain().map(|(_, sc)| sc)
+    }
 }
 
 pub struct Dispatcher {
     midi_dispatcher: MidiDispatcher,
+    osc_dispatcher: osc::Dispatcher,
     recv: Receiver<ControlEvent>,
     // Hang onto a copy of this for when we're running in test mode, otherwise
     // the channel is closed instantly and we do not block properly.
     _send: Sender<ControlEvent>,
+    /// Coalesces bursts of state changes so the control surfaces only see the latest value per
+    /// control on each flush, rather than every intermediate value.
+    pending: CoalescingBuffer,
+    /// A loaded recording being replayed into the live control stream, if any.
+    player: Option<Player>,
 }
 
 impl Dispatcher {
     /// Instantiate the master control dispatcher.
+    /// `reference` is the instant the control recorder timestamps captured events against.
+    /// If `control_mapping_config_path` is set, the custom MIDI bindings it names are merged
+    /// over the built-in defaults.
     pub fn new(
         midi_devices: Vec<MidiDeviceSpec<MidiDevice>>,
         osc_devices: Vec<OscDeviceSpec>,
+        reference: Instant,
+        control_mapping_config_path: Option<PathBuf>,
     ) -> Result<Self> {
         let (send, recv) = channel();
 
-        for osc_device in osc_devices {
-            osc::listen(osc_device, send.clone())?;
-        }
+        let osc_dispatcher = osc::Dispatcher::new(osc_devices, send.clone())?;
+
+        keyboard::listen(send.clone());
 
         Ok(Self {
-            midi_dispatcher: MidiDispatcher::new(midi_devices, send.clone())?,
+            midi_dispatcher: MidiDispatcher::new(
+                midi_devices,
+                send.clone(),
+                reference,
+                control_mapping_config_path,
+            )?,
+            osc_dispatcher,
             recv,
             _send: send,
+            pending: CoalescingBuffer::default(),
+            player: None,
         })
     }
 
-    pub fn receive(&self, timeout: Duration) -> Result<Option<ControlMessage>> {
+    /// Take (clearing it) whether a reconnected midi input requires a full resync of show state
+    /// back out to every control surface, to cover whatever it missed while disconnected.
+    pub fn take_resync_needed(&mut self) -> bool {
+        self.midi_dispatcher.take_resync_needed()
+    }
+
+    /// Advertise the live captured control stream as a DNS-SD "inspector" service, so a remote
+    /// operator can subscribe and watch control traffic in real time without touching the
+    /// console.
+    pub fn publish_inspector_feed(&mut self, ctx: &zmq::Context) -> Result<()> {
+        self.midi_dispatcher.recorder_mut().publish_inspector_feed(ctx)
+    }
+
+    /// Save everything captured by the recorder so far, to be loaded and replayed later via
+    /// `load_recording`.
+    pub fn save_recording(&self, path: &Path) -> Result<()> {
+        self.midi_dispatcher.recorder().save(path)
+    }
+
+    /// Load a recorded control log and begin replaying it into the live control stream,
+    /// scheduled against elapsed wall-clock time since this call. If `loop_playback` is set,
+    /// playback restarts from the beginning once the recording is exhausted.
+    pub fn load_recording(&mut self, path: &Path, loop_playback: bool) -> Result<()> {
+        self.player = Some(Player::load(path, loop_playback)?);
+        Ok(())
+    }
+
+    pub fn receive(&mut self, timeout: Duration) -> Result<Option<ControlMessage>> {
+        self.midi_dispatcher.recorder_mut().drain_outbound();
+        if let Some(player) = &mut self.player {
+            for (device, event) in player.poll() {
+                // The channel can't be disconnected; we're still holding the receiver ourselves.
+                let _ = self._send.send(ControlEvent::Midi((device, event)));
+            }
+        }
+
         let event = match self.recv.recv_timeout(timeout) {
             Ok(e) => e,
             Err(RecvTimeoutError::Timeout) => {
@@ -63,16 +231,28 @@ impl Dispatcher {
                 .midi_dispatcher
                 .map_event_to_show_control(device, event)),
             Osc((device, event)) => osc::map_event_to_show_control(device, event),
+            MidiRealTime(msg) => Ok(Some(map_real_time_to_show_control(msg))),
+            Keyboard(event) => Ok(keyboard::map_event_to_show_control(event)),
+        }
+    }
+
+    /// Push every coalesced state change out to the control surfaces, draining the mailbox.
+    /// Call this once per show tick rather than emitting straight through, so a burst of
+    /// updates to the same control (for example sweeping a fader) collapses to a single
+    /// outgoing message per control.
+    pub fn flush(&mut self) {
+        for sc in self.pending.drain().collect::<Vec<_>>() {
+            self.midi_dispatcher.emit(sc);
         }
     }
 }
 
 impl EmitStateChange for Dispatcher {
-    /// Map application state changes into UI update messages.
+    /// Fan a state change out to every control type. OSC talkback isn't coalesced, since OSC
+    /// surfaces don't share MIDI's flush-once-per-tick cadence concern; MIDI still goes through
+    /// the coalescing mailbox so a burst of updates collapses to one message per control.
     fn emit(&mut self, sc: StateChange) {
-        self.midi_dispatcher.emit(sc);
-        // FIXME: need to borrow state change messages instead of moving them
-        // if we want state changes to fan-out to different control types.
-        // self.osc_dispatcher.emit(sc);
+        self.osc_dispatcher.emit(sc.clone());
+        self.pending.push(sc);
     }
 }
@@ -0,0 +1,181 @@
+//! Capture and replay of midi control traffic, plus a live "inspector" feed for remote
+//! packet-style debugging of the protocol.
+//!
+//! `Recorder` timestamps every inbound control event and outbound UI-feedback event against a
+//! reference instant and appends it to a log, in the same msgpack encoding `PublisherService`
+//! already uses for other show data. That log can be saved to disk and later driven back through
+//! `Player`, which re-emits its inbound events scheduled against elapsed wall-clock time so a
+//! show can be deterministically replayed or looped for automated performances and regression
+//! testing of the waveform/animation pipeline. The recorder can also advertise its live stream
+//! as its own DNS-SD service, so an operator on another machine can subscribe and watch control
+//! traffic in real time without touching the console.
+
+use anyhow::Result;
+use rmp_serde::{Deserializer, Serializer};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    sync::mpsc::{channel, Receiver, Sender},
+    time::Instant,
+};
+use tunnels_lib::Timestamp;
+use zero_configure::pub_sub::PublisherService;
+use zmq::Context;
+
+use crate::{midi::Event, midi_controls::Device};
+
+/// DNS-SD service name the live inspector feed is advertised under.
+const INSPECTOR_SERVICE_NAME: &str = "tunnels_control_inspector";
+const INSPECTOR_PORT: u16 = 9091;
+
+/// Which direction a captured midi event travelled, relative to the show controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// A control event received from a device, to be mapped to a show control.
+    Inbound,
+    /// A UI-feedback event sent back out to a device.
+    Outbound,
+}
+
+/// A single captured midi event, timestamped relative to the recorder's reference instant.
+/// This captures the raw control-surface traffic (mapping, channel, value) rather than the
+/// decoded `ControlMessage`, since most `ControlMessage` variants don't implement `Serialize`;
+/// an inspector watching the live feed can decode a mapping the same way the console's
+/// `ControlMap` does.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CapturedEvent {
+    pub timestamp: Timestamp,
+    pub direction: Direction,
+    pub device: Device,
+    pub event: Event,
+}
+
+/// Records inbound and outbound midi traffic, timestamped against a reference instant, so a show
+/// can be saved to disk for deterministic replay or inspected live over the network.
+pub struct Recorder {
+    reference: Instant,
+    log: Vec<CapturedEvent>,
+    /// Outbound events arrive here from `Manager::send`, via the sender handed out by `new`.
+    outbound: Receiver<(Device, Event)>,
+    /// Advertises the live stream over DNS-SD, if enabled.
+    inspector: Option<PublisherService<CapturedEvent>>,
+}
+
+impl Recorder {
+    /// Create a new recorder, along with the sender its `Manager` should be given via
+    /// `Manager::capture_sent_events` to forward outbound events here for capture.
+    pub fn new(reference: Instant) -> (Self, Sender<(Device, Event)>) {
+        let (send, recv) = channel();
+        (
+            Self {
+                reference,
+                log: Vec::new(),
+                outbound: recv,
+                inspector: None,
+            },
+            send,
+        )
+    }
+
+    /// Advertise the live captured control stream as a DNS-SD service, so a remote operator can
+    /// subscribe and watch control traffic in real time.
+    pub fn publish_inspector_feed(&mut self, ctx: &Context) -> Result<()> {
+        self.inspector = Some(PublisherService::new(
+            ctx,
+            INSPECTOR_SERVICE_NAME,
+            INSPECTOR_PORT,
+            HashMap::new(),
+        )?);
+        Ok(())
+    }
+
+    /// Record an inbound control event, received from a device and about to be mapped to a
+    /// show control.
+    pub fn record_inbound(&mut self, device: Device, event: Event) {
+        self.capture(Direction::Inbound, device, event);
+    }
+
+    /// Drain every outbound event captured since the last call and append it to the log.
+    /// `Manager::send` can't call back into the recorder directly, since it doesn't own it, so
+    /// this must be polled periodically; the show does this once per control tick.
+    pub fn drain_outbound(&mut self) {
+        while let Ok((device, event)) = self.outbound.try_recv() {
+            self.capture(Direction::Outbound, device, event);
+        }
+    }
+
+    fn capture(&mut self, direction: Direction, device: Device, event: Event) {
+        let captured = CapturedEvent {
+            timestamp: Timestamp::since(self.reference),
+            direction,
+            device,
+            event,
+        };
+        if let Some(inspector) = &mut self.inspector {
+            if let Err(e) = inspector.send(&captured) {
+                log::error!("Failed to publish control inspector event: {e}");
+            }
+        }
+        self.log.push(captured);
+    }
+
+    /// Save everything captured so far to `path`, to be loaded and replayed later via
+    /// `Player::load`.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut file = File::create(path)?;
+        self.log
+            .serialize(&mut Serializer::new(BufWriter::new(&mut file)))?;
+        Ok(())
+    }
+}
+
+/// Replays a captured control log by re-emitting its inbound events scheduled against elapsed
+/// wall-clock time, for deterministic regression testing or automated performances.
+pub struct Player {
+    reference: Instant,
+    loop_playback: bool,
+    /// The full recording, retained so playback can restart from the beginning when looping.
+    original: Vec<CapturedEvent>,
+    remaining: std::vec::IntoIter<CapturedEvent>,
+}
+
+impl Player {
+    /// Load a captured control log from disk and start replaying it from the beginning,
+    /// scheduled against the moment of this call.
+    pub fn load(path: &Path, loop_playback: bool) -> Result<Self> {
+        let file = File::open(path)?;
+        let log: Vec<CapturedEvent> = Deserialize::deserialize(&mut Deserializer::new(file))?;
+        Ok(Self {
+            reference: Instant::now(),
+            loop_playback,
+            remaining: log.clone().into_iter(),
+            original: log,
+        })
+    }
+
+    /// Return every recorded inbound event whose scheduled time has now elapsed, oldest first.
+    /// Outbound events in the log are feedback the original run already sent to its control
+    /// surfaces, so they're skipped here rather than re-emitted. If this player loops, restart
+    /// from the beginning once the log is exhausted.
+    pub fn poll(&mut self) -> Vec<(Device, Event)> {
+        let elapsed = Timestamp::since(self.reference);
+        let mut due = Vec::new();
+        while let Some(next) = self.remaining.as_slice().first() {
+            if next.timestamp > elapsed {
+                break;
+            }
+            let next = self.remaining.next().unwrap();
+            if next.direction == Direction::Inbound {
+                due.push((next.device, next.event));
+            }
+        }
+        if self.remaining.as_slice().is_empty() && self.loop_playback {
+            self.reference = Instant::now();
+            self.remaining = self.original.clone().into_iter();
+        }
+        due
+    }
+}
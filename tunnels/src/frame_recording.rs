@@ -0,0 +1,266 @@
+//! Chunked on-disk capture and replay of rendered frame output, for offline rendering, debugging,
+//! and regression-testing the render pipeline by replaying a past performance.
+//!
+//! Mirrors `control_recorder`'s recorder/player split and `Timestamp`-scheduled playback, but
+//! captures the per-video-channel `LayerCollection`s a [`crate::mixer::Mixer`] renders each frame
+//! rather than control traffic. A frame recording can run far longer than a control-event log, so
+//! rather than holding the whole session in memory it's written as a small self-describing header
+//! plus a sequence of fixed-size chunk files, each loaded into memory only while it's in use.
+
+use anyhow::{bail, Context, Result};
+use rmp_serde::{Deserializer, Serializer};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tunnels_lib::{LayerCollection, Timestamp};
+use uuid::Uuid;
+
+use crate::mixer::Mixer;
+
+/// How many frames to buffer in memory before flushing them to their own chunk file.
+const FRAMES_PER_CHUNK: usize = 1000;
+
+const HEADER_FILE_NAME: &str = "header.msgpack";
+const INDEX_FILE_NAME: &str = "index.msgpack";
+
+fn chunk_file_name(chunk_number: u64) -> String {
+    format!("chunk_{chunk_number:06}.msgpack")
+}
+
+/// Self-describing metadata for a frame recording, written once when recording starts so the
+/// recording can be correctly replayed without also having on hand the show configuration that
+/// produced it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Header {
+    /// Uniquely identifies this recording.
+    pub id: Uuid,
+    /// Wall-clock time recording began, as microseconds since the Unix epoch.
+    pub wall_clock_start_micros: i64,
+    /// The show's update interval at the time of recording. Played-back frames are scheduled
+    /// by their own recorded timestamps; playback polls for due frames at half this interval.
+    pub render_interval: Duration,
+    /// How many virtual video channels each frame record holds, i.e. `Mixer::N_VIDEO_CHANNELS`
+    /// at the time of recording.
+    pub video_channels: usize,
+}
+
+/// One frame's rendered output across every video channel, the unit a recording is chunked by.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FrameRecord {
+    pub frame_number: u64,
+    pub timestamp: Timestamp,
+    pub channels: Vec<LayerCollection>,
+}
+
+/// Metadata about a flushed chunk, small enough to keep the whole recording's index in memory so
+/// a player can locate the right chunk for a seek without scanning the chunks themselves.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ChunkIndexEntry {
+    chunk_number: u64,
+    first_timestamp: Timestamp,
+}
+
+/// Captures rendered frame output to a chunked on-disk log as a show runs.
+pub struct Recorder {
+    dir: PathBuf,
+    chunk_number: u64,
+    chunk: Vec<FrameRecord>,
+    index: Vec<ChunkIndexEntry>,
+}
+
+impl Recorder {
+    /// Start a new recording in `dir`, creating it if necessary, and write its header.
+    pub fn start(dir: &Path, render_interval: Duration) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("could not create recording directory {}", dir.display()))?;
+        let header = Header {
+            id: Uuid::new_v4(),
+            wall_clock_start_micros: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_micros() as i64)
+                .unwrap_or(0),
+            render_interval,
+            video_channels: Mixer::N_VIDEO_CHANNELS,
+        };
+        write_msgpack(&dir.join(HEADER_FILE_NAME), &header)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            chunk_number: 0,
+            chunk: Vec::with_capacity(FRAMES_PER_CHUNK),
+            index: Vec::new(),
+        })
+    }
+
+    /// Append one frame's rendered output to the recording, flushing a chunk to disk once it
+    /// reaches `FRAMES_PER_CHUNK` frames.
+    pub fn record(
+        &mut self,
+        frame_number: u64,
+        timestamp: Timestamp,
+        channels: Vec<LayerCollection>,
+    ) -> Result<()> {
+        self.chunk.push(FrameRecord {
+            frame_number,
+            timestamp,
+            channels,
+        });
+        if self.chunk.len() >= FRAMES_PER_CHUNK {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    fn flush_chunk(&mut self) -> Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        self.index.push(ChunkIndexEntry {
+            chunk_number: self.chunk_number,
+            first_timestamp: self.chunk[0].timestamp,
+        });
+        write_msgpack(
+            &self.dir.join(chunk_file_name(self.chunk_number)),
+            &self.chunk,
+        )?;
+        write_msgpack(&self.dir.join(INDEX_FILE_NAME), &self.index)?;
+        self.chunk.clear();
+        self.chunk_number += 1;
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    /// Flush whatever is left in the in-progress chunk, so a recording stopped mid-chunk (e.g. by
+    /// the show exiting) isn't silently missing its last few seconds.
+    fn drop(&mut self) {
+        if let Err(e) = self.flush_chunk() {
+            log::error!("Failed to flush final frame recording chunk: {e}");
+        }
+    }
+}
+
+fn write_msgpack<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let file =
+        File::create(path).with_context(|| format!("could not create {}", path.display()))?;
+    value
+        .serialize(&mut Serializer::new(BufWriter::new(file)))
+        .with_context(|| format!("could not write {}", path.display()))
+}
+
+fn read_msgpack<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T> {
+    let file = File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+    Deserialize::deserialize(&mut Deserializer::new(file))
+        .with_context(|| format!("could not parse {}", path.display()))
+}
+
+/// Replays a chunked frame recording by feeding its frames back into the render/publish path,
+/// scheduled against elapsed wall-clock time, for offline rendering, debugging, and
+/// regression-testing of the render pipeline.
+pub struct Player {
+    dir: PathBuf,
+    header: Header,
+    index: Vec<ChunkIndexEntry>,
+    loop_playback: bool,
+    reference: Instant,
+    chunk_cursor: usize,
+    current_chunk: std::vec::IntoIter<FrameRecord>,
+}
+
+impl Player {
+    /// Load a recording's header and chunk index from `dir` and start replaying it from the
+    /// beginning, scheduled against the moment of this call.
+    pub fn load(dir: &Path, loop_playback: bool) -> Result<Self> {
+        let header: Header = read_msgpack(&dir.join(HEADER_FILE_NAME))?;
+        let index: Vec<ChunkIndexEntry> = read_msgpack(&dir.join(INDEX_FILE_NAME))?;
+        if index.is_empty() {
+            bail!("recording at {} has no recorded frames", dir.display());
+        }
+        let mut player = Self {
+            dir: dir.to_path_buf(),
+            header,
+            index,
+            loop_playback,
+            reference: Instant::now(),
+            chunk_cursor: 0,
+            current_chunk: Vec::new().into_iter(),
+        };
+        player.load_chunk(0)?;
+        Ok(player)
+    }
+
+    /// The header this recording was started with.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Whether playback has reached the end of a non-looping recording, with nothing left to
+    /// poll.
+    pub fn is_finished(&self) -> bool {
+        !self.loop_playback
+            && self.chunk_cursor + 1 >= self.index.len()
+            && self.current_chunk.as_slice().is_empty()
+    }
+
+    fn load_chunk(&mut self, chunk_cursor: usize) -> Result<()> {
+        let chunk_number = self.index[chunk_cursor].chunk_number;
+        let chunk: Vec<FrameRecord> = read_msgpack(&self.dir.join(chunk_file_name(chunk_number)))?;
+        self.chunk_cursor = chunk_cursor;
+        self.current_chunk = chunk.into_iter();
+        Ok(())
+    }
+
+    /// Jump playback to the first recorded frame at or after `target`, rescheduling subsequent
+    /// polls as if that frame were being recorded right now.
+    pub fn seek(&mut self, target: Timestamp) -> Result<()> {
+        let chunk_cursor = self
+            .index
+            .iter()
+            .rposition(|entry| entry.first_timestamp <= target)
+            .unwrap_or(0);
+        self.load_chunk(chunk_cursor)?;
+        while let Some(next) = self.current_chunk.as_slice().first() {
+            if next.timestamp >= target {
+                break;
+            }
+            self.current_chunk.next();
+        }
+        let resume_at = self
+            .current_chunk
+            .as_slice()
+            .first()
+            .map_or(target, |next| next.timestamp);
+        self.reference = Instant::now()
+            .checked_sub(Duration::from_micros(resume_at.0.max(0) as u64))
+            .unwrap_or_else(Instant::now);
+        Ok(())
+    }
+
+    /// Return every recorded frame whose scheduled playback time has now elapsed, oldest first,
+    /// loading subsequent chunks from disk as needed. If this player loops, restart from the
+    /// beginning once the recording is exhausted.
+    pub fn poll(&mut self) -> Result<Vec<FrameRecord>> {
+        let elapsed = Timestamp::since(self.reference);
+        let mut due = Vec::new();
+        loop {
+            match self.current_chunk.as_slice().first() {
+                Some(next) if next.timestamp <= elapsed => {
+                    due.push(self.current_chunk.next().unwrap());
+                }
+                Some(_) => break,
+                None if self.chunk_cursor + 1 < self.index.len() => {
+                    self.load_chunk(self.chunk_cursor + 1)?;
+                }
+                None if self.loop_playback => {
+                    self.load_chunk(0)?;
+                    self.reference = Instant::now();
+                }
+                None => break,
+            }
+        }
+        Ok(due)
+    }
+}
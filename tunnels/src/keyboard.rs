@@ -0,0 +1,183 @@
+//! Tap-tempo and Ctrl-chord keyboard input for the console. The only input path that doesn't
+//! need any attached hardware: tapping a key derives a global tempo without a MIDI clock, and a
+//! Ctrl+digit chord reaches a mixer channel directly, independent of whatever channel the UI
+//! currently has selected.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::control::ControlEvent;
+use crate::master_ui::EmitStateChange as EmitShowStateChange;
+use crate::mixer::ChannelIdx;
+use crate::show::{ControlMessage as ShowControlMessage, StateChange as ShowStateChange};
+
+/// A keyboard event from the console's own input surface.
+#[derive(Debug, Clone, Copy)]
+pub enum KeyEvent {
+    /// The dedicated tap-tempo key, pressed with no modifier.
+    Tap,
+    /// A digit key, optionally chorded with Ctrl to address a mixer channel directly.
+    Digit { digit: u8, ctrl: bool },
+}
+
+/// Listen for keyboard shorthand on stdin in a dedicated thread: a blank line is the tap-tempo
+/// key, and `c<digit>` is a Ctrl+digit chord. Line-buffered stdin can't see a bare modifier key
+/// or an un-terminated keypress, so this is the least-friction approximation of raw keyboard
+/// input available without a terminal UI dependency.
+pub fn listen(send: Sender<ControlEvent>) {
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let event = match line.trim() {
+                "" => Some(KeyEvent::Tap),
+                s => s
+                    .strip_prefix('c')
+                    .and_then(|digit| digit.parse::<u8>().ok())
+                    .map(|digit| KeyEvent::Digit { digit, ctrl: true }),
+            };
+            let Some(event) = event else {
+                continue;
+            };
+            if send.send(ControlEvent::Keyboard(event)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Map a keyboard event to the show control message it produces, if any.
+pub fn map_event_to_show_control(event: KeyEvent) -> Option<ShowControlMessage> {
+    match event {
+        KeyEvent::Tap => Some(ShowControlMessage::Keyboard(ControlMessage::Tap)),
+        KeyEvent::Digit { ctrl: false, .. } => None,
+        KeyEvent::Digit { digit, ctrl: true } => Some(ShowControlMessage::Keyboard(
+            ControlMessage::ToggleMirror(digit as usize),
+        )),
+    }
+}
+
+/// Estimate tempo from a series of taps by the median of the inter-tap intervals, rather than
+/// `clock::TapSync`'s least-squares fit over the whole buffer: a keyboard tapper just wants a
+/// quick "about this fast", and the median shrugs off the occasional fat-fingered double-tap
+/// instead of letting it drag a fitted line off course.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TapTempo {
+    /// Timestamp of the most recent tap, used to measure the gap to the next one.
+    #[serde(skip)]
+    last_tap: Option<Instant>,
+    /// Ring buffer of recent inter-tap intervals, reduced to a tempo by their median.
+    #[serde(skip)]
+    intervals: Vec<Duration>,
+}
+
+impl TapTempo {
+    /// Cap the interval buffer to this many of the most recent intervals, so the estimate tracks
+    /// tempo changes rather than averaging over a whole song.
+    const WINDOW: usize = 8;
+
+    /// Gap beyond which we discard the interval buffer and start a fresh estimate, so a new
+    /// series of taps re-syncs instead of averaging against a stale tempo.
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// Record a tap, returning the freshly estimated tempo in BPM once at least one interval is
+    /// buffered.
+    fn tap(&mut self, at: Instant) -> Option<f64> {
+        let bpm = self.last_tap.and_then(|last| {
+            let gap = at.saturating_duration_since(last);
+            if gap > Self::TIMEOUT {
+                self.intervals.clear();
+                return None;
+            }
+            self.intervals.push(gap);
+            if self.intervals.len() > Self::WINDOW {
+                self.intervals.remove(0);
+            }
+            Some(Self::median_bpm(&self.intervals))
+        });
+        self.last_tap = Some(at);
+        bpm
+    }
+
+    /// Convert a buffer of inter-tap intervals into a tempo in BPM via their median.
+    fn median_bpm(intervals: &[Duration]) -> f64 {
+        let mut sorted = intervals.to_vec();
+        sorted.sort();
+        let mid = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2
+        } else {
+            sorted[mid]
+        };
+        60.0 / median.as_secs_f64()
+    }
+}
+
+/// Keyboard input state: the tap-tempo estimator, plus which mixer channels have had their
+/// mirror transform toggled on via a Ctrl+digit chord.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Controller {
+    tempo: TapTempo,
+    mirrored_channels: HashSet<ChannelIdx>,
+}
+
+impl Controller {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a tap-tempo keypress, returning the freshly estimated tempo in BPM once enough
+    /// taps have landed to produce one.
+    pub fn tap(&mut self, at: Instant) -> Option<f64> {
+        self.tempo.tap(at)
+    }
+
+    /// Toggle the mirror transform on `channel`, returning its new state.
+    pub fn toggle_mirror(&mut self, channel: ChannelIdx) -> bool {
+        if self.mirrored_channels.remove(&channel) {
+            false
+        } else {
+            self.mirrored_channels.insert(channel);
+            true
+        }
+    }
+
+    /// Return every channel that currently has the mirror transform toggled on.
+    pub fn mirrored_channels(&self) -> impl Iterator<Item = ChannelIdx> + '_ {
+        self.mirrored_channels.iter().copied()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ControlMessage {
+    /// A tap-tempo keypress.
+    Tap,
+    /// A Ctrl+digit chord addressing a mixer channel by raw index, independent of the UI's
+    /// current channel selection.
+    ToggleMirror(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StateChange {
+    /// The latest tap-tempo estimate, in beats per minute.
+    Bpm(f64),
+    /// Whether the addressed channel now has the mirror transform toggled on.
+    Mirror((ChannelIdx, bool)),
+}
+
+pub trait EmitStateChange {
+    fn emit_keyboard_state_change(&mut self, sc: StateChange);
+}
+
+impl<T: EmitShowStateChange> EmitStateChange for T {
+    fn emit_keyboard_state_change(&mut self, sc: StateChange) {
+        self.emit(ShowStateChange::Keyboard(sc))
+    }
+}
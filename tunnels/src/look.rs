@@ -1,3 +1,5 @@
+use crate::audio::AudioEnvelopes;
+use crate::master_ui::EmitStateChange;
 use crate::palette::ColorPalette;
 use crate::{clock_bank::ClockBank, mixer::Channel};
 use serde::{Deserialize, Serialize};
@@ -17,9 +19,15 @@ impl Look {
         Self { channels }
     }
 
-    pub fn update_state(&mut self, delta_t: Duration, audio_envelope: UnipolarFloat) {
+    pub fn update_state<E: EmitStateChange>(
+        &mut self,
+        delta_t: Duration,
+        audio_envelopes: &AudioEnvelopes,
+        external_clocks: &ClockBank,
+        emitter: &mut E,
+    ) {
         for channel in &mut self.channels {
-            channel.update_state(delta_t, audio_envelope);
+            channel.update_state(delta_t, audio_envelopes, external_clocks, emitter);
         }
     }
 
@@ -33,12 +41,12 @@ impl Look {
         mask: bool,
         external_clocks: &ClockBank,
         color_palette: &ColorPalette,
-        audio_envelope: UnipolarFloat,
+        audio_envelopes: &AudioEnvelopes,
     ) -> Vec<ArcSegment> {
         let mut arcs = Vec::new();
         for channel in &self.channels {
             let mut rendered =
-                channel.render(level, mask, external_clocks, color_palette, audio_envelope);
+                channel.render(level, mask, external_clocks, color_palette, audio_envelopes);
             arcs.append(&mut rendered);
         }
         arcs
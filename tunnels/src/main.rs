@@ -4,15 +4,22 @@ mod beam_store;
 mod clock;
 mod clock_bank;
 mod control;
+mod control_recorder;
+mod frame_recording;
+mod keyboard;
 mod look;
 mod master_ui;
 mod midi;
 mod midi_controls;
 mod mixer;
+mod multicast_send;
 mod osc;
 mod palette;
+mod quic_send;
 mod send;
 mod show;
+mod step_sequencer;
+mod streamdeck;
 mod test_mode;
 mod timesync;
 mod tunnel;
@@ -1,17 +1,29 @@
 use crate::{
     animation::Animation,
+    animation::{ControlMessage as AnimationControlMessage, StateChange as AnimationStateChange},
     audio::AudioInput,
     beam::Beam,
     beam_store::{BeamStore, BeamStoreAddr},
-    clock_bank::ClockBank,
+    clock::ControllableClock,
+    clock_bank::{ClockBank, ClockIdxExt},
+    keyboard,
+    keyboard::EmitStateChange as EmitKeyboardStateChange,
     midi_controls::MIXER_CHANNELS_PER_PAGE,
     mixer::{ChannelIdx, Mixer},
     palette::ColorPalette,
+    position_bank::PositionBank,
     show::{ControlMessage as ShowControlMessage, StateChange as ShowStateChange},
+    step_sequencer::{StepIdx, StepPayload, StepSequencer},
     tunnel::AnimationIdx,
 };
 
+use log::error;
 use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tunnels_lib::number::BipolarFloat;
+
+/// How many steps the built-in step sequencer has.
+pub const N_STEPS: usize = 8;
 
 /// Manage stateful aspects of the UI.
 /// Mediate between the input systems and the show data.
@@ -22,9 +34,11 @@ pub struct MasterUI {
     /// associated index.
     /// Enables stable animation selection when jumping between beams.
     current_animation_for_channel: Vec<AnimationIdx>,
-    animation_clipboard: Animation,
+    animation_bank: AnimationBank,
     beam_store: BeamStore,
     beam_store_state: BeamStoreState,
+    step_sequencer: StepSequencer,
+    keyboard: keyboard::Controller,
 }
 
 impl MasterUI {
@@ -35,9 +49,16 @@ impl MasterUI {
                 AnimationIdx(0);
                 n_mixer_pages * MIXER_CHANNELS_PER_PAGE
             ],
-            animation_clipboard: Animation::new(),
+            animation_bank: AnimationBank::new(),
             beam_store: BeamStore::new(n_mixer_pages),
             beam_store_state: BeamStoreState::Idle,
+            step_sequencer: StepSequencer::new(
+                N_STEPS,
+                ClockIdxExt(0)
+                    .try_into()
+                    .expect("clock 0 always exists in the bank"),
+            ),
+            keyboard: keyboard::Controller::new(),
         }
     }
 
@@ -66,22 +87,45 @@ impl MasterUI {
         mixer: &mut Mixer,
         clocks: &mut ClockBank,
         color_palette: &mut ColorPalette,
+        positions: &mut PositionBank,
         audio_input: &mut AudioInput,
         emitter: &mut E,
     ) {
         use ShowControlMessage::*;
         match msg {
-            Tunnel(tm) => match self.current_beam(mixer) {
-                Beam::Look(_) => (),
-                Beam::Tunnel(t) => t.control(tm, emitter),
-            },
+            // Tunnel, Animation and Mixer controls are diffed against a snapshot of the state
+            // they touch, so a no-op control (e.g. nudging a value that's already clamped at its
+            // limit) doesn't re-emit a StateChange and spam MIDI/UI feedback or loop back through
+            // a bidirectional control surface.
+            Tunnel(tm) => {
+                let mut old = Vec::new();
+                if let Beam::Tunnel(t) = self.current_beam(mixer) {
+                    t.emit_state(&mut BufferingEmitter(&mut old));
+                }
+                let mut new = Vec::new();
+                match self.current_beam(mixer) {
+                    Beam::Look(_) => (),
+                    Beam::Tunnel(t) => t.control(tm, &mut BufferingEmitter(&mut new)),
+                }
+                emit_diffed(&old, new, emitter);
+            }
             Animation(am) => {
+                let mut old = Vec::new();
+                if let Some(a) = self.current_animation(mixer) {
+                    a.emit_state(&mut BufferingEmitter(&mut old));
+                }
+                let mut new = Vec::new();
                 if let Some(a) = self.current_animation(mixer) {
-                    a.control(am, emitter);
+                    a.control(am, &mut BufferingEmitter(&mut new));
                 }
+                emit_diffed(&old, new, emitter);
             }
             Mixer(mm) => {
-                mixer.control(mm, emitter);
+                let mut old = Vec::new();
+                mixer.emit_state(&mut BufferingEmitter(&mut old));
+                let mut new = Vec::new();
+                mixer.control(mm, &mut BufferingEmitter(&mut new));
+                emit_diffed(&old, new, emitter);
             }
             Clock(cm) => {
                 clocks.control(cm, emitter);
@@ -89,10 +133,69 @@ impl MasterUI {
             ColorPalette(cm) => {
                 color_palette.control(cm, emitter);
             }
+            Position(pm) => {
+                positions.control(pm, emitter);
+            }
             Audio(cm) => {
                 audio_input.control(cm, emitter);
             }
             MasterUI(uim) => self.control(uim, mixer, emitter),
+            Keyboard(km) => self.handle_keyboard_control(km, mixer, emitter),
+            Cut => {
+                mixer.cut(self.current_channel);
+                self.emit_tally(mixer, emitter);
+            }
+            Auto(duration) => {
+                mixer.auto(self.current_channel, duration);
+                self.emit_tally(mixer, emitter);
+            }
+            LookPreview(look) => {
+                mixer.arm_look_preview(look);
+            }
+            LookCut => {
+                mixer.cut_look(emitter);
+            }
+            LookAuto(duration) => {
+                mixer.auto_look(duration);
+            }
+        }
+    }
+
+    /// Handle a keyboard control event: a tap-tempo keypress drives the current channel's
+    /// animation rate, while a Ctrl+digit chord toggles the mirror transform on the mixer
+    /// channel it addresses directly, regardless of which channel the UI currently has selected.
+    fn handle_keyboard_control<E: EmitStateChange>(
+        &mut self,
+        msg: keyboard::ControlMessage,
+        mixer: &mut Mixer,
+        emitter: &mut E,
+    ) {
+        use keyboard::ControlMessage::*;
+        match msg {
+            Tap => {
+                let Some(bpm) = self.keyboard.tap(Instant::now()) else {
+                    return;
+                };
+                emitter.emit_keyboard_state_change(keyboard::StateChange::Bpm(bpm));
+                if let Some(a) = self.current_animation(mixer) {
+                    let speed = BipolarFloat::new((bpm / 60.0) / ControllableClock::RATE_SCALE);
+                    a.control(
+                        AnimationControlMessage::Set(AnimationStateChange::Speed(speed)),
+                        emitter,
+                    );
+                }
+            }
+            ToggleMirror(digit) => {
+                if digit >= mixer.channel_count() {
+                    error!("keyboard mirror chord addressed nonexistent channel {digit}");
+                    return;
+                }
+                let channel = ChannelIdx(digit);
+                let mirrored = self.keyboard.toggle_mirror(channel);
+                emitter.emit_keyboard_state_change(keyboard::StateChange::Mirror((
+                    channel, mirrored,
+                )));
+            }
         }
     }
 
@@ -102,18 +205,70 @@ impl MasterUI {
         mixer: &mut Mixer,
         clocks: &mut ClockBank,
         color_palette: &mut ColorPalette,
+        positions: &PositionBank,
         audio_input: &mut AudioInput,
         emitter: &mut E,
     ) {
         emitter.emit_master_ui_state_change(StateChange::Channel(self.current_channel));
         self.emit_beam_store_state(emitter);
+        self.emit_animation_bank_state(emitter);
+        self.emit_step_sequencer_state(emitter);
+        self.emit_keyboard_state(emitter);
         self.emit_current_channel_state(mixer, emitter);
         mixer.emit_state(emitter);
         clocks.emit_state(emitter);
         color_palette.emit_state(emitter);
+        positions.emit_state(emitter);
         audio_input.emit_state(emitter);
     }
 
+    /// Advance the step sequencer against the current state of the clock
+    /// bank, applying and emitting the step it lands on whenever it crosses
+    /// into a new one.
+    pub fn update_state<E: EmitStateChange>(&mut self, clocks: &ClockBank, mixer: &mut Mixer, emitter: &mut E) {
+        if let Some((index, payload)) = self.step_sequencer.update(clocks) {
+            emitter.emit_master_ui_state_change(StateChange::StepAdvanced((index, payload)));
+            if let Some(payload) = payload {
+                self.apply_step_payload(payload, mixer, emitter);
+            }
+        }
+    }
+
+    /// Emit state for the step sequencer's grid.
+    fn emit_step_sequencer_state<E: EmitStateChange>(&self, emitter: &mut E) {
+        for i in 0..self.step_sequencer.n_steps() {
+            let index = StepIdx(i);
+            emitter.emit_master_ui_state_change(StateChange::Step((
+                index,
+                self.step_sequencer.step(index),
+            )));
+        }
+    }
+
+    /// Emit state for every channel with the keyboard's mirror transform toggled on.
+    fn emit_keyboard_state<E: EmitStateChange>(&self, emitter: &mut E) {
+        for channel in self.keyboard.mirrored_channels() {
+            emitter.emit_keyboard_state_change(keyboard::StateChange::Mirror((channel, true)));
+        }
+    }
+
+    /// Apply a step's payload: recall a beam into the current channel's preview, or swap its
+    /// active animation.
+    fn apply_step_payload<E: EmitStateChange>(
+        &mut self,
+        payload: StepPayload,
+        mixer: &mut Mixer,
+        emitter: &mut E,
+    ) {
+        match payload {
+            StepPayload::RecallBeam(addr) => self.handle_beam_grid_button_press(addr, mixer, emitter),
+            StepPayload::Animation(idx) => {
+                self.current_animation_for_channel[self.current_channel.0] = idx;
+                self.emit_animator_state(mixer, emitter);
+            }
+        }
+    }
+
     /// Emit state for the beam store.
     fn emit_beam_store_state<E: EmitStateChange>(&self, emitter: &mut E) {
         for (addr, beam) in self.beam_store.items() {
@@ -124,6 +279,24 @@ impl MasterUI {
         }
     }
 
+    /// Emit tally feedback for whether the current channel is live (program only) or previewing
+    /// (an armed preview beam is staged or mid-crossfade).
+    fn emit_tally<E: EmitStateChange>(&self, mixer: &Mixer, emitter: &mut E) {
+        emitter.emit_master_ui_state_change(StateChange::Tally(
+            mixer.is_previewing(self.current_channel),
+        ));
+    }
+
+    /// Emit state for the animation clipboard bank.
+    fn emit_animation_bank_state<E: EmitStateChange>(&self, emitter: &mut E) {
+        for (slot, anim) in self.animation_bank.items() {
+            emitter.emit_master_ui_state_change(StateChange::AnimationBankSlot((
+                slot,
+                AnimationSlotState::from_animation(anim),
+            )));
+        }
+    }
+
     /// Emit state for the active animator.
     fn emit_animator_state<E: EmitStateChange>(&self, mixer: &mut Mixer, emitter: &mut E) {
         if let Some(a) = self.current_animation(mixer) {
@@ -145,6 +318,7 @@ impl MasterUI {
             }
         }
         self.emit_animator_state(mixer, emitter);
+        self.emit_tally(mixer, emitter);
     }
 
     fn set_beam_store_state<E: EmitStateChange>(&mut self, state: BeamStoreState, emitter: &mut E) {
@@ -173,18 +347,29 @@ impl MasterUI {
 
         match msg {
             Set(sc) => self.handle_state_change(sc, mixer, emitter),
-            AnimationCopy => {
+            AnimationCopyTo(slot) => {
                 if let Some(a) = self.current_animation(mixer) {
-                    self.animation_clipboard = a.clone();
+                    self.animation_bank.put(slot, Some(a.clone()));
+                    emitter.emit_master_ui_state_change(StateChange::AnimationBankSlot((
+                        slot,
+                        AnimationSlotState::Occupied,
+                    )));
                 }
             }
-            AnimationPaste => {
-                if let Some(a) = self.current_animation(mixer) {
-                    *a = self.animation_clipboard.clone();
+            AnimationPasteFrom(slot) => {
+                if let Some(anim) = self.animation_bank.get(slot) {
+                    if let Some(a) = self.current_animation(mixer) {
+                        *a = anim;
+                    }
+                    self.emit_animator_state(mixer, emitter);
                 }
-                self.emit_animator_state(mixer, emitter);
             }
             BeamGridButtonPress(addr) => self.handle_beam_grid_button_press(addr, mixer, emitter),
+            StepGridButtonPress(index) => {
+                if let Some(payload) = self.step_sequencer.step(index) {
+                    self.apply_step_payload(payload, mixer, emitter);
+                }
+            }
         }
     }
 
@@ -197,11 +382,11 @@ impl MasterUI {
         use BeamStoreState::*;
         match self.beam_store_state {
             Idle => {
-                // Request to replace the beam in the current mixer with
-                // the beam in this button.
+                // Arm the beam in this button as the preview for the current channel,
+                // ready to be brought to program with a cut or auto-transition.
                 if let Some(beam) = self.beam_store.get(addr) {
-                    *self.current_beam(mixer) = beam;
-                    self.emit_current_channel_state(mixer, emitter);
+                    mixer.arm_preview(self.current_channel, beam);
+                    self.emit_tally(mixer, emitter);
                 }
             }
             BeamSave => {
@@ -260,8 +445,15 @@ impl MasterUI {
                     emitter,
                 );
             }
+            StateChange::Step((index, payload)) => {
+                self.step_sequencer.set_step(index, payload);
+                emitter.emit_master_ui_state_change(sc);
+            }
             // Output only.
             StateChange::BeamButton(_) => (),
+            StateChange::AnimationBankSlot(_) => (),
+            StateChange::Tally(_) => (),
+            StateChange::StepAdvanced(_) => (),
         }
     }
 }
@@ -273,6 +465,26 @@ pub trait EmitMasterUIStateChange {
     fn emit_master_ui_state_change(&mut self, sc: StateChange);
 }
 
+/// Collects every `StateChange` emitted through it instead of forwarding them, so a sub-state's
+/// `emit_state`/`control` methods can be run against a scratch buffer for diffing purposes.
+struct BufferingEmitter<'b>(&'b mut Vec<ShowStateChange>);
+
+impl<'b> EmitStateChange for BufferingEmitter<'b> {
+    fn emit(&mut self, sc: ShowStateChange) {
+        self.0.push(sc);
+    }
+}
+
+/// Forward every `StateChange` in `new` that doesn't already appear in `old`, dropping the rest
+/// as no-ops relative to the pre-change snapshot.
+fn emit_diffed<E: EmitStateChange>(old: &[ShowStateChange], new: Vec<ShowStateChange>, emitter: &mut E) {
+    for sc in new {
+        if !old.contains(&sc) {
+            emitter.emit(sc);
+        }
+    }
+}
+
 impl<T: EmitStateChange> EmitMasterUIStateChange for T {
     fn emit_master_ui_state_change(&mut self, sc: StateChange) {
         self.emit(ShowStateChange::MasterUI(sc))
@@ -281,20 +493,74 @@ impl<T: EmitStateChange> EmitMasterUIStateChange for T {
 
 pub enum ControlMessage {
     Set(StateChange),
-    AnimationCopy,
-    AnimationPaste,
+    AnimationCopyTo(AnimationBankSlot),
+    AnimationPasteFrom(AnimationBankSlot),
     BeamGridButtonPress(BeamStoreAddr),
+    /// Manually fire a step's payload right now, independent of the clock.
+    StepGridButtonPress(StepIdx),
 }
 
+#[derive(Clone, PartialEq)]
 pub enum StateChange {
     Channel(ChannelIdx),
     Animation(AnimationIdx),
     BeamButton((BeamStoreAddr, BeamButtonState)),
+    AnimationBankSlot((AnimationBankSlot, AnimationSlotState)),
     // Note that when provided as a control, this acts like a toggle.
     // One press sets the mode, a second press sets back to idle.
     BeamStoreState(BeamStoreState),
+    /// Whether the current channel has an armed preview beam or in-progress transition.
+    /// Output only.
+    Tally(bool),
+    /// Program a step of the step sequencer with a new payload, replacing whatever was there.
+    /// Also used for grid LED feedback.
+    Step((StepIdx, Option<StepPayload>)),
+    /// The step sequencer has advanced into a new step, carrying that step's payload, if any.
+    /// Output only.
+    StepAdvanced((StepIdx, Option<StepPayload>)),
+}
+
+/// Save animations in an indexed bank, so operators can stage several presets per channel and
+/// recall them mid-show instead of the single-depth copy/paste being clobbered on every copy.
+#[derive(Serialize, Deserialize)]
+pub struct AnimationBank {
+    slots: Vec<Option<Animation>>,
+}
+
+impl AnimationBank {
+    pub const N_SLOTS: usize = 8;
+
+    pub fn new() -> Self {
+        Self {
+            slots: vec![None; Self::N_SLOTS],
+        }
+    }
+
+    pub fn put(&mut self, slot: AnimationBankSlot, anim: Option<Animation>) {
+        self.slots[slot.0] = anim;
+    }
+
+    pub fn get(&self, slot: AnimationBankSlot) -> Option<Animation> {
+        self.slots[slot.0].clone()
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = (AnimationBankSlot, &Option<Animation>)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .map(|(i, anim)| (AnimationBankSlot(i), anim))
+    }
+}
+
+impl Default for AnimationBank {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct AnimationBankSlot(pub usize);
+
 #[derive(Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum BeamStoreState {
     Idle,
@@ -320,3 +586,18 @@ impl BeamButtonState {
         }
     }
 }
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum AnimationSlotState {
+    Empty,
+    Occupied,
+}
+
+impl AnimationSlotState {
+    pub fn from_animation(anim: &Option<Animation>) -> Self {
+        match anim {
+            Some(_) => Self::Occupied,
+            None => Self::Empty,
+        }
+    }
+}
@@ -0,0 +1,616 @@
+use anyhow::{anyhow, bail, Result};
+use log::{debug, error};
+use midir::{MidiIO, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection, SendError};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+use tunnels_lib::prompt::{prompt_bool, prompt_indexed_value};
+
+use crate::{
+    control::ControlEvent,
+    midi_controls::{Device, MidiDevice},
+};
+
+mod reconnect;
+use self::reconnect::ReconnectingMidiInput;
+
+/// Specification for what type of midi event.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum EventType {
+    NoteOn,
+    NoteOff,
+    ControlChange,
+    /// A high-resolution control change: `Mapping::control` names the coarse (MSB) controller
+    /// number `n`, and its conventional fine (LSB) partner is `n + 32` per the MIDI spec. Emitted
+    /// once both halves of a pair have arrived, carrying the combined 14-bit value in
+    /// `Event::value_hi_res`; the coarse half is still dispatched on its own as a plain
+    /// `ControlChange` too, for any mapping that only wants 7-bit resolution.
+    ControlChangeHighRes,
+    /// Continuous 14-bit pitch wheel position, centered at 0x2000. Has no controller number of
+    /// its own - `Mapping::control` is unused and left at 0 by convention (see `pitch_bend`).
+    PitchBend,
+    /// Selects a numbered program/patch. Carries a single 7-bit value and, like `PitchBend`, no
+    /// controller number of its own.
+    ProgramChange,
+    /// Continuous pressure applied to an already-held key/pad, for the whole channel rather than
+    /// any one note. Carries a single 7-bit value and no controller number of its own.
+    ChannelPressure,
+}
+
+/// A specification of a midi mapping.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Mapping {
+    pub event_type: EventType,
+    pub channel: u8,
+    pub control: u8,
+}
+
+impl fmt::Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}:{}",
+            match self.event_type {
+                EventType::NoteOn => "NoteOn ",
+                EventType::NoteOff => "NoteOff",
+                EventType::ControlChange => "CntChng",
+                EventType::ControlChangeHighRes => "CntChHR",
+                EventType::PitchBend => "PtchBnd",
+                EventType::ProgramChange => "PrgChng",
+                EventType::ChannelPressure => "ChnPres",
+            },
+            self.channel,
+            self.control
+        )
+    }
+}
+
+/// Helper constructor for a note on mapping.
+pub const fn note_on(channel: u8, control: u8) -> Mapping {
+    Mapping {
+        event_type: EventType::NoteOn,
+        channel,
+        control,
+    }
+}
+
+/// Helper constructor for a note off mapping.
+pub const fn note_off(channel: u8, control: u8) -> Mapping {
+    Mapping {
+        event_type: EventType::NoteOff,
+        channel,
+        control,
+    }
+}
+
+/// Helper constructor - most controls are on channel 0.
+pub const fn note_on_ch0(control: u8) -> Mapping {
+    note_on(0, control)
+}
+
+/// Helper constructor - other relevant special case is channel 1.
+pub const fn note_on_ch1(control: u8) -> Mapping {
+    note_on(1, control)
+}
+
+/// Helper constructor for a control change mapping.
+pub const fn cc(channel: u8, control: u8) -> Mapping {
+    Mapping {
+        event_type: EventType::ControlChange,
+        channel,
+        control,
+    }
+}
+
+/// Helper constructor - most controls are on channel 0.
+pub const fn cc_ch0(control: u8) -> Mapping {
+    cc(0, control)
+}
+
+/// Helper constructor for a high-resolution control change mapping. `control` is the coarse
+/// (MSB) controller number; its fine (LSB) partner at `control + 32` is implied.
+pub const fn cc_hi_res(channel: u8, control: u8) -> Mapping {
+    Mapping {
+        event_type: EventType::ControlChangeHighRes,
+        channel,
+        control,
+    }
+}
+
+/// Helper constructor - most controls are on channel 0.
+pub const fn cc_hi_res_ch0(control: u8) -> Mapping {
+    cc_hi_res(0, control)
+}
+
+/// Helper constructor for a pitch bend mapping. Pitch bend has no controller number of its own,
+/// so `control` is always 0.
+pub const fn pitch_bend(channel: u8) -> Mapping {
+    Mapping {
+        event_type: EventType::PitchBend,
+        channel,
+        control: 0,
+    }
+}
+
+/// Helper constructor for a program change mapping. Like `pitch_bend`, `control` is always 0.
+pub const fn program_change(channel: u8) -> Mapping {
+    Mapping {
+        event_type: EventType::ProgramChange,
+        channel,
+        control: 0,
+    }
+}
+
+/// Helper constructor for a channel pressure (aftertouch) mapping. Like `pitch_bend`, `control`
+/// is always 0.
+pub const fn channel_pressure(channel: u8) -> Mapping {
+    Mapping {
+        event_type: EventType::ChannelPressure,
+        channel,
+        control: 0,
+    }
+}
+
+/// Build a mapping for an event type with no controller number of its own (`PitchBend`,
+/// `ProgramChange`, `ChannelPressure`), matching `pitch_bend`/`program_change`/
+/// `channel_pressure`'s convention of leaving `control` at 0.
+fn channel_mapping(event_type: EventType, channel: u8) -> Mapping {
+    Mapping {
+        event_type,
+        channel,
+        control: 0,
+    }
+}
+
+/// A fully-specified midi event.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub mapping: Mapping,
+    /// 7-bit value, the only representation most event types have. For `PitchBend` and a
+    /// completed `ControlChangeHighRes` pair, this is just the coarse half of `value_hi_res`
+    /// (the top 7 bits), kept populated so a mapping that only wants 7-bit resolution doesn't
+    /// need to know hi-res events exist at all.
+    pub value: u8,
+    /// The full 14-bit value, set only for `PitchBend` and a completed `ControlChangeHighRes`
+    /// pair. A mapping that wants smooth fader sweeps reads this instead of `value`.
+    pub value_hi_res: Option<u16>,
+}
+
+/// Helper constructor for a midi event carrying only a 7-bit value.
+pub const fn event(mapping: Mapping, value: u8) -> Event {
+    Event {
+        mapping,
+        value,
+        value_hi_res: None,
+    }
+}
+
+/// Helper constructor for a midi event carrying a 14-bit value (`PitchBend` or a completed
+/// `ControlChangeHighRes` pair).
+pub const fn event_hi_res(mapping: Mapping, value: u16) -> Event {
+    Event {
+        mapping,
+        value: (value >> 7) as u8,
+        value_hi_res: Some(value),
+    }
+}
+
+/// A MIDI system real-time message. Unlike `Event`, these are single status
+/// bytes with no channel, controller, or value, and are used to slave a
+/// clock to an external 24 PPQN MIDI clock rather than to address a specific
+/// control.
+#[derive(Debug, Copy, Clone)]
+pub enum RealTimeMessage {
+    /// Status byte 0xF8, sent 24 times per quarter note while the clock is running.
+    Clock(Instant),
+    /// Status byte 0xFA: start the sequence from the beginning.
+    Start,
+    /// Status byte 0xFB: resume the sequence from where it was stopped.
+    Continue,
+    /// Status byte 0xFC: stop the sequence.
+    Stop,
+}
+
+// Return the available ports by name,
+pub fn list_ports() -> Result<(Vec<String>, Vec<String>)> {
+    let input = MidiInput::new("tunnels")?;
+    let inputs = input
+        .ports()
+        .iter()
+        .filter_map(|p| input.port_name(p).ok())
+        .collect::<Vec<String>>();
+    let output = MidiOutput::new("tunnels")?;
+    let outputs = output
+        .ports()
+        .iter()
+        .filter_map(|p| output.port_name(p).ok())
+        .collect::<Vec<String>>();
+    Ok((inputs, outputs))
+}
+
+fn get_named_port<T: MidiIO>(source: &T, name: &str) -> Result<T::Port> {
+    for port in source.ports() {
+        if let Ok(this_name) = source.port_name(&port) {
+            if this_name == name {
+                return Ok(port);
+            }
+        }
+    }
+    bail!("no port found with name {}", name);
+}
+
+pub struct Output {
+    name: String,
+    conn: MidiOutputConnection,
+}
+
+impl Output {
+    pub fn new(name: String) -> Result<Self> {
+        let output = MidiOutput::new(&name)?;
+        let port = get_named_port(&output, &name)?;
+        let conn = output
+            .connect(&port, &name)
+            .map_err(|err| anyhow!("failed to connect to midi output: {err}"))?;
+        Ok(Self { conn, name })
+    }
+
+    pub fn send(&mut self, event: Event) -> Result<(), SendError> {
+        let channel = event.mapping.channel;
+        match event.mapping.event_type {
+            EventType::ControlChange => {
+                self.conn
+                    .send(&[(11 << 4) + channel, event.mapping.control, event.value])
+            }
+            EventType::NoteOn => self
+                .conn
+                .send(&[(9 << 4) + channel, event.mapping.control, event.value]),
+            EventType::NoteOff => self
+                .conn
+                .send(&[(8 << 4) + channel, event.mapping.control, event.value]),
+            EventType::ProgramChange => self.conn.send(&[(12 << 4) + channel, event.value]),
+            EventType::ChannelPressure => self.conn.send(&[(13 << 4) + channel, event.value]),
+            EventType::PitchBend => {
+                let value = event.value_hi_res.unwrap_or((event.value as u16) << 7);
+                self.conn.send(&[
+                    (14 << 4) + channel,
+                    (value & 0x7F) as u8,
+                    ((value >> 7) & 0x7F) as u8,
+                ])
+            }
+            EventType::ControlChangeHighRes => {
+                let value = event.value_hi_res.unwrap_or((event.value as u16) << 7);
+                let msb = ((value >> 7) & 0x7F) as u8;
+                let lsb = (value & 0x7F) as u8;
+                self.conn
+                    .send(&[(11 << 4) + channel, event.mapping.control, msb])?;
+                self.conn
+                    .send(&[(11 << 4) + channel, event.mapping.control + 32, lsb])
+            }
+        }
+    }
+
+    pub fn send_raw(&mut self, msg: &[u8]) -> Result<(), SendError> {
+        self.conn.send(msg)
+    }
+}
+
+pub struct Input {
+    _conn: MidiInputConnection<()>,
+}
+
+pub trait CreateControlEvent<D> {
+    fn from_event(event: Event, device: D) -> Self;
+}
+
+impl CreateControlEvent<Device> for ControlEvent {
+    fn from_event(event: Event, device: Device) -> Self {
+        ControlEvent::Midi((device, event))
+    }
+}
+
+/// Implemented by the type used to carry MIDI real-time messages into the
+/// show's control event channel. Unlike `CreateControlEvent`, this isn't
+/// parameterized by device, since real-time messages carry no device-level
+/// addressing of their own.
+pub trait CreateRealTimeEvent {
+    fn from_real_time(msg: RealTimeMessage) -> Self;
+}
+
+impl CreateRealTimeEvent for ControlEvent {
+    fn from_real_time(msg: RealTimeMessage) -> Self {
+        ControlEvent::MidiRealTime(msg)
+    }
+}
+
+impl Input {
+    pub fn new<D, E>(name: String, device: D, sender: Sender<E>) -> Result<Self>
+    where
+        D: Send + 'static + Clone,
+        E: CreateControlEvent<D> + CreateRealTimeEvent + Send + 'static,
+    {
+        let input = MidiInput::new(&name)?;
+        let port = get_named_port(&input, &name)?;
+        let handler_name = name.clone();
+
+        // Holds the most recent coarse (MSB) value for each (channel, controller) pair that has
+        // a controller number in 0..32, awaiting its fine (LSB) partner at controller + 32 to
+        // complete a `ControlChangeHighRes` pair. See `EventType::ControlChangeHighRes`.
+        let mut pending_msb: HashMap<(u8, u8), u8> = HashMap::new();
+
+        let conn = input
+            .connect(
+                &port,
+                &name,
+                move |_, msg: &[u8], _| {
+                    // System real-time messages are a single status byte with no
+                    // channel/controller/value, used to slave a clock to this
+                    // device rather than to address a specific mapped control.
+                    if msg.len() == 1 {
+                        let real_time = match msg[0] {
+                            0xF8 => RealTimeMessage::Clock(Instant::now()),
+                            0xFA => RealTimeMessage::Start,
+                            0xFB => RealTimeMessage::Continue,
+                            0xFC => RealTimeMessage::Stop,
+                            other => {
+                                debug!(
+                                    "Ignoring midi input event on {handler_name} of unimplemented type {other}."
+                                );
+                                return;
+                            }
+                        };
+                        sender.send(E::from_real_time(real_time)).unwrap();
+                        return;
+                    }
+                    let channel = msg[0] & 15;
+                    let send_event = |event: Event| {
+                        sender
+                            .send(E::from_event(event, device.clone()))
+                            .unwrap();
+                    };
+                    match msg[0] >> 4 {
+                        // Most midi devices just send NoteOn with a velocity of 0 for NoteOff.
+                        8 | 9 if msg[2] == 0 => send_event(event(
+                            Mapping {
+                                event_type: EventType::NoteOff,
+                                channel,
+                                control: msg[1],
+                            },
+                            msg[2],
+                        )),
+                        9 => send_event(event(
+                            Mapping {
+                                event_type: EventType::NoteOn,
+                                channel,
+                                control: msg[1],
+                            },
+                            msg[2],
+                        )),
+                        11 => {
+                            let control = msg[1];
+                            let value = msg[2];
+                            send_event(event(
+                                Mapping {
+                                    event_type: EventType::ControlChange,
+                                    channel,
+                                    control,
+                                },
+                                value,
+                            ));
+                            if control < 32 {
+                                pending_msb.insert((channel, control), value);
+                            } else if let Some(msb) =
+                                pending_msb.remove(&(channel, control - 32))
+                            {
+                                let combined = ((msb as u16) << 7) | value as u16;
+                                send_event(event_hi_res(
+                                    Mapping {
+                                        event_type: EventType::ControlChangeHighRes,
+                                        channel,
+                                        control: control - 32,
+                                    },
+                                    combined,
+                                ));
+                            }
+                        }
+                        12 => send_event(event(
+                            channel_mapping(EventType::ProgramChange, channel),
+                            msg[1],
+                        )),
+                        13 => send_event(event(
+                            channel_mapping(EventType::ChannelPressure, channel),
+                            msg[1],
+                        )),
+                        14 => {
+                            let combined = ((msg[2] as u16) << 7) | msg[1] as u16;
+                            send_event(event_hi_res(
+                                channel_mapping(EventType::PitchBend, channel),
+                                combined,
+                            ));
+                        }
+                        other => {
+                            debug!(
+                                "Ignoring midi input event on {handler_name} of unimplemented type {other}."
+                            );
+                        }
+                    }
+                },
+                (),
+            )
+            .map_err(|err| anyhow!("failed to connect to midi input: {err}"))?;
+        Ok(Input { _conn: conn })
+    }
+}
+
+/// Maintain midi inputs and outputs.
+/// Provide synchronous dispatch for outgoing messages based on device type.
+pub struct Manager<D: MidiDevice> {
+    inputs: Vec<ReconnectingMidiInput>,
+    /// Shared with each input's reconnect thread, which locks the matching output to re-run
+    /// device initialization once a dropped port comes back.
+    outputs: Vec<(D, Arc<Mutex<Output>>)>,
+    /// Set by a reconnected input, and taken by the show's update loop to push a full resync of
+    /// show state back out to every control surface, covering whatever that surface missed while
+    /// it was disconnected.
+    resync_needed: Arc<AtomicBool>,
+    /// If set, every event passed to `send` is also forwarded here before being sent, so the
+    /// control recorder can capture outbound UI-feedback traffic without this generic manager
+    /// needing to know anything about it.
+    capture: Option<Sender<(D, Event)>>,
+}
+
+impl<D: MidiDevice> Default for Manager<D> {
+    fn default() -> Self {
+        Self {
+            inputs: Default::default(),
+            outputs: Default::default(),
+            resync_needed: Arc::new(AtomicBool::new(false)),
+            capture: None,
+        }
+    }
+}
+
+impl<D: MidiDevice + 'static> Manager<D> {
+    /// Add a device to the manager given input and output port names.
+    pub fn add_device(
+        &mut self,
+        spec: DeviceSpec<D>,
+        send: Sender<impl CreateControlEvent<D> + CreateRealTimeEvent + Send + 'static>,
+    ) -> Result<()> {
+        let mut output = Output::new(spec.output_port_name)?;
+
+        // Send initialization commands to the device.
+        spec.device.init_midi(&mut output)?;
+
+        let output = Arc::new(Mutex::new(output));
+        let reinit_device = spec.device.clone();
+        let reinit_output = output.clone();
+        let resync_needed = self.resync_needed.clone();
+
+        let input = ReconnectingMidiInput::new(
+            spec.input_port_name,
+            spec.device.clone(),
+            send,
+            move || {
+                if let Err(e) = reinit_device.init_midi(&mut reinit_output.lock().unwrap()) {
+                    error!(
+                        "Failed to re-initialize midi device {} after reconnect: {}.",
+                        reinit_device.device_name(),
+                        e
+                    );
+                }
+                resync_needed.store(true, Ordering::Relaxed);
+            },
+        );
+
+        self.inputs.push(input);
+        self.outputs.push((spec.device, output));
+        Ok(())
+    }
+
+    /// Forward a copy of every future outbound event, prior to sending, to `capture`.
+    /// Used to wire the control recorder/inspector up to this manager's send path without this
+    /// generic manager needing to depend on it directly.
+    pub fn capture_sent_events(&mut self, capture: Sender<(D, Event)>) {
+        self.capture = Some(capture);
+    }
+
+    /// Send a message to the specified device type.
+    /// Error conditions are logged rather than returned.
+    pub fn send(&mut self, device: &D, event: Event) {
+        if let Some(capture) = &self.capture {
+            if capture.send((device.clone(), event)).is_err() {
+                // The recorder has been dropped; stop bothering to capture.
+                self.capture = None;
+            }
+        }
+        for (d, output) in &mut self.outputs {
+            if d == device {
+                let mut output = output.lock().unwrap();
+                if let Err(e) = output.send(event) {
+                    error!("Failed to send midi event to {}: {}.", output.name, e);
+                }
+            }
+        }
+    }
+
+    /// Return an iterator over all outputs.
+    pub fn outputs(&mut self) -> impl Iterator<Item = &mut (D, Arc<Mutex<Output>>)> {
+        self.outputs.iter_mut()
+    }
+
+    /// Take (clearing it) whether a reconnected input requires a full resync of show state back
+    /// out to every control surface.
+    pub fn take_resync_needed(&mut self) -> bool {
+        self.resync_needed.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Wrapper struct for the data needed to describe a device to connect to.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceSpec<D> {
+    pub device: D,
+    pub input_port_name: String,
+    pub output_port_name: String,
+}
+
+/// Prompt the user to configure midi devices.
+pub fn prompt_midi<D: MidiDevice>(
+    input_ports: &[String],
+    output_ports: &[String],
+    known_device_types: Vec<D>,
+) -> Result<Vec<DeviceSpec<D>>> {
+    let mut devices = Vec::new();
+    println!("Available devices:");
+    for (i, port) in input_ports.iter().enumerate() {
+        println!("{i}: {port}");
+    }
+    for (i, port) in output_ports.iter().enumerate() {
+        println!("{i}: {port}");
+    }
+    println!();
+
+    let mut add_device = |device: D| -> Result<()> {
+        if prompt_bool(&format!("Use {}?", device.device_name()))? {
+            devices.push(prompt_input_output(device, input_ports, output_ports)?);
+        }
+        Ok(())
+    };
+
+    for d in known_device_types {
+        add_device(d)?;
+    }
+
+    Ok(devices)
+}
+
+/// Prompt the user to select input and output ports for a device.
+fn prompt_input_output<D: MidiDevice>(
+    device: D,
+    input_ports: &[String],
+    output_ports: &[String],
+) -> Result<DeviceSpec<D>> {
+    let name = device.device_name().to_string();
+    if input_ports.contains(&name) && output_ports.contains(&name) {
+        return Ok(DeviceSpec {
+            device,
+            input_port_name: name.to_string(),
+            output_port_name: name.to_string(),
+        });
+    }
+    println!("Didn't find a device of the expected name. Please manually select input and output.");
+    let input_port_name = prompt_indexed_value("Input port:", input_ports)?;
+    let output_port_name = prompt_indexed_value("Output port:", output_ports)?;
+    Ok(DeviceSpec {
+        device,
+        input_port_name,
+        output_port_name,
+    })
+}
@@ -0,0 +1,178 @@
+//! Automatically reconnect a named MIDI input port if it disappears, mirroring
+//! `audio::reconnect::ReconnectingInput`'s `Cmd::{Stop, Disconnected}` retry loop.
+//!
+//! Unlike cpal, midir gives us no disconnect callback on an open connection, so a dedicated
+//! watcher thread polls `list_ports` for the named port's continued presence and reports its
+//! disappearance the same way cpal's error callback does for audio.
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use super::{list_ports, CreateControlEvent, CreateRealTimeEvent, Input};
+
+/// How often the watcher thread polls whether the named input port is still present.
+const PORT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Try to reopen a vanished midi input this often.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+type StopReconnect = Box<dyn FnOnce()>;
+
+pub struct ReconnectingMidiInput {
+    stop: Option<StopReconnect>,
+}
+
+impl ReconnectingMidiInput {
+    /// Open `name` as a self-reconnecting midi input for `device`, forwarding events to `sender`.
+    /// Port disconnection is handled asynchronously and reconnection is retried until this
+    /// struct is dropped. `on_connect` is called after every successful reconnect (but not the
+    /// initial connect, which the caller handles synchronously), so it can re-run device
+    /// initialization and request a full UI state resync once a dropped surface comes back.
+    pub fn new<D, E>(
+        name: String,
+        device: D,
+        sender: Sender<E>,
+        on_connect: impl Fn() + Send + 'static,
+    ) -> Self
+    where
+        D: Send + 'static + Clone,
+        E: CreateControlEvent<D> + CreateRealTimeEvent + Send + 'static,
+    {
+        Self {
+            stop: Some(reconnect(name, device, sender, on_connect)),
+        }
+    }
+}
+
+impl Drop for ReconnectingMidiInput {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            stop()
+        }
+    }
+}
+
+/// Spawn a thread to handle port disconnection.
+/// Return a closure that can be called to terminate the input, blocking until it completes.
+fn reconnect<D, E>(
+    name: String,
+    device: D,
+    sender: Sender<E>,
+    on_connect: impl Fn() + Send + 'static,
+) -> StopReconnect
+where
+    D: Send + 'static + Clone,
+    E: CreateControlEvent<D> + CreateRealTimeEvent + Send + 'static,
+{
+    enum Cmd {
+        Stop,
+        Disconnected,
+    }
+    use Cmd::*;
+
+    let (send, recv) = channel::<Cmd>();
+    // Load an initial command into the queue to open the port.
+    send.send(Cmd::Disconnected).unwrap();
+    let disconnected_sender = send.clone();
+
+    let reconnect_thread = thread::spawn(move || {
+        let mut _input = None;
+        let mut _watcher = None;
+        // The first successful open is an ordinary connect, not a reconnect: the caller already
+        // ran device initialization synchronously before handing this off. `on_connect` only
+        // fires for connects that follow a disconnection.
+        let mut first_connect = true;
+        for event in recv {
+            match event {
+                Stop => {
+                    info!("Midi reconnect thread for {name} is stopping.");
+                    return;
+                }
+                Disconnected => {
+                    // Drop the existing connection and its watcher before trying to reopen.
+                    _input = None;
+                    _watcher = None;
+
+                    match Input::new(name.clone(), device.clone(), sender.clone()) {
+                        Ok(input) => {
+                            info!("Successfully opened midi input {name}.");
+                            if first_connect {
+                                first_connect = false;
+                            } else {
+                                on_connect();
+                            }
+                            let watch_sender = disconnected_sender.clone();
+                            _watcher = Some(PortWatcher::start(name.clone(), move || {
+                                watch_sender.send(Disconnected).ok();
+                            }));
+                            _input = Some(input);
+                        }
+                        Err(e) => {
+                            warn!("Unable to open midi input {name}: {e}.");
+                            let sender = disconnected_sender.clone();
+                            // Spawn a thread to wake us up and try again after a delay.
+                            thread::spawn(move || {
+                                thread::sleep(RECONNECT_INTERVAL);
+                                sender.send(Disconnected).ok();
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    Box::new(move || {
+        send.send(Stop)
+            .expect("Sending stop to midi reconnect thread failed");
+        reconnect_thread
+            .join()
+            .expect("Joining midi reconnect thread failed");
+    })
+}
+
+/// Polls for the continued presence of a named input port, calling `on_disconnect` once and
+/// exiting as soon as it vanishes (or a port query fails outright).
+struct PortWatcher {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl PortWatcher {
+    fn start(name: String, on_disconnect: impl FnOnce() + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread = thread::spawn(move || {
+            let mut on_disconnect = Some(on_disconnect);
+            while !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(PORT_POLL_INTERVAL);
+                if thread_stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let still_present = matches!(list_ports(), Ok((inputs, _)) if inputs.contains(&name));
+                if !still_present {
+                    if let Some(on_disconnect) = on_disconnect.take() {
+                        on_disconnect();
+                    }
+                    return;
+                }
+            }
+        });
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for PortWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("Joining midi port watcher thread failed");
+        }
+    }
+}
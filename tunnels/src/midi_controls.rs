@@ -2,23 +2,30 @@ mod animation;
 mod animation_target;
 mod audio;
 mod clock;
+mod config;
 mod device;
 mod master_ui;
 mod mixer;
+mod profile;
 mod tunnel;
 
 use log::debug;
-use std::{collections::HashMap, sync::mpsc::Sender};
+use std::{collections::HashMap, path::PathBuf, sync::mpsc::Sender, time::Instant};
 
 use crate::{
     control::ControlEvent,
+    control_recorder::Recorder,
     master_ui::EmitStateChange,
     midi::{DeviceSpec, Event, Manager, Mapping},
+    mixer::ChannelStateChange,
     show::ControlMessage,
     show::StateChange,
 };
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 
+pub use self::config::ControlMappingConfig;
+
+use tunnels_lib::color::Rgb;
 use tunnels_lib::number::{BipolarFloat, UnipolarFloat};
 
 use self::animation::{map_animation_controls, update_animation_control};
@@ -68,9 +75,36 @@ impl ControlMap {
     }
 
     pub fn add(&mut self, device: Device, mapping: Mapping, creator: ControlMessageCreator) {
+        if let Err(e) = self.try_add(device, mapping, creator) {
+            panic!("{e}");
+        }
+    }
+
+    /// Like `add`, but return an error on a duplicate binding instead of panicking. Used when
+    /// merging a user-supplied `ControlMappingConfig` over the built-in defaults, so a bad
+    /// config is reported rather than crashing the show.
+    pub fn try_add(
+        &mut self,
+        device: Device,
+        mapping: Mapping,
+        creator: ControlMessageCreator,
+    ) -> Result<()> {
         if self.0.insert((device, mapping), creator).is_some() {
-            panic!("duplicate control definition: {:?} {:?}", device, mapping);
+            bail!("duplicate control definition: {:?} {:?}", device, mapping);
         }
+        Ok(())
+    }
+
+    /// Merge a user-supplied config's bindings over the built-in defaults, returning an error
+    /// (rather than panicking) if an entry collides with a default or with another entry in the
+    /// same config.
+    pub fn apply_config(&mut self, config: &ControlMappingConfig) -> Result<()> {
+        for entry in config.mappings.clone() {
+            let (device, mapping, creator) = entry.into_parts();
+            self.try_add(device, mapping, creator)
+                .with_context(|| format!("in custom control mapping for {device}"))?;
+        }
+        Ok(())
     }
 
     /// Map a midi source device and event into a tunnels control message.
@@ -111,32 +145,86 @@ impl ControlMap {
 pub struct Dispatcher {
     midi_map: ControlMap,
     midi_manager: Manager<Device>,
+    /// Connected Stream Decks, which speak USB HID rather than midir and so are kept separate
+    /// from `midi_manager`. See `crate::streamdeck`.
+    streamdecks: Vec<crate::streamdeck::Output>,
+    recorder: Recorder,
 }
 
 impl Dispatcher {
     /// Instantiate the master midi control dispatcher.
     /// Create the midi control map and initialize midi inputs/outputs.
-    pub fn new(midi_devices: Vec<DeviceSpec<Device>>, send: Sender<ControlEvent>) -> Result<Self> {
-        let midi_map = ControlMap::new();
+    /// `reference` is the instant the control recorder timestamps captured events against.
+    /// If `control_mapping_config_path` is set, the custom bindings it names are loaded and
+    /// merged over the built-in defaults.
+    pub fn new(
+        midi_devices: Vec<DeviceSpec<Device>>,
+        send: Sender<ControlEvent>,
+        reference: Instant,
+        control_mapping_config_path: Option<PathBuf>,
+    ) -> Result<Self> {
+        let mut midi_map = ControlMap::new();
+        if let Some(path) = &control_mapping_config_path {
+            let mapping_config = ControlMappingConfig::load(path).with_context(|| {
+                format!("loading custom control mapping config {}", path.display())
+            })?;
+            midi_map.apply_config(&mapping_config)?;
+        }
 
         let mut midi_manager = Manager::default();
+        let (recorder, capture_send) = Recorder::new(reference);
+        midi_manager.capture_sent_events(capture_send);
+        let mut streamdecks = Vec::new();
         for device_spec in midi_devices.into_iter() {
-            midi_manager.add_device(device_spec, send.clone())?;
+            if device_spec.device == Device::StreamDeck {
+                // The Stream Deck connects over USB HID, not a named midir port. Repurpose
+                // `input_port_name` to carry its serial number (empty to match the first deck
+                // found) and ignore `output_port_name`, since feedback goes back out over the
+                // same HID handle used for input.
+                let serial = (!device_spec.input_port_name.is_empty())
+                    .then_some(device_spec.input_port_name.as_str());
+                streamdecks.push(
+                    crate::streamdeck::connect(serial, send.clone())
+                        .context("connecting to Stream Deck")?,
+                );
+            } else {
+                midi_manager.add_device(device_spec, send.clone())?;
+            }
         }
 
         Ok(Self {
             midi_map,
             midi_manager,
+            streamdecks,
+            recorder,
         })
     }
 
-    /// Map the provided midi event to a show control message.
+    /// Take (clearing it) whether a reconnected midi input requires a full resync of show state
+    /// back out to every control surface, to cover whatever it missed while disconnected.
+    pub fn take_resync_needed(&mut self) -> bool {
+        self.midi_manager.take_resync_needed()
+    }
+
+    /// Access the control recorder, to save/publish what it has captured.
+    pub fn recorder(&self) -> &Recorder {
+        &self.recorder
+    }
+
+    /// Access the control recorder mutably, to drain captured outbound events or enable the
+    /// live inspector feed.
+    pub fn recorder_mut(&mut self) -> &mut Recorder {
+        &mut self.recorder
+    }
+
+    /// Map the provided midi event to a show control message, recording it as inbound traffic.
     /// Return None if the event does not map to a known control.
     pub fn map_event_to_show_control(
-        &self,
+        &mut self,
         device: Device,
         event: Event,
     ) -> Option<ControlMessage> {
+        self.recorder.record_inbound(device, event);
         match self.midi_map.dispatch(device, event) {
             Some(cm) => Some(cm),
             None => {
@@ -159,13 +247,22 @@ impl EmitStateChange for Dispatcher {
             StateChange::AnimationTarget(sc) => {
                 update_animation_target_control(sc, &mut self.midi_manager)
             }
-            StateChange::Mixer(sc) => update_mixer_control(sc, &mut self.midi_manager),
+            StateChange::Mixer(sc) => {
+                update_streamdeck_mixer_control(&sc, &mut self.streamdecks);
+                update_mixer_control(sc, &mut self.midi_manager)
+            }
             StateChange::Clock(sc) => update_clock_control(sc, &mut self.midi_manager),
             StateChange::ColorPalette(_) => {
                 // TODO: emit color data to interfaces if we build a color palette monitor
             }
+            StateChange::Position(_) => {
+                // TODO: no hardware surface reflects position state back yet.
+            }
             StateChange::MasterUI(sc) => update_master_ui_control(sc, &mut self.midi_manager),
             StateChange::Audio(sc) => update_audio_control(sc, &mut self.midi_manager),
+            StateChange::Keyboard(_) => {
+                // TODO: no hardware surface reflects tap tempo or mirror state back yet.
+            }
         }
     }
 }
@@ -228,6 +325,7 @@ impl RadioButtons {
             send(Event {
                 mapping: *mapping,
                 value,
+                value_hi_res: None,
             });
         }
     }
@@ -238,11 +336,41 @@ impl RadioButtons {
             send(Event {
                 mapping: *mapping,
                 value: self.off,
+                value_hi_res: None,
             });
         }
     }
 }
 
+/// Mirror a mixer channel's bump state onto a Stream Deck key, one key per channel on the
+/// deck's first page. This is only a representative slice of show state rendered to the deck -
+/// tunnel hue, mixer level, and paged banks beyond the first aren't wired up yet - the same
+/// incremental scope `config::Action` and `profile::Profile` have each taken on so far.
+fn update_streamdeck_mixer_control(
+    sc: &crate::mixer::StateChange,
+    streamdecks: &mut [crate::streamdeck::Output],
+) {
+    let ChannelStateChange::Bump(lit) = &sc.change else {
+        return;
+    };
+    let Ok(key) = u8::try_from(sc.channel.0) else {
+        return;
+    };
+    if key >= crate::streamdeck::KEY_COUNT {
+        return;
+    }
+    let color = if *lit {
+        Rgb::from_u8(255, 255, 255)
+    } else {
+        Rgb::from_u8(0, 0, 0)
+    };
+    for deck in streamdecks.iter_mut() {
+        if deck.page() == 0 {
+            deck.render_key(key, color);
+        }
+    }
+}
+
 #[test]
 fn test_quadratic_knob_roundtrip() {
     fn roundtrip(v: f64) {
@@ -157,5 +157,18 @@ pub fn update_animation_control(sc: StateChange, manager: &mut Manager) {
         }
         UseAudioSize(v) => send(event(USE_AUDIO_SIZE, v as u8)),
         UseAudioSpeed(v) => send(event(USE_AUDIO_SPEED, v as u8)),
+        // TODO: no control surface mapping yet for the ADSR envelope.
+        EnvelopeEnable(_) => (),
+        Attack(_) => (),
+        Decay(_) => (),
+        Sustain(_) => (),
+        Release(_) => (),
+        // TODO: no control surface mapping yet for the custom wavetable.
+        Wavetable(_) => (),
+        // TODO: no control surface mapping yet for the LFSR noise width toggle.
+        NoiseWidthNarrow(_) => (),
+        // TODO: no control surface mapping yet for FM cross-modulation.
+        Modulator(_) => (),
+        ModDepth(_) => (),
     }
 }
@@ -1,10 +1,12 @@
 use crate::{
     animation_target::AnimationTarget as AnimationTargetState,
-    midi::{note_on_ch0, Manager, Mapping},
+    midi::{event, note_on_ch0, Manager, Mapping},
     midi_controls::Device,
+    show::ControlMessage,
     show::ControlMessage::AnimationTarget as Animation,
 };
 use lazy_static::lazy_static;
+use std::time::Duration;
 
 use super::{ControlMap, RadioButtons};
 
@@ -20,6 +22,17 @@ const TARGET_MARQUEE: Mapping = note_on_ch0(43);
 const TARGET_POSITIONX: Mapping = note_on_ch0(44);
 const TARGET_POSITIONY: Mapping = note_on_ch0(45);
 
+// Spare buttons next to the animation target block, for program/preview staging.
+const CUT: Mapping = note_on_ch0(46);
+const AUTO: Mapping = note_on_ch0(47);
+
+/// Duration of a timed crossfade triggered by the AUTO button.
+const AUTO_TRANSITION_DURATION: Duration = Duration::from_secs(2);
+
+// Tally LED states, matching the APC40/TouchOSC momentary button convention used elsewhere.
+const TALLY_LIVE: u8 = 0;
+const TALLY_PREVIEWING: u8 = 1;
+
 lazy_static! {
     static ref TARGET_SELECT_BUTTONS: RadioButtons = RadioButtons {
         mappings: vec!(
@@ -88,6 +101,13 @@ pub fn map_animation_target_controls(device: Device, map: &mut ControlMap) {
         TARGET_POSITIONY,
         Box::new(|_| Animation(AnimationTargetState::PositionY)),
     );
+
+    // Program/preview staging: instant cut, or a timed auto-transition.
+    add(CUT, Box::new(|_| ControlMessage::Cut));
+    add(
+        AUTO,
+        Box::new(|_| ControlMessage::Auto(AUTO_TRANSITION_DURATION)),
+    );
 }
 
 /// Emit midi messages to update UIs given the provided state change.
@@ -114,3 +134,16 @@ pub fn update_animation_target_control(sc: AnimationTargetState, manager: &mut M
         send,
     );
 }
+
+/// Emit tally feedback on the CUT/AUTO buttons for whether the current channel is live
+/// (program only) or previewing (an armed preview beam is staged or mid-crossfade).
+pub fn update_tally(previewing: bool, manager: &mut Manager<Device>) {
+    let send = |event| manager.send(Device::TouchOsc, event);
+    let value = if previewing {
+        TALLY_PREVIEWING
+    } else {
+        TALLY_LIVE
+    };
+    send(event(CUT, value));
+    send(event(AUTO, value));
+}
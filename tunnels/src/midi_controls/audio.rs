@@ -20,6 +20,9 @@ const ENVELOPE_RELEASE: Mapping = cc(1, 3);
 const GAIN: Mapping = cc(1, 4);
 const RESET: Mapping = note_on_ch1(5);
 const IS_CLIPPING: Mapping = note_on_ch1(6);
+const ENVELOPE_DECAY: Mapping = cc(1, 7);
+const SUSTAIN_LEVEL: Mapping = cc(1, 8);
+const ENVELOPE_MODE_TOGGLE: Mapping = note_on_ch1(9);
 
 // Midi mappings for CMD MM-1.
 const CMD_MM1_VU_METER: Mapping = cc(4, 81);
@@ -44,6 +47,18 @@ pub(crate) fn map_touch_osc_audio_controls(map: &mut ControlMap) {
         ENVELOPE_RELEASE,
         Box::new(|v| Audio(Set(EnvelopeRelease(envelope_edge_from_midi(v))))),
     );
+    add(
+        ENVELOPE_DECAY,
+        Box::new(|v| Audio(Set(EnvelopeDecay(envelope_edge_from_midi(v))))),
+    );
+    add(
+        SUSTAIN_LEVEL,
+        Box::new(|v| Audio(Set(SustainLevel(unipolar_from_midi(v))))),
+    );
+    add(
+        ENVELOPE_MODE_TOGGLE,
+        Box::new(|_| Audio(ToggleEnvelopeMode)),
+    );
     add(RESET, Box::new(|_| Audio(ResetParameters)));
     add(GAIN, Box::new(|v| Audio(Set(Gain(gain_from_midi(v))))));
 }
@@ -82,46 +97,93 @@ pub(crate) fn update_audio_control(sc: StateChange, manager: &mut Manager<Device
         FilterCutoff(v) => send(event(FILTER_CUTOFF, filter_to_midi(v))),
         EnvelopeAttack(v) => send(event(ENVELOPE_ATTACK, envelope_edge_to_midi(v))),
         EnvelopeRelease(v) => send(event(ENVELOPE_RELEASE, envelope_edge_to_midi(v))),
+        EnvelopeDecay(v) => send(event(ENVELOPE_DECAY, envelope_edge_to_midi(v))),
+        SustainLevel(v) => send(event(SUSTAIN_LEVEL, unipolar_to_midi(v))),
+        EnvelopeMode(v) => send(event(
+            ENVELOPE_MODE_TOGGLE,
+            (v == crate::audio::EnvelopeMode::Gated) as u8,
+        )),
         Gain(v) => send(event(GAIN, gain_to_midi(v))),
         IsClipping(v) => send(event(IS_CLIPPING, v as u8)),
+        // TODO: no physical meter for the true-peak reading yet.
+        TruePeak(_) => (),
+        // TODO: no physical indicator for beat onsets/tempo yet.
+        Beat => (),
+        BeatTempo(_) => (),
+        // TODO: no physical meter for the per-band envelopes yet.
+        BandEnvelope(_, _) => (),
+        // TODO: no physical meter for the detected tempo yet.
+        DetectedTempo(_) => (),
+        // TODO: no physical control surface for per-source mixer gain/add/remove yet.
+        SourceGain(_, _) => (),
+        SourceAdded(_) => (),
+        SourceRemoved(_) => (),
+        // TODO: no physical indicator for device connection state yet.
+        DeviceConnected(_, _) => (),
     }
 }
 
-/// Get midi value plus 1, in milliseconds.
+// Envelope edge times (attack/decay/release) - exponential, so short attacks and long releases
+// both get usable resolution out of the same knob.
+
+const ENVELOPE_EDGE_MIN_SECS: f64 = 0.0001;
+const ENVELOPE_EDGE_MAX_SECS: f64 = 5.;
+
 pub fn envelope_edge_from_midi(v: u8) -> Duration {
-    Duration::from_millis(v as u64 + 1)
+    Duration::from_secs_f64(exponential_from_midi(
+        v,
+        ENVELOPE_EDGE_MIN_SECS,
+        ENVELOPE_EDGE_MAX_SECS,
+    ))
 }
 
-/// Clamp duration in integer milliseconds to midi range.
 pub fn envelope_edge_to_midi(d: Duration) -> u8 {
-    let clamped = d.as_millis().clamp(1, 128);
-    (clamped - 1) as u8
+    exponential_to_midi(d.as_secs_f64(), ENVELOPE_EDGE_MIN_SECS, ENVELOPE_EDGE_MAX_SECS)
 }
 
-// Crude filter control - linear, roughly 1kHz range, "0" is 40 Hz.
-// FIXME: make this logarithmic
+// Filter control - exponential across the audible band, so the knob is perceptually even.
 
-const FILTER_LOWER_BOUND: f64 = 40.;
-const FILTER_SCALE: f64 = 1000.;
+const FILTER_MIN_HZ: f64 = 20.;
+const FILTER_MAX_HZ: f64 = 20_000.;
 
 pub fn filter_from_midi(v: u8) -> f32 {
-    (unipolar_from_midi(v).val() * FILTER_SCALE + FILTER_LOWER_BOUND) as f32
+    exponential_from_midi(v, FILTER_MIN_HZ, FILTER_MAX_HZ) as f32
 }
 
 pub fn filter_to_midi(f: f32) -> u8 {
-    unipolar_to_midi(UnipolarFloat::new(
-        ((f as f64) - FILTER_LOWER_BOUND) / FILTER_SCALE,
-    ))
+    exponential_to_midi(f as f64, FILTER_MIN_HZ, FILTER_MAX_HZ)
+}
+
+/// Map a midi value onto an exponential (perceptually-even) range `min..max`:
+/// `min * (max/min)^(v/127)`.
+fn exponential_from_midi(v: u8, min: f64, max: f64) -> f64 {
+    min * (max / min).powf(unipolar_from_midi(v).val())
+}
+
+/// Inverse of `exponential_from_midi`: `127 * ln(value/min) / ln(max/min)`, clamped to 0..127.
+fn exponential_to_midi(value: f64, min: f64, max: f64) -> u8 {
+    let portion = (value.max(min) / min).ln() / (max / min).ln();
+    unipolar_to_midi(UnipolarFloat::new(portion))
 }
 
 // Set gain as a unipolar knob, scaled by 20, interpreted as dB.
 
 pub fn gain_from_midi(v: u8) -> f64 {
     let gain_db = 20. * unipolar_from_midi(v).val();
-    (10_f64).powf(gain_db / 20.)
+    db_to_gain(gain_db)
 }
 
 pub fn gain_to_midi(g: f64) -> u8 {
-    let gain_db = 20. * g.log10();
+    let gain_db = gain_to_db(g);
     unipolar_to_midi(UnipolarFloat::new(gain_db / 20.))
 }
+
+/// Convert a gain expressed in dB to a linear gain factor.
+pub fn db_to_gain(db: f64) -> f64 {
+    10_f64.powf(db / 20.)
+}
+
+/// Convert a linear gain factor to dB.
+pub fn gain_to_db(gain: f64) -> f64 {
+    20. * gain.log10()
+}
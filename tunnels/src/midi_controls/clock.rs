@@ -7,7 +7,8 @@ use crate::{
     clock_bank::ControlMessage,
     clock_bank::StateChange,
     clock_bank::N_CLOCKS,
-    midi::{cc, event, note_on, Manager, Mapping},
+    midi::{event, Manager, Mapping},
+    midi_controls::profile::{MappingKind, MappingTemplate, Profile},
     midi_controls::Device,
     midi_controls::{bipolar_to_midi, unipolar_to_midi},
     show::ControlMessage::Clock,
@@ -25,71 +26,98 @@ enum Control {
     Retrigger,
     AudioSize,
     AudioSpeed,
+    AudioTempoFollow,
 }
 
-/// Return a control mapping for the CMD-MM1.
-fn mapping_cmd_mm1(control: Control, channel: usize) -> Option<Mapping> {
+/// The CMD MM-1 only has 4 physical channel rows.
+const CMD_MM1_MAX_CHANNELS: usize = 4;
+
+/// Built-in profile for the Behringer CMD MM-1.
+fn cmd_mm1_profile() -> Profile<Control> {
     use Control::*;
+    use MappingKind::{Cc, NoteOn};
+    use MappingTemplate as T;
 
-    let channel = channel as u8;
     let midi_channel = 4;
 
-    match control {
-        Rate => Some(cc(midi_channel, 6 + channel)),
-        RateFine => Some(cc(midi_channel, 18 + channel)),
-        Level => Some(cc(midi_channel, 48 + channel)),
-        Tap => Some(note_on(midi_channel, 48 + channel)),
-        OneShot => Some(note_on(midi_channel, 19 + channel * 4)),
-        Retrigger => Some(note_on(midi_channel, 20 + channel * 4)),
-        AudioSize | AudioSpeed => None, // FIXME: not enough physical buttons
-    }
+    Profile::new(
+        vec![
+            (Rate, Cc(T::by_number(midi_channel, 6, 1))),
+            (RateFine, Cc(T::by_number(midi_channel, 18, 1))),
+            (Level, Cc(T::by_number(midi_channel, 48, 1))),
+            (Tap, NoteOn(T::by_number(midi_channel, 48, 1))),
+            (OneShot, NoteOn(T::by_number(midi_channel, 19, 4))),
+            (Retrigger, NoteOn(T::by_number(midi_channel, 20, 4))),
+            // AudioSize/AudioSpeed/AudioTempoFollow: not enough physical buttons.
+        ],
+        N_CLOCKS,
+        CMD_MM1_MAX_CHANNELS,
+    )
 }
 
-/// Return a control mapping for TouchOSC.
-fn mapping_touchosc(control: Control, channel: usize) -> Option<Mapping> {
+/// Built-in profile for TouchOSC.
+fn touchosc_profile() -> Profile<Control> {
     use Control::*;
+    use MappingKind::{Cc, NoteOn};
+    use MappingTemplate as T;
+
+    // lay out controls with same values, increment midi channel per clock channel
+    // start at a high midi channel where we have no existing mappings
+    let base_midi_channel = 9;
+
+    Profile::new(
+        vec![
+            (Rate, Cc(T::by_midi_channel(base_midi_channel, 0, 1))),
+            // RateFine: no fine rate control on TouchOSC yet.
+            (Level, Cc(T::by_midi_channel(base_midi_channel, 1, 1))),
+            (Tap, NoteOn(T::by_midi_channel(base_midi_channel, 0, 1))),
+            (OneShot, NoteOn(T::by_midi_channel(base_midi_channel, 1, 1))),
+            (
+                Retrigger,
+                NoteOn(T::by_midi_channel(base_midi_channel, 2, 1)),
+            ),
+            (
+                AudioSize,
+                NoteOn(T::by_midi_channel(base_midi_channel, 3, 1)),
+            ),
+            (
+                AudioSpeed,
+                NoteOn(T::by_midi_channel(base_midi_channel, 4, 1)),
+            ),
+            (
+                AudioTempoFollow,
+                NoteOn(T::by_midi_channel(base_midi_channel, 5, 1)),
+            ),
+        ],
+        N_CLOCKS,
+        usize::MAX,
+    )
+}
 
-    // lay out controls with same values, increment channels
-    // start at a high channel where we have no existing mappings
-    let channel = 9 + channel as u8;
-
-    Some(match control {
-        Rate => cc(channel, 0),
-        RateFine => {
-            return None;
-        } // TODO: fine rate control on TouchOSC
-        Level => cc(channel, 1),
-        Tap => note_on(channel, 0),
-        OneShot => note_on(channel, 1),
-        Retrigger => note_on(channel, 2),
-        AudioSize => note_on(channel, 3),
-        AudioSpeed => note_on(channel, 4),
-    })
+/// Return the built-in profile for a device, if it has one for clocks.
+fn profile_for(device: Device) -> Profile<Control> {
+    match device {
+        Device::BehringerCmdMM1 => cmd_mm1_profile(),
+        Device::TouchOsc => touchosc_profile(),
+        _ => panic!("No clock control mappings for {device}."),
+    }
 }
 
 pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
     use ClockControlMessage::*;
     use ClockStateChange::*;
 
+    let profile = profile_for(device);
+
     let mut add = |mapping: Option<Mapping>, creator| {
         if let Some(mapping) = mapping {
             map.add(device, mapping, creator)
         }
     };
 
-    let get_mapping = match device {
-        Device::BehringerCmdMM1 => mapping_cmd_mm1,
-        Device::TouchOsc => mapping_touchosc,
-        _ => panic!("No clock control mappings for {device}."),
-    };
-
-    // This is to catch a future change to N_CLOCKS.
-    #[allow(clippy::assertions_on_constants)]
-    (assert!(N_CLOCKS <= 4, "The CMD MM-1 only has 4 channel rows."));
-
     for channel in 0..N_CLOCKS {
         add(
-            get_mapping(Control::Rate, channel),
+            profile.mapping(Control::Rate, channel),
             Box::new(move |v| {
                 Clock(ControlMessage {
                     channel: ClockIdxExt(channel),
@@ -98,7 +126,7 @@ pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
             }),
         );
         add(
-            get_mapping(Control::RateFine, channel),
+            profile.mapping(Control::RateFine, channel),
             Box::new(move |v| {
                 Clock(ControlMessage {
                     channel: ClockIdxExt(channel),
@@ -107,7 +135,7 @@ pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
             }),
         );
         add(
-            get_mapping(Control::Level, channel),
+            profile.mapping(Control::Level, channel),
             Box::new(move |v| {
                 Clock(ControlMessage {
                     channel: ClockIdxExt(channel),
@@ -116,7 +144,7 @@ pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
             }),
         );
         add(
-            get_mapping(Control::Tap, channel),
+            profile.mapping(Control::Tap, channel),
             Box::new(move |_| {
                 Clock(ControlMessage {
                     channel: ClockIdxExt(channel),
@@ -125,7 +153,7 @@ pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
             }),
         );
         add(
-            get_mapping(Control::OneShot, channel),
+            profile.mapping(Control::OneShot, channel),
             Box::new(move |_| {
                 Clock(ControlMessage {
                     channel: ClockIdxExt(channel),
@@ -134,7 +162,7 @@ pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
             }),
         );
         add(
-            get_mapping(Control::Retrigger, channel),
+            profile.mapping(Control::Retrigger, channel),
             Box::new(move |_| {
                 Clock(ControlMessage {
                     channel: ClockIdxExt(channel),
@@ -143,7 +171,7 @@ pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
             }),
         );
         add(
-            get_mapping(Control::AudioSize, channel),
+            profile.mapping(Control::AudioSize, channel),
             Box::new(move |_| {
                 Clock(ControlMessage {
                     channel: ClockIdxExt(channel),
@@ -152,7 +180,7 @@ pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
             }),
         );
         add(
-            get_mapping(Control::AudioSpeed, channel),
+            profile.mapping(Control::AudioSpeed, channel),
             Box::new(move |_| {
                 Clock(ControlMessage {
                     channel: ClockIdxExt(channel),
@@ -160,6 +188,15 @@ pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
                 })
             }),
         );
+        add(
+            profile.mapping(Control::AudioTempoFollow, channel),
+            Box::new(move |_| {
+                Clock(ControlMessage {
+                    channel: ClockIdxExt(channel),
+                    msg: ToggleAudioTempoFollow,
+                })
+            }),
+        );
     }
 }
 
@@ -167,12 +204,16 @@ pub fn map_clock_controls(device: Device, map: &mut ControlMap) {
 pub fn update_clock_control(sc: StateChange, manager: &mut Manager<Device>) {
     use ClockStateChange::*;
 
+    let profiles = [
+        (Device::BehringerCmdMM1, cmd_mm1_profile()),
+        (Device::TouchOsc, touchosc_profile()),
+    ];
+
     let mut send = |control, value| {
-        if let Some(mapping) = mapping_cmd_mm1(control, sc.channel.into()) {
-            manager.send(&Device::BehringerCmdMM1, event(mapping, value));
-        }
-        if let Some(mapping) = mapping_touchosc(control, sc.channel.into()) {
-            manager.send(&Device::TouchOsc, event(mapping, value));
+        for (device, profile) in &profiles {
+            if let Some(mapping) = profile.mapping(control, sc.channel.into()) {
+                manager.send(device, event(mapping, value));
+            }
         }
     };
 
@@ -184,5 +225,14 @@ pub fn update_clock_control(sc: StateChange, manager: &mut Manager<Device>) {
         SubmasterLevel(v) => send(Control::Level, unipolar_to_midi(v)),
         UseAudioSize(v) => send(Control::AudioSize, v as u8),
         UseAudioSpeed(v) => send(Control::AudioSpeed, v as u8),
+        AudioTempoFollow(v) => send(Control::AudioTempoFollow, v as u8),
+        // TODO: no physical indicator for external MIDI clock sync yet.
+        MidiSyncEnabled(_) => (),
+        // TODO: no physical indicator or control for the submaster curve yet.
+        SubmasterCurve(_) => (),
+        // TODO: no physical indicator or control for the audio envelope source yet.
+        AudioEnvelopeSourceChange(_) => (),
+        // TODO: no physical indicator for MIDI-derived tempo/beat yet.
+        MidiBeat { .. } => (),
     }
 }
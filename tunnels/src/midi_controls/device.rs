@@ -3,14 +3,33 @@ use std::fmt;
 use crate::midi::{Event, EventType, Mapping, Output};
 use log::debug;
 use midir::SendError;
+use serde::{Deserialize, Serialize};
 
 /// The input MIDI device types that tunnels can work with.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Device {
     AkaiApc40,
     AkaiApc20,
     TouchOsc,
     BehringerCmdMM1,
+    /// An Elgato Stream Deck. Unlike the other variants this isn't a midir device at all - it
+    /// connects over USB HID via `crate::streamdeck` - but it shares this enum so it can be
+    /// addressed through the same `ControlMap`/`DeviceSpec` plumbing as every other control
+    /// surface. See `crate::streamdeck` for how its events and feedback are actually handled.
+    StreamDeck,
+}
+
+impl Device {
+    /// Every known device type, for prompting the user over the full set of options.
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::TouchOsc,
+            Self::AkaiApc40,
+            Self::BehringerCmdMM1,
+            Self::AkaiApc20,
+            Self::StreamDeck,
+        ]
+    }
 }
 
 impl fmt::Display for Device {
@@ -23,6 +42,7 @@ impl fmt::Display for Device {
                 Self::AkaiApc20 => "Akai APC20",
                 Self::TouchOsc => "Touch OSC",
                 Self::BehringerCmdMM1 => "Behringer CMD MM-1",
+                Self::StreamDeck => "Elgato Stream Deck",
             }
         )
     }
@@ -47,6 +67,9 @@ impl MidiDevice for Device {
             Self::AkaiApc20 => init_apc_20(out),
             Self::TouchOsc => Ok(()),
             Self::BehringerCmdMM1 => Ok(()),
+            // The Stream Deck is never actually handed to `Manager`/`Output` - it's connected
+            // via `crate::streamdeck::connect` instead - so this is never called in practice.
+            Self::StreamDeck => Ok(()),
         }
     }
 
@@ -57,6 +80,7 @@ impl MidiDevice for Device {
             Self::AkaiApc20 => "Akai APC20",
             Self::TouchOsc => "TouchOSC Bridge",
             Self::BehringerCmdMM1 => "CMD MM-1",
+            Self::StreamDeck => "Elgato Stream Deck",
         }
     }
 }
@@ -82,6 +106,7 @@ fn init_apc_40(out: &mut Output<impl MidiDevice>) -> Result<(), SendError> {
                 control,
             },
             value,
+            value_hi_res: None,
         });
     };
 
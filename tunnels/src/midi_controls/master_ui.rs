@@ -1,13 +1,17 @@
-use super::{mixer::PAGE_SIZE, ControlMap, RadioButtons};
+use super::{animation_target::update_tally, mixer::PAGE_SIZE, ControlMap, RadioButtons};
 use crate::{
     beam_store::{BeamStore, BeamStoreAddr},
     master_ui::ControlMessage,
     master_ui::StateChange,
-    master_ui::{BeamButtonState, BeamStoreState as BeamStoreStatePayload},
+    master_ui::{
+        AnimationBankSlot, AnimationSlotState, BeamButtonState,
+        BeamStoreState as BeamStoreStatePayload, N_STEPS,
+    },
     midi::{event, note_on, note_on_ch0, Manager, Mapping},
     midi_controls::Device,
     mixer::ChannelIdx,
     show::ControlMessage::MasterUI,
+    step_sequencer::StepIdx,
     tunnel::{AnimationIdx, N_ANIM},
 };
 use lazy_static::lazy_static;
@@ -23,6 +27,8 @@ const BEAM_DELETE: Mapping = note_on_ch0(0x54);
 const LOOK_EDIT: Mapping = note_on_ch0(0x56);
 
 const BEAM_GRID_ROW_0: u8 = 0x35;
+// Directly below the beam grid rows (0x35..=0x39).
+const STEP_GRID_ROW: u8 = 0x3A;
 
 // APC40 main button grid LED states
 const LED_OFF: u8 = 0;
@@ -78,8 +84,16 @@ pub fn map_master_ui_controls(device: Device, page: usize, map: &mut ControlMap)
             Box::new(move |_| MasterUI(Set(Channel(ChannelIdx(cid + channel_offset))))),
         );
     }
-    add(ANIM_COPY, Box::new(|_| MasterUI(AnimationCopy)));
-    add(ANIM_PASTE, Box::new(|_| MasterUI(AnimationPaste)));
+    // Only the first bank slot is currently wired to hardware; the rest of the bank is reachable
+    // via the MasterUI control message for any control surface with more buttons to spare.
+    add(
+        ANIM_COPY,
+        Box::new(|_| MasterUI(AnimationCopyTo(AnimationBankSlot(0)))),
+    );
+    add(
+        ANIM_PASTE,
+        Box::new(|_| MasterUI(AnimationPasteFrom(AnimationBankSlot(0)))),
+    );
     add(
         BEAM_SAVE,
         Box::new(|_| MasterUI(Set(BeamStoreState(BeamStoreStatePayload::BeamSave)))),
@@ -111,6 +125,15 @@ pub fn map_master_ui_controls(device: Device, page: usize, map: &mut ControlMap)
             )
         }
     }
+
+    // Only one page's worth of steps is wired to hardware. Unlike the channel select and beam
+    // grid buttons above, the step row isn't offset by page: every page addresses the same steps.
+    for step in 0..N_STEPS.min(BeamStore::COLS_PER_PAGE) {
+        add(
+            note_on(step as u8, STEP_GRID_ROW),
+            Box::new(move |_| MasterUI(StepGridButtonPress(StepIdx(step)))),
+        )
+    }
 }
 
 /// Emit midi messages to update UIs given the provided state change.
@@ -165,6 +188,20 @@ pub fn update_master_ui_control(sc: StateChange, manager: &mut Manager<Device>)
                 manager.send(Device::AkaiApc20, e);
             }
         }
+        AnimationBankSlot((slot, state)) => {
+            // Only the first bank slot has hardware feedback today; see the comment on the
+            // ANIM_COPY/ANIM_PASTE mappings above.
+            if slot == AnimationBankSlot(0) {
+                let e = event(
+                    ANIM_COPY,
+                    match state {
+                        AnimationSlotState::Empty => LED_OFF,
+                        AnimationSlotState::Occupied => LED_SOLID_GREEN,
+                    },
+                );
+                send_main(e);
+            }
+        }
         BeamStoreState(state) => {
             let send_all = |event| {
                 manager.send(Device::TouchOsc, event);
@@ -180,5 +217,17 @@ pub fn update_master_ui_control(sc: StateChange, manager: &mut Manager<Device>)
                 LookEdit => BEAM_STORE_STATE_BUTTONS.select(LOOK_EDIT, send_all),
             }
         }
+        Tally(previewing) => update_tally(previewing, manager),
+        Step((index, payload)) => {
+            if index.0 < BeamStore::COLS_PER_PAGE {
+                let e = event(
+                    note_on(index.0 as u8, STEP_GRID_ROW),
+                    if payload.is_some() { LED_SOLID_GREEN } else { LED_OFF },
+                );
+                send_main(e);
+            }
+        }
+        // No dedicated "currently playing step" LED yet; reuse the programmed-step LED state.
+        StepAdvanced(_) => (),
     }
 }
@@ -1,7 +1,6 @@
 use crate::{
-    clock::ClockIdx,
-    device::Device,
-    midi::{cc, event, note_off, note_on, Manager, Mapping},
+    midi::{event, EventType, Manager, Mapping},
+    midi_controls::Device,
     mixer::ControlMessage,
     mixer::StateChange,
     mixer::{
@@ -11,28 +10,59 @@ use crate::{
     show::ControlMessage as ShowControlMessage,
 };
 
-use super::{
-    bipolar_from_midi, bipolar_to_midi, unipolar_from_midi, unipolar_to_midi, ControlMap,
-    RadioButtons,
-};
-
-const FADER: u8 = 0x7;
-const BUMP: u8 = 0x32;
-const MASK: u8 = 0x31;
-const LOOK: u8 = 0x30;
-
-/// The midi note value for the 0th video channel selector.
-const VIDEO_CHAN_0: u8 = 66;
+use super::profile::{MappingKind, MappingTemplate, Profile};
+use super::{unipolar_from_midi, unipolar_to_midi, ControlMap};
 
 /// The number of mixer channels on a single mixer page.
 const PAGE_SIZE: usize = 8;
 
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Control {
+    Fader,
+    Bump,
+    Mask,
+    /// The outgoing-only "contains look" indicator.
+    Look,
+    VideoChannel(usize),
+}
+
+/// The built-in profile shared by the APC40/APC20/TouchOSC mixer layouts: every channel row maps
+/// to the same controller/note numbers on its own MIDI channel.
+fn mixer_profile() -> Profile<Control> {
+    use Control::*;
+    use MappingKind::NoteOn;
+    use MappingTemplate as T;
+
+    const FADER: u8 = 0x7;
+    const BUMP: u8 = 0x32;
+    const MASK: u8 = 0x31;
+    const LOOK: u8 = 0x30;
+    /// The midi note value for the 0th video channel selector.
+    const VIDEO_CHAN_0: u8 = 66;
+
+    let mut entries = vec![
+        (Fader, MappingKind::Cc(T::by_midi_channel(0, FADER, 1))),
+        (Bump, NoteOn(T::by_midi_channel(0, BUMP, 1))),
+        (Mask, NoteOn(T::by_midi_channel(0, MASK, 1))),
+        (Look, NoteOn(T::by_midi_channel(0, LOOK, 1))),
+    ];
+    for vc in 0..Mixer::N_VIDEO_CHANNELS {
+        entries.push((
+            VideoChannel(vc),
+            NoteOn(T::by_midi_channel(0, VIDEO_CHAN_0 + vc as u8, 1)),
+        ));
+    }
+    Profile::new(entries, PAGE_SIZE, PAGE_SIZE)
+}
+
 pub fn map_mixer_controls(device: Device, page: usize, map: &mut ControlMap) {
     use ChannelControlMessage::*;
     use ChannelStateChange::*;
 
     let mut add = |mapping, creator| map.add(device, mapping, creator);
 
+    let profile = mixer_profile();
+
     // Offset the mixer channels to correspond to this page.
     let channel_offset = page * PAGE_SIZE;
 
@@ -43,62 +73,71 @@ pub fn map_mixer_controls(device: Device, page: usize, map: &mut ControlMap) {
                 ccm,
             )))
         };
-        add(
-            cc(chan as u8, FADER),
-            Box::new(move |v| mkmsg(Set(Level(unipolar_from_midi(v))))),
-        );
-        add(
-            note_on(chan as u8, BUMP),
-            Box::new(move |_| mkmsg(Set(Bump(true)))),
-        );
-        add(
-            note_off(chan as u8, BUMP),
-            Box::new(move |_| mkmsg(Set(Bump(false)))),
-        );
-        add(
-            note_on(chan as u8, MASK),
-            Box::new(move |_| mkmsg(ToggleMask)),
-        );
+        if let Some(mapping) = profile.mapping(Control::Fader, chan) {
+            add(
+                mapping,
+                Box::new(move |v| mkmsg(Set(Level(unipolar_from_midi(v))))),
+            );
+        }
+        if let Some(mapping) = profile.mapping(Control::Bump, chan) {
+            add(mapping, Box::new(move |_| mkmsg(Set(Bump(true)))));
+            add(
+                Mapping {
+                    event_type: EventType::NoteOff,
+                    ..mapping
+                },
+                Box::new(move |_| mkmsg(Set(Bump(false)))),
+            );
+        }
+        if let Some(mapping) = profile.mapping(Control::Mask, chan) {
+            add(mapping, Box::new(move |_| mkmsg(ToggleMask)));
+        }
 
         // Configure the video channel selectors.
         for vc in 0..Mixer::N_VIDEO_CHANNELS {
-            add(
-                note_on(chan as u8, vc as u8 + VIDEO_CHAN_0),
-                Box::new(move |_| mkmsg(ToggleVideoChannel(VideoChannelIdx(vc)))),
-            );
+            if let Some(mapping) = profile.mapping(Control::VideoChannel(vc), chan) {
+                add(
+                    mapping,
+                    Box::new(move |_| mkmsg(ToggleVideoChannel(VideoChannelIdx(vc)))),
+                );
+            }
         }
     }
 }
 
 /// Emit midi messages to update UIs given the provided state change.
-pub fn update_mixer_control(sc: StateChange, manager: &mut Manager) {
+pub fn update_mixer_control(sc: StateChange, manager: &mut Manager<Device>) {
     use ChannelStateChange::*;
 
+    let profile = mixer_profile();
     let page = sc.channel.0 / PAGE_SIZE;
     let channel_offset = page * PAGE_SIZE;
-    let midi_channel = (sc.channel.0 - channel_offset) as u8;
+    let chan = sc.channel.0 - channel_offset;
 
     let mut send = |event| {
         // Send page 0 to the APC40, page 1 to APC20
         manager.send(
-            if page == 0 {
+            &if page == 0 {
                 Device::AkaiApc40
             } else {
                 Device::AkaiApc20
             },
             event,
         );
-        manager.send(Device::TouchOsc, event);
+        manager.send(&Device::TouchOsc, event);
+    };
+
+    let mapping_for = |control| {
+        profile
+            .mapping(control, chan)
+            .unwrap_or_else(|| panic!("no mixer mapping for channel {chan}"))
     };
 
     match sc.change {
-        Level(v) => send(event(cc(midi_channel, FADER), unipolar_to_midi(v))),
-        Bump(v) => send(event(note_on(midi_channel, BUMP), v as u8)),
-        Mask(v) => send(event(note_on(midi_channel, MASK), v as u8)),
-        ContainsLook(v) => send(event(note_on(midi_channel, LOOK), v as u8)),
-        VideoChannel((vc, v)) => send(event(
-            note_on(midi_channel, vc.0 as u8 + VIDEO_CHAN_0),
-            v as u8,
-        )),
+        Level(v) => send(event(mapping_for(Control::Fader), unipolar_to_midi(v))),
+        Bump(v) => send(event(mapping_for(Control::Bump), v as u8)),
+        Mask(v) => send(event(mapping_for(Control::Mask), v as u8)),
+        ContainsLook(v) => send(event(mapping_for(Control::Look), v as u8)),
+        VideoChannel((vc, v)) => send(event(mapping_for(Control::VideoChannel(vc.0)), v as u8)),
     }
 }
@@ -0,0 +1,124 @@
+//! A declarative, data-driven mapping from abstract controls to concrete MIDI mappings.
+//!
+//! Historically each control module had one `match device` function per device
+//! (`mapping_cmd_mm1`, `mapping_touchosc`, etc.) plus a scattering of hardcoded note/CC constants,
+//! so supporting a new controller meant editing and recompiling this crate. A [`Profile`] is a
+//! small table from an abstract control (an enum local to the owning module, e.g. `Control::Rate`)
+//! to a [`MappingTemplate`] describing how its concrete MIDI mapping varies across repeated
+//! channel rows. The CMD-MM1/TouchOSC/APC layouts are still built directly into this crate as
+//! `Profile`-returning functions, but nothing about `Profile` itself is tied to Rust source, so a
+//! profile for an arbitrary grid controller could equally be assembled from a config file loaded
+//! at startup.
+//!
+//! Only [`super::clock`] and [`super::mixer`] have been converted to this scheme so far; the
+//! other control modules still use the older per-device `match` pattern.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::midi::{cc, note_off, note_on, Mapping};
+
+/// How a control's concrete mapping varies across repeated channel rows: either the MIDI channel
+/// or the controller/note number increments with the row, with the other held fixed.
+#[derive(Debug, Clone, Copy)]
+pub struct MappingTemplate {
+    midi_channel: u8,
+    number: u8,
+    midi_channel_stride: u8,
+    number_stride: u8,
+}
+
+impl MappingTemplate {
+    /// A mapping whose channel rows increment the controller/note number, on a fixed MIDI
+    /// channel. This is how the CMD-MM1 lays out its channel strips.
+    pub const fn by_number(midi_channel: u8, base_number: u8, stride: u8) -> Self {
+        Self {
+            midi_channel,
+            number: base_number,
+            midi_channel_stride: 0,
+            number_stride: stride,
+        }
+    }
+
+    /// A mapping whose channel rows increment the MIDI channel, on a fixed controller/note
+    /// number. This is how TouchOSC and the mixer's per-channel controls are laid out.
+    pub const fn by_midi_channel(base_midi_channel: u8, number: u8, stride: u8) -> Self {
+        Self {
+            midi_channel: base_midi_channel,
+            number,
+            midi_channel_stride: stride,
+            number_stride: 0,
+        }
+    }
+
+    fn resolve(&self, channel: usize) -> (u8, u8) {
+        let channel = channel as u8;
+        (
+            self.midi_channel + self.midi_channel_stride * channel,
+            self.number + self.number_stride * channel,
+        )
+    }
+}
+
+/// The kind of MIDI event a [`MappingTemplate`] resolves to.
+#[derive(Debug, Clone, Copy)]
+pub enum MappingKind {
+    Cc(MappingTemplate),
+    NoteOn(MappingTemplate),
+    NoteOff(MappingTemplate),
+}
+
+impl MappingKind {
+    fn mapping(&self, channel: usize) -> Mapping {
+        match self {
+            Self::Cc(t) => {
+                let (midi_channel, number) = t.resolve(channel);
+                cc(midi_channel, number)
+            }
+            Self::NoteOn(t) => {
+                let (midi_channel, number) = t.resolve(channel);
+                note_on(midi_channel, number)
+            }
+            Self::NoteOff(t) => {
+                let (midi_channel, number) = t.resolve(channel);
+                note_off(midi_channel, number)
+            }
+        }
+    }
+}
+
+/// A loaded collection of control mappings for one device, covering some number of repeated
+/// channel rows (clock channels, mixer channels, etc).
+pub struct Profile<C> {
+    entries: HashMap<C, MappingKind>,
+    channel_count: usize,
+}
+
+impl<C: Eq + Hash + Copy> Profile<C> {
+    /// Build a profile from its control entries.
+    ///
+    /// `channel_count` is how many channel rows this profile should support; `max_channels` is
+    /// the most this device/layout can physically provide. Panics if `channel_count` exceeds
+    /// `max_channels` - this is the load-time equivalent of the old per-module "row count" asserts
+    /// (e.g. the CMD-MM1 only having 4 channel strips).
+    pub fn new(entries: Vec<(C, MappingKind)>, channel_count: usize, max_channels: usize) -> Self {
+        assert!(
+            channel_count <= max_channels,
+            "profile requests {channel_count} channel rows but this device supports at most {max_channels}",
+        );
+        Self {
+            entries: entries.into_iter().collect(),
+            channel_count,
+        }
+    }
+
+    /// Return the concrete mapping for a control on the given channel row, if this profile maps
+    /// it. Returns `None` if the control is unmapped on this device, or if the channel is out of
+    /// range for this profile.
+    pub fn mapping(&self, control: C, channel: usize) -> Option<Mapping> {
+        if channel >= self.channel_count {
+            return None;
+        }
+        self.entries.get(&control).map(|kind| kind.mapping(channel))
+    }
+}
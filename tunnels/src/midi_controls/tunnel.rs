@@ -165,5 +165,16 @@ pub fn update_tunnel_control(sc: StateChange, manager: &mut Manager<Device>) {
         // Clamp outgoing tunnel position messages to regular midi range.
         PositionX(v) => send(event(POSITION_X, bipolar_to_midi(BipolarFloat::new(v)))),
         PositionY(v) => send(event(POSITION_Y, bipolar_to_midi(BipolarFloat::new(v)))),
+        // TODO: no control surface mapping yet for clock-locked rotation/marquee.
+        RotationClock(_) => (),
+        RotationClockMultiplier(_) => (),
+        MarqueeClock(_) => (),
+        MarqueeClockMultiplier(_) => (),
+        // TODO: no control surface mapping yet for the lightning flash effect.
+        Lightning(_) => (),
+        // TODO: no control surface mapping yet for mirror/symmetry modes.
+        MirrorX(_) => (),
+        MirrorY(_) => (),
+        Symmetry(_) => (),
     };
 }
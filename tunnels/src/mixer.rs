@@ -1,10 +1,15 @@
+use crate::audio::{AudioEnvelopeSource, AudioEnvelopes};
 use crate::midi_controls::MIXER_CHANNELS_PER_PAGE;
 use crate::palette::ColorPalette;
+use crate::waveforms::{self, WaveformArgs};
 use crate::{beam::Beam, look::Look, tunnel::Tunnel};
-use crate::{clock_bank::ClockBank, master_ui::EmitStateChange as EmitShowStateChange};
+use crate::{
+    clock_bank::{ClockBank, ClockIdx, ClockStore},
+    master_ui::EmitStateChange as EmitShowStateChange,
+};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashSet, sync::Arc, time::Duration};
-use tunnels_lib::number::UnipolarFloat;
+use tunnels_lib::number::{Phase, UnipolarFloat};
 use tunnels_lib::{ArcSegment, LayerCollection};
 use typed_index_derive::TypedIndex;
 
@@ -12,6 +17,15 @@ use typed_index_derive::TypedIndex;
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Mixer {
     channels: Vec<Channel>,
+    /// If Some, a show-wide LFO that modulates every tunnel's output level in sync, for
+    /// breathing/pulsing effects across the whole mixer.
+    master_lfo: Option<MasterLfoConfig>,
+    /// A look armed off-air, staged for the whole mixer in advance of going live.
+    /// Brought to program either instantly via `cut_look` or over time via `auto_look`.
+    preview_look: Option<Look>,
+    /// An in-progress timed crossfade from the mixer's program channels to the armed preview
+    /// look.
+    look_transition: Option<Transition>,
 }
 
 impl Mixer {
@@ -23,9 +37,39 @@ impl Mixer {
             channels: (0..n_channels)
                 .map(|_| Channel::new(Beam::Tunnel(Tunnel::default())))
                 .collect(),
+            master_lfo: None,
+            preview_look: None,
+            look_transition: None,
         }
     }
 
+    /// Arm or disarm the show-wide master LFO.
+    pub fn set_master_lfo(&mut self, config: Option<MasterLfoConfig>) {
+        self.master_lfo = config;
+    }
+
+    /// Sample the master LFO for this frame, returning a level multiplier in [0, 1].
+    /// Returns 1.0 (no effect) when no LFO is armed.
+    fn sample_master_lfo(&self, external_clocks: &ClockBank) -> UnipolarFloat {
+        let Some(config) = &self.master_lfo else {
+            return UnipolarFloat::ONE;
+        };
+        let args = WaveformArgs {
+            phase_spatial: Phase::ZERO,
+            phase_temporal: external_clocks.phase(config.clock),
+            smoothing: UnipolarFloat::ZERO,
+            duty_cycle: UnipolarFloat::ONE,
+            pulse: true,
+            standing: false,
+        };
+        let sample = match config.shape {
+            MasterLfoShape::Sine => waveforms::sine(&args),
+            MasterLfoShape::Triangle => waveforms::triangle(&args),
+            MasterLfoShape::Sawtooth => waveforms::sawtooth(&args),
+        };
+        UnipolarFloat::new(1.0 - config.depth.val() * (1.0 - sample))
+    }
+
     /// Clone the contents of this mixer as a Look.
     pub fn as_look(&self) -> Look {
         Look::from_channels(self.channels.clone())
@@ -38,9 +82,24 @@ impl Mixer {
     }
 
     /// Update the state of all of the beams contained in this mixer.
-    pub fn update_state(&mut self, delta_t: Duration, audio_envelope: UnipolarFloat) {
+    pub fn update_state<E: EmitShowStateChange>(
+        &mut self,
+        delta_t: Duration,
+        audio_envelopes: &AudioEnvelopes,
+        external_clocks: &ClockBank,
+        emitter: &mut E,
+    ) {
         for channel in &mut self.channels {
-            channel.update_state(delta_t, audio_envelope);
+            channel.update_state(delta_t, audio_envelopes, external_clocks, emitter);
+        }
+        if let Some(look) = &mut self.preview_look {
+            look.update_state(delta_t, audio_envelopes, external_clocks, emitter);
+        }
+        if let Some(transition) = &mut self.look_transition {
+            transition.elapsed += delta_t;
+            if transition.is_complete() {
+                self.cut_look(emitter);
+            }
         }
     }
 
@@ -48,6 +107,61 @@ impl Mixer {
         &mut self.channels[channel].beam
     }
 
+    /// Arm a beam in the preview slot of a channel, to be brought to program via `cut` or `auto`.
+    pub fn arm_preview(&mut self, channel: ChannelIdx, beam: Beam) {
+        self.channels[channel].arm_preview(beam);
+    }
+
+    /// Instantly swap the armed preview beam into program for a channel, discarding any
+    /// in-progress transition. Has no effect if nothing is armed in preview.
+    pub fn cut(&mut self, channel: ChannelIdx) {
+        self.channels[channel].cut();
+    }
+
+    /// Begin a timed crossfade from program to the armed preview beam for a channel.
+    /// Has no effect if nothing is armed in preview.
+    pub fn auto(&mut self, channel: ChannelIdx, duration: Duration) {
+        self.channels[channel].auto(duration);
+    }
+
+    /// Whether a channel currently has a preview armed or a transition in progress.
+    pub fn is_previewing(&self, channel: ChannelIdx) -> bool {
+        self.channels[channel].is_previewing()
+    }
+
+    /// Arm a look in the mixer-wide preview bus, to be brought to program via `cut_look` or
+    /// `auto_look`. Leaves the current per-channel preview/program state untouched until then.
+    pub fn arm_look_preview(&mut self, look: Look) {
+        self.preview_look = Some(look);
+        self.look_transition = None;
+    }
+
+    /// Instantly swap the mixer-wide preview bus's armed look into program, discarding any
+    /// in-progress transition. Has no effect if nothing is armed in the preview bus.
+    pub fn cut_look<E: EmitStateChange>(&mut self, emitter: &mut E) {
+        if let Some(look) = self.preview_look.take() {
+            self.set_look(look, emitter);
+        }
+        self.look_transition = None;
+    }
+
+    /// Begin a timed crossfade from the mixer's current program channels to the mixer-wide
+    /// preview bus's armed look. Has no effect if nothing is armed in the preview bus.
+    pub fn auto_look(&mut self, duration: Duration) {
+        if self.preview_look.is_some() {
+            self.look_transition = Some(Transition {
+                elapsed: Duration::ZERO,
+                duration,
+            });
+        }
+    }
+
+    /// Whether the mixer-wide preview bus currently has a look armed or a transition in
+    /// progress.
+    pub fn is_previewing_look(&self) -> bool {
+        self.preview_look.is_some()
+    }
+
     pub fn channels(&mut self) -> impl Iterator<Item = &mut Channel> {
         self.channels.iter_mut()
     }
@@ -56,25 +170,68 @@ impl Mixer {
         self.channels.len()
     }
 
-    /// Render the current state of the mixer.
+    /// Render the current state of the mixer. While a mixer-wide look transition is in progress,
+    /// the program channels and the preview bus's armed look are both rendered and blended by
+    /// scaling their level with the transition's progress, producing a crossfade of the whole
+    /// board.
     /// Each inner vector represents one virtual video channel.
     pub fn render(
         &self,
         external_clocks: &ClockBank,
         color_palette: &ColorPalette,
-        audio_envelope: UnipolarFloat,
+        audio_envelopes: &AudioEnvelopes,
     ) -> Vec<LayerCollection> {
         let mut video_outs = Vec::with_capacity(Self::N_VIDEO_CHANNELS);
         for _ in 0..Self::N_VIDEO_CHANNELS {
             video_outs.push(Vec::new());
         }
-        for channel in &self.channels {
+        // Sample the master LFO once per frame so every channel pulses in sync.
+        let master_lfo_scale = self.sample_master_lfo(external_clocks);
+        let preview_scale = match &self.look_transition {
+            Some(transition) => transition.progress(),
+            None => UnipolarFloat::ZERO,
+        };
+        let program_scale = UnipolarFloat::ONE - preview_scale;
+        Self::render_channels(
+            &self.channels,
+            master_lfo_scale * program_scale,
+            external_clocks,
+            color_palette,
+            audio_envelopes,
+            &mut video_outs,
+        );
+        if preview_scale > UnipolarFloat::ZERO {
+            if let Some(look) = &self.preview_look {
+                Self::render_channels(
+                    &look.channels,
+                    master_lfo_scale * preview_scale,
+                    external_clocks,
+                    color_palette,
+                    audio_envelopes,
+                    &mut video_outs,
+                );
+            }
+        }
+        video_outs
+    }
+
+    /// Render a collection of channels at the given level scale, routing each channel's rendered
+    /// output into `video_outs` by its own video channel assignment.
+    fn render_channels(
+        channels: &[Channel],
+        level_scale: UnipolarFloat,
+        external_clocks: &ClockBank,
+        color_palette: &ColorPalette,
+        audio_envelopes: &AudioEnvelopes,
+        video_outs: &mut [LayerCollection],
+    ) {
+        for channel in channels {
             let rendered_beam = channel.render(
-                UnipolarFloat::ONE,
+                level_scale,
                 false,
                 external_clocks,
                 color_palette,
-                audio_envelope,
+                audio_envelopes,
             );
             if rendered_beam.is_empty() {
                 continue;
@@ -84,7 +241,6 @@ impl Mixer {
                 video_outs[video_chan.0].push(rendered_ptr.clone());
             }
         }
-        video_outs
     }
 
     /// Emit the current value of all controllable mixer state.
@@ -103,6 +259,9 @@ impl Mixer {
                 channel.beam,
                 Beam::Look(_)
             )));
+            emit(ChannelStateChange::AudioEnvelopeSource(
+                channel.audio_envelope_source,
+            ));
             for video_chan in 0..Self::N_VIDEO_CHANNELS {
                 let vc = VideoChannel(video_chan);
                 emit(ChannelStateChange::VideoChannel((
@@ -154,6 +313,7 @@ impl Mixer {
             Level(v) => self.channels[sc.channel].level = v,
             Bump(v) => self.channels[sc.channel].bump = v,
             Mask(v) => self.channels[sc.channel].mask = v,
+            AudioEnvelopeSource(v) => self.channels[sc.channel].audio_envelope_source = v,
             VideoChannel((vc, active)) => {
                 if active {
                     self.channels[sc.channel].video_outs.insert(vc);
@@ -173,10 +333,18 @@ impl Mixer {
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Channel {
     pub beam: Beam,
+    /// A beam armed off-air, composed/selected in advance of going live.
+    /// Brought to program either instantly via `cut` or over time via `auto`.
+    preview: Option<Beam>,
+    /// An in-progress timed crossfade from program to the armed preview beam.
+    transition: Option<Transition>,
     pub level: UnipolarFloat,
     pub bump: bool,
     pub mask: bool,
     pub video_outs: HashSet<VideoChannel>,
+    /// Which of the audio subsystem's envelope signals (wideband, or one band of the spectral
+    /// filterbank) this channel's beam(s) react to.
+    pub audio_envelope_source: AudioEnvelopeSource,
 }
 
 impl Channel {
@@ -185,26 +353,94 @@ impl Channel {
         video_outs.insert(VideoChannel(0));
         Self {
             beam,
+            preview: None,
+            transition: None,
             level: UnipolarFloat::ZERO,
             bump: false,
             mask: false,
             video_outs,
+            audio_envelope_source: AudioEnvelopeSource::default(),
+        }
+    }
+
+    /// Arm a beam in the preview slot, to be brought to program via `cut` or `auto`.
+    fn arm_preview(&mut self, beam: Beam) {
+        self.preview = Some(beam);
+        self.transition = None;
+    }
+
+    /// Instantly swap the armed preview beam into program, discarding any in-progress
+    /// transition. Has no effect if nothing is armed in preview.
+    fn cut(&mut self) {
+        if let Some(preview) = self.preview.take() {
+            self.beam = preview;
         }
+        self.transition = None;
+    }
+
+    /// Begin a timed crossfade from program to the armed preview beam.
+    /// Has no effect if nothing is armed in preview.
+    fn auto(&mut self, duration: Duration) {
+        if self.preview.is_some() {
+            self.transition = Some(Transition {
+                elapsed: Duration::ZERO,
+                duration,
+            });
+        }
+    }
+
+    fn is_previewing(&self) -> bool {
+        self.preview.is_some()
     }
 
-    /// Update the state of the beam in this channel.
-    pub fn update_state(&mut self, delta_t: Duration, audio_envelope: UnipolarFloat) {
-        self.beam.update_state(delta_t, audio_envelope);
+    /// Update the state of the beam(s) in this channel, advancing any in-progress transition.
+    /// If a transition completes this tick, the preview beam is committed to program and the
+    /// preview slot is cleared. The scalar envelope driving the update is selected from
+    /// `audio_envelopes` by this channel's `audio_envelope_source`, so different channels can
+    /// react to different spectral content (e.g. bass vs. treble) rather than all pulsing to the
+    /// same broadband loudness.
+    pub fn update_state<E: EmitShowStateChange>(
+        &mut self,
+        delta_t: Duration,
+        audio_envelopes: &AudioEnvelopes,
+        external_clocks: &ClockBank,
+        emitter: &mut E,
+    ) {
+        let audio_envelope = audio_envelopes.select(self.audio_envelope_source);
+        self.beam.update_state(
+            delta_t,
+            audio_envelope,
+            audio_envelopes,
+            external_clocks,
+            emitter,
+        );
+        if let Some(preview) = &mut self.preview {
+            preview.update_state(
+                delta_t,
+                audio_envelope,
+                audio_envelopes,
+                external_clocks,
+                emitter,
+            );
+        }
+        if let Some(transition) = &mut self.transition {
+            transition.elapsed += delta_t;
+            if transition.is_complete() {
+                self.cut();
+            }
+        }
     }
 
-    /// Render the beam in this channel.
+    /// Render the beam(s) in this channel. While a transition is in progress, the program and
+    /// preview beams are both rendered and blended by scaling their level with the transition's
+    /// progress, producing a crossfade.
     pub fn render(
         &self,
         level_scale: UnipolarFloat,
         mask: bool,
         external_clocks: &ClockBank,
         color_palette: &ColorPalette,
-        audio_envelope: UnipolarFloat,
+        audio_envelopes: &AudioEnvelopes,
     ) -> Vec<ArcSegment> {
         let mut level: UnipolarFloat = if self.bump {
             UnipolarFloat::ONE
@@ -216,16 +452,80 @@ impl Channel {
         if level == 0. {
             return Vec::new();
         }
-        self.beam.render(
-            level,
-            self.mask || mask,
+        let mask = self.mask || mask;
+        let transition = match &self.transition {
+            None => {
+                return self.beam.render(
+                    level,
+                    mask,
+                    external_clocks,
+                    color_palette,
+                    audio_envelopes,
+                )
+            }
+            Some(transition) => transition,
+        };
+        let t = transition.progress();
+        let mut segments = self.beam.render(
+            level * (UnipolarFloat::ONE - t),
+            mask,
             external_clocks,
             color_palette,
-            audio_envelope,
-        )
+            audio_envelopes,
+        );
+        if let Some(preview) = &self.preview {
+            segments.extend(preview.render(
+                level * t,
+                mask,
+                external_clocks,
+                color_palette,
+                audio_envelopes,
+            ));
+        }
+        segments
+    }
+}
+
+/// An in-progress timed crossfade from a channel's program beam to its armed preview beam.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct Transition {
+    elapsed: Duration,
+    duration: Duration,
+}
+
+impl Transition {
+    /// Fraction of the way through this transition, clamped to [0, 1].
+    fn progress(&self) -> UnipolarFloat {
+        if self.duration.is_zero() {
+            return UnipolarFloat::ONE;
+        }
+        UnipolarFloat::new(self.elapsed.as_secs_f64() / self.duration.as_secs_f64())
+    }
+
+    fn is_complete(&self) -> bool {
+        self.elapsed >= self.duration
     }
 }
 
+/// Waveform shapes selectable for the master LFO.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq)]
+pub enum MasterLfoShape {
+    Sine,
+    Triangle,
+    Sawtooth,
+}
+
+/// Configuration for the show-wide master LFO. See `Mixer::set_master_lfo`.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct MasterLfoConfig {
+    pub shape: MasterLfoShape,
+    /// How strongly the LFO dips the level away from full brightness, from 0 (no effect) to 1
+    /// (swings all the way down to black at the trough).
+    pub depth: UnipolarFloat,
+    /// Clock to read the LFO's phase from, for beat-locked pulsing.
+    pub clock: ClockIdx,
+}
+
 /// Index into a particular mixer channel.
 #[derive(
     Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize, TypedIndex,
@@ -247,16 +547,19 @@ pub enum ChannelControlMessage {
     ToggleVideoChannel(VideoChannel),
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct StateChange {
     pub channel: ChannelIdx,
     pub change: ChannelStateChange,
 }
+#[derive(Debug, Clone, PartialEq)]
 pub enum ChannelStateChange {
     Level(UnipolarFloat),
     Bump(bool),
     Mask(bool),
     VideoChannel((VideoChannel, bool)),
     ContainsLook(bool),
+    AudioEnvelopeSource(AudioEnvelopeSource),
 }
 
 pub trait EmitStateChange {
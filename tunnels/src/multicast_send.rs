@@ -0,0 +1,211 @@
+//! An alternate Snapshot-only render transport for LAN display nodes: the server serializes each
+//! video channel's frame once and sends it to that channel's UDP multicast group, rather than
+//! publishing over zmq PUB (one socket, unicast per subscriber under the hood) or accepting a
+//! dedicated QUIC stream per subscriber as [`crate::quic_send`] does. An arbitrary number of
+//! clients can join the same group and receive the same frame for the cost of one send, at the
+//! price of the usual UDP tradeoff: a dropped chunk drops the whole frame rather than stalling
+//! for a retransmit. See [`tunnels_lib::multicast`] for the chunk-and-sequence framing this needs
+//! on top of raw datagrams.
+
+use anyhow::{Context as _, Result};
+use log::{error, info, warn};
+use rmp_serde::Serializer;
+use serde::Serialize;
+use std::net::UdpSocket;
+use std::sync::mpsc::Sender;
+use std::thread;
+use tunnels_lib::multicast::{chunk, MulticastConfig};
+use tunnels_lib::{wire, ClockReference, ClockSourceKind, Snapshot};
+use zmq::Context as ZmqContext;
+
+use crate::clock_server::SharedClockData;
+use crate::{
+    clock_server::{clock_publisher, StaticClockBank},
+    frame_recording::Recorder,
+    send::{get_frame, RenderJob},
+};
+
+/// Render the show state and publish each video channel's snapshots to its multicast group.
+/// Returns a channel for sending frames to be rendered; the service runs until the channel is
+/// dropped.
+/// If `recorder` is provided, every live frame's rendered output is also appended to it before
+/// being published, so a session can be captured for replay without a separate render pass.
+pub fn start_render_service(
+    zmq_ctx: &ZmqContext,
+    run_clock_service: bool,
+    multicast: MulticastConfig,
+    mut recorder: Option<Recorder>,
+) -> Result<Sender<RenderJob>> {
+    let socket = UdpSocket::bind("0.0.0.0:0").context("failed to open multicast send socket")?;
+
+    // This transport doesn't offer the MQTT clock fan-out `send.rs`'s zmq transport does either;
+    // a local show only ever runs one render transport, so there's no functionality gap today.
+    let mut clock_service = if run_clock_service {
+        Some(clock_publisher(zmq_ctx, None)?)
+    } else {
+        None
+    };
+    // Minted once per process start; see the identical comment in `send::start_render_service`.
+    let clock_ref = ClockReference::new(ClockSourceKind::Builtin);
+
+    let (send, mut recv) = std::sync::mpsc::channel();
+    let mut send_buf = Vec::new();
+    let mut compact_encoders = CompactEncoders::default();
+
+    thread::Builder::new()
+        .name("multicast-render".to_string())
+        .spawn(move || loop {
+            match get_frame(&mut recv) {
+                None => {
+                    info!("Multicast render server shutting down.");
+                    return;
+                }
+                Some((dropped_frames, RenderJob::Live(frame))) => {
+                    if dropped_frames > 0 {
+                        warn!("Multicast render server dropped {} frames.", dropped_frames);
+                    }
+
+                    let video_outs = frame.mixer.render(
+                        &frame.clocks,
+                        &frame.color_palette,
+                        &frame.audio_envelopes,
+                    );
+
+                    if let Some(recorder) = &mut recorder {
+                        if let Err(e) =
+                            recorder.record(frame.number, frame.timestamp, video_outs.clone())
+                        {
+                            error!("Failed to record frame {}: {}", frame.number, e);
+                        }
+                    }
+
+                    for (video_chan, draw_commands) in video_outs.into_iter().enumerate() {
+                        let snapshot = Snapshot {
+                            frame_number: frame.number,
+                            time: frame.timestamp,
+                            layers: draw_commands,
+                        };
+                        send_snapshot(
+                            &mut send_buf,
+                            &mut compact_encoders,
+                            &socket,
+                            &multicast,
+                            video_chan,
+                            snapshot,
+                        );
+                    }
+
+                    if let Some(ref mut clock_service) = clock_service {
+                        if let Err(e) = clock_service.send(&SharedClockData {
+                            clock_bank: StaticClockBank(frame.clocks.as_static()),
+                            audio_envelopes: frame.audio_envelopes,
+                            clock_ref: Some(clock_ref.clone()),
+                        }) {
+                            error!(
+                                "failed to send clock snapshot for frame {}: {}",
+                                frame.number, e
+                            );
+                        }
+                    }
+                }
+                Some((
+                    dropped_frames,
+                    RenderJob::Recorded {
+                        number,
+                        timestamp,
+                        channels,
+                    },
+                )) => {
+                    if dropped_frames > 0 {
+                        warn!(
+                            "Multicast render server dropped {} recorded frames.",
+                            dropped_frames
+                        );
+                    }
+                    for (video_chan, draw_commands) in channels.into_iter().enumerate() {
+                        let snapshot = Snapshot {
+                            frame_number: number,
+                            time: timestamp,
+                            layers: draw_commands,
+                        };
+                        send_snapshot(
+                            &mut send_buf,
+                            &mut compact_encoders,
+                            &socket,
+                            &multicast,
+                            video_chan,
+                            snapshot,
+                        );
+                    }
+                }
+            }
+        })?;
+    info!("Multicast render server started.");
+    Ok(send)
+}
+
+/// How often, in frames, a channel using the compact wire format sends a full keyframe rather
+/// than a delta, so a client that joins mid-stream (or missed a delta's datagrams) resyncs within
+/// a bounded number of frames instead of waiting forever for a keyframe that was already sent.
+const KEYFRAME_INTERVAL: u64 = 120;
+
+/// Per-channel encoding state for `MulticastConfig::compact`: the last snapshot actually sent on
+/// each channel, which a delta-encoded frame is diffed against.
+#[derive(Default)]
+struct CompactEncoders(Vec<Option<Snapshot>>);
+
+impl CompactEncoders {
+    /// Encode `snapshot` into `buf` in the compact wire format, as a keyframe or a delta against
+    /// the last snapshot sent on `video_channel`, and remember it as the new delta reference.
+    fn encode(&mut self, buf: &mut Vec<u8>, video_channel: usize, snapshot: &Snapshot) {
+        if self.0.len() <= video_channel {
+            self.0.resize_with(video_channel + 1, || None);
+        }
+        match &self.0[video_channel] {
+            Some(reference) if snapshot.frame_number % KEYFRAME_INTERVAL != 0 => {
+                buf.push(wire::FRAME_TAG_DELTA);
+                buf.extend_from_slice(&wire::encode_delta(snapshot, reference));
+            }
+            _ => {
+                buf.push(wire::FRAME_TAG_KEYFRAME);
+                buf.extend_from_slice(&wire::encode(snapshot));
+            }
+        }
+        self.0[video_channel] = Some(snapshot.clone());
+    }
+}
+
+/// Serialize `snapshot`, split it into MTU-sized chunks, and send each to `video_channel`'s
+/// multicast group. Error conditions are logged. Uses `tunnels_lib::wire`'s compact binary
+/// format, tagged with `FRAME_TAG_KEYFRAME`/`FRAME_TAG_DELTA`, if `multicast.compact` is set;
+/// otherwise falls back to the derived msgpack encoding every other transport uses.
+fn send_snapshot(
+    send_buf: &mut Vec<u8>,
+    compact_encoders: &mut CompactEncoders,
+    socket: &UdpSocket,
+    multicast: &MulticastConfig,
+    video_channel: usize,
+    snapshot: Snapshot,
+) {
+    send_buf.clear();
+
+    if multicast.compact {
+        compact_encoders.encode(send_buf, video_channel, &snapshot);
+    } else if let Err(e) = snapshot.serialize(&mut Serializer::new(&mut *send_buf)) {
+        error!(
+            "Snapshot serialization error for frame {} channel {}: {}.",
+            snapshot.frame_number, video_channel, e,
+        );
+        return;
+    }
+
+    let addr = multicast.channel_addr(video_channel);
+    for datagram in chunk(snapshot.frame_number, send_buf) {
+        if let Err(e) = socket.send_to(&datagram, addr) {
+            error!(
+                "Multicast send error for frame {} channel {}: {}.",
+                snapshot.frame_number, video_channel, e,
+            );
+        }
+    }
+}
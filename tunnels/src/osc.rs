@@ -3,27 +3,34 @@ use anyhow::Result;
 use derive_more::Display;
 use log::{debug, error, warn};
 use rosc::{OscMessage, OscPacket, OscType};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 use std::net::{SocketAddr, UdpSocket};
 use std::sync::mpsc::Sender;
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tunnels_lib::color::Rgb;
 use tunnels_lib::number::UnipolarFloat;
 
 use crate::control::ControlEvent;
 use crate::master_ui::EmitStateChange;
-use crate::palette::{ControlMessage as PaletteControlMessage, StateChange as PaletteStateChange};
-use crate::position_bank::Position;
+use crate::palette::{
+    self, ControlMessage as PaletteControlMessage, StateChange as PaletteStateChange,
+};
+use crate::position_bank::{self, Position};
 use crate::show::{ControlMessage, StateChange};
 
 /// The OSC device types that tunnels can work with.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Display)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Display, Serialize, Deserialize)]
 pub enum Device {
     PaletteController,
     PositionController,
 }
 
 /// Wrapper struct for the data needed to describe a device to connect to.
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, Serialize, Deserialize)]
 pub struct DeviceSpec {
     pub device: Device,
     pub addr: SocketAddr,
@@ -31,15 +38,32 @@ pub struct DeviceSpec {
 
 pub struct Dispatcher {
     _inputs: Vec<Input>,
+    outputs: Vec<Output>,
 }
 
 impl Dispatcher {
     pub fn new(osc_devices: Vec<DeviceSpec>, send: Sender<ControlEvent>) -> Result<Self> {
+        let scheduler = ScheduledEvents::start(send.clone());
         let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
         for osc_device in osc_devices {
-            inputs.push(Input::new(osc_device, send.clone())?);
+            inputs.push(Input::new(osc_device, send.clone(), scheduler.clone())?);
+            outputs.push(Output::new(osc_device)?);
+        }
+        Ok(Self {
+            _inputs: inputs,
+            outputs,
+        })
+    }
+
+    /// Send an OSC message to every connected device of the given type.
+    fn send_to(&self, device: Device, addr: &str, args: Vec<OscType>) {
+        for output in self.outputs.iter().filter(|o| o.spec.device == device) {
+            output.send(OscMessage {
+                addr: addr.to_string(),
+                args: args.clone(),
+            });
         }
-        Ok(Self { _inputs: inputs })
     }
 
     /// Map the provided OSC event to a show control message.
@@ -127,9 +151,66 @@ fn get_osc_float(v: &OscType) -> Result<f64> {
 }
 
 impl EmitStateChange for Dispatcher {
-    /// Map application state changes into OSC update midi messages.
-    fn emit(&mut self, _: StateChange) {
-        // For the moment there's no talkback over OSC.
+    /// Map application state changes into OSC talkback, so a bidirectional surface like TouchOSC
+    /// can reflect show state it didn't itself originate.
+    fn emit(&mut self, sc: StateChange) {
+        match sc {
+            StateChange::ColorPalette(palette::StateChange::Contents(colors)) => {
+                let args = colors
+                    .iter()
+                    .flat_map(|hsv| {
+                        let rgb = hsv.as_rgb();
+                        [rgb.red.val(), rgb.green.val(), rgb.blue.val()]
+                    })
+                    .map(|v| OscType::Float(v as f32))
+                    .collect();
+                self.send_to(Device::PaletteController, "/palette", args);
+            }
+            StateChange::Position(position_bank::StateChange::Contents(positions)) => {
+                let args = positions
+                    .iter()
+                    .flat_map(|p| [p.x, p.y])
+                    .map(|v| OscType::Float(v as f32))
+                    .collect();
+                self.send_to(Device::PositionController, "/position", args);
+            }
+            _ => {
+                // No other state is currently mirrored back over OSC.
+            }
+        }
+    }
+}
+
+/// A connection for sending OSC messages back out to a single device.
+struct Output {
+    spec: DeviceSpec,
+    socket: UdpSocket,
+}
+
+impl Output {
+    fn new(spec: DeviceSpec) -> Result<Self> {
+        // Bind an ephemeral local port; we only ever send from this socket.
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        Ok(Self { spec, socket })
+    }
+
+    fn send(&self, msg: OscMessage) {
+        match rosc::encoder::encode(&OscPacket::Message(msg)) {
+            Ok(buf) => {
+                if let Err(e) = self.socket.send_to(&buf, self.spec.addr) {
+                    error!(
+                        "Error sending OSC message to device {}: {}",
+                        self.spec.device, e
+                    );
+                }
+            }
+            Err(e) => {
+                error!(
+                    "Error encoding OSC message for device {}: {}",
+                    self.spec.device, e
+                );
+            }
+        }
     }
 }
 
@@ -138,7 +219,11 @@ impl EmitStateChange for Dispatcher {
 struct Input(DeviceSpec);
 
 impl Input {
-    pub fn new(spec: DeviceSpec, send: Sender<ControlEvent>) -> Result<Self> {
+    pub fn new(
+        spec: DeviceSpec,
+        send: Sender<ControlEvent>,
+        scheduler: ScheduledEvents,
+    ) -> Result<Self> {
         let socket = UdpSocket::bind(spec.addr)?;
 
         let mut buf = [0u8; rosc::decoder::MTU];
@@ -152,7 +237,7 @@ impl Input {
         thread::spawn(move || loop {
             match recv() {
                 Ok(packet) => {
-                    forward_packet(packet, spec.device, &send);
+                    forward_packet(packet, spec.device, &send, &scheduler);
                 }
                 Err(e) => {
                     error!("Error receiving from OSC device {}: {}", spec.device, e);
@@ -163,16 +248,133 @@ impl Input {
     }
 }
 
-/// Recursively unpack OSC packets and send all the inner messages as control events.
-fn forward_packet(packet: OscPacket, device: Device, send: &Sender<ControlEvent>) {
+/// Recursively unpack OSC packets, honoring any bundle timetag: a bundle scheduled for the
+/// future has its contained messages enqueued on `scheduler` to fire when due, while a bundle
+/// tagged "immediately" (or already past) forwards straight through, preserving prior behavior.
+fn forward_packet(
+    packet: OscPacket,
+    device: Device,
+    send: &Sender<ControlEvent>,
+    scheduler: &ScheduledEvents,
+) {
+    dispatch_packet(packet, device, send, scheduler, None);
+}
+
+/// Unpack a packet, scheduling messages for `fire_at` if set (inherited from an enclosing
+/// bundle), or sending them immediately otherwise. A nested bundle's own timetag, if it
+/// resolves to a future time, takes precedence over the one it's nested within.
+fn dispatch_packet(
+    packet: OscPacket,
+    device: Device,
+    send: &Sender<ControlEvent>,
+    scheduler: &ScheduledEvents,
+    fire_at: Option<Instant>,
+) {
     match packet {
         OscPacket::Message(m) => {
-            send.send(ControlEvent::Osc((device, m))).unwrap();
+            let event = ControlEvent::Osc((device, m));
+            match fire_at {
+                Some(fire_at) => scheduler.push(fire_at, event),
+                None => send.send(event).unwrap(),
+            }
         }
-        OscPacket::Bundle(msgs) => {
-            for subpacket in msgs.content {
-                forward_packet(subpacket, device, send);
+        OscPacket::Bundle(bundle) => {
+            let fire_at = osc_timetag_to_instant(bundle.timetag).or(fire_at);
+            for subpacket in bundle.content {
+                dispatch_packet(subpacket, device, send, scheduler, fire_at);
             }
         }
     }
 }
+
+/// Seconds between the NTP epoch (1900-01-01) and the Unix epoch (1970-01-01).
+const NTP_UNIX_EPOCH_OFFSET_SECS: i64 = 2_208_988_800;
+
+/// Convert an OSC NTP timetag into a local `Instant` to fire at, or `None` if it names
+/// "immediately" (the reserved value `1`) or a time that has already passed.
+fn osc_timetag_to_instant(timetag: rosc::OscTime) -> Option<Instant> {
+    if timetag.seconds == 0 && timetag.fractional <= 1 {
+        return None;
+    }
+    let unix_seconds = timetag.seconds as i64 - NTP_UNIX_EPOCH_OFFSET_SECS;
+    if unix_seconds < 0 {
+        return None;
+    }
+    let nanos = ((timetag.fractional as u64) * 1_000_000_000) >> 32;
+    let target = UNIX_EPOCH + Duration::new(unix_seconds as u64, nanos as u32);
+    let delay = target.duration_since(SystemTime::now()).ok()?;
+    Some(Instant::now() + delay)
+}
+
+/// A time-ordered queue of pending `ControlEvent`s, drained by a dedicated timer thread that
+/// wakes up when the next entry is due (or sooner, if a new earlier entry arrives) and sends it
+/// on to the ordinary control event channel. Backs OSC bundle timetag scheduling.
+#[derive(Clone)]
+struct ScheduledEvents {
+    state: Arc<(Mutex<BinaryHeap<Scheduled>>, Condvar)>,
+}
+
+struct Scheduled {
+    fire_at: Instant,
+    event: ControlEvent,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.fire_at == other.fire_at
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the BinaryHeap, normally a max-heap, pops the earliest fire time first.
+        other.fire_at.cmp(&self.fire_at)
+    }
+}
+
+impl ScheduledEvents {
+    /// Start the timer thread and return a handle that can enqueue events onto it.
+    fn start(send: Sender<ControlEvent>) -> Self {
+        let state = Arc::new((Mutex::new(BinaryHeap::new()), Condvar::new()));
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            let (lock, condvar) = &*thread_state;
+            let mut heap = lock.lock().unwrap();
+            loop {
+                match heap.peek() {
+                    None => {
+                        heap = condvar.wait(heap).unwrap();
+                    }
+                    Some(next) => {
+                        let now = Instant::now();
+                        if next.fire_at > now {
+                            let (h, _) = condvar.wait_timeout(heap, next.fire_at - now).unwrap();
+                            heap = h;
+                            continue;
+                        }
+                    }
+                }
+                while matches!(heap.peek(), Some(next) if next.fire_at <= Instant::now()) {
+                    let due = heap.pop().unwrap();
+                    send.send(due.event).ok();
+                }
+            }
+        });
+        Self { state }
+    }
+
+    /// Enqueue `event` to be delivered once `fire_at` arrives.
+    fn push(&self, fire_at: Instant, event: ControlEvent) {
+        let (lock, condvar) = &*self.state;
+        lock.lock().unwrap().push(Scheduled { fire_at, event });
+        condvar.notify_one();
+    }
+}
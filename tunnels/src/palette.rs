@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use tunnels_lib::color::Hsv;
+use tunnels_lib::number::UnipolarFloat;
 use typed_index_derive::TypedIndex;
 
 use crate::master_ui::EmitStateChange as EmitShowStateChange;
@@ -12,24 +14,67 @@ const MIN_PALETTE_SIZE: usize = 1;
 #[typed_index(Hsv)]
 pub struct ColorPaletteIdx(pub usize);
 
-/// Store an array of colors that can be used by beams.
+/// Store an array of colors that can be used by beams, with an optional in-progress
+/// timed crossfade to a new set of colors.
 #[derive(Serialize, Deserialize, Clone)]
-pub struct ColorPalette(Vec<Hsv>);
+pub struct ColorPalette {
+    colors: Vec<Hsv>,
+    crossfade: Option<Crossfade>,
+}
 
 impl ColorPalette {
     pub fn new() -> Self {
-        ColorPalette(vec![Hsv::BLACK; MIN_PALETTE_SIZE])
+        ColorPalette {
+            colors: vec![Hsv::BLACK; MIN_PALETTE_SIZE],
+            crossfade: None,
+        }
     }
 
-    /// Return the color in the palette from the requested index.
+    /// Return the color in the palette from the requested index, blending towards an
+    /// in-progress crossfade's target if one is running.
     pub fn get(&self, index: ColorPaletteIdx) -> Option<Hsv> {
-        self.0.get(index.0).copied()
+        let Some(crossfade) = &self.crossfade else {
+            return self.colors.get(index.0).copied();
+        };
+        let from = crossfade.from.get(index.0).copied();
+        let to = crossfade.to.get(index.0).copied();
+        match (from, to) {
+            (Some(from), Some(to)) => Some(from.interpolate(&to, crossfade.transition.progress())),
+            (Some(from), None) => Some(from),
+            (None, Some(to)) => Some(to),
+            (None, None) => None,
+        }
+    }
+
+    /// The colors as currently displayed, accounting for any in-progress crossfade.
+    fn current_colors(&self) -> Vec<Hsv> {
+        let len = match &self.crossfade {
+            None => self.colors.len(),
+            Some(crossfade) => crossfade.from.len().max(crossfade.to.len()),
+        };
+        (0..len)
+            .filter_map(|i| self.get(ColorPaletteIdx(i)))
+            .collect()
+    }
+
+    /// Advance any in-progress crossfade. Once it completes, commit the target colors and
+    /// emit the updated palette contents.
+    pub fn update_state<E: EmitStateChange>(&mut self, delta_t: Duration, emitter: &mut E) {
+        let Some(crossfade) = &mut self.crossfade else {
+            return;
+        };
+        crossfade.transition.elapsed += delta_t;
+        if crossfade.transition.is_complete() {
+            let colors = crossfade.to.clone();
+            self.crossfade = None;
+            self.handle_state_change(StateChange::Contents(colors), emitter);
+        }
     }
 
     /// Emit the current value of all controllable palette state.
     pub fn emit_state<E: EmitStateChange>(&self, emitter: &mut E) {
         use StateChange::*;
-        emitter.emit_palette_state_change(Contents(self.0.clone()));
+        emitter.emit_palette_state_change(Contents(self.colors.clone()));
     }
 
     /// Handle a control event.
@@ -38,25 +83,70 @@ impl ColorPalette {
         use ControlMessage::*;
         match msg {
             Set(sc) => self.handle_state_change(sc, emitter),
+            Crossfade(colors, duration) => self.start_crossfade(colors, duration),
         }
     }
 
+    /// Begin a timed crossfade from the currently-displayed colors to `colors`, smoothly
+    /// continuing from wherever a crossfade already in progress had reached.
+    fn start_crossfade(&mut self, colors: Vec<Hsv>, duration: Duration) {
+        self.crossfade = Some(Crossfade {
+            from: self.current_colors(),
+            to: colors,
+            transition: Transition {
+                elapsed: Duration::ZERO,
+                duration,
+            },
+        });
+    }
+
     fn handle_state_change<E: EmitStateChange>(&mut self, sc: StateChange, emitter: &mut E) {
         use StateChange::*;
         match sc {
             Contents(ref colors) => {
-                self.0.clear();
-                self.0.extend_from_slice(colors);
+                self.colors.clear();
+                self.colors.extend_from_slice(colors);
             }
         };
         emitter.emit_palette_state_change(sc);
     }
 }
 
+/// An in-progress timed crossfade from one set of palette colors to another.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct Crossfade {
+    from: Vec<Hsv>,
+    to: Vec<Hsv>,
+    transition: Transition,
+}
+
+/// An in-progress timed transition, tracked as elapsed time against a total duration.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct Transition {
+    elapsed: Duration,
+    duration: Duration,
+}
+
+impl Transition {
+    /// Fraction of the way through this transition, clamped to [0, 1].
+    fn progress(&self) -> UnipolarFloat {
+        if self.duration.is_zero() {
+            return UnipolarFloat::ONE;
+        }
+        UnipolarFloat::new(self.elapsed.as_secs_f64() / self.duration.as_secs_f64())
+    }
+
+    fn is_complete(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
 pub enum ControlMessage {
     Set(StateChange),
+    Crossfade(Vec<Hsv>, Duration),
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum StateChange {
     Contents(Vec<Hsv>),
 }
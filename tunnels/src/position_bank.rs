@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use typed_index_derive::TypedIndex;
 
+use crate::master_ui::EmitStateChange as EmitShowStateChange;
+
 const MIN_POSITION_COUNT: usize = 1;
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, Default)]
@@ -27,10 +29,15 @@ impl PositionBank {
         self.0.get(index.0).copied()
     }
 
+    /// Emit the current value of all controllable position state.
+    pub fn emit_state<E: EmitStateChange>(&self, emitter: &mut E) {
+        emitter.emit_position_state_change(StateChange::Contents(self.0.clone()));
+    }
+
     /// Handle a control event.
-    /// No state is emitted as a result of this action.
-    pub fn control(&mut self, positions: Positions) {
-        self.0 = positions;
+    pub fn control<E: EmitStateChange>(&mut self, positions: Positions, emitter: &mut E) {
+        self.0 = positions.clone();
+        emitter.emit_position_state_change(StateChange::Contents(positions));
     }
 }
 
@@ -41,3 +48,19 @@ impl Default for PositionBank {
 }
 
 pub type ControlMessage = Positions;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StateChange {
+    Contents(Positions),
+}
+
+pub trait EmitStateChange {
+    fn emit_position_state_change(&mut self, sc: StateChange);
+}
+
+impl<T: EmitShowStateChange> EmitStateChange for T {
+    fn emit_position_state_change(&mut self, sc: StateChange) {
+        use crate::show::StateChange as ShowStateChange;
+        self.emit(ShowStateChange::Position(sc))
+    }
+}
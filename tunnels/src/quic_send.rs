@@ -0,0 +1,309 @@
+//! An alternate render-frame transport for clients that aren't reachable over a reliable local
+//! link, modeled loosely on media-over-QUIC pub/sub. [`crate::send::start_render_service`]
+//! publishes over a local zmq PUB socket, which assumes render clients are co-located and on a
+//! reliable network; this module publishes the same per-channel `Snapshot` stream over QUIC
+//! instead, so render/video clients can subscribe across a lossy LAN or WAN.
+//!
+//! A subscriber's dedicated stream carries the frame sequence in order: on connect it is first
+//! sent the most recently rendered frame as a keyframe, then every frame published after that
+//! point. If a subscriber falls behind, its backlog of unsent frames is capped; once exceeded,
+//! stale frames are dropped for that subscriber alone rather than stalling `Show::run`'s render
+//! loop or any other subscriber.
+
+use anyhow::{Context as _, Result};
+use log::{error, info, warn};
+use quinn::{Endpoint, SendStream, ServerConfig};
+use rmp_serde::Serializer;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use tokio::sync::broadcast;
+use tunnels_lib::{ClockReference, ClockSourceKind, LayerCollection, Snapshot, Timestamp};
+use zmq::Context as ZmqContext;
+
+use crate::clock_server::SharedClockData;
+use crate::{
+    clock_server::{clock_publisher, StaticClockBank},
+    frame_recording::Recorder,
+    send::{get_frame, RenderJob},
+};
+
+/// ALPN protocol identifier clients must negotiate to speak this frame stream.
+const ALPN: &[u8] = b"tunnels-render";
+
+/// How many published frames a single subscriber may fall behind by before its oldest buffered
+/// frames are dropped, rather than letting a slow or lossy subscriber stall delivery to everyone
+/// else.
+const SUBSCRIBER_BACKLOG: usize = 8;
+
+/// Every video channel's rendered snapshot for one frame, msgpack-encoded and paired with the
+/// same topic byte the local zmq transport multiplexes its channels with. Cheaply cloned so it
+/// can be handed to a late-joining subscriber as a keyframe and also broadcast to everyone else.
+type EncodedFrame = Arc<Vec<(u8, Vec<u8>)>>;
+
+/// Render the show state and publish it to QUIC subscribers.
+/// Returns a channel for sending frames to be rendered.
+/// The service runs until the channel is dropped.
+/// If `recorder` is provided, every live frame's rendered output is also appended to it before
+/// being published, so a session can be captured for replay without a separate render pass.
+pub fn start_render_service(
+    zmq_ctx: &ZmqContext,
+    run_clock_service: bool,
+    bind_addr: SocketAddr,
+    mut recorder: Option<Recorder>,
+) -> Result<Sender<RenderJob>> {
+    let server_config = self_signed_server_config(bind_addr)
+        .context("failed to configure QUIC render service TLS")?;
+    let endpoint =
+        Endpoint::server(server_config, bind_addr).context("failed to bind QUIC render socket")?;
+
+    // The QUIC transport doesn't yet offer the MQTT clock/snapshot fan-out `send.rs` does; a
+    // local show only ever runs one render transport, so there's no functionality gap today.
+    let mut clock_service = if run_clock_service {
+        Some(clock_publisher(zmq_ctx, None)?)
+    } else {
+        None
+    };
+    // Minted once per process start; see the identical comment in `send::start_render_service`.
+    let clock_ref = ClockReference::new(ClockSourceKind::Builtin);
+
+    let (frame_send, mut frame_recv) = std::sync::mpsc::channel();
+    let (delta_send, _) = broadcast::channel::<EncodedFrame>(SUBSCRIBER_BACKLOG);
+    let latest: Arc<RwLock<Option<EncodedFrame>>> = Arc::new(RwLock::new(None));
+
+    // Accept connections and fan out published frames on a dedicated tokio runtime; rendering
+    // stays on its own thread below so a stalled network task can never hold up the render loop.
+    {
+        let delta_send = delta_send.clone();
+        let latest = Arc::clone(&latest);
+        thread::Builder::new()
+            .name("quic-render-accept".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Runtime::new() {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        error!("Failed to start QUIC render service runtime: {e}.");
+                        return;
+                    }
+                };
+                runtime.block_on(accept_loop(endpoint, delta_send, latest));
+            })?;
+    }
+
+    let mut send_buf = Vec::new();
+    thread::Builder::new()
+        .name("quic-render".to_string())
+        .spawn(move || loop {
+            match get_frame(&mut frame_recv) {
+                None => {
+                    info!("QUIC render server shutting down.");
+                    return;
+                }
+                Some((dropped_frames, RenderJob::Live(frame))) => {
+                    if dropped_frames > 0 {
+                        warn!("QUIC render server dropped {} frames.", dropped_frames);
+                    }
+
+                    let video_outs = frame.mixer.render(
+                        &frame.clocks,
+                        &frame.color_palette,
+                        &frame.audio_envelopes,
+                    );
+
+                    if let Some(recorder) = &mut recorder {
+                        if let Err(e) =
+                            recorder.record(frame.number, frame.timestamp, video_outs.clone())
+                        {
+                            error!("Failed to record frame {}: {}", frame.number, e);
+                        }
+                    }
+
+                    let encoded = match encode_frame(
+                        &mut send_buf,
+                        frame.number,
+                        frame.timestamp,
+                        video_outs,
+                    ) {
+                        Ok(encoded) => Arc::new(encoded),
+                        Err(e) => {
+                            error!(
+                                "QUIC snapshot serialization error for frame {}: {}.",
+                                frame.number, e
+                            );
+                            continue;
+                        }
+                    };
+
+                    *latest.write().unwrap() = Some(Arc::clone(&encoded));
+                    // No subscribers is not an error; the frame is simply dropped.
+                    let _ = delta_send.send(encoded);
+
+                    if let Some(ref mut clock_service) = clock_service {
+                        if let Err(e) = clock_service.send(&SharedClockData {
+                            clock_bank: StaticClockBank(frame.clocks.as_static()),
+                            audio_envelopes: frame.audio_envelopes,
+                            clock_ref: Some(clock_ref.clone()),
+                        }) {
+                            error!(
+                                "failed to send clock snapshot for frame {}: {}",
+                                frame.number, e
+                            );
+                        }
+                    }
+                }
+                Some((
+                    dropped_frames,
+                    RenderJob::Recorded {
+                        number,
+                        timestamp,
+                        channels,
+                    },
+                )) => {
+                    if dropped_frames > 0 {
+                        warn!(
+                            "QUIC render server dropped {} recorded frames.",
+                            dropped_frames
+                        );
+                    }
+                    let encoded = match encode_frame(&mut send_buf, number, timestamp, channels) {
+                        Ok(encoded) => Arc::new(encoded),
+                        Err(e) => {
+                            error!(
+                                "QUIC snapshot serialization error for recorded frame {}: {}.",
+                                number, e
+                            );
+                            continue;
+                        }
+                    };
+                    *latest.write().unwrap() = Some(Arc::clone(&encoded));
+                    let _ = delta_send.send(encoded);
+                }
+            }
+        })?;
+    info!("QUIC render server started on {}.", bind_addr);
+    Ok(frame_send)
+}
+
+/// Serialize every video channel's rendered output into a single frame-shaped unit, reusing
+/// `Snapshot`'s existing `Serialize` impl so the wire format stays identical to the local path.
+fn encode_frame(
+    send_buf: &mut Vec<u8>,
+    frame_number: u64,
+    time: Timestamp,
+    video_outs: Vec<LayerCollection>,
+) -> Result<Vec<(u8, Vec<u8>)>> {
+    video_outs
+        .into_iter()
+        .enumerate()
+        .map(|(video_chan, layers)| {
+            let snapshot = Snapshot {
+                frame_number,
+                time,
+                layers,
+            };
+            send_buf.clear();
+            snapshot.serialize(&mut Serializer::new(&mut *send_buf))?;
+            Ok((video_chan as u8, send_buf.clone()))
+        })
+        .collect()
+}
+
+/// Accept incoming subscriber connections for as long as `endpoint` stays open, handing each one
+/// its own copy of the delta stream and a handle to the latest published frame.
+async fn accept_loop(
+    endpoint: Endpoint,
+    delta_send: broadcast::Sender<EncodedFrame>,
+    latest: Arc<RwLock<Option<EncodedFrame>>>,
+) {
+    while let Some(connecting) = endpoint.accept().await {
+        let delta_recv = delta_send.subscribe();
+        let latest = Arc::clone(&latest);
+        tokio::spawn(async move {
+            match connecting.await {
+                Ok(connection) => serve_subscriber(connection, delta_recv, latest).await,
+                Err(e) => warn!("QUIC render subscriber failed to connect: {e}."),
+            }
+        });
+    }
+}
+
+/// Catch a newly-connected subscriber up with the most recently published frame, then stream
+/// every frame published after that point until it disconnects or falls too far behind.
+async fn serve_subscriber(
+    connection: quinn::Connection,
+    delta_recv: broadcast::Receiver<EncodedFrame>,
+    latest: Arc<RwLock<Option<EncodedFrame>>>,
+) {
+    let mut stream = match connection.open_uni().await {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!("Failed to open a QUIC render stream to a subscriber: {e}.");
+            return;
+        }
+    };
+
+    // Resubscribe while holding the read lock, so frames queued during the handshake aren't
+    // replayed after the newer keyframe; only the keyframe itself can still arrive again.
+    let (keyframe, mut delta_recv) = {
+        let latest = latest.read().unwrap();
+        (latest.clone(), delta_recv.resubscribe())
+    };
+    if let Some(keyframe) = &keyframe {
+        if write_frame(&mut stream, keyframe).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        match delta_recv.recv().await {
+            Ok(frame) => {
+                if matches!(&keyframe, Some(k) if Arc::ptr_eq(k, &frame)) {
+                    continue;
+                }
+                if write_frame(&mut stream, &frame).await.is_err() {
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!(
+                    "QUIC render subscriber fell {} frames behind; dropping them and continuing.",
+                    missed
+                );
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+/// Write one frame to `stream` as a sequence of length-prefixed `[topic, snapshot]` pairs, one
+/// per video channel, so a subscriber can demultiplex channels from the ordered byte stream the
+/// same way the local zmq transport's multipart messages do.
+async fn write_frame(stream: &mut SendStream, frame: &[(u8, Vec<u8>)]) -> Result<()> {
+    for (topic, payload) in frame {
+        let len = payload.len() as u32 + 1;
+        stream.write_all(&len.to_le_bytes()).await?;
+        stream.write_all(&[*topic]).await?;
+        stream.write_all(payload).await?;
+    }
+    Ok(())
+}
+
+/// Build a server config bound to a throwaway self-signed certificate. This transport is meant
+/// for render clients reachable over a trusted LAN/WAN link, not a public-facing service, so
+/// there's no certificate authority to hand clients anything better to verify against.
+fn self_signed_server_config(bind_addr: SocketAddr) -> Result<ServerConfig> {
+    let cert = rcgen::generate_simple_self_signed(vec![bind_addr.ip().to_string()])
+        .context("failed to generate self-signed certificate")?;
+    let key = rustls::PrivateKey(cert.serialize_private_key_der());
+    let cert = rustls::Certificate(cert.serialize_der()?);
+
+    let mut crypto = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert], key)?;
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+
+    Ok(ServerConfig::with_crypto(Arc::new(crypto)))
+}
@@ -4,37 +4,60 @@ use rmp_serde::Serializer;
 use serde::Serialize;
 use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::thread;
-use tunnels_lib::{number::UnipolarFloat, Snapshot, Timestamp};
+use tunnels_lib::mqtt::{MqttPublisher, MqttSinkConfig};
+use tunnels_lib::shm::ShmWriter;
+use tunnels_lib::{ClockReference, ClockSourceKind, LayerCollection, Snapshot, Timestamp};
 use zmq::{Context, Socket};
 
 use crate::clock_server::SharedClockData;
 use crate::{
+    audio::AudioEnvelopes,
     clock_bank::ClockBank,
     clock_server::{clock_publisher, StaticClockBank},
+    frame_recording::Recorder,
     mixer::Mixer,
     palette::ColorPalette,
     position_bank::PositionBank,
 };
 
 const PORT: u16 = 6000;
+const SNAPSHOT_MQTT_CLIENT_ID: &str = "tunnels_snapshots";
 
 /// Renders the show state and sends it to all connected clients.
 /// Returns a channel for sending frames to be rendered.
 /// The service runs until the channel is dropped.
-pub fn start_render_service(ctx: &Context, run_clock_service: bool) -> Result<Sender<Frame>> {
+/// If `recorder` is provided, every live frame's rendered output is also appended to it before
+/// being published, so a session can be captured for replay without a separate render pass.
+/// If `clock_mqtt`/`snapshot_mqtt` are provided, the clock stream and/or each video channel's
+/// snapshots are additionally mirrored to a topic on an MQTT broker, alongside their existing
+/// zmq/DNS-SD transports, for subscribers that would rather not speak zmq. A snapshot channel's
+/// topic is `{snapshot_mqtt.topic}/{video_channel}`.
+pub fn start_render_service(
+    ctx: &Context,
+    run_clock_service: bool,
+    mut recorder: Option<Recorder>,
+    clock_mqtt: Option<MqttSinkConfig>,
+    snapshot_mqtt: Option<MqttSinkConfig>,
+) -> Result<Sender<RenderJob>> {
     let socket = ctx.socket(zmq::PUB)?;
     let addr = format!("tcp://*:{}", PORT);
     socket.bind(&addr)?;
 
     let mut clock_service = if run_clock_service {
-        Some(clock_publisher(ctx)?)
+        Some(clock_publisher(ctx, clock_mqtt)?)
     } else {
         None
     };
+    // Minted once per process start, so a client watching the clock-reference stream can tell a
+    // host restart apart from a steady-state run, even though this service only ever uses the
+    // builtin timesync exchange as its clock source.
+    let clock_ref = ClockReference::new(ClockSourceKind::Builtin);
 
     let (send, mut recv) = channel();
 
     let mut send_buf = Vec::new();
+    let mut shm_writers = ShmWriters::new();
+    let mut mqtt_sinks = SnapshotMqttSinks::new(snapshot_mqtt);
     thread::Builder::new()
         .name("render".to_string())
         .spawn(move || loop {
@@ -43,7 +66,7 @@ pub fn start_render_service(ctx: &Context, run_clock_service: bool) -> Result<Se
                     info!("Render server shutting down.");
                     return;
                 }
-                Some((dropped_frames, frame)) => {
+                Some((dropped_frames, RenderJob::Live(frame))) => {
                     if dropped_frames > 0 {
                         warn!("Render server dropped {} frames.", dropped_frames);
                     }
@@ -51,22 +74,38 @@ pub fn start_render_service(ctx: &Context, run_clock_service: bool) -> Result<Se
                     let video_outs = frame.mixer.render(
                         &frame.clocks,
                         &frame.color_palette,
-                        &frame.positions,
-                        frame.audio_envelope,
+                        &frame.audio_envelopes,
                     );
+
+                    if let Some(recorder) = &mut recorder {
+                        if let Err(e) =
+                            recorder.record(frame.number, frame.timestamp, video_outs.clone())
+                        {
+                            error!("Failed to record frame {}: {}", frame.number, e);
+                        }
+                    }
+
                     for (video_chan, draw_commands) in video_outs.into_iter().enumerate() {
                         let snapshot = Snapshot {
                             frame_number: frame.number,
                             time: frame.timestamp,
                             layers: draw_commands,
                         };
-                        send_snapshot(&mut send_buf, &socket, video_chan, snapshot);
+                        send_snapshot(
+                            &mut send_buf,
+                            &socket,
+                            &mut shm_writers,
+                            &mut mqtt_sinks,
+                            video_chan,
+                            snapshot,
+                        );
                     }
 
                     if let Some(ref mut clock_service) = clock_service {
                         if let Err(e) = clock_service.send(&SharedClockData {
                             clock_bank: StaticClockBank(frame.clocks.as_static()),
-                            audio_envelope: frame.audio_envelope,
+                            audio_envelopes: frame.audio_envelopes,
+                            clock_ref: Some(clock_ref.clone()),
                         }) {
                             error!(
                                 "failed to send clock snapshot for frame {}: {}",
@@ -75,18 +114,45 @@ pub fn start_render_service(ctx: &Context, run_clock_service: bool) -> Result<Se
                         }
                     }
                 }
+                Some((
+                    dropped_frames,
+                    RenderJob::Recorded {
+                        number,
+                        timestamp,
+                        channels,
+                    },
+                )) => {
+                    if dropped_frames > 0 {
+                        warn!("Render server dropped {} recorded frames.", dropped_frames);
+                    }
+                    for (video_chan, draw_commands) in channels.into_iter().enumerate() {
+                        let snapshot = Snapshot {
+                            frame_number: number,
+                            time: timestamp,
+                            layers: draw_commands,
+                        };
+                        send_snapshot(
+                            &mut send_buf,
+                            &socket,
+                            &mut shm_writers,
+                            &mut mqtt_sinks,
+                            video_chan,
+                            snapshot,
+                        );
+                    }
+                }
             }
         })?;
     info!("Render server started.");
     Ok(send)
 }
 
-/// Block until a frame is available.
-/// Also optimistically check if there is already one or more frames backed up
-/// behind the first frame.  If so, drain them all and return the last frame
-/// received as well as the number of dropped frames.
+/// Block until a render job is available.
+/// Also optimistically check if there is already one or more jobs backed up
+/// behind the first one.  If so, drain them all and return the last job
+/// received as well as the number of dropped jobs.
 /// If the receiver has disconnected, return None.
-fn get_frame(recv: &mut Receiver<Frame>) -> Option<(u32, Frame)> {
+pub(crate) fn get_frame(recv: &mut Receiver<RenderJob>) -> Option<(u32, RenderJob)> {
     let mut dropped_frames = 0;
     // Wait for a frame.
     let mut frame = match recv.recv() {
@@ -109,11 +175,14 @@ fn get_frame(recv: &mut Receiver<Frame>) -> Option<(u32, Frame)> {
     }
 }
 
-/// Serialize the provided snapshot and send it to the specified video channel.
-/// Error conditions are logged.
+/// Serialize the provided snapshot and send it to the specified video channel's shared-memory
+/// ring (for any client running on this host), over the zmq PUB socket (for clients elsewhere on
+/// the network), and, if configured, to an MQTT broker. Error conditions are logged.
 fn send_snapshot(
     mut send_buf: &mut Vec<u8>,
     socket: &Socket,
+    shm_writers: &mut ShmWriters,
+    mqtt_sinks: &mut SnapshotMqttSinks,
     video_channel: usize,
     snapshot: Snapshot,
 ) {
@@ -128,6 +197,9 @@ fn send_snapshot(
         return;
     }
 
+    shm_writers.write(video_channel, send_buf);
+    mqtt_sinks.publish(video_channel, send_buf);
+
     let messages: [&[u8]; 2] = [&topic, send_buf];
     if let Err(e) = socket.send_multipart(messages.iter(), 0) {
         error!(
@@ -137,6 +209,108 @@ fn send_snapshot(
     }
 }
 
+/// One video channel's lazily-created `ShmWriter`, or a record that creating it already failed
+/// so `ShmWriters::write` doesn't keep retrying (and re-logging) every frame.
+enum ShmSlot {
+    Untried,
+    Active(ShmWriter),
+    Failed,
+}
+
+/// Lazily creates and caches one `ShmWriter` per video channel, so a channel whose ring failed
+/// to create (e.g. `/dev/shm` unwritable) is only logged about once rather than on every frame.
+struct ShmWriters(Vec<ShmSlot>);
+
+impl ShmWriters {
+    fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Publish `payload` to `channel`'s ring, creating it on first use. Does nothing beyond
+    /// logging if the ring couldn't be created or a previous attempt already failed.
+    fn write(&mut self, channel: usize, payload: &[u8]) {
+        if self.0.len() <= channel {
+            self.0.resize_with(channel + 1, || ShmSlot::Untried);
+        }
+        if matches!(self.0[channel], ShmSlot::Untried) {
+            self.0[channel] = match ShmWriter::create(channel as u8) {
+                Ok(writer) => ShmSlot::Active(writer),
+                Err(e) => {
+                    error!(
+                        "Failed to create shared-memory snapshot ring for channel {}: {}. \
+                         Local clients on this channel will use the network transport.",
+                        channel, e
+                    );
+                    ShmSlot::Failed
+                }
+            };
+        }
+        if let ShmSlot::Active(writer) = &mut self.0[channel] {
+            writer.write(payload);
+        }
+    }
+}
+
+/// One video channel's lazily-created `MqttPublisher`, or a record that creating it already
+/// failed so `SnapshotMqttSinks::publish` doesn't keep retrying (and re-logging) every frame.
+enum MqttSlot {
+    Untried,
+    Active(MqttPublisher<Snapshot>),
+    Failed,
+}
+
+/// Lazily creates and caches one `MqttPublisher` per video channel, each on its own topic under
+/// the configured base topic, so a show with several video channels doesn't cram them all onto
+/// one MQTT topic. Does nothing if no `MqttSinkConfig` was configured at launch.
+struct SnapshotMqttSinks {
+    base: Option<MqttSinkConfig>,
+    sinks: Vec<MqttSlot>,
+}
+
+impl SnapshotMqttSinks {
+    fn new(base: Option<MqttSinkConfig>) -> Self {
+        Self {
+            base,
+            sinks: Vec::new(),
+        }
+    }
+
+    /// Publish `payload`, the already-serialized snapshot for `channel`, creating that channel's
+    /// publisher on first use. Does nothing if MQTT publishing wasn't configured, or beyond
+    /// logging if creating or publishing to this channel's topic fails.
+    fn publish(&mut self, channel: usize, payload: &[u8]) {
+        let Some(base) = &self.base else {
+            return;
+        };
+        if self.sinks.len() <= channel {
+            self.sinks.resize_with(channel + 1, || MqttSlot::Untried);
+        }
+        if matches!(self.sinks[channel], MqttSlot::Untried) {
+            let config = MqttSinkConfig {
+                broker_host: base.broker_host.clone(),
+                broker_port: base.broker_port,
+                topic: format!("{}/{}", base.topic, channel),
+            };
+            self.sinks[channel] =
+                match MqttPublisher::new(&format!("{SNAPSHOT_MQTT_CLIENT_ID}_{channel}"), &config) {
+                    Ok(sink) => MqttSlot::Active(sink),
+                    Err(e) => {
+                        error!(
+                            "Failed to create MQTT snapshot sink for channel {channel}: {e}. \
+                             This channel's snapshots will not be published to MQTT."
+                        );
+                        MqttSlot::Failed
+                    }
+                };
+        }
+        if let MqttSlot::Active(sink) = &mut self.sinks[channel] {
+            if let Err(e) = sink.publish_bytes(payload) {
+                error!("Failed to publish snapshot to MQTT for channel {channel}: {e}");
+            }
+        }
+    }
+}
+
 pub struct Frame {
     pub number: u64,
     pub timestamp: Timestamp,
@@ -144,5 +318,17 @@ pub struct Frame {
     pub clocks: ClockBank,
     pub color_palette: ColorPalette,
     pub positions: PositionBank,
-    pub audio_envelope: UnipolarFloat,
+    pub audio_envelopes: AudioEnvelopes,
+}
+
+/// Work handed to the render thread: either a live show frame to render and publish, or a
+/// recorded frame's already-rendered channels to publish directly, read back by a
+/// [`crate::frame_recording::Player`].
+pub enum RenderJob {
+    Live(Frame),
+    Recorded {
+        number: u64,
+        timestamp: Timestamp,
+        channels: Vec<LayerCollection>,
+    },
 }
@@ -5,9 +5,12 @@ use serde::{Deserialize, Serialize};
 use std::{
     fs::File,
     io::BufWriter,
+    net::SocketAddr,
     path::{Path, PathBuf},
     time::{Duration, Instant},
 };
+use tunnels_lib::mqtt::MqttSinkConfig;
+use tunnels_lib::multicast::MulticastConfig;
 use tunnels_lib::Timestamp;
 
 use crate::{
@@ -16,16 +19,20 @@ use crate::{
     audio::{self, AudioInput},
     clock_bank::{self, ClockBank},
     control::Dispatcher,
+    frame_recording, keyboard,
+    look::Look,
     master_ui,
     master_ui::MasterUI,
     midi::DeviceSpec as MidiDeviceSpec,
     midi_controls::Device,
     mixer,
     mixer::Mixer,
+    multicast_send,
     osc::DeviceSpec as OscDeviceSpec,
     palette::{self, ColorPalette},
     position_bank::{self, PositionBank},
-    send::{start_render_service, Frame},
+    quic_send,
+    send::{self, Frame, RenderJob},
     test_mode::TestModeSetup,
     timesync::TimesyncServer,
     tunnel,
@@ -34,23 +41,55 @@ use crate::{
 /// How often should we autosave the show?
 pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
 
+/// Which transport carries the rendered frame stream to render/video clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RenderTransport {
+    /// A local zmq PUB socket. Assumes render clients are co-located and reachable over a
+    /// reliable link.
+    Local,
+    /// A QUIC server bound to `bind_addr`. For render clients reached over a lossy LAN or WAN,
+    /// where a dropped packet shouldn't stall the whole stream.
+    Quic(SocketAddr),
+    /// UDP multicast, one group per video channel. For a fleet of LAN display nodes watching the
+    /// same channel, where a single multicast send per frame beats one unicast send per client.
+    Multicast(MulticastConfig),
+}
+
 pub struct Show {
     dispatcher: Dispatcher,
     audio_input: AudioInput,
     run_clock_service: bool,
+    render_transport: RenderTransport,
+    /// If set, the clock stream is additionally published to this broker/topic; see
+    /// `send::start_render_service`.
+    clock_mqtt: Option<MqttSinkConfig>,
+    /// If set, each video channel's snapshots are additionally published to a broker topic; see
+    /// `send::start_render_service`.
+    snapshot_mqtt: Option<MqttSinkConfig>,
     state: ShowState,
     save_path: Option<PathBuf>,
     last_save: Option<Instant>,
+    /// Reference instant the control recorder and render frames timestamp themselves against.
+    start: Instant,
+    /// If set, `run` captures every rendered frame to this directory via a `frame_recording`.
+    frame_record_path: Option<PathBuf>,
 }
 
 impl Show {
     /// Create a new show from the provided config.
+    /// If `control_mapping_config_path` is set, the custom MIDI bindings it names are merged
+    /// over the built-in defaults.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        midi_devices: Vec<MidiDeviceSpec>,
+        midi_devices: Vec<MidiDeviceSpec<Device>>,
         osc_devices: Vec<OscDeviceSpec>,
-        audio_input_device: Option<String>,
+        audio_input_devices: Vec<String>,
         run_clock_service: bool,
+        render_transport: RenderTransport,
         save_path: Option<PathBuf>,
+        control_mapping_config_path: Option<PathBuf>,
+        clock_mqtt: Option<MqttSinkConfig>,
+        snapshot_mqtt: Option<MqttSinkConfig>,
     ) -> Result<Self> {
         // Determine if we need to configure a double-wide mixer for APC20 wing.
         let use_wing = midi_devices
@@ -58,11 +97,20 @@ impl Show {
             .any(|spec| spec.device == Device::AkaiApc20);
 
         let n_pages = if use_wing { 2 } else { 1 };
+        let start = Instant::now();
 
         Ok(Self {
-            dispatcher: Dispatcher::new(midi_devices, osc_devices)?,
-            audio_input: AudioInput::new(audio_input_device)?,
+            dispatcher: Dispatcher::new(
+                midi_devices,
+                osc_devices,
+                start,
+                control_mapping_config_path,
+            )?,
+            audio_input: AudioInput::new(audio_input_devices)?,
             run_clock_service,
+            render_transport,
+            clock_mqtt,
+            snapshot_mqtt,
             state: ShowState {
                 ui: MasterUI::new(n_pages),
                 mixer: Mixer::new(n_pages),
@@ -72,9 +120,39 @@ impl Show {
             },
             save_path,
             last_save: None,
+            start,
+            frame_record_path: None,
         })
     }
 
+    /// Advertise the live captured control stream as a DNS-SD "inspector" service, so a remote
+    /// operator can subscribe and watch control traffic in real time without touching the
+    /// console.
+    pub fn publish_control_inspector_feed(&mut self, ctx: &zmq::Context) -> Result<()> {
+        self.dispatcher.publish_inspector_feed(ctx)
+    }
+
+    /// Save everything the control recorder has captured so far to `path`, to be loaded and
+    /// replayed later via `load_recording`.
+    pub fn save_recording(&self, path: &Path) -> Result<()> {
+        self.dispatcher.save_recording(path)
+    }
+
+    /// Load a recorded control log and begin replaying it into the live control stream,
+    /// scheduled against elapsed wall-clock time since this call. If `loop_playback` is set,
+    /// playback restarts from the beginning once the recording is exhausted, looping the
+    /// performance.
+    pub fn load_recording(&mut self, path: &Path, loop_playback: bool) -> Result<()> {
+        self.dispatcher.load_recording(path, loop_playback)
+    }
+
+    /// Capture every frame `run` renders to `dir` as a chunked, self-describing frame recording,
+    /// to be read back and replayed later via `run_playback`. Takes effect the next time `run`
+    /// is called.
+    pub fn start_frame_recording(&mut self, dir: PathBuf) {
+        self.frame_record_path = Some(dir);
+    }
+
     /// Load the saved show at file into self.
     /// Return an error if the dimensions of the loaded data don't match the
     /// current show.
@@ -141,21 +219,45 @@ impl Show {
     pub fn run(&mut self, update_interval: Duration) -> Result<()> {
         info!("Show is starting.");
 
-        // Emit initial UI state.
+        // Emit initial UI state, then flush it straight through so control surfaces start out
+        // in sync even though ordinary state changes are now coalesced until the next tick.
         self.state.ui.emit_state(
             &mut self.state.mixer,
             &mut self.state.clocks,
             &mut self.state.color_palette,
+            &self.state.positions,
             &mut self.audio_input,
             &mut self.dispatcher,
         );
+        self.dispatcher.flush();
 
         let mut frame_number = 0;
         let ctx = zmq::Context::new();
-        let start = Instant::now();
+        let start = self.start;
 
         let _timesync = TimesyncServer::start(&ctx, start)?;
-        let frame_sender = start_render_service(&ctx, self.run_clock_service)?;
+        let recorder = match self.frame_record_path.take() {
+            Some(dir) => Some(frame_recording::Recorder::start(&dir, update_interval)?),
+            None => None,
+        };
+        let frame_sender = match self.render_transport {
+            RenderTransport::Local => send::start_render_service(
+                &ctx,
+                self.run_clock_service,
+                recorder,
+                self.clock_mqtt.clone(),
+                self.snapshot_mqtt.clone(),
+            )?,
+            RenderTransport::Quic(bind_addr) => {
+                quic_send::start_render_service(&ctx, self.run_clock_service, bind_addr, recorder)?
+            }
+            RenderTransport::Multicast(multicast) => multicast_send::start_render_service(
+                &ctx,
+                self.run_clock_service,
+                multicast,
+                recorder,
+            )?,
+        };
 
         let mut last_update = start;
 
@@ -168,15 +270,15 @@ impl Show {
                 let timestamp = Timestamp::since(start);
 
                 if frame_sender
-                    .send(Frame {
+                    .send(RenderJob::Live(Frame {
                         number: frame_number,
                         timestamp,
                         mixer: self.state.mixer.clone(),
                         clocks: self.state.clocks.clone(),
                         color_palette: self.state.color_palette.clone(),
                         positions: self.state.positions.clone(),
-                        audio_envelope: self.audio_input.envelope(),
-                    })
+                        audio_envelopes: self.audio_input.audio_envelopes(),
+                    }))
                     .is_err()
                 {
                     bail!("Render server hung up.  Aborting show.");
@@ -202,26 +304,101 @@ impl Show {
         }
     }
 
+    /// Replay a frame recording captured by `start_frame_recording`, feeding its frames into the
+    /// same render/publish path `run` uses, scheduled at the recording's original inter-frame
+    /// timing. Does not drive any live show state; used for offline rendering, debugging, and
+    /// regression-testing the render pipeline against a captured performance.
+    pub fn run_playback(&mut self, dir: &Path, loop_playback: bool) -> Result<()> {
+        info!("Replaying frame recording from {}.", dir.display());
+        let mut player = frame_recording::Player::load(dir, loop_playback)?;
+        let poll_interval = player.header().render_interval.mul_f64(0.5);
+
+        let ctx = zmq::Context::new();
+        let frame_sender = match self.render_transport {
+            RenderTransport::Local => {
+                send::start_render_service(&ctx, false, None, None, self.snapshot_mqtt.clone())?
+            }
+            RenderTransport::Quic(bind_addr) => {
+                quic_send::start_render_service(&ctx, false, bind_addr, None)?
+            }
+            RenderTransport::Multicast(multicast) => {
+                multicast_send::start_render_service(&ctx, false, multicast, None)?
+            }
+        };
+
+        loop {
+            for record in player.poll()? {
+                if frame_sender
+                    .send(RenderJob::Recorded {
+                        number: record.frame_number,
+                        timestamp: record.timestamp,
+                        channels: record.channels,
+                    })
+                    .is_err()
+                {
+                    bail!("Render server hung up.  Aborting playback.");
+                }
+            }
+            if player.is_finished() {
+                info!("Frame recording playback finished.");
+                return Ok(());
+            }
+            std::thread::sleep(poll_interval);
+        }
+    }
+
     fn update_state(&mut self, delta_t: Duration) {
+        if self.dispatcher.take_resync_needed() {
+            info!("A midi control surface reconnected; pushing a full state resync.");
+            self.state.ui.emit_state(
+                &mut self.state.mixer,
+                &mut self.state.clocks,
+                &mut self.state.color_palette,
+                &self.state.positions,
+                &mut self.audio_input,
+                &mut self.dispatcher,
+            );
+        }
         self.audio_input.update_state(delta_t, &mut self.dispatcher);
-        let audio_envelope = self.audio_input.envelope();
+        let audio_envelopes = self.audio_input.audio_envelopes();
+        let audio_speed = self.audio_input.speed();
+        let audio_tempo_bpm = self.audio_input.tempo_bpm();
         self.state
             .clocks
-            .update_state(delta_t, audio_envelope, &mut self.dispatcher);
-        self.state.mixer.update_state(delta_t, audio_envelope);
+            .update_state(delta_t, audio_speed, audio_tempo_bpm, &mut self.dispatcher);
+        self.state.mixer.update_state(
+            delta_t,
+            &audio_envelopes,
+            &self.state.clocks,
+            &mut self.dispatcher,
+        );
+        self.state
+            .color_palette
+            .update_state(delta_t, &mut self.dispatcher);
+        self.state.ui.update_state(
+            &self.state.clocks,
+            &mut self.state.mixer,
+            &mut self.dispatcher,
+        );
+        // Push any state changes that accumulated this tick out to the control surfaces,
+        // collapsing bursts of updates to the same control down to their latest value.
+        self.dispatcher.flush();
     }
 
     fn service_control_event(&mut self, timeout: Duration) {
         match self.dispatcher.receive(timeout) {
-            Ok(Some(msg)) => self.state.ui.handle_control_message(
-                msg,
-                &mut self.state.mixer,
-                &mut self.state.clocks,
-                &mut self.state.color_palette,
-                &mut self.state.positions,
-                &mut self.audio_input,
-                &mut self.dispatcher,
-            ),
+            Ok(Some(msg)) => {
+                self.state.ui.handle_control_message(
+                    msg,
+                    &mut self.state.mixer,
+                    &mut self.state.clocks,
+                    &mut self.state.color_palette,
+                    &mut self.state.positions,
+                    &mut self.audio_input,
+                    &mut self.dispatcher,
+                );
+                self.dispatcher.flush();
+            }
             Ok(None) => (),
             Err(e) => {
                 warn!("{}", e);
@@ -240,8 +417,21 @@ pub enum ControlMessage {
     Position(position_bank::ControlMessage),
     Audio(audio::ControlMessage),
     MasterUI(master_ui::ControlMessage),
+    Keyboard(keyboard::ControlMessage),
+    /// Instantly swap the current channel's armed preview beam into program.
+    Cut,
+    /// Begin a timed crossfade from the current channel's program beam to its armed preview.
+    Auto(Duration),
+    /// Arm a look in the mixer-wide preview bus, to be brought to program via `LookCut` or
+    /// `LookAuto`.
+    LookPreview(Look),
+    /// Instantly swap the mixer-wide preview bus's armed look into program.
+    LookCut,
+    /// Begin a timed crossfade from program to the mixer-wide preview bus's armed look.
+    LookAuto(Duration),
 }
 
+#[derive(Clone, PartialEq)]
 pub enum StateChange {
     Tunnel(tunnel::StateChange),
     Animation(animation::StateChange),
@@ -249,8 +439,10 @@ pub enum StateChange {
     Mixer(mixer::StateChange),
     Clock(clock_bank::StateChange),
     ColorPalette(palette::StateChange),
+    Position(position_bank::StateChange),
     Audio(audio::StateChange),
     MasterUI(master_ui::StateChange),
+    Keyboard(keyboard::StateChange),
 }
 
 /// Proxy type for easily saving and loading show state.
@@ -267,9 +459,10 @@ pub struct ShowState {
 mod test {
     use std::{collections::HashSet, sync::Arc};
 
-    use tunnels_lib::{number::UnipolarFloat, ArcSegment, LayerCollection};
+    use tunnels_lib::{ArcSegment, LayerCollection};
 
     use super::*;
+    use crate::audio::AudioEnvelopes;
     use crate::test_mode::stress;
     use insta::assert_yaml_snapshot;
 
@@ -278,7 +471,17 @@ mod test {
     /// tunnel state or rendering algorithm.
     #[test]
     fn test_render() -> Result<()> {
-        let mut show = Show::new(Vec::new(), Vec::new(), None, false, None)?;
+        let mut show = Show::new(
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            false,
+            RenderTransport::Local,
+            None,
+            None,
+            None,
+            None,
+        )?;
 
         show.test_mode(stress);
 
@@ -300,7 +503,7 @@ mod test {
             &show.state.clocks,
             &show.state.color_palette,
             &show.state.positions,
-            UnipolarFloat::ZERO,
+            &AudioEnvelopes::default(),
         );
 
         // Should have the expected number of video channels.
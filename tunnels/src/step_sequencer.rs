@@ -0,0 +1,97 @@
+//! A phase-driven step sequencer, modeled on a hardware frame sequencer.
+//!
+//! Subdivides a clock's phase into a fixed number of equal steps and fires a
+//! discrete event each time the clock's phase crosses into a new step, so an
+//! operator can program a repeating sequence of beam recalls or animation
+//! swaps that stays locked to the master tempo.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{beam_store::BeamStoreAddr, clock_bank::ClockIdx, clock_bank::ClockStore, tunnel::AnimationIdx};
+
+/// Index of a step in a `StepSequencer`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct StepIdx(pub usize);
+
+/// What a single step does when the sequencer advances into it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum StepPayload {
+    /// Recall a beam from the beam store into the current channel.
+    RecallBeam(BeamStoreAddr),
+    /// Swap the current channel's active animation.
+    Animation(AnimationIdx),
+}
+
+/// Subdivides a clock's phase into `steps.len()` equal buckets and detects
+/// when the clock has advanced into a new one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepSequencer {
+    /// Which clock in the bank drives this sequencer.
+    source: ClockIdx,
+    steps: Vec<Option<StepPayload>>,
+    /// The step bucket, `floor(phase * steps.len())`, we were in as of the
+    /// last call to `update`. `None` until the first update, so we don't
+    /// fire a spurious advance on startup. Taken from the clock's wrapped
+    /// phase, so it stays within `0..=steps.len()`; any change of bucket,
+    /// in either direction, counts as an advance.
+    current_bucket: Option<i64>,
+}
+
+impl StepSequencer {
+    /// Construct a new sequencer with `n_steps` empty steps, driven by `source`.
+    pub fn new(n_steps: usize, source: ClockIdx) -> Self {
+        Self {
+            source,
+            steps: vec![None; n_steps],
+            current_bucket: None,
+        }
+    }
+
+    pub fn n_steps(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn step(&self, index: StepIdx) -> Option<StepPayload> {
+        self.steps[index.0]
+    }
+
+    /// Program a step with a new payload, replacing whatever was there.
+    pub fn set_step(&mut self, index: StepIdx, payload: Option<StepPayload>) {
+        self.steps[index.0] = payload;
+    }
+
+    /// Check the source clock's current phase against the last-seen step
+    /// bucket. If the clock has crossed into a new step since the last call,
+    /// return that step's index and payload. Handles wraparound and negative
+    /// rates correctly, since the bucket walks backward along with the phase.
+    pub fn update(&mut self, clocks: &impl ClockStore) -> Option<(StepIdx, Option<StepPayload>)> {
+        let phase = clocks.phase(self.source).val();
+        let bucket = (phase * self.steps.len() as f64).floor() as i64;
+
+        let advanced = matches!(self.current_bucket, Some(previous) if previous != bucket);
+        self.current_bucket = Some(bucket);
+        if !advanced {
+            return None;
+        }
+
+        let index = bucket.rem_euclid(self.steps.len() as i64) as usize;
+        Some((StepIdx(index), self.steps[index]))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ControlMessage {
+    /// Program a step with a new payload, replacing whatever was there.
+    SetStep((StepIdx, Option<StepPayload>)),
+    /// Manually fire a step's payload right now, independent of the clock.
+    TriggerStep(StepIdx),
+}
+
+#[derive(Debug, Clone)]
+pub enum StateChange {
+    /// A step has been programmed (or cleared). Also used for grid LED feedback.
+    Step((StepIdx, Option<StepPayload>)),
+    /// The sequencer has advanced into a new step, carrying that step's payload, if any.
+    /// Output only, no effect as control.
+    StepAdvanced((StepIdx, Option<StepPayload>)),
+}
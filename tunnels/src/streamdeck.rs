@@ -0,0 +1,192 @@
+//! Driver for the Elgato Stream Deck, a grid of physical buttons each with its own small LCD,
+//! connected over USB HID rather than MIDI. It's exposed as `midi_controls::Device::StreamDeck`
+//! so it can be bound through the same `ControlMap` as every other control surface, but its
+//! connection and feedback mechanisms have nothing to do with `midir`, so it gets its own driver
+//! here instead of living inside `midi::Manager`.
+//!
+//! Only the original 15-key Stream Deck is supported; the Mini and XL have different key counts,
+//! icon sizes, and (for the XL) a different report format, and aren't implemented yet.
+
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use hidapi::HidApi;
+use image::{Rgb as ImageRgb, RgbImage};
+use log::error;
+use std::sync::mpsc::Sender;
+use tunnels_lib::color::Rgb;
+
+use crate::control::ControlEvent;
+use crate::midi::{Event, EventType, Mapping};
+use crate::midi_controls::Device;
+
+const VENDOR_ID: u16 = 0x0fd9;
+const PRODUCT_ID: u16 = 0x0060;
+
+/// The keys are arranged 5 wide by 3 tall.
+pub const KEY_COUNT: u8 = 15;
+
+const ICON_SIZE: u32 = 72;
+
+/// Number of pages of controls that can be addressed by paging the deck's physical keys, so a
+/// 15-key deck can reach more controls than it has buttons for. Only paging the device itself is
+/// implemented here; which controls live on which page is up to the caller, the same way
+/// `midi_controls::mixer`/`master_ui` page their own controls across APC40/APC20 banks.
+pub const PAGE_COUNT: usize = 4;
+
+/// Open the Stream Deck identified by `serial` (or the first one found, if `None`), spawn a
+/// thread that translates its key-down/key-up HID reports into `ControlEvent`s addressed to
+/// `Device::StreamDeck`, and return a handle for rendering feedback back onto its keys.
+///
+/// Key events are translated into ordinary midi `Event`s - `NoteOn`/`NoteOff` with the key index
+/// as the control number and channel 0 - so they flow through the existing
+/// `ControlMap::dispatch` path unchanged, the same as every other control surface.
+pub fn connect(serial: Option<&str>, send: Sender<ControlEvent>) -> Result<Output> {
+    let api = HidApi::new().context("initializing HID API for Stream Deck")?;
+    let path = api
+        .device_list()
+        .find(|d| {
+            d.vendor_id() == VENDOR_ID
+                && d.product_id() == PRODUCT_ID
+                && serial.map(|s| d.serial_number() == Some(s)).unwrap_or(true)
+        })
+        .context("no matching Stream Deck found")?
+        .path()
+        .to_owned();
+
+    let reader = api
+        .open_path(&path)
+        .context("opening Stream Deck for key input")?;
+    let writer = api
+        .open_path(&path)
+        .context("opening Stream Deck for key feedback")?;
+
+    thread::spawn(move || {
+        let mut buf = [0u8; 1 + KEY_COUNT as usize];
+        let mut pressed = [false; KEY_COUNT as usize];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(_) => {
+                    for (key, was_pressed) in pressed.iter_mut().enumerate() {
+                        let now_pressed = buf[1 + key] != 0;
+                        if now_pressed == *was_pressed {
+                            continue;
+                        }
+                        *was_pressed = now_pressed;
+                        let event = Event {
+                            mapping: Mapping {
+                                event_type: if now_pressed {
+                                    EventType::NoteOn
+                                } else {
+                                    EventType::NoteOff
+                                },
+                                channel: 0,
+                                control: key as u8,
+                            },
+                            value: if now_pressed { 127 } else { 0 },
+                            value_hi_res: None,
+                        };
+                        if send
+                            .send(ControlEvent::Midi((Device::StreamDeck, event)))
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!("Error reading from Stream Deck: {e}");
+                    thread::sleep(Duration::from_millis(500));
+                }
+            }
+        }
+    });
+
+    Ok(Output {
+        device: writer,
+        page: 0,
+    })
+}
+
+/// A connected Stream Deck, for rendering feedback onto its keys.
+pub struct Output {
+    device: hidapi::HidDevice,
+    /// Which page of controls is currently visible. Purely bookkeeping for the caller; this
+    /// driver doesn't interpret page numbers itself.
+    page: usize,
+}
+
+impl Output {
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    /// Switch to a different page of controls. Out-of-range pages saturate at the last page.
+    pub fn set_page(&mut self, page: usize) {
+        self.page = page.min(PAGE_COUNT - 1);
+    }
+
+    /// Render a solid color onto one key, or black to turn it off.
+    pub fn render_key(&mut self, key: u8, color: Rgb) {
+        if let Err(e) = self.send_key_image(key, color) {
+            error!("Failed to render Stream Deck key {key}: {e}");
+        }
+    }
+
+    fn send_key_image(&self, key: u8, color: Rgb) -> Result<()> {
+        let pixel = ImageRgb([
+            (color.red.val() * 255.) as u8,
+            (color.green.val() * 255.) as u8,
+            (color.blue.val() * 255.) as u8,
+        ]);
+        let image = RgbImage::from_pixel(ICON_SIZE, ICON_SIZE, pixel);
+
+        // The real firmware wants raw BGR pixel data, with no container format, split across one
+        // or more numbered "SET_IMAGE" output reports. A solid color fill always fits in a single
+        // report, so unlike the real protocol this doesn't implement multi-packet chunking for
+        // photographic images.
+        const HEADER_LEN: usize = 8;
+        let mut report = vec![0u8; HEADER_LEN + (ICON_SIZE * ICON_SIZE * 3) as usize];
+        report[0] = 0x02; // SET_IMAGE report id
+        report[1] = 0x01; // this report holds the whole (only) page of image data
+        report[2] = 1; // last-page marker
+        report[4] = key;
+        for (i, px) in image.pixels().enumerate() {
+            let out = &mut report[HEADER_LEN + i * 3..HEADER_LEN + i * 3 + 3];
+            out.copy_from_slice(&[px.0[2], px.0[1], px.0[0]]);
+        }
+        self.device
+            .write(&report)
+            .context("writing Stream Deck key image report")?;
+        Ok(())
+    }
+}
+
+/// Defines a group of keys standing for mutually-exclusive choices, analogous to
+/// `midi_controls::RadioButtons` but rendering feedback via key color instead of MIDI note
+/// velocity.
+pub struct RadioKeys {
+    keys: Vec<u8>,
+    lit: Rgb,
+    unlit: Rgb,
+}
+
+impl RadioKeys {
+    pub fn new(keys: Vec<u8>, lit: Rgb, unlit: Rgb) -> Self {
+        Self { keys, lit, unlit }
+    }
+
+    /// Light only the key at `selected_index`, if any, leaving every other key in this group
+    /// unlit. Performs no check that the selected index is actually present in this group.
+    pub fn select(&self, selected_index: Option<usize>, output: &mut Output) {
+        for (i, key) in self.keys.iter().enumerate() {
+            let color = if Some(i) == selected_index {
+                self.lit
+            } else {
+                self.unlit
+            };
+            output.render_key(*key, color);
+        }
+    }
+}
@@ -1,23 +1,104 @@
 //! TODO: destroy this part of the codebase once the clients no longer expect it
-use anyhow::Result;
+use anyhow::{bail, Result};
 use log::{error, info};
+use std::collections::VecDeque;
+use std::io;
 use std::thread;
 use std::time::Instant;
 
-use rmp_serde::Serializer;
-use serde::Serialize;
-use tunnels_lib::{RunFlag, Timestamp};
+use rmp_serde::{Deserializer, Serializer};
+use serde::{Deserialize, Serialize};
+use tunnels_lib::time_transport::{RxToken, TimeTransport, TxToken};
+use tunnels_lib::{RunFlag, Timestamp, TimesyncReply};
 
-use zmq::Context;
+use zmq::{Context, Socket};
 
 const PORT: u64 = 8989;
+
+/// How many recent probes to retain when estimating the offset, balancing how quickly we react
+/// to real clock drift against how much a burst of jitter can skew the estimate.
+const WINDOW_SIZE: usize = 16;
+
+/// Carries one request or reply over a zmq REQ or REP socket. The REQ/REP ordering (recv-then-
+/// send for the server, send-then-recv for the client) is enforced by zmq itself, so the same
+/// wrapper serves both ends.
+///
+/// Receiving goes through `recv_msg` rather than `recv_bytes`, so `ZmqRxToken` borrows zmq's own
+/// message buffer instead of copying it into a freshly allocated `Vec`. Sending reuses a scratch
+/// buffer across calls instead of allocating one per message, the same way the REP loop already
+/// reused `resp_buf` before this transport existed.
+pub struct ZmqTransport {
+    socket: Socket,
+    tx_buf: Vec<u8>,
+}
+
+impl ZmqTransport {
+    /// Wrap an already-bound/connected socket. Any receive timeout should be configured on
+    /// `socket` before constructing this, the same as the raw zmq calls this replaces.
+    pub fn new(socket: Socket) -> Self {
+        Self {
+            socket,
+            tx_buf: Vec::new(),
+        }
+    }
+}
+
+fn zmq_err_to_io(e: zmq::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, e)
+}
+
+impl TimeTransport for ZmqTransport {
+    type RxToken<'a> = ZmqRxToken;
+    type TxToken<'a> = ZmqTxToken<'a>;
+
+    fn receive(&mut self) -> io::Result<Option<ZmqRxToken>> {
+        match self.socket.recv_msg(0) {
+            Ok(msg) => Ok(Some(ZmqRxToken(msg))),
+            Err(zmq::Error::EAGAIN) => Ok(None),
+            Err(e) => Err(zmq_err_to_io(e)),
+        }
+    }
+
+    fn transmit(&mut self) -> io::Result<ZmqTxToken<'_>> {
+        Ok(ZmqTxToken {
+            socket: &self.socket,
+            buf: &mut self.tx_buf,
+        })
+    }
+}
+
+pub struct ZmqRxToken(zmq::Message);
+
+impl RxToken for ZmqRxToken {
+    fn consume<R>(self, f: impl FnOnce(&[u8]) -> R) -> R {
+        f(&self.0)
+    }
+}
+
+pub struct ZmqTxToken<'a> {
+    socket: &'a Socket,
+    buf: &'a mut Vec<u8>,
+}
+
+impl<'a> TxToken for ZmqTxToken<'a> {
+    fn consume<R>(self, len: usize, f: impl FnOnce(&mut [u8]) -> R) -> R {
+        self.buf.clear();
+        self.buf.resize(len, 0);
+        let result = f(&mut self.buf[..]);
+        if let Err(e) = self.socket.send(&self.buf[..], 0) {
+            error!("Timesync transport send error: {e}.");
+        }
+        result
+    }
+}
+
 pub struct TimesyncServer {
     join_handle: Option<thread::JoinHandle<()>>,
     run: RunFlag,
 }
 
 impl TimesyncServer {
-    /// Start the timesync server.
+    /// Start the timesync server over the usual zmq REP socket.
     /// The server will run until it is dropped.
     pub fn start(ctx: &Context, start: Instant) -> Result<Self> {
         let socket = ctx.socket(zmq::REP)?;
@@ -25,7 +106,18 @@ impl TimesyncServer {
         socket.bind(&addr)?;
         // time out once per second
         socket.set_rcvtimeo(1000)?;
-        let run = RunFlag::default();
+        Self::start_with_transport(ZmqTransport::new(socket), start)
+    }
+
+    /// Run the timesync REP loop against any [`TimeTransport`], rather than assuming zmq. Lets
+    /// tests exercise the protocol over an in-process transport without binding a real socket,
+    /// and opens the door to e.g. a plain-UDP transport for clients that can't link libzmq.
+    /// The server will run until it is dropped.
+    pub fn start_with_transport<T>(mut transport: T, start: Instant) -> Result<Self>
+    where
+        T: TimeTransport + Send + 'static,
+    {
+        let run = RunFlag::new();
         let run_local = run.clone();
 
         // start up the service in a new thread
@@ -37,21 +129,34 @@ impl TimesyncServer {
                     return;
                 }
 
-                match socket.recv_bytes(0) {
-                    Err(zmq::Error::EAGAIN) => (),
+                match transport.receive() {
+                    Ok(None) => (),
                     Err(e) => {
-                        error!("Timesync receieve error: {e}.");
+                        error!("Timesync receive error: {e}.");
                     }
-                    Ok(_) => {
-                        if let Err(e) =
-                            Timestamp::since(start).serialize(&mut Serializer::new(&mut resp_buf))
-                        {
+                    Ok(Some(rx)) => {
+                        // The request carries no payload; only its arrival matters.
+                        rx.consume(|_req| ());
+                        // Stamp our receipt time (t1) as soon as the request arrives, and our
+                        // transmit time (t2) right before we send the reply, so the client can
+                        // estimate both offset and round-trip delay with the standard NTP
+                        // four-timestamp calculation.
+                        let receive_time = Timestamp::since(start);
+                        let reply = TimesyncReply {
+                            receive_time,
+                            transmit_time: Timestamp::since(start),
+                        };
+                        resp_buf.clear();
+                        if let Err(e) = reply.serialize(&mut Serializer::new(&mut resp_buf)) {
                             error!("Timesync serialization error: {e}.");
+                            continue;
                         }
-                        if let Err(e) = socket.send(&resp_buf, 0) {
-                            error!("Timesync send error: {e}.");
+                        match transport.transmit() {
+                            Ok(tx) => {
+                                tx.consume(resp_buf.len(), |out| out.copy_from_slice(&resp_buf));
+                            }
+                            Err(e) => error!("Timesync transmit error: {e}."),
                         }
-                        resp_buf.clear();
                     }
                 }
             })?;
@@ -71,3 +176,184 @@ impl Drop for TimesyncServer {
         info!("Timesync server shut down.");
     }
 }
+
+/// One probe's round-trip delay and resulting offset estimate.
+#[derive(Copy, Clone, Debug)]
+struct Sample {
+    /// Estimated offset between the server's clock and ours (server - ours).
+    offset: Timestamp,
+    round_trip_delay: Timestamp,
+}
+
+/// Estimate the offset between this host's clock and a `TimesyncServer`'s, filtering network
+/// jitter out of individual round trips.
+///
+/// Each probe stamps the local send time (t0), reads the server's `transmit_time` out of the
+/// reply as its single timestamp (ts), and stamps the local receive time (t1). Per Cristian's
+/// algorithm, `offset = ts + rtt/2 - t1` assumes the trip there and back took equally long, which
+/// only holds for the least-delayed probes; queuing or contention on a busier probe inflates its
+/// `rtt` and skews its offset estimate away from the truth. Rather than trusting the latest
+/// sample, a sliding window of recent probes lets us pick the one with the minimum `rtt` as the
+/// filtered estimate, the same "reject everything but the cleanest edge" idea a hardware DDMTD
+/// deglitcher uses, and separately report the window's median offset to catch transient outliers
+/// the minimum-delay pick alone might still miss.
+pub struct TimesyncClient<T: TimeTransport = ZmqTransport> {
+    transport: T,
+    reference_time: Instant,
+    window: VecDeque<Sample>,
+}
+
+impl TimesyncClient<ZmqTransport> {
+    /// Connect to a `TimesyncServer` running on `host` over the usual zmq REQ socket.
+    pub fn new(host: &str, ctx: &Context, reference_time: Instant) -> Result<Self> {
+        let socket = ctx.socket(zmq::REQ)?;
+        let addr = format!("tcp://{host}:{PORT}");
+        socket.connect(&addr)?;
+        Ok(Self::with_transport(ZmqTransport::new(socket), reference_time))
+    }
+}
+
+impl<T: TimeTransport> TimesyncClient<T> {
+    /// Probe a `TimesyncServer` over any [`TimeTransport`], rather than assuming zmq.
+    pub fn with_transport(transport: T, reference_time: Instant) -> Self {
+        Self {
+            transport,
+            reference_time,
+            window: VecDeque::with_capacity(WINDOW_SIZE),
+        }
+    }
+
+    /// Perform one probe/reply exchange with the server, fold the resulting sample into the
+    /// sliding window (discarding the oldest sample if it's full), and return the updated
+    /// minimum-delay offset estimate.
+    pub fn poll_offset(&mut self) -> Result<Timestamp> {
+        let t0 = Timestamp::since(self.reference_time);
+        // The request carries no payload; only its arrival matters to the server.
+        self.transport.transmit()?.consume(0, |_| ());
+        let reply = match self.transport.receive()? {
+            Some(rx) => {
+                rx.consume(|buf| TimesyncReply::deserialize(&mut Deserializer::new(buf)))?
+            }
+            None => bail!("timesync transport timed out waiting for a reply"),
+        };
+        let t1 = Timestamp::since(self.reference_time);
+
+        let round_trip_delay = t1 - t0;
+        let offset = reply.transmit_time + Timestamp(round_trip_delay.0 / 2) - t1;
+
+        if self.window.len() == WINDOW_SIZE {
+            self.window.pop_front();
+        }
+        self.window.push_back(Sample {
+            offset,
+            round_trip_delay,
+        });
+
+        Ok(self.filtered_offset())
+    }
+
+    /// The offset from whichever sample in the current window had the minimum round-trip delay.
+    fn filtered_offset(&self) -> Timestamp {
+        self.window
+            .iter()
+            .min_by_key(|s| s.round_trip_delay)
+            .map(|s| s.offset)
+            .unwrap_or(Timestamp(0))
+    }
+
+    /// The median offset across the current window, to deglitch transient outliers that the
+    /// minimum-delay pick alone might still miss.
+    pub fn median_offset(&self) -> Timestamp {
+        let mut offsets: Vec<i64> = self.window.iter().map(|s| s.offset.0).collect();
+        if offsets.is_empty() {
+            return Timestamp(0);
+        }
+        offsets.sort_unstable();
+        let mid = offsets.len() / 2;
+        if offsets.len() % 2 == 0 {
+            Timestamp((offsets[mid - 1] + offsets[mid]) / 2)
+        } else {
+            Timestamp(offsets[mid])
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender};
+    use std::time::Duration;
+
+    /// An in-process transport backed by a pair of channels, so the full request/reply exchange
+    /// can be driven against a real `TimesyncServer`/`TimesyncClient` pair without binding a
+    /// socket.
+    struct ChannelTransport {
+        tx: SyncSender<Vec<u8>>,
+        rx: Receiver<Vec<u8>>,
+    }
+
+    /// Build a pair of linked transports: whatever one end transmits, the other end receives.
+    fn channel_pair() -> (ChannelTransport, ChannelTransport) {
+        let (tx_a, rx_b) = sync_channel(1);
+        let (tx_b, rx_a) = sync_channel(1);
+        (
+            ChannelTransport { tx: tx_a, rx: rx_a },
+            ChannelTransport { tx: tx_b, rx: rx_b },
+        )
+    }
+
+    impl TimeTransport for ChannelTransport {
+        type RxToken<'a> = ChannelRxToken;
+        type TxToken<'a> = ChannelTxToken<'a>;
+
+        fn receive(&mut self) -> io::Result<Option<ChannelRxToken>> {
+            match self.rx.recv_timeout(Duration::from_secs(1)) {
+                Ok(buf) => Ok(Some(ChannelRxToken(buf))),
+                Err(RecvTimeoutError::Timeout) => Ok(None),
+                Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "channel transport peer dropped",
+                )),
+            }
+        }
+
+        fn transmit(&mut self) -> io::Result<ChannelTxToken<'_>> {
+            Ok(ChannelTxToken { tx: &self.tx })
+        }
+    }
+
+    struct ChannelRxToken(Vec<u8>);
+
+    impl RxToken for ChannelRxToken {
+        fn consume<R>(self, f: impl FnOnce(&[u8]) -> R) -> R {
+            f(&self.0)
+        }
+    }
+
+    struct ChannelTxToken<'a> {
+        tx: &'a SyncSender<Vec<u8>>,
+    }
+
+    impl<'a> TxToken for ChannelTxToken<'a> {
+        fn consume<R>(self, len: usize, f: impl FnOnce(&mut [u8]) -> R) -> R {
+            let mut buf = vec![0u8; len];
+            let result = f(&mut buf);
+            // Only tests drive this transport, and they always expect the peer to still be
+            // listening.
+            self.tx.send(buf).unwrap();
+            result
+        }
+    }
+
+    #[test]
+    fn test_channel_transport_round_trip() {
+        let (server_transport, client_transport) = channel_pair();
+        let start = Instant::now();
+        let _server = TimesyncServer::start_with_transport(server_transport, start).unwrap();
+        let mut client = TimesyncClient::with_transport(client_transport, start);
+        client.poll_offset().unwrap();
+        // The offset between two clocks sharing the same `start` reference should be small; this
+        // is mostly exercising that the exchange completes at all over the in-process transport.
+        assert!(client.median_offset().0.abs() < 1_000_000);
+    }
+}
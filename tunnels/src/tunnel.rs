@@ -1,12 +1,14 @@
 use crate::{
     animation::Animation,
     animation_target::AnimationTarget,
-    clock_bank::ClockBank,
+    audio::AudioEnvelopes,
+    clock_bank::{ClockBank, ClockIdx, ClockIdxExt, ClockStore},
     palette::{ColorPalette, ColorPaletteIdx},
     position_bank::{PositionBank, PositionIdx},
     waveforms::WaveformArgs,
 };
 use crate::{master_ui::EmitStateChange as EmitShowStateChange, waveforms::sawtooth};
+use log::error;
 use serde::{Deserialize, Serialize};
 use std::cmp::max;
 use std::time::Duration;
@@ -50,9 +52,34 @@ pub struct Tunnel {
     blacking: BipolarFloat,
     curr_rot_angle: Phase,
     curr_marquee_angle: Phase,
+    /// If Some, drive rotation angle directly from this clock's phase instead of free-running
+    /// from rot_speed, locking spin across every tunnel that shares the clock.
+    rotation_clock: Option<ClockIdx>,
+    /// Number of full rotations per clock cycle when rotation_clock is set.
+    rotation_clock_multiplier: u32,
+    /// If Some, drive marquee angle directly from this clock's phase instead of free-running
+    /// from marquee_speed.
+    marquee_clock: Option<ClockIdx>,
+    /// Number of full marquee cycles per clock cycle when marquee_clock is set.
+    marquee_clock_multiplier: u32,
     x_offset: Smoother<f64>,
     y_offset: Smoother<f64>,
     anims: [TargetedAnimation; N_ANIM],
+    /// An in-progress timed morph from this tunnel's state to a stored preset.
+    crossfade: Option<Crossfade>,
+    /// Reflect the rendered pattern across the horizontal axis.
+    mirror_y: bool,
+    /// Reflect the rendered pattern across the vertical axis.
+    mirror_x: bool,
+    /// Number of rotationally-symmetric copies of the pattern to render, evenly spaced around
+    /// the marquee. 1 means no extra copies.
+    symmetry: u8,
+    /// If Some, inject sudden brightness flashes on rising edges of the audio envelope.
+    lightning: Option<LightningConfig>,
+    /// Current transient brightness boost from the lightning effect, decaying toward zero.
+    flash_level: f64,
+    /// The audio envelope observed on the previous frame, used to detect a rising edge.
+    prev_audio_envelope: UnipolarFloat,
 }
 
 impl Default for Tunnel {
@@ -63,17 +90,17 @@ impl Default for Tunnel {
             thickness: Smoother::new(
                 UnipolarFloat::new(0.1),
                 Self::GEOM_SMOOTH_TIME,
-                SmoothMode::Linear,
+                SmoothMode::Smoothstep,
             ),
             size: Smoother::new(
                 UnipolarFloat::new(0.5),
                 Self::GEOM_SMOOTH_TIME,
-                SmoothMode::Linear,
+                SmoothMode::Smoothstep,
             ),
             aspect_ratio: Smoother::new(
                 UnipolarFloat::new(0.5),
                 Self::GEOM_SMOOTH_TIME,
-                SmoothMode::Linear,
+                SmoothMode::Smoothstep,
             ),
             col_center: UnipolarFloat::ZERO,
             col_width: UnipolarFloat::ZERO,
@@ -85,9 +112,20 @@ impl Default for Tunnel {
             blacking: BipolarFloat::new(0.15),
             curr_rot_angle: Phase::ZERO,
             curr_marquee_angle: Phase::ZERO,
-            x_offset: Smoother::new(0.0, Self::MOVE_SMOOTH_TIME, SmoothMode::Linear),
-            y_offset: Smoother::new(0.0, Self::MOVE_SMOOTH_TIME, SmoothMode::Linear),
+            rotation_clock: None,
+            rotation_clock_multiplier: 1,
+            marquee_clock: None,
+            marquee_clock_multiplier: 1,
+            x_offset: Smoother::new(0.0, Self::MOVE_SMOOTH_TIME, SmoothMode::EaseInOutCubic),
+            y_offset: Smoother::new(0.0, Self::MOVE_SMOOTH_TIME, SmoothMode::EaseInOutCubic),
             anims: Default::default(),
+            crossfade: None,
+            mirror_y: false,
+            mirror_x: false,
+            symmetry: 1,
+            lightning: None,
+            flash_level: 0.0,
+            prev_audio_envelope: UnipolarFloat::ZERO,
         }
     }
 }
@@ -126,8 +164,122 @@ impl Tunnel {
         self.anims.iter_mut()
     }
 
+    /// Capture this tunnel's speed, geometry, offset, color, segment, blacking and palette/position
+    /// parameters as a snapshot, suitable for storing as a preset and later crossfading back into
+    /// via `crossfade_to`. Clock locks, lightning, mirroring and symmetry are not captured.
+    pub fn snapshot(&self) -> TunnelSnapshot {
+        TunnelSnapshot {
+            marquee_speed: self.marquee_speed,
+            rot_speed: self.rot_speed,
+            thickness: self.thickness.target(),
+            size: self.size.target(),
+            aspect_ratio: self.aspect_ratio.target(),
+            x_offset: self.x_offset.target(),
+            y_offset: self.y_offset.target(),
+            col_center: self.col_center,
+            col_width: self.col_width,
+            col_spread: self.col_spread,
+            col_sat: self.col_sat,
+            segs: self.segs,
+            blacking: self.blacking,
+            palette_selection: self.palette_selection,
+            position_selection: self.position_selection,
+        }
+    }
+
+    /// Begin a timed morph from this tunnel's current state to `target`, overwriting any
+    /// crossfade already in progress.
+    pub fn crossfade_to(&mut self, target: TunnelSnapshot, dur: Duration) {
+        self.crossfade = Some(Crossfade {
+            from: self.snapshot(),
+            to: target,
+            elapsed: Duration::ZERO,
+            dur,
+        });
+    }
+
+    /// Advance any in-progress crossfade, applying the interpolated parameters for this frame.
+    /// Returns true if a crossfade is in progress (and so this tunnel's state should be emitted).
+    fn update_crossfade(&mut self, delta_t: Duration) -> bool {
+        let Some(crossfade) = &mut self.crossfade else {
+            return false;
+        };
+        crossfade.elapsed += delta_t;
+        let t = crossfade.progress();
+        let (from, to) = (crossfade.from, crossfade.to);
+        let done = crossfade.is_complete();
+
+        self.marquee_speed = BipolarFloat::new(lerp(from.marquee_speed.val(), to.marquee_speed.val(), t));
+        self.rot_speed = BipolarFloat::new(lerp(from.rot_speed.val(), to.rot_speed.val(), t));
+        self.thickness
+            .set_target(UnipolarFloat::new(lerp(from.thickness.val(), to.thickness.val(), t)));
+        self.size
+            .set_target(UnipolarFloat::new(lerp(from.size.val(), to.size.val(), t)));
+        self.aspect_ratio.set_target(UnipolarFloat::new(lerp(
+            from.aspect_ratio.val(),
+            to.aspect_ratio.val(),
+            t,
+        )));
+        self.x_offset.set_target(lerp(from.x_offset, to.x_offset, t));
+        self.y_offset.set_target(lerp(from.y_offset, to.y_offset, t));
+        self.col_center = UnipolarFloat::new(lerp(from.col_center.val(), to.col_center.val(), t));
+        self.col_width = UnipolarFloat::new(lerp(from.col_width.val(), to.col_width.val(), t));
+        self.col_spread = UnipolarFloat::new(lerp(from.col_spread.val(), to.col_spread.val(), t));
+        self.col_sat = UnipolarFloat::new(lerp(from.col_sat.val(), to.col_sat.val(), t));
+        self.blacking = BipolarFloat::new(lerp(from.blacking.val(), to.blacking.val(), t));
+
+        // Discrete parameters switch over at the halfway point rather than interpolating.
+        let use_target = t >= 0.5;
+        self.segs = if use_target { to.segs } else { from.segs };
+        self.palette_selection = if use_target {
+            to.palette_selection
+        } else {
+            from.palette_selection
+        };
+        self.position_selection = if use_target {
+            to.position_selection
+        } else {
+            from.position_selection
+        };
+
+        if done {
+            self.crossfade = None;
+        }
+        true
+    }
+
     /// Update the state of this tunnel in preparation for drawing a frame.
-    pub fn update_state(&mut self, delta_t: Duration, audio_envelope: UnipolarFloat) {
+    pub fn update_state<E: EmitShowStateChange>(
+        &mut self,
+        delta_t: Duration,
+        audio_envelope: UnipolarFloat,
+        external_clocks: &ClockBank,
+        emitter: &mut E,
+    ) {
+        if self.update_crossfade(delta_t) {
+            self.emit_state(emitter);
+        }
+
+        // Update the lightning flash envelope, if armed: trigger on a rising edge through the
+        // threshold, otherwise decay exponentially toward zero.
+        if let Some(lightning) = self.lightning {
+            let rising_edge = audio_envelope > lightning.threshold
+                && self.prev_audio_envelope <= lightning.threshold;
+            if rising_edge {
+                self.flash_level = lightning.intensity;
+            } else {
+                let decay_secs = lightning.decay.as_secs_f64();
+                self.flash_level *= if decay_secs > 0.0 {
+                    (-delta_t.as_secs_f64() / decay_secs).exp()
+                } else {
+                    0.0
+                };
+            }
+        } else {
+            self.flash_level = 0.0;
+        }
+        self.prev_audio_envelope = audio_envelope;
+
         // Update smoothers.
         self.x_offset.update_state(delta_t);
         self.y_offset.update_state(delta_t);
@@ -137,19 +289,34 @@ impl Tunnel {
 
         // Update the state of the animations.
         for anim in &mut self.anims {
-            anim.animation.update_state(delta_t, audio_envelope);
+            anim.animation
+                .update_state(delta_t, audio_envelope, external_clocks, emitter);
         }
         let timestep_secs = delta_t.as_secs_f64();
 
-        // calulcate the rotation
-        // delta_t*30. implies the same speed scale as we had at 30fps with evolution tied to frame
-        self.curr_rot_angle +=
-            (scale_speed(self.rot_speed).val() * timestep_secs * 30.) * ROT_SPEED_SCALE;
+        // If locked to a clock, drive the angle directly from its phase, scaled by the number of
+        // rotations per clock cycle. Otherwise free-run from the speed knob as before, picking up
+        // from wherever the angle was left so disabling the lock resumes smoothly.
+        self.curr_rot_angle = match self.rotation_clock {
+            Some(idx) => external_clocks.phase(idx) * self.rotation_clock_multiplier as f64,
+            None => {
+                // calulcate the rotation
+                // delta_t*30. implies the same speed scale as we had at 30fps with evolution tied to frame
+                self.curr_rot_angle
+                    + (scale_speed(self.rot_speed).val() * timestep_secs * 30.) * ROT_SPEED_SCALE
+            }
+        };
 
-        // calulcate the marquee angle
-        // delta_t*30 implies the same speed scale as we had at 30fps with evolution tied to frame
-        self.curr_marquee_angle +=
-            (scale_speed(self.marquee_speed).val() * timestep_secs * 30.) * MARQUEE_SPEED_SCALE;
+        self.curr_marquee_angle = match self.marquee_clock {
+            Some(idx) => external_clocks.phase(idx) * self.marquee_clock_multiplier as f64,
+            None => {
+                // calulcate the marquee angle
+                // delta_t*30 implies the same speed scale as we had at 30fps with evolution tied to frame
+                self.curr_marquee_angle
+                    + (scale_speed(self.marquee_speed).val() * timestep_secs * 30.)
+                        * MARQUEE_SPEED_SCALE
+            }
+        };
     }
 
     /// Render the current state of the tunnel.
@@ -160,7 +327,7 @@ impl Tunnel {
         external_clocks: &ClockBank,
         color_palette: &ColorPalette,
         positions: &PositionBank,
-        audio_envelope: UnipolarFloat,
+        audio_envelopes: &AudioEnvelopes,
     ) -> Vec<ArcSegment> {
         // for artistic reasons/convenience, eliminate odd numbers of segments above 40.
         let segs = if self.segs > 40 && self.segs % 2 != 0 {
@@ -195,6 +362,23 @@ impl Tunnel {
             self.col_center.val()
         };
 
+        // Evaluate each animation's modulator value once per frame, rather than once per
+        // segment, since FM modulation is meant to sweep the carrier's overall phase rather than
+        // vary per-segment. Processing in index order guarantees each animation's modulator (if
+        // any) has already been computed, since an animation may only be modulated by a
+        // lower-indexed sibling; this also means a higher-or-equal-indexed "modulator" silently
+        // reads as zero rather than ever forming a cycle.
+        let mut modulator_values = [0.0; N_ANIM];
+        for (i, anim) in self.anims.iter().enumerate() {
+            modulator_values[i] = anim.animation.get_value(
+                Phase::ZERO,
+                0,
+                external_clocks,
+                audio_envelopes,
+                &modulator_values,
+            );
+        }
+
         // Iterate over each segment ID and skip the segments that are blacked.
         for seg_num in 0..segs {
             let should_draw_segment = if blacking > 0 {
@@ -224,7 +408,8 @@ impl Tunnel {
                     rel_angle,
                     seg_num as usize,
                     external_clocks,
-                    audio_envelope,
+                    audio_envelopes,
+                    &modulator_values,
                 );
 
                 use AnimationTarget::*;
@@ -304,7 +489,7 @@ impl Tunnel {
                 let sat = UnipolarFloat::new(self.col_sat.val() + col_sat_adjust);
 
                 ArcSegment {
-                    level: level_scale.val(),
+                    level: (level_scale.val() * (1.0 + self.flash_level)).clamp(0.0, 1.0),
                     thickness: stroke_weight,
                     hue: hue.val(),
                     sat: sat.val(),
@@ -320,6 +505,50 @@ impl Tunnel {
             };
             arcs.push(arc);
         }
+
+        // Append mirrored and rotationally-symmetric copies of the base pattern, skipping any
+        // copy that would be indistinguishable from what's already present (e.g. a reflection
+        // with zero offset).
+        let base = arcs.clone();
+        if self.mirror_y {
+            for arc in &base {
+                let mut mirrored = arc.clone();
+                mirrored.y = -arc.y;
+                mirrored.start = -arc.stop;
+                mirrored.stop = -arc.start;
+                mirrored.rot_angle = -arc.rot_angle;
+                if !arcs.contains(&mirrored) {
+                    arcs.push(mirrored);
+                }
+            }
+        }
+        if self.mirror_x {
+            for arc in &base {
+                let mut mirrored = arc.clone();
+                mirrored.x = -arc.x;
+                mirrored.start = 0.5 - arc.stop;
+                mirrored.stop = 0.5 - arc.start;
+                mirrored.rot_angle = 0.5 - arc.rot_angle;
+                if !arcs.contains(&mirrored) {
+                    arcs.push(mirrored);
+                }
+            }
+        }
+        if self.symmetry > 1 {
+            let working = arcs.clone();
+            for i in 1..self.symmetry {
+                let marquee_phase_offset = i as f64 / self.symmetry as f64;
+                for arc in &working {
+                    let mut rotated = arc.clone();
+                    rotated.start = arc.start + marquee_phase_offset;
+                    rotated.stop = arc.stop + marquee_phase_offset;
+                    if !arcs.contains(&rotated) {
+                        arcs.push(rotated);
+                    }
+                }
+            }
+        }
+
         arcs
     }
 
@@ -340,6 +569,14 @@ impl Tunnel {
         emitter.emit_tunnel_state_change(Blacking(self.blacking));
         emitter.emit_tunnel_state_change(PositionX(self.x_offset.target()));
         emitter.emit_tunnel_state_change(PositionY(self.y_offset.target()));
+        emitter.emit_tunnel_state_change(RotationClock(self.rotation_clock));
+        emitter.emit_tunnel_state_change(RotationClockMultiplier(self.rotation_clock_multiplier));
+        emitter.emit_tunnel_state_change(MarqueeClock(self.marquee_clock));
+        emitter.emit_tunnel_state_change(MarqueeClockMultiplier(self.marquee_clock_multiplier));
+        emitter.emit_tunnel_state_change(Lightning(self.lightning));
+        emitter.emit_tunnel_state_change(MirrorX(self.mirror_x));
+        emitter.emit_tunnel_state_change(MirrorY(self.mirror_y));
+        emitter.emit_tunnel_state_change(Symmetry(self.symmetry));
     }
 
     /// Handle a control event.
@@ -378,6 +615,32 @@ impl Tunnel {
                 self.curr_marquee_angle = Phase::ZERO;
                 emitter.emit_tunnel_state_change(StateChange::MarqueeSpeed(BipolarFloat::ZERO));
             }
+            SetRotationClock(source) => {
+                let source: Option<ClockIdx> = match source {
+                    Some(s) => match s.try_into() {
+                        Ok(s) => Some(s),
+                        Err(e) => {
+                            error!("could not process tunnel control message: {e}");
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                self.handle_state_change(StateChange::RotationClock(source), emitter);
+            }
+            SetMarqueeClock(source) => {
+                let source: Option<ClockIdx> = match source {
+                    Some(s) => match s.try_into() {
+                        Ok(s) => Some(s),
+                        Err(e) => {
+                            error!("could not process tunnel control message: {e}");
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                self.handle_state_change(StateChange::MarqueeClock(source), emitter);
+            }
         }
     }
 
@@ -398,11 +661,81 @@ impl Tunnel {
             Blacking(v) => self.blacking = v,
             PositionX(v) => self.x_offset.set_target(v),
             PositionY(v) => self.y_offset.set_target(v),
+            RotationClock(v) => self.rotation_clock = v,
+            RotationClockMultiplier(v) => self.rotation_clock_multiplier = v,
+            MarqueeClock(v) => self.marquee_clock = v,
+            MarqueeClockMultiplier(v) => self.marquee_clock_multiplier = v,
+            Lightning(v) => self.lightning = v,
+            MirrorX(v) => self.mirror_x = v,
+            MirrorY(v) => self.mirror_y = v,
+            Symmetry(v) => self.symmetry = v,
         };
         emitter.emit_tunnel_state_change(sc);
     }
 }
 
+/// Configuration for the audio-triggered lightning/strobe flash effect.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct LightningConfig {
+    /// Audio envelope level that triggers a flash on a rising edge.
+    pub threshold: UnipolarFloat,
+    /// Time constant the flash decays back towards zero with.
+    pub decay: Duration,
+    /// Peak brightness boost applied on trigger.
+    pub intensity: f64,
+}
+
+/// A snapshot of a `Tunnel`'s speed, geometry, offset, color, segment, blacking and
+/// palette/position parameters, suitable for storing as a preset and morphing into via
+/// `Tunnel::crossfade_to`. Clock locks, lightning, mirroring and symmetry are not included.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct TunnelSnapshot {
+    marquee_speed: BipolarFloat,
+    rot_speed: BipolarFloat,
+    thickness: UnipolarFloat,
+    size: UnipolarFloat,
+    aspect_ratio: UnipolarFloat,
+    x_offset: f64,
+    y_offset: f64,
+    col_center: UnipolarFloat,
+    col_width: UnipolarFloat,
+    col_spread: UnipolarFloat,
+    col_sat: UnipolarFloat,
+    segs: u8,
+    blacking: BipolarFloat,
+    palette_selection: Option<ColorPaletteIdx>,
+    position_selection: Option<PositionIdx>,
+}
+
+/// An in-progress timed morph from one tunnel snapshot to another.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Crossfade {
+    from: TunnelSnapshot,
+    to: TunnelSnapshot,
+    elapsed: Duration,
+    dur: Duration,
+}
+
+impl Crossfade {
+    /// Fraction of the way through this crossfade, clamped to [0, 1].
+    fn progress(&self) -> f64 {
+        if self.dur.is_zero() {
+            return 1.0;
+        }
+        (self.elapsed.as_secs_f64() / self.dur.as_secs_f64()).clamp(0.0, 1.0)
+    }
+
+    fn is_complete(&self) -> bool {
+        self.elapsed >= self.dur
+    }
+}
+
+/// Linearly interpolate between `from` and `to` by fraction `t`, which is expected to already be
+/// clamped to [0, 1].
+fn lerp(from: f64, to: f64, t: f64) -> f64 {
+    from + (to - from) * t
+}
+
 /// Scale speeds with a quadratic curve.
 /// This provides more resolution for slower speeds.
 fn scale_speed(speed: BipolarFloat) -> BipolarFloat {
@@ -441,6 +774,7 @@ const Y_NUDGE: f64 = 0.025;
 const THICKNESS_SCALE: f64 = 0.5;
 const MAX_ASPECT_RATIO: f64 = 2.0;
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum StateChange {
     MarqueeSpeed(BipolarFloat),
     RotationSpeed(BipolarFloat),
@@ -456,6 +790,14 @@ pub enum StateChange {
     Blacking(BipolarFloat),
     PositionX(f64),
     PositionY(f64),
+    RotationClock(Option<ClockIdx>),
+    RotationClockMultiplier(u32),
+    MarqueeClock(Option<ClockIdx>),
+    MarqueeClockMultiplier(u32),
+    Lightning(Option<LightningConfig>),
+    MirrorX(bool),
+    MirrorY(bool),
+    Symmetry(u8),
 }
 pub enum ControlMessage {
     Set(StateChange),
@@ -466,6 +808,9 @@ pub enum ControlMessage {
     ResetPosition,
     ResetRotation,
     ResetMarquee,
+    /// Since clock IDs need to be validated, this path handles the fallible case.
+    SetRotationClock(Option<ClockIdxExt>),
+    SetMarqueeClock(Option<ClockIdxExt>),
 }
 
 pub trait EmitStateChange {
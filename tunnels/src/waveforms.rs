@@ -195,6 +195,80 @@ fn sawtooth_spatial(args: &WaveformArgsSpatial) -> f64 {
     }
 }
 
+pub fn harmonic(args: &WaveformArgs, weights: &[f64]) -> f64 {
+    let (amplitude, args) = args.spatial_params();
+    amplitude * harmonic_spatial(&args, weights)
+}
+
+/// Sum `weights[k] * sin(2*pi*(k+1)*phase)` over the duty-cycle-scaled phase, normalized by the
+/// sum of the absolute weights so the peak magnitude can never exceed 1 regardless of what the
+/// caller dials in.
+fn harmonic_spatial(args: &WaveformArgsSpatial, weights: &[f64]) -> f64 {
+    if args.outside_duty_cycle() || weights.is_empty() {
+        return 0.0;
+    }
+    let phase = args.duty_cycle_scaled_phase();
+    let sum: f64 = weights
+        .iter()
+        .enumerate()
+        .map(|(k, weight)| weight * (TWO_PI * (k as f64 + 1.0) * phase.val()).sin())
+        .sum();
+    let norm: f64 = weights.iter().map(|weight| weight.abs()).sum();
+    let normalized = if norm == 0.0 { 0.0 } else { sum / norm };
+    if args.pulse {
+        (normalized + 1.0) / 2.0
+    } else {
+        normalized
+    }
+}
+
+pub fn wavetable(args: &WaveformArgs, table: &[f64]) -> f64 {
+    let (amplitude, args) = args.spatial_params();
+    amplitude * wavetable_spatial(&args, table)
+}
+
+/// Treat `table` as one period sampled uniformly over `phase in [0, 1)`, reading it with linear
+/// interpolation between adjacent samples (wrapping at the end). `smoothing` widens this into a
+/// moving-average window of neighboring samples, to act as a simple low-pass on a spiky or noisy
+/// user-authored table.
+fn wavetable_spatial(args: &WaveformArgsSpatial, table: &[f64]) -> f64 {
+    if args.outside_duty_cycle() || table.is_empty() {
+        return 0.0;
+    }
+    let phase = args.duty_cycle_scaled_phase();
+    let sample = if args.smoothing == 0.0 {
+        read_wavetable(table, phase.val())
+    } else {
+        // internal smoothing scale is 0 to 0.25, matching the other waveforms.
+        let smoothing = args.smoothing.val() * 0.25;
+        let half_window = ((smoothing * table.len() as f64).round() as i64).max(1);
+        let samples = -half_window..=half_window;
+        let count = samples.clone().count() as f64;
+        samples
+            .map(|offset| {
+                let p = (phase.val() + offset as f64 / table.len() as f64).rem_euclid(1.0);
+                read_wavetable(table, p)
+            })
+            .sum::<f64>()
+            / count
+    };
+    if args.pulse {
+        (sample + 1.0) / 2.0
+    } else {
+        sample
+    }
+}
+
+/// Linearly interpolate between the two wavetable samples bracketing `phase`.
+fn read_wavetable(table: &[f64], phase: f64) -> f64 {
+    let len = table.len();
+    let pos = phase * len as f64;
+    let index = pos.floor() as usize % len;
+    let next_index = (index + 1) % len;
+    let frac = pos.fract();
+    table[index] * (1.0 - frac) + table[next_index] * frac
+}
+
 #[cfg(test)]
 #[allow(unused)]
 mod test {
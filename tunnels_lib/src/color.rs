@@ -36,6 +36,50 @@ impl Hsv {
             val: UnipolarFloat::ONE,
         }
     }
+
+    /// Convert back to RGB via standard sextant reconstruction.
+    pub fn as_rgb(&self) -> Rgb {
+        let h = self.hue.val() * 6.;
+        let s = self.sat.val();
+        let v = self.val.val();
+
+        let chroma = v * s;
+        let x = chroma * (1. - ((h % 2.) - 1.).abs());
+        let m = v - chroma;
+
+        let (r, g, b) = if h < 1. {
+            (chroma, x, 0.)
+        } else if h < 2. {
+            (x, chroma, 0.)
+        } else if h < 3. {
+            (0., chroma, x)
+        } else if h < 4. {
+            (0., x, chroma)
+        } else if h < 5. {
+            (x, 0., chroma)
+        } else {
+            (chroma, 0., x)
+        };
+
+        Rgb::from_f32((r + m) as f32, (g + m) as f32, (b + m) as f32)
+    }
+
+    /// Blend towards `other`, taking the shortest way around the hue circle and
+    /// linearly interpolating saturation and value. `t` of `ZERO` returns `self`,
+    /// `ONE` returns `other`.
+    pub fn interpolate(&self, other: &Self, t: UnipolarFloat) -> Self {
+        let mut hue_diff = other.hue.val() - self.hue.val();
+        if hue_diff > 0.5 {
+            hue_diff -= 1.;
+        } else if hue_diff < -0.5 {
+            hue_diff += 1.;
+        }
+        Self {
+            hue: Phase::new(self.hue.val() + hue_diff * t.val()),
+            sat: UnipolarFloat::new(self.sat.val() + (other.sat.val() - self.sat.val()) * t.val()),
+            val: UnipolarFloat::new(self.val.val() + (other.val.val() - self.val.val()) * t.val()),
+        }
+    }
 }
 
 /// A color in the RGB color space.
@@ -49,9 +93,9 @@ pub struct Rgb {
 impl Rgb {
     pub fn from_u8(red: u8, green: u8, blue: u8) -> Self {
         Self {
-            red: UnipolarFloat::new(red as f64 / 127.),
-            green: UnipolarFloat::new(green as f64 / 127.),
-            blue: UnipolarFloat::new(blue as f64 / 127.),
+            red: UnipolarFloat::new(red as f64 / 255.),
+            green: UnipolarFloat::new(green as f64 / 255.),
+            blue: UnipolarFloat::new(blue as f64 / 255.),
         }
     }
 
@@ -141,3 +185,22 @@ fn test_rgb_to_hsv() {
         }
     );
 }
+
+#[test]
+fn test_rgb_hsv_round_trip() {
+    use crate::assert_almost_eq;
+
+    fn assert_round_trips(red: u8, green: u8, blue: u8) {
+        let original = Rgb::from_u8(red, green, blue);
+        let round_tripped = original.as_hsv().as_rgb();
+        assert_almost_eq(original.red.val(), round_tripped.red.val());
+        assert_almost_eq(original.green.val(), round_tripped.green.val());
+        assert_almost_eq(original.blue.val(), round_tripped.blue.val());
+    }
+
+    assert_round_trips(255, 255, 255);
+    assert_round_trips(0, 0, 0);
+    assert_round_trips(255, 0, 0);
+    assert_round_trips(0, 255, 0);
+    assert_round_trips(0, 0, 255);
+}
@@ -1,5 +1,12 @@
 //! Code shared between the tunnels console and client.
 
+pub mod mqtt;
+pub mod multicast;
+pub mod number;
+pub mod shm;
+pub mod time_transport;
+pub mod wire;
+
 use derive_more::{Add, Display, Div, Mul, Sub};
 use serde::{Deserialize, Serialize};
 use std::{
@@ -9,6 +16,7 @@ use std::{
     },
     time::{Duration, Instant},
 };
+use uuid::Uuid;
 
 /// Timestamp used for expressing moments in time, has units of microseconds.
 /// Normally computed by the show controller as the number of microseconds since
@@ -49,6 +57,58 @@ impl Timestamp {
     }
 }
 
+/// Reply to a timesync request, carrying the host's receipt and transmit timestamps so the
+/// client can run the standard NTP four-timestamp offset/delay calculation:
+/// offset = ((t1 - t0) + (t2 - t3)) / 2, round_trip_delay = (t3 - t0) - (t2 - t1).
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct TimesyncReply {
+    /// Host time when it received the client's request (t1).
+    pub receive_time: Timestamp,
+    /// Host time when it sent this reply (t2).
+    pub transmit_time: Timestamp,
+}
+
+/// Which clock a render host is stamping its `Timestamp`s against, echoing
+/// `tunnelclient::clock_source::ClockSource`'s variants so a client can compare the two without
+/// a round trip through strings. The host/session identity lives alongside this in
+/// [`ClockReference`] rather than here, since it's the same regardless of which clock is chosen.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ClockSourceKind {
+    /// The host's own system clock, trusted outright.
+    System,
+    /// The builtin zmq REQ/REP timesync exchange (see `tunnels::timesync::TimesyncServer`).
+    Builtin,
+    /// An external NTP server the host's OS clock is disciplined against.
+    Ntp,
+    /// An external PTP (IEEE-1588) grandmaster the host's OS clock is disciplined against.
+    Ptp,
+}
+
+/// Identifies the clock a render host's published `Timestamp`s are measured against, so a
+/// client can confirm its `Synchronizer` is locked to the *same* clock the frames it's receiving
+/// were stamped with, rather than silently rendering against a stale or mismatched reference
+/// after the host restarts or switches `ClockSource`.
+///
+/// `session_id` is generated fresh each time a render service starts, so a host restart always
+/// produces a reference a client can tell apart from the one it had previously resynced against,
+/// even if the clock source kind happens to be unchanged.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ClockReference {
+    /// Identifies one run of a render host's clock; changes whenever the host restarts.
+    pub session_id: Uuid,
+    pub source: ClockSourceKind,
+}
+
+impl ClockReference {
+    /// Mint a fresh reference for a render host starting up with the given clock source.
+    pub fn new(source: ClockSourceKind) -> Self {
+        Self {
+            session_id: Uuid::new_v4(),
+            source,
+        }
+    }
+}
+
 impl num_traits::cast::ToPrimitive for Timestamp {
     fn to_i64(&self) -> Option<i64> {
         return Some(self.0);
@@ -0,0 +1,76 @@
+//! A generic MQTT publish sink for mirroring a serde-encoded stream (e.g. [`crate::Snapshot`] or
+//! a show's clock bank) to a broker, as an optional fan-out alongside whatever zmq transport
+//! already carries it. This lets lightweight or third-party subscribers consume the data with a
+//! plain MQTT client instead of linking zmq and browsing DNS-SD.
+//!
+//! Reuses the same msgpack encoding the zmq transports already use, so a payload published here
+//! round-trips through exactly the same `Deserialize` impl a subscriber already has.
+
+use std::thread;
+
+use anyhow::{Context, Result};
+use rmp_serde::Serializer;
+use rumqttc::{Client, MqttOptions, QoS};
+use serde::{Deserialize, Serialize};
+
+/// Where to reach a broker and which topic to publish a stream under.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MqttSinkConfig {
+    pub broker_host: String,
+    pub broker_port: u16,
+    pub topic: String,
+}
+
+/// Publishes serialized values of type `T` to one broker topic. Owns a background thread driving
+/// the client's network event loop, which this publisher never reads from directly; dropping the
+/// publisher drops the client and ends the thread.
+pub struct MqttPublisher<T> {
+    topic: String,
+    client: Client,
+    send_buf: Vec<u8>,
+    _msg_type: std::marker::PhantomData<T>,
+}
+
+impl<T: Serialize> MqttPublisher<T> {
+    /// Connect to the broker named in `config` and start its event loop thread. `client_id`
+    /// should be unique per publisher on the broker, e.g. the stream's service name.
+    pub fn new(client_id: &str, config: &MqttSinkConfig) -> Result<Self> {
+        let options = MqttOptions::new(client_id, config.broker_host.clone(), config.broker_port);
+        let (client, mut connection) = Client::new(options, 16);
+
+        // `rumqttc` requires something to keep draining the event loop for the client to make
+        // progress; a pure publisher has nothing in its notifications it needs to act on.
+        thread::Builder::new()
+            .name(format!("mqtt-publish-{client_id}"))
+            .spawn(move || for _ in connection.iter() {})
+            .context("failed to spawn MQTT event loop thread")?;
+
+        Ok(Self {
+            topic: config.topic.clone(),
+            client,
+            send_buf: Vec::new(),
+            _msg_type: std::marker::PhantomData,
+        })
+    }
+
+    /// Serialize and publish `val` to this sink's topic.
+    pub fn publish(&mut self, val: &T) -> Result<()> {
+        let mut buf = std::mem::take(&mut self.send_buf);
+        buf.clear();
+        val.serialize(&mut Serializer::new(&mut buf))
+            .context("failed to serialize value for MQTT publish")?;
+        let result = self.publish_bytes(&buf);
+        self.send_buf = buf;
+        result
+    }
+
+    /// Publish an already-serialized `T` to this sink's topic, for a caller that has already
+    /// paid the serialization cost for another transport (e.g. zmq) and doesn't want to pay it
+    /// twice.
+    pub fn publish_bytes(&mut self, payload: &[u8]) -> Result<()> {
+        self.client
+            .publish(&self.topic, QoS::AtMostOnce, false, payload)
+            .context("failed to publish to MQTT broker")?;
+        Ok(())
+    }
+}
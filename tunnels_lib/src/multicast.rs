@@ -0,0 +1,112 @@
+//! Chunk-and-sequence framing for delivering a serialized [`crate::Snapshot`] over UDP multicast,
+//! where (unlike the zmq PUB/SUB or QUIC transports) nothing else frames a message or retransmits
+//! a dropped one. [`chunk`] splits a payload into MTU-sized datagrams tagged with their frame
+//! number and position; [`Reassembler`] is the matching receive-side state machine, discarding a
+//! frame as soon as a newer one starts rather than waiting forever for a chunk that was dropped
+//! on the wire.
+
+use std::convert::TryInto;
+use std::net::Ipv4Addr;
+
+use serde::{Deserialize, Serialize};
+
+/// Where to reach a show's per-channel multicast groups: video channel `n` is published to
+/// `group:{base_port + n}`. Shared between the server, which needs it to send, and a client,
+/// which needs it to know which group/port to join for its configured video channel.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MulticastConfig {
+    pub group: Ipv4Addr,
+    pub base_port: u16,
+    /// Send each channel's snapshots using `tunnels_lib::wire`'s compact binary format (a
+    /// keyframe every so often, delta-encoded frames in between) instead of the derived msgpack
+    /// encoding every other transport uses. Worth the extra complexity specifically here because
+    /// multicast frames are split into MTU-sized UDP datagrams (see `chunk`/`MAX_CHUNK_PAYLOAD`)
+    /// and a dropped chunk drops the whole frame - fewer, smaller datagrams per frame means fewer
+    /// chances of that happening. Both the server and every client joining this group must agree
+    /// on this setting.
+    #[serde(default)]
+    pub compact: bool,
+}
+
+impl MulticastConfig {
+    /// The group/port carrying `video_channel`'s snapshots.
+    pub fn channel_addr(&self, video_channel: usize) -> (Ipv4Addr, u16) {
+        (self.group, self.base_port + video_channel as u16)
+    }
+}
+
+/// Conservative UDP payload size per chunk, comfortably under the ~1500-byte Ethernet MTU once
+/// IP/UDP headers and this module's own header are accounted for.
+pub const MAX_CHUNK_PAYLOAD: usize = 1400;
+
+/// Frame number (u64) + chunk index (u16) + chunk count (u16).
+const HEADER_LEN: usize = 12;
+
+/// Split `payload`, the serialized bytes of `frame_number`'s snapshot, into one or more
+/// header-tagged datagrams no larger than [`MAX_CHUNK_PAYLOAD`] plus the header.
+pub fn chunk(frame_number: u64, payload: &[u8]) -> Vec<Vec<u8>> {
+    let bodies: Vec<&[u8]> = if payload.is_empty() {
+        vec![payload]
+    } else {
+        payload.chunks(MAX_CHUNK_PAYLOAD).collect()
+    };
+    let chunk_count = bodies.len() as u16;
+    bodies
+        .into_iter()
+        .enumerate()
+        .map(|(index, body)| {
+            let mut datagram = Vec::with_capacity(HEADER_LEN + body.len());
+            datagram.extend_from_slice(&frame_number.to_be_bytes());
+            datagram.extend_from_slice(&(index as u16).to_be_bytes());
+            datagram.extend_from_slice(&chunk_count.to_be_bytes());
+            datagram.extend_from_slice(body);
+            datagram
+        })
+        .collect()
+}
+
+/// Reassembles the datagrams produced by [`chunk`] back into complete frame payloads. Holds the
+/// in-progress state for exactly one frame number at a time.
+#[derive(Default)]
+pub struct Reassembler {
+    frame_number: Option<u64>,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one received datagram. Returns the reassembled payload once every chunk of its frame
+    /// has arrived. A datagram for a frame number other than the one currently in progress starts
+    /// a fresh frame and discards whatever chunks had arrived for the old one, since a display
+    /// node only ever wants the newest complete snapshot rather than to keep waiting for a chunk
+    /// that was dropped on the wire.
+    pub fn receive(&mut self, datagram: &[u8]) -> Option<Vec<u8>> {
+        if datagram.len() < HEADER_LEN {
+            return None;
+        }
+        let frame_number = u64::from_be_bytes(datagram[0..8].try_into().unwrap());
+        let chunk_index = u16::from_be_bytes(datagram[8..10].try_into().unwrap()) as usize;
+        let chunk_count = u16::from_be_bytes(datagram[10..12].try_into().unwrap()) as usize;
+        let body = &datagram[HEADER_LEN..];
+
+        if self.frame_number != Some(frame_number) {
+            self.frame_number = Some(frame_number);
+            self.chunks = vec![None; chunk_count];
+        }
+        if chunk_index >= self.chunks.len() {
+            return None;
+        }
+        self.chunks[chunk_index] = Some(body.to_vec());
+
+        if self.chunks.iter().all(Option::is_some) {
+            let payload = self.chunks.iter_mut().flat_map(|c| c.take().unwrap()).collect();
+            self.frame_number = None;
+            Some(payload)
+        } else {
+            None
+        }
+    }
+}
@@ -146,6 +146,45 @@ fn clamp(v: &mut f64, min: f64, max: f64) {
     *v = f64::min(f64::max(*v, min), max)
 }
 
+/// Linearly interpolate between two values of this type at parameter `t`, so a client can draw a
+/// blended frame between two received snapshots instead of only ever showing the latest one.
+pub trait Lerp {
+    fn lerp(self, other: Self, t: UnipolarFloat) -> Self;
+}
+
+impl Lerp for f64 {
+    fn lerp(self, other: Self, t: UnipolarFloat) -> Self {
+        self + (other - self) * t.val()
+    }
+}
+
+impl Lerp for UnipolarFloat {
+    fn lerp(self, other: Self, t: UnipolarFloat) -> Self {
+        Self::new(self.0 + (other.0 - self.0) * t.val())
+    }
+}
+
+impl Lerp for BipolarFloat {
+    fn lerp(self, other: Self, t: UnipolarFloat) -> Self {
+        Self::new(self.0 + (other.0 - self.0) * t.val())
+    }
+}
+
+impl Lerp for Phase {
+    /// Interpolate along the shorter arc between the two phases, rather than running
+    /// monotonically from `self` to `other` the way a naive lerp would - e.g. halfway from 0.95
+    /// to 0.05 is 0.0, not 0.5.
+    fn lerp(self, other: Self, t: UnipolarFloat) -> Self {
+        let mut delta = other.0 - self.0;
+        if delta >= 0.5 {
+            delta -= 1.0;
+        } else if delta < -0.5 {
+            delta += 1.0;
+        }
+        Self::new(self.0 + t.val() * delta)
+    }
+}
+
 /// Phase represents a unit angular phase (on the range [0.0, 1.0]).
 /// Phase upholds the invariant that the valye contained inside is always in
 /// range via wrapping the phase using euclidean modulus.
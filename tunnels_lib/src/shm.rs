@@ -0,0 +1,178 @@
+//! A ring-buffer shared-memory transport for delivering a [`crate::Snapshot`]'s serialized bytes
+//! to a render client running on the same host as the server, skipping the per-frame socket round
+//! trip the zmq PUB/SUB path needs for clients elsewhere on the network. Each video channel gets
+//! its own small memory-mapped region under `/dev/shm`, with a fixed number of slots the writer
+//! cycles through; a reader finds the newest complete slot by checking each slot's sequence counter,
+//! discarding a read the writer lapped mid-copy rather than taking a lock.
+//!
+//! This module only moves bytes around - callers still serialize and deserialize the payload
+//! themselves (e.g. via msgpack or [`crate::wire`]) exactly as they would over zmq. Whether to use
+//! this transport instead of zmq is a decision made by the caller, typically via [`is_localhost`]
+//! on the server hostname it would otherwise have connected to over the network.
+
+use std::fs::OpenOptions;
+use std::io;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use memmap2::{Mmap, MmapMut, MmapOptions};
+
+/// Number of slots in the ring. A reader that falls more than this many frames behind the writer
+/// starts seeing discarded (lapped) reads instead of just slightly stale frames.
+const SLOT_COUNT: usize = 4;
+
+/// Maximum serialized payload size of a single frame. A frame that doesn't fit is dropped rather
+/// than corrupting a neighboring slot; comfortably covers a show's worth of arcs on one channel.
+const SLOT_CAPACITY: usize = 1 << 20;
+
+/// Sequence number reserved to mean "a write is currently in progress on this slot".
+const IN_PROGRESS: u64 = u64::MAX;
+
+const HEADER_LEN: usize = 16; // sequence (u64) + payload length (u64)
+const SLOT_LEN: usize = HEADER_LEN + SLOT_CAPACITY;
+
+/// True if `host` names this machine, i.e. a client connecting to it could use the
+/// shared-memory transport in this module instead of reaching out over the network.
+pub fn is_localhost(host: &str) -> bool {
+    if host.eq_ignore_ascii_case("localhost") {
+        return true;
+    }
+    // Reuse the DNS resolution a network connection to this host would need anyway, purely to
+    // inspect the resulting address rather than to actually connect to it.
+    (host, 0)
+        .to_socket_addrs()
+        .into_iter()
+        .flatten()
+        .any(|addr| addr.ip().is_loopback())
+}
+
+fn ring_path(channel: u8) -> PathBuf {
+    PathBuf::from(format!("/dev/shm/tunnels_snapshot_{channel}"))
+}
+
+/// Writes frames into one video channel's ring for local readers to pick up. Only one writer
+/// should exist per channel at a time; the render service owns one per video output.
+pub struct ShmWriter {
+    channel: u8,
+    mmap: MmapMut,
+    next_slot: usize,
+    /// Next sequence number to assign. Starts at 1 so a reader can treat 0 as "never written".
+    next_sequence: u64,
+}
+
+impl ShmWriter {
+    /// Create (or truncate and reuse) the backing file for `channel` and map it in.
+    pub fn create(channel: u8) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(ring_path(channel))?;
+        file.set_len((SLOT_LEN * SLOT_COUNT) as u64)?;
+        let mmap = unsafe { MmapOptions::new().map_mut(&file)? };
+        Ok(Self {
+            channel,
+            mmap,
+            next_slot: 0,
+            next_sequence: 1,
+        })
+    }
+
+    /// Publish `payload` as the newest frame, returning false instead of writing it if it's
+    /// larger than a slot can hold.
+    pub fn write(&mut self, payload: &[u8]) -> bool {
+        if payload.len() > SLOT_CAPACITY {
+            return false;
+        }
+        let seq = self.next_sequence;
+        self.next_sequence += 1;
+        let base = self.next_slot * SLOT_LEN;
+        self.next_slot = (self.next_slot + 1) % SLOT_COUNT;
+
+        // Mark the slot in-progress before touching its body, so a reader that catches us
+        // mid-write backs off instead of reading a torn frame.
+        self.sequence_at(base).store(IN_PROGRESS, Ordering::Release);
+        self.mmap[base + HEADER_LEN..base + HEADER_LEN + payload.len()].copy_from_slice(payload);
+        self.len_at(base).store(payload.len() as u64, Ordering::Release);
+        self.sequence_at(base).store(seq, Ordering::Release);
+        true
+    }
+
+    fn sequence_at(&self, base: usize) -> &AtomicU64 {
+        // SAFETY: `base` is always slot-aligned and within the mapping, and a `u64`-sized,
+        // `u64`-aligned region at the front of every slot is reserved for this counter.
+        unsafe { &*(self.mmap[base..].as_ptr() as *const AtomicU64) }
+    }
+
+    fn len_at(&self, base: usize) -> &AtomicU64 {
+        // SAFETY: as above, for the length field immediately following the sequence counter.
+        unsafe { &*(self.mmap[base + 8..].as_ptr() as *const AtomicU64) }
+    }
+}
+
+impl Drop for ShmWriter {
+    /// Remove the backing file so a later writer on the same channel starts from a clean ring
+    /// instead of a reader picking up stale frames left over from this process.
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(ring_path(self.channel));
+    }
+}
+
+/// Reads the newest complete frame from one video channel's ring, published by an [`ShmWriter`]
+/// possibly in a different process.
+pub struct ShmReader {
+    mmap: Mmap,
+}
+
+impl ShmReader {
+    /// Map an already-created channel ring read-only. Fails if no writer has created it yet, so
+    /// a caller can fall back to the network transport instead of retrying forever.
+    pub fn open(channel: u8) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).open(ring_path(channel))?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        Ok(Self { mmap })
+    }
+
+    /// Return the newest frame more recent than `since` (exclusive), alongside its sequence
+    /// number for the next call's `since`, or `None` if nothing newer has landed. A slot the
+    /// writer lapped while this call was copying it out is discarded rather than returned, since
+    /// its bytes may be torn; the caller just tries again on its next poll.
+    pub fn read_latest(&self, since: Option<u64>) -> Option<(u64, Vec<u8>)> {
+        let mut newest: Option<(u64, usize)> = None;
+        for slot in 0..SLOT_COUNT {
+            let base = slot * SLOT_LEN;
+            let seq = self.sequence_at(base).load(Ordering::Acquire);
+            if seq == 0 || seq == IN_PROGRESS {
+                continue;
+            }
+            if newest.is_none_or(|(best, _)| seq > best) {
+                newest = Some((seq, base));
+            }
+        }
+        let (seq, base) = newest?;
+        if since.is_some_and(|since| seq <= since) {
+            return None;
+        }
+
+        let len = self.len_at(base).load(Ordering::Acquire) as usize;
+        let payload = self.mmap[base + HEADER_LEN..base + HEADER_LEN + len].to_vec();
+
+        // If the sequence moved while we were copying, the writer lapped this slot mid-read.
+        if self.sequence_at(base).load(Ordering::Acquire) != seq {
+            return None;
+        }
+        Some((seq, payload))
+    }
+
+    fn sequence_at(&self, base: usize) -> &AtomicU64 {
+        // SAFETY: see `ShmWriter::sequence_at`; the same layout is shared read-only here.
+        unsafe { &*(self.mmap[base..].as_ptr() as *const AtomicU64) }
+    }
+
+    fn len_at(&self, base: usize) -> &AtomicU64 {
+        // SAFETY: see `ShmWriter::len_at`.
+        unsafe { &*(self.mmap[base + 8..].as_ptr() as *const AtomicU64) }
+    }
+}
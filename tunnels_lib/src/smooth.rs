@@ -53,6 +53,13 @@ impl<T: Add<Output = T> + Clone + Copy + Mul<UnipolarFloat, Output = T>> Smoothe
         let smoother = match self.mode {
             SmoothMode::Linear => linear,
             SmoothMode::Cosine => cosine,
+            SmoothMode::EaseInQuad => ease_in_quad,
+            SmoothMode::EaseOutQuad => ease_out_quad,
+            SmoothMode::EaseInOutCubic => ease_in_out_cubic,
+            SmoothMode::Smoothstep => smoothstep,
+            SmoothMode::Exponential => exponential,
+            SmoothMode::EaseInCubic => ease_in_cubic,
+            SmoothMode::EaseOutCubic => ease_out_cubic,
         };
         let target_weight = smoother(self.alpha);
         (self.target * target_weight) + (self.previous * (UnipolarFloat::ONE - target_weight))
@@ -63,6 +70,22 @@ impl<T: Add<Output = T> + Clone + Copy + Mul<UnipolarFloat, Output = T>> Smoothe
 pub enum SmoothMode {
     Linear,
     Cosine,
+    /// Accelerate from a standstill; ease in, no ease out.
+    EaseInQuad,
+    /// Decelerate to a standstill; ease out, no ease in.
+    EaseOutQuad,
+    /// Accelerate from and decelerate to a standstill.
+    EaseInOutCubic,
+    /// Classic smoothstep; similar feel to `EaseInOutCubic` but with a gentler shoulder.
+    Smoothstep,
+    /// Exponential decay towards the target; a perceptually constant decay rate, good for
+    /// knobs like rotation/marquee speed where an abrupt settle reads as more natural than
+    /// a gradual ease.
+    Exponential,
+    /// Cubic ease-in; accelerate from a standstill, no ease out.
+    EaseInCubic,
+    /// Cubic ease-out; decelerate to a standstill, no ease in.
+    EaseOutCubic,
 }
 
 // Linear smoothing function.
@@ -76,6 +99,58 @@ fn cosine(alpha: UnipolarFloat) -> UnipolarFloat {
     UnipolarFloat::new(-0.5 * phase.cos() + 0.5)
 }
 
+// Quadratic ease-in: starts slow, accelerates into the target.
+fn ease_in_quad(alpha: UnipolarFloat) -> UnipolarFloat {
+    let t = alpha.val();
+    UnipolarFloat::new(t * t)
+}
+
+// Quadratic ease-out: starts fast, decelerates into the target.
+fn ease_out_quad(alpha: UnipolarFloat) -> UnipolarFloat {
+    let t = alpha.val();
+    UnipolarFloat::new(1.0 - (1.0 - t) * (1.0 - t))
+}
+
+// Cubic ease-in-out: accelerates away from the start, decelerates into the target.
+fn ease_in_out_cubic(alpha: UnipolarFloat) -> UnipolarFloat {
+    let t = alpha.val();
+    let eased = if t < 0.5 {
+        4.0 * t * t * t
+    } else {
+        1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+    };
+    UnipolarFloat::new(eased)
+}
+
+// Smoothstep: a gentler accelerate/decelerate shape than `ease_in_out_cubic`.
+fn smoothstep(alpha: UnipolarFloat) -> UnipolarFloat {
+    let t = alpha.val();
+    UnipolarFloat::new(3.0 * t * t - 2.0 * t * t * t)
+}
+
+// Exponential decay towards the target, with a fixed time constant. Clamped to reach exactly
+// 1.0 at alpha == 1.0, since the underlying curve only asymptotically approaches it.
+fn exponential(alpha: UnipolarFloat) -> UnipolarFloat {
+    if alpha == UnipolarFloat::ONE {
+        return UnipolarFloat::ONE;
+    }
+    const TIME_CONSTANT: f64 = 5.0;
+    let t = alpha.val();
+    UnipolarFloat::new(1.0 - (-TIME_CONSTANT * t).exp())
+}
+
+// Cubic ease-in: starts slow, accelerates into the target.
+fn ease_in_cubic(alpha: UnipolarFloat) -> UnipolarFloat {
+    let t = alpha.val();
+    UnipolarFloat::new(t * t * t)
+}
+
+// Cubic ease-out: starts fast, decelerates into the target.
+fn ease_out_cubic(alpha: UnipolarFloat) -> UnipolarFloat {
+    let t = 1.0 - alpha.val();
+    UnipolarFloat::new(1.0 - t * t * t)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -87,6 +162,27 @@ mod test {
         assert_almost_eq(0.5, cosine(UnipolarFloat::new(0.5)).val());
     }
 
+    #[test]
+    fn test_easing_smooth_funcs_bound_endpoints() {
+        for f in [
+            ease_in_quad,
+            ease_out_quad,
+            ease_in_out_cubic,
+            smoothstep,
+            exponential,
+            ease_in_cubic,
+            ease_out_cubic,
+        ] {
+            assert_almost_eq(0.0, f(UnipolarFloat::ZERO).val());
+            assert_almost_eq(1.0, f(UnipolarFloat::ONE).val());
+        }
+    }
+
+    #[test]
+    fn test_smoothstep_midpoint() {
+        assert_almost_eq(0.5, smoothstep(UnipolarFloat::new(0.5)).val());
+    }
+
     #[test]
     fn test_smoother() {
         let smooth_time = Duration::from_micros(10);
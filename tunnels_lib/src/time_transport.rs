@@ -0,0 +1,41 @@
+//! Transport abstraction for a single request/reply exchange, used by the timesync protocol (see
+//! `tunnels::timesync::TimesyncServer`/`TimesyncClient`).
+//!
+//! Modeled on the `RxToken`/`TxToken` split smoltcp uses for its `phy::Device` trait: rather than
+//! handing back an owned buffer on every round trip of what is otherwise a tiny,
+//! latency-sensitive probe, a transport hands back a token borrowed for exactly one operation.
+//! `consume` takes the token by value, so it can only be used once, and the closure it drives
+//! never outlives the underlying buffer. This keeps the exchange allocation-free for transports
+//! that can manage it, and lets the protocol run against something other than a real socket - an
+//! in-process channel for tests, or in principle a transport that doesn't need libzmq at all.
+
+use std::io;
+
+/// A transport capable of carrying one request/reply exchange at a time.
+pub trait TimeTransport {
+    type RxToken<'a>: RxToken
+    where
+        Self: 'a;
+    type TxToken<'a>: TxToken
+    where
+        Self: 'a;
+
+    /// Wait for an incoming message and return a token to consume it, or `Ok(None)` if the
+    /// transport's configured timeout elapsed with nothing received.
+    fn receive(&mut self) -> io::Result<Option<Self::RxToken<'_>>>;
+
+    /// Obtain a token for sending the next outgoing message.
+    fn transmit(&mut self) -> io::Result<Self::TxToken<'_>>;
+}
+
+/// A single incoming message, borrowed for exactly one read.
+pub trait RxToken {
+    /// Run `f` against the received message's bytes and return its result.
+    fn consume<R>(self, f: impl FnOnce(&[u8]) -> R) -> R;
+}
+
+/// A slot for exactly one outgoing message of a known length.
+pub trait TxToken {
+    /// Run `f` against a `len`-byte buffer to fill in, then send it.
+    fn consume<R>(self, len: usize, f: impl FnOnce(&mut [u8]) -> R) -> R;
+}
@@ -0,0 +1,413 @@
+//! A compact binary wire format for [`Snapshot`], offered alongside this crate's derived
+//! msgpack `Serialize`/`Deserialize` impls (the format actually used as the fallback elsewhere in
+//! this workspace) for callers that want to spend fewer bytes per arc at high arc counts and
+//! frame rates.
+//!
+//! Layout: a fixed header (`frame_number`, `frame_time`, then a varint layer count), followed by
+//! one varint arc count and that many tightly packed arc records per layer. Hue/saturation/value/
+//! level/thickness are perceptually safe to quantize, so they're stored as normalized `u16`s;
+//! position/radius/angle fields keep full `f32` precision, since they accumulate visible drift
+//! more easily than a color channel does.
+//!
+//! Frame-to-frame delta encoding is also provided: [`encode_delta`] emits only the arcs that
+//! differ from a reference snapshot (per [`ArcSegment`]'s existing tolerance-based `PartialEq`),
+//! addressed by a leading varint index within their layer, and [`decode_delta`] reconstructs the
+//! rest from that same reference. Callers are expected to hold onto the reference snapshot
+//! themselves - `VecDequeSnapshotManager` already keeps recent snapshots around for
+//! interpolation/extrapolation and is a natural place to keep the one delta encoding needs too.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{ArcSegment, LayerCollection, Snapshot, Timestamp};
+
+/// Number of `f32` geometry fields packed per arc, after the five quantized color/level fields.
+const GEOMETRY_FIELDS: usize = 7;
+
+/// Number of bytes a single encoded arc record occupies on the wire: five quantized `u16`
+/// color/level fields plus `GEOMETRY_FIELDS` full-precision `f32` geometry fields.
+const ARC_RECORD_SIZE: usize = 5 * 2 + GEOMETRY_FIELDS * 4;
+
+/// Tags a datagram as a [`encode`]-d full snapshot, for a transport (e.g. UDP multicast) that
+/// multiplexes keyframes and deltas over a single untyped channel and needs a leading byte to
+/// tell them apart on receipt.
+pub const FRAME_TAG_KEYFRAME: u8 = 0;
+/// Tags a datagram as an [`encode_delta`]-d snapshot; see [`FRAME_TAG_KEYFRAME`].
+pub const FRAME_TAG_DELTA: u8 = 1;
+
+/// An error decoding a buffer produced by [`encode`] or [`encode_delta`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The buffer ended before a complete header, count, or record could be read.
+    UnexpectedEof,
+    /// A varint was longer than the widest integer this format ever encodes.
+    InvalidVarint,
+    /// A delta record referenced an arc index that doesn't exist in the reference snapshot.
+    ReferenceIndexOutOfRange,
+    /// Delta decoding needs a previous snapshot to diff against, but none was available.
+    NoReference,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            Self::UnexpectedEof => "unexpected end of buffer",
+            Self::InvalidVarint => "invalid varint",
+            Self::ReferenceIndexOutOfRange => "delta referenced an out-of-range arc index",
+            Self::NoReference => "no reference snapshot available to decode a delta against",
+        };
+        write!(f, "{msg}")
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(buf: &[u8], pos: &mut usize) -> Result<u64, DecodeError> {
+    let mut result = 0u64;
+    let mut shift = 0u32;
+    loop {
+        let byte = *buf.get(*pos).ok_or(DecodeError::UnexpectedEof)?;
+        *pos += 1;
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+        if shift >= 64 {
+            return Err(DecodeError::InvalidVarint);
+        }
+    }
+}
+
+fn read_bytes<'a>(buf: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8], DecodeError> {
+    let start = *pos;
+    let end = start.checked_add(n).ok_or(DecodeError::UnexpectedEof)?;
+    let slice = buf.get(start..end).ok_or(DecodeError::UnexpectedEof)?;
+    *pos = end;
+    Ok(slice)
+}
+
+/// Check that `count` items, each costing at least `min_item_size` bytes on the wire, could
+/// actually fit in the `available` bytes remaining in the buffer, before trusting `count` to size
+/// an allocation. A corrupt or hostile length prefix (e.g. a UDP datagram with a valid header
+/// followed by a varint claiming a count of `u64::MAX`) would otherwise drive an allocation
+/// failure that aborts the process, long before the decode loop that actually reads `count` items
+/// would run out of buffer and fail cleanly on its own.
+fn check_count(count: usize, min_item_size: usize, available: usize) -> Result<(), DecodeError> {
+    if count.saturating_mul(min_item_size) > available {
+        Err(DecodeError::UnexpectedEof)
+    } else {
+        Ok(())
+    }
+}
+
+fn quantize_unit(v: f64) -> u16 {
+    (v.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16
+}
+
+fn dequantize_unit(v: u16) -> f64 {
+    v as f64 / u16::MAX as f64
+}
+
+fn write_arc(out: &mut Vec<u8>, arc: &ArcSegment) {
+    for v in [arc.level, arc.thickness, arc.hue, arc.sat, arc.val] {
+        out.extend_from_slice(&quantize_unit(v).to_le_bytes());
+    }
+    for v in [
+        arc.x,
+        arc.y,
+        arc.rad_x,
+        arc.rad_y,
+        arc.start,
+        arc.stop,
+        arc.rot_angle,
+    ] {
+        out.extend_from_slice(&(v as f32).to_le_bytes());
+    }
+}
+
+fn read_arc(buf: &[u8], pos: &mut usize) -> Result<ArcSegment, DecodeError> {
+    let mut unit_fields = [0f64; 5];
+    for field in unit_fields.iter_mut() {
+        let bytes: [u8; 2] = read_bytes(buf, pos, 2)?.try_into().unwrap();
+        *field = dequantize_unit(u16::from_le_bytes(bytes));
+    }
+    let mut geometry_fields = [0f64; GEOMETRY_FIELDS];
+    for field in geometry_fields.iter_mut() {
+        let bytes: [u8; 4] = read_bytes(buf, pos, 4)?.try_into().unwrap();
+        *field = f32::from_le_bytes(bytes) as f64;
+    }
+    let [level, thickness, hue, sat, val] = unit_fields;
+    let [x, y, rad_x, rad_y, start, stop, rot_angle] = geometry_fields;
+    Ok(ArcSegment {
+        level,
+        thickness,
+        hue,
+        sat,
+        val,
+        x,
+        y,
+        rad_x,
+        rad_y,
+        start,
+        stop,
+        rot_angle,
+    })
+}
+
+/// Encode a snapshot into this crate's compact binary wire format.
+pub fn encode(snapshot: &Snapshot) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&snapshot.frame_number.to_le_bytes());
+    out.extend_from_slice(&snapshot.time.0.to_le_bytes());
+    write_varint(&mut out, snapshot.layers.len() as u64);
+    for layer in &snapshot.layers {
+        write_varint(&mut out, layer.len() as u64);
+        for arc in layer.iter() {
+            write_arc(&mut out, arc);
+        }
+    }
+    out
+}
+
+/// Decode a snapshot previously encoded with [`encode`].
+pub fn decode(buf: &[u8]) -> Result<Snapshot, DecodeError> {
+    let mut pos = 0;
+    let frame_number = u64::from_le_bytes(read_bytes(buf, &mut pos, 8)?.try_into().unwrap());
+    let time = Timestamp(i64::from_le_bytes(
+        read_bytes(buf, &mut pos, 8)?.try_into().unwrap(),
+    ));
+    let layer_count = read_varint(buf, &mut pos)? as usize;
+    check_count(layer_count, 1, buf.len() - pos)?;
+    let mut layers = Vec::with_capacity(layer_count);
+    for _ in 0..layer_count {
+        let arc_count = read_varint(buf, &mut pos)? as usize;
+        check_count(arc_count, ARC_RECORD_SIZE, buf.len() - pos)?;
+        let mut arcs = Vec::with_capacity(arc_count);
+        for _ in 0..arc_count {
+            arcs.push(read_arc(buf, &mut pos)?);
+        }
+        layers.push(Arc::new(arcs));
+    }
+    Ok(Snapshot {
+        frame_number,
+        time,
+        layers,
+    })
+}
+
+/// Encode a snapshot as a delta against `reference`, emitting only the arcs that differ from the
+/// arc at the same layer/index in `reference` (per [`ArcSegment`]'s tolerance-based equality).
+/// Falls back to treating every arc as changed wherever a layer is missing, or shorter, in
+/// `reference`.
+pub fn encode_delta(snapshot: &Snapshot, reference: &Snapshot) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&snapshot.frame_number.to_le_bytes());
+    out.extend_from_slice(&snapshot.time.0.to_le_bytes());
+    write_varint(&mut out, snapshot.layers.len() as u64);
+    for (layer_idx, layer) in snapshot.layers.iter().enumerate() {
+        let reference_layer = reference.layers.get(layer_idx).map(|l| l.as_slice());
+        write_varint(&mut out, layer.len() as u64);
+        let changed: Vec<(usize, &ArcSegment)> = layer
+            .iter()
+            .enumerate()
+            .filter(|(arc_idx, arc)| {
+                !matches!(reference_layer.and_then(|l| l.get(*arc_idx)), Some(r) if r == *arc)
+            })
+            .collect();
+        write_varint(&mut out, changed.len() as u64);
+        for (arc_idx, arc) in changed {
+            write_varint(&mut out, arc_idx as u64);
+            write_arc(&mut out, arc);
+        }
+    }
+    out
+}
+
+/// Decode a delta previously encoded with [`encode_delta`] against the same `reference` snapshot
+/// used to produce it.
+pub fn decode_delta(buf: &[u8], reference: &Snapshot) -> Result<Snapshot, DecodeError> {
+    let mut pos = 0;
+    let frame_number = u64::from_le_bytes(read_bytes(buf, &mut pos, 8)?.try_into().unwrap());
+    let time = Timestamp(i64::from_le_bytes(
+        read_bytes(buf, &mut pos, 8)?.try_into().unwrap(),
+    ));
+    let layer_count = read_varint(buf, &mut pos)? as usize;
+    // Each layer costs at least two 1-byte varints (an `arc_count` and a `changed_count` of 0).
+    check_count(layer_count, 2, buf.len() - pos)?;
+    let mut layers: LayerCollection = Vec::with_capacity(layer_count);
+    for layer_idx in 0..layer_count {
+        let arc_count = read_varint(buf, &mut pos)? as usize;
+        let changed_count = read_varint(buf, &mut pos)? as usize;
+        let reference_layer = reference.layers.get(layer_idx).map(|l| l.as_slice());
+        // Every arc index beyond the reference layer's length must appear as an explicit changed
+        // entry below (anything else resolves via `reference_layer`, which is already allocated),
+        // and each changed entry costs at least a 1-byte index varint plus a full arc record - so
+        // an `arc_count` claiming far more new entries than the remaining buffer could possibly
+        // encode is corrupt or hostile.
+        let new_entries = arc_count.saturating_sub(reference_layer.map_or(0, |l| l.len()));
+        check_count(new_entries, 1 + ARC_RECORD_SIZE, buf.len() - pos)?;
+        let mut arcs: Vec<Option<ArcSegment>> = vec![None; arc_count];
+        for _ in 0..changed_count {
+            let arc_idx = read_varint(buf, &mut pos)? as usize;
+            let arc = read_arc(buf, &mut pos)?;
+            *arcs
+                .get_mut(arc_idx)
+                .ok_or(DecodeError::ReferenceIndexOutOfRange)? = Some(arc);
+        }
+        let resolved = arcs
+            .into_iter()
+            .enumerate()
+            .map(|(arc_idx, arc)| match arc {
+                Some(arc) => Ok(arc),
+                None => reference_layer
+                    .and_then(|l| l.get(arc_idx))
+                    .cloned()
+                    .ok_or(DecodeError::ReferenceIndexOutOfRange),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        layers.push(Arc::new(resolved));
+    }
+    Ok(Snapshot {
+        frame_number,
+        time,
+        layers,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn mkarc(level: f64, hue: f64, x: f64) -> ArcSegment {
+        ArcSegment {
+            level,
+            thickness: 0.5,
+            hue,
+            sat: 1.0,
+            val: 1.0,
+            x,
+            y: 0.0,
+            rad_x: 1.0,
+            rad_y: 1.0,
+            start: 0.0,
+            stop: 0.5,
+            rot_angle: 0.0,
+        }
+    }
+
+    fn mksnapshot(frame_number: u64, layers: LayerCollection) -> Snapshot {
+        Snapshot {
+            frame_number,
+            time: Timestamp(1000),
+            layers,
+        }
+    }
+
+    /// `u16` quantization of the color/level fields is inherently lossy, so a roundtripped
+    /// snapshot is only equal to the original up to quantization error, not bit-for-bit.
+    const QUANTIZATION_TOLERANCE: f64 = 1e-4;
+
+    fn assert_snapshots_close(a: &Snapshot, b: &Snapshot) {
+        assert_eq!(a.frame_number, b.frame_number);
+        assert_eq!(a.time, b.time);
+        assert_eq!(a.layers.len(), b.layers.len());
+        for (layer_a, layer_b) in a.layers.iter().zip(b.layers.iter()) {
+            assert_eq!(layer_a.len(), layer_b.len());
+            for (arc_a, arc_b) in layer_a.iter().zip(layer_b.iter()) {
+                for (field_a, field_b) in [
+                    (arc_a.level, arc_b.level),
+                    (arc_a.thickness, arc_b.thickness),
+                    (arc_a.hue, arc_b.hue),
+                    (arc_a.sat, arc_b.sat),
+                    (arc_a.val, arc_b.val),
+                ] {
+                    assert!(
+                        (field_a - field_b).abs() < QUANTIZATION_TOLERANCE,
+                        "{field_a} != {field_b}"
+                    );
+                }
+                // Geometry fields only lose f32 precision, which every value used in these
+                // tests survives exactly.
+                assert_eq!(arc_a.x, arc_b.x);
+                assert_eq!(arc_a.y, arc_b.y);
+                assert_eq!(arc_a.rad_x, arc_b.rad_x);
+                assert_eq!(arc_a.rad_y, arc_b.rad_y);
+                assert_eq!(arc_a.start, arc_b.start);
+                assert_eq!(arc_a.stop, arc_b.stop);
+                assert_eq!(arc_a.rot_angle, arc_b.rot_angle);
+            }
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let snapshot = mksnapshot(
+            42,
+            vec![
+                Arc::new(vec![mkarc(0.5, 0.25, 1.0), mkarc(1.0, 0.75, -1.0)]),
+                Arc::new(vec![]),
+            ],
+        );
+        let encoded = encode(&snapshot);
+        let decoded = decode(&encoded).unwrap();
+        assert_snapshots_close(&decoded, &snapshot);
+    }
+
+    #[test]
+    fn test_decode_truncated_buffer_errors() {
+        let snapshot = mksnapshot(1, vec![Arc::new(vec![mkarc(0.5, 0.25, 1.0)])]);
+        let mut encoded = encode(&snapshot);
+        encoded.truncate(encoded.len() - 1);
+        assert!(decode(&encoded).is_err());
+    }
+
+    #[test]
+    fn test_delta_roundtrip_with_unchanged_arc() {
+        let reference = mksnapshot(
+            1,
+            vec![Arc::new(vec![
+                mkarc(0.5, 0.25, 1.0),
+                mkarc(1.0, 0.75, -1.0),
+            ])],
+        );
+        // Change only the second arc.
+        let current = mksnapshot(
+            2,
+            vec![Arc::new(vec![
+                mkarc(0.5, 0.25, 1.0),
+                mkarc(0.2, 0.75, -1.0),
+            ])],
+        );
+        let delta = encode_delta(&current, &reference);
+        let decoded = decode_delta(&delta, &reference).unwrap();
+        assert_snapshots_close(&decoded, &current);
+    }
+
+    #[test]
+    fn test_delta_against_shorter_reference_layer() {
+        let reference = mksnapshot(1, vec![Arc::new(vec![mkarc(0.5, 0.25, 1.0)])]);
+        let current = mksnapshot(
+            2,
+            vec![Arc::new(vec![
+                mkarc(0.5, 0.25, 1.0),
+                mkarc(1.0, 0.75, -1.0),
+            ])],
+        );
+        let delta = encode_delta(&current, &reference);
+        let decoded = decode_delta(&delta, &reference).unwrap();
+        assert_snapshots_close(&decoded, &current);
+    }
+}
@@ -3,6 +3,7 @@
 use async_dnssd::{
     browse, register_extended, BrowsedFlags, RegisterData, RegisterFlags, ResolveResult,
 };
+use futures::sync::mpsc;
 use futures::{Future, Stream};
 
 use simple_error::bail;
@@ -10,7 +11,7 @@ use tokio_core::reactor::{Core, Timeout};
 
 use std::collections::HashMap;
 use std::error::Error;
-use std::sync::mpsc::channel;
+use std::sync::mpsc::{channel, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
@@ -22,13 +23,60 @@ pub fn reg_type(name: &str) -> String {
     format!("_{}._tcp", name)
 }
 
-/// Register a vanilla service over DNS-SD.
+/// Encode metadata (e.g. protocol version, supported capabilities) as a DNS-SD TXT record: a
+/// sequence of length-prefixed `key=value` entries, per RFC 6763 section 6.3.
+fn encode_txt_record(metadata: &HashMap<String, String>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut txt = Vec::new();
+    for (key, value) in metadata {
+        let entry = format!("{}={}", key, value);
+        if entry.len() > 255 {
+            bail!(
+                "TXT record entry for '{}' is {} bytes, exceeding the 255-byte DNS-SD limit",
+                key,
+                entry.len()
+            );
+        }
+        txt.push(entry.len() as u8);
+        txt.extend_from_slice(entry.as_bytes());
+    }
+    Ok(txt)
+}
+
+/// Decode a DNS-SD TXT record back into its `key=value` entries. Entries that are malformed or
+/// not valid UTF-8 are skipped rather than failing the whole record, since a single bad entry
+/// shouldn't keep an otherwise-healthy service from being discovered.
+fn decode_txt_record(txt: &[u8]) -> HashMap<String, String> {
+    let mut metadata = HashMap::new();
+    let mut remaining = txt;
+    while let Some((&len, rest)) = remaining.split_first() {
+        let len = len as usize;
+        if rest.len() < len {
+            break;
+        }
+        let (entry, next) = rest.split_at(len);
+        remaining = next;
+        if let Ok(entry) = std::str::from_utf8(entry) {
+            if let Some((key, value)) = entry.split_once('=') {
+                metadata.insert(key.to_string(), value.to_string());
+            }
+        }
+    }
+    metadata
+}
+
+/// Register a vanilla service over DNS-SD, advertising `metadata` in its TXT record so browsers
+/// can filter on capability (e.g. protocol version) before connecting.
 /// Return a callback that will deregister the service.
-pub fn register_service(name: &str, port: u16) -> Result<StopFn, Box<dyn Error>> {
+pub fn register_service(
+    name: &str,
+    port: u16,
+    metadata: HashMap<String, String>,
+) -> Result<StopFn, Box<dyn Error>> {
     // FIXME: figure out how to better integrate tokio and deduplicate this code
     let (send_stop, receive_stop) = channel();
     let (send_success, receive_success) = channel();
     let full_name = reg_type(name);
+    let txt = encode_txt_record(&metadata)?;
 
     thread::spawn(move || {
         let core = match Core::new() {
@@ -42,6 +90,7 @@ pub fn register_service(name: &str, port: u16) -> Result<StopFn, Box<dyn Error>>
         // Start advertising this service over DNS-SD.
         let mut register_data = RegisterData::default();
         register_data.flags = RegisterFlags::SHARED;
+        register_data.txt = txt;
 
         match register_extended(&full_name, port, register_data, &core.handle()) {
             Err(e) => {
@@ -62,24 +111,50 @@ pub fn register_service(name: &str, port: u16) -> Result<StopFn, Box<dyn Error>>
 }
 
 /// Maintain a collection of service instances we can remotely interact with.
-/// FIXME: there's currently no way to stop the browse thread, it will run until
-/// the process terminates even if we drop this struct.
 pub struct Browser<S: Send + 'static> {
     service_name: String,
     services: Arc<Mutex<HashMap<String, S>>>,
+    /// Fires the stop signal that lets the browse thread's reactor return and the thread exit,
+    /// rather than running until process exit the way it used to. `None` once `stop` has
+    /// consumed it (or after `Drop` has run).
+    stop: Option<mpsc::Sender<()>>,
 }
 
 impl<S: Send> Browser<S> {
     /// Start up a new service controller at the given service name.
-    /// Asynchronously browse for new services, and remove them if they deregister.
-    /// For the moment, panic if anything goes wrong during initialization.
-    /// This is acceptable as this action will run once during startup and there's nothing to do
-    /// except bail completely if this process fails.
+    /// Asynchronously browse for new services, and remove them if they deregister. `open_service`
+    /// is handed the decoded TXT-record metadata alongside the resolve result, so it can reject a
+    /// service with an incompatible protocol version or missing capability before connecting.
+    /// Panic if anything goes wrong during initialization; callers that need to handle discovery
+    /// failure gracefully instead of aborting the process should use `new_fallible`.
     pub fn new<F>(name: String, open_service: F) -> Self
     where
-        F: Fn(&ResolveResult) -> Result<S, Box<dyn Error>> + Send + 'static,
+        F: Fn(&ResolveResult, &HashMap<String, String>) -> Result<S, Box<dyn Error>>
+            + Send
+            + 'static,
+    {
+        Self::new_fallible(name, open_service).expect("failed to start DNS-SD browser")
+    }
+
+    /// Start up a new service controller at the given service name, returning once browsing has
+    /// either started successfully or failed to start (for example because DNS-SD isn't
+    /// available on this host). Asynchronously browses for new services, and removes them if
+    /// they deregister, for as long as the returned `Browser` stays in scope.
+    pub fn new_fallible<F>(
+        name: String,
+        open_service: F,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>>
+    where
+        F: Fn(&ResolveResult, &HashMap<String, String>) -> Result<S, Box<dyn Error>>
+            + Send
+            + 'static,
     {
         let services = Arc::new(Mutex::new(HashMap::new()));
+        // Mirrors the success channel `register_service` uses: the browse thread reports back
+        // whether it managed to start browsing before it settles into browsing until stopped.
+        let (send_started, receive_started) = channel();
+        // Buffer of 1 is enough: `stop` only ever sends a single, idempotent signal.
+        let (stop, stop_received) = mpsc::channel(1);
 
         let services_remote = services.clone();
         let service_name = name.clone();
@@ -88,12 +163,17 @@ impl<S: Send> Browser<S> {
         thread::spawn(move || {
             browse_forever(
                 &service_name,
-                |(service, name)| match open_service(&service) {
-                    Ok(service) => {
-                        services_remote.lock().unwrap().insert(name, service);
-                    }
-                    Err(e) => {
-                        println!("Could not connect to '{}':\n{}", service.host_target, e);
+                send_started,
+                stop_received,
+                |(service, name)| {
+                    let metadata = decode_txt_record(&service.txt);
+                    match open_service(&service, &metadata) {
+                        Ok(service) => {
+                            services_remote.lock().unwrap().insert(name, service);
+                        }
+                        Err(e) => {
+                            println!("Could not connect to '{}':\n{}", service.host_target, e);
+                        }
                     }
                 },
                 |name| {
@@ -102,9 +182,23 @@ impl<S: Send> Browser<S> {
             );
         });
 
-        Browser {
+        receive_started
+            .recv()
+            .map_err(|e| Box::new(e) as Box<dyn Error + Send + Sync>)??;
+
+        Ok(Browser {
             services,
             service_name: name,
+            stop: Some(stop),
+        })
+    }
+
+    /// Stop browsing for new services, idempotently. The collected services remain available.
+    pub fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            // The receiving end may already be gone if the browse thread exited on its own
+            // (e.g. a DNS-SD error); either way, there's nothing left to signal.
+            let _ = stop.try_send(());
         }
     }
 
@@ -128,20 +222,50 @@ impl<S: Send> Browser<S> {
     }
 }
 
+impl<S: Send> Drop for Browser<S> {
+    /// Stop browsing deterministically rather than leaking the browse thread for the life of the
+    /// process.
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
 /// Use the current thread to browse for services.
-/// Continues browsing forever.
-pub fn browse_forever<A, D>(name: &str, mut on_service_appear: A, mut on_service_drop: D)
-where
+/// Reports whether browsing started successfully on `started` before settling into browsing
+/// forever, mirroring the success-channel pattern `register_service` uses. Races the browse loop
+/// against `stop`, so that sending (or dropping) it lets `core.run` return and this thread exit,
+/// rather than running until process exit.
+pub fn browse_forever<A, D>(
+    name: &str,
+    started: Sender<Result<(), Box<dyn Error + Send + Sync>>>,
+    stop: mpsc::Receiver<()>,
+    mut on_service_appear: A,
+    mut on_service_drop: D,
+) where
     A: FnMut((ResolveResult, String)),
     D: FnMut(&str),
 {
     let registration_type = reg_type(name);
-    let mut core = Core::new().unwrap();
+    let mut core = match Core::new() {
+        Ok(core) => core,
+        Err(e) => {
+            let _ = started.send(Err(Box::new(e)));
+            return;
+        }
+    };
 
     let handle = core.handle();
 
-    let browse_result = browse(&registration_type, &handle)
-        .unwrap()
+    let browse_stream = match browse(&registration_type, &handle) {
+        Ok(stream) => stream,
+        Err(e) => {
+            let _ = started.send(Err(Box::new(e)));
+            return;
+        }
+    };
+    let _ = started.send(Ok(()));
+
+    let browse_result = browse_stream
         .filter_map(|event| {
             // If this service was added, continue processing.
             if event.flags.contains(BrowsedFlags::ADD) {
@@ -177,5 +301,6 @@ where
             Ok(())
         });
 
-    core.run(browse_result).unwrap();
+    let stop_signal = stop.into_future().then(|_| -> Result<(), ()> { Ok(()) });
+    let _ = core.run(browse_result.map_err(|_| ()).select(stop_signal));
 }
@@ -3,8 +3,11 @@
 
 use async_dnssd::{browse, register_extended, BrowsedFlags, RegisterData, RegisterFlags};
 use futures::{Future, Stream};
+use rmp_serde::{Deserializer, Serializer};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use simple_error::bail;
 use tokio_core::reactor::{Core, Timeout};
+use tunnels_lib::RunFlag;
 
 use zmq::{Context, Socket};
 
@@ -15,6 +18,10 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
+/// How long a single `zmq::poll` call waits for a request before waking up to check `run` again,
+/// so `stop()` is noticed promptly without busy-waiting between requests.
+const POLL_TIMEOUT_MS: i64 = 100;
+
 /// Format a service name into a DNS-SD TCP registration type.
 fn reg_type(name: &str) -> String {
     format!("_{}._tcp", name)
@@ -23,10 +30,14 @@ fn reg_type(name: &str) -> String {
 /// Advertise a service over DNS-SD, using a 0mq REQ/REP socket as the subsequent transport.
 /// Pass each message received on the socket to the action callback.  Send the byte buffer returned
 /// by the action callback back to the requester.
+/// Polls the socket with a short timeout rather than blocking on it forever, waking periodically
+/// to check `run`; once `stop()` is called, this returns cleanly and drops the DNS-SD
+/// registration instead of holding the service advertised for the life of the process.
 pub fn run_service_req_rep<F>(
     ctx: Context,
     name: &str,
     port: u16,
+    run: RunFlag,
     mut action: F,
 ) -> Result<(), Box<dyn Error>>
 where
@@ -45,13 +56,61 @@ where
     register_data.flags = RegisterFlags::SHARED;
     let _registration = register_extended(&reg_type(name), port, register_data, &core.handle())?;
 
-    loop {
+    while run.should_run() {
+        let mut items = [socket.as_poll_item(zmq::POLLIN)];
+        if zmq::poll(&mut items, POLL_TIMEOUT_MS).is_err() || !items[0].is_readable() {
+            continue;
+        }
         if let Ok(msg) = socket.recv_bytes(0) {
             if let Err(e) = socket.send(&action(&msg), 0) {
                 println!("Failed to send response: {}", e);
             }
         }
     }
+
+    // `_registration` drops here, deregistering the service from DNS-SD.
+    Ok(())
+}
+
+/// Serialize `val` with this crate's standard msgpack encoding, for a typed RPC payload.
+fn encode<T: Serialize>(val: &T) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut buf = Vec::new();
+    val.serialize(&mut Serializer::new(&mut buf))?;
+    Ok(buf)
+}
+
+/// Deserialize a typed RPC payload encoded by `encode`.
+fn decode<T: DeserializeOwned>(buf: &[u8]) -> Result<T, Box<dyn Error>> {
+    let mut de = Deserializer::new(buf);
+    Ok(Deserialize::deserialize(&mut de)?)
+}
+
+/// Like `run_service_req_rep`, but the action closure exchanges typed values instead of raw
+/// bytes. Each request and response is (de)serialized with this crate's standard msgpack
+/// encoding, matching `Controller::call` on the other end; a single 0mq REQ/REP exchange is
+/// already its own length-delimited message, so no extra framing is needed on top of that.
+/// A request that fails to decode, or a response that fails to encode, logs and replies with an
+/// empty message rather than panicking or hanging the caller's blocking REQ socket forever.
+pub fn run_service_typed<Req, Resp, F>(
+    ctx: Context,
+    name: &str,
+    port: u16,
+    run: RunFlag,
+    mut action: F,
+) -> Result<(), Box<dyn Error>>
+where
+    Req: DeserializeOwned,
+    Resp: Serialize,
+    F: FnMut(Req) -> Resp,
+{
+    run_service_req_rep(ctx, name, port, run, move |buf| {
+        decode::<Req>(buf)
+            .and_then(|req| encode(&action(req)))
+            .unwrap_or_else(|e| {
+                println!("Failed to decode/encode typed RPC message: {}", e);
+                Vec::new()
+            })
+    })
 }
 
 /// Register a vanilla service over DNS-SD.
@@ -183,6 +242,42 @@ impl Controller {
         let response = socket.recv_bytes(0)?;
         Ok(response)
     }
+
+    /// Call a typed RPC method on a service, encoding the request and decoding the response with
+    /// this crate's standard msgpack encoding, so callers exchange typed values instead of
+    /// manually marshalling buffers through `send`.
+    pub fn call<Req: Serialize, Resp: DeserializeOwned>(
+        &self,
+        name: &str,
+        req: &Req,
+    ) -> Result<Resp, Box<dyn Error>> {
+        let response = self.send(name, &encode(req)?)?;
+        decode(&response)
+    }
+
+    /// Send a message to one of the services on this controller, returning the response, but
+    /// give up and return an error if no reply arrives within `timeout`.
+    /// Note that 0mq's REQ/REP state machine expects exactly one reply per request; if this
+    /// times out, the socket is left waiting for the reply that never arrived, so a subsequent
+    /// call against the same service may itself fail until that stale reply (if it ever shows up)
+    /// is drained. Good enough for callers like a synchronized-apply barrier that would rather
+    /// drop a slow or dead service than block the whole operation.
+    pub fn send_with_timeout(
+        &self,
+        name: &str,
+        msg: &[u8],
+        timeout: Duration,
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let services = self.services.lock().unwrap();
+        let socket = match services.get(name) {
+            None => bail!(format!("No service named '{}' available.", name)),
+            Some(socket) => socket,
+        };
+        socket.set_rcvtimeo(timeout.as_millis() as i32)?;
+        socket.send(msg, 0)?;
+        let response = socket.recv_bytes(0)?;
+        Ok(response)
+    }
 }
 
 /// Try to connect a REQ socket at this host and port.
@@ -229,7 +324,7 @@ mod tests {
 
         // Start up the service; return DEADBEEF as a response.
         thread::spawn(move || {
-            run_service_req_rep(Context::new(), name, port, |buffer| {
+            run_service_req_rep(Context::new(), name, port, RunFlag::new(), |buffer| {
                 assert_eq!(testbytes(), buffer);
                 deadbeef()
             })
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::marker::PhantomData;
 
 use anyhow::{bail, Result};
@@ -20,8 +21,16 @@ pub struct PublisherService<T: Serialize> {
 }
 
 impl<T: Serialize> PublisherService<T> {
-    pub fn new(ctx: &Context, name: &str, port: u16) -> Result<Self> {
-        let stop = register_service(name, port)?;
+    /// Advertise a pub/sub service, publishing `metadata` (e.g. protocol version, stream
+    /// capabilities) in its DNS-SD TXT record so subscribers can filter on compatibility before
+    /// connecting.
+    pub fn new(
+        ctx: &Context,
+        name: &str,
+        port: u16,
+        metadata: HashMap<String, String>,
+    ) -> Result<Self> {
+        let stop = register_service(name, port, metadata)?;
         let socket = ctx.socket(zmq::PUB)?;
         let addr = format!("tcp://*:{}", port);
         socket.bind(&addr)?;
@@ -39,6 +48,18 @@ impl<T: Serialize> PublisherService<T> {
         self.socket.send(&self.send_buf, 0)?;
         Ok(())
     }
+
+    /// Send a message as a two-part `[topic, payload]` multipart message, so subscribers can
+    /// filter to just this topic rather than receiving every message this service publishes.
+    /// This lets a single advertised service carry several independently-filterable streams, for
+    /// example one render server publishing a separate stream per physical output or layer.
+    pub fn send_to_topic(&mut self, topic: &[u8], val: &T) -> Result<()> {
+        self.send_buf.clear();
+        val.serialize(&mut Serializer::new(&mut self.send_buf))?;
+        self.socket.send(topic, zmq::SNDMORE)?;
+        self.socket.send(&self.send_buf, 0)?;
+        Ok(())
+    }
 }
 
 impl<T: Serialize> Drop for PublisherService<T> {
@@ -65,7 +86,7 @@ impl<T: DeserializeOwned> SubscriberService<T> {
     /// Connect SUB sockets upon request.
     pub fn new(ctx: Context, name: String) -> Self {
         Self {
-            browser: Browser::new(name, |service| {
+            browser: Browser::new(name, |service, _metadata| {
                 Ok(SubConfig {
                     hostname: service.host_target.clone(),
                     port: service.port,
@@ -1,30 +1,115 @@
 //! Advertise a service over DNS-SD.  Browse for and agglomerate instances of this service.
-//! Interact with one or more instances of this service, using 0mq REQ/REP sockets.
+//! Interact with one or more instances of this service, using a 0mq DEALER socket multiplexed
+//! over a ROUTER, so many requests to the same peer can be outstanding at once instead of the
+//! lockstep one-at-a-time exchange a REQ/REP pair forces.
 
 use async_dnssd::{register_extended, RegisterData, RegisterFlags};
-use simple_error::bail;
+use futures::sync::mpsc;
+use thiserror::Error;
 use tokio_core::reactor::Core;
 
 use zmq::{Context, Socket};
 
-use std::error::Error;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::error::Error as StdError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::bare::{reg_type, Browser};
+use crate::bare::{browse_forever, reg_type};
 
-/// Advertise a service over DNS-SD, using a 0mq REQ/REP socket as the subsequent transport.
-/// Pass each message received on the socket to the action callback.  Send the byte buffer returned
-/// by the action callback back to the requester.
+/// Initial delay before the first reconnect attempt after a service becomes unreachable.
+const INITIAL_RECONNECT_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Cap on how large a single backoff delay may grow to.
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Total time to keep retrying an unreachable service before giving up on it until it reappears
+/// via DNS-SD.
+const RECONNECT_BUDGET: Duration = Duration::from_secs(5 * 60);
+
+/// How long to wait for a response to an outstanding request before giving up on it.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many requests to a single service instance may be outstanding at once. Once this many are
+/// pending, further sends are rejected rather than growing the pending map without bound.
+const MAX_IN_FLIGHT: usize = 64;
+
+/// How long a connection's poll loop waits for a reply before checking for queued outbound
+/// requests again.
+const POLL_TIMEOUT_MS: i64 = 10;
+
+/// Everything that can go wrong discovering, connecting to, or talking to a service through a
+/// `Controller`. Lets a caller match on the failure mode instead of parsing an opaque message, and
+/// lets a show refuse to start cleanly when discovery is unavailable rather than panicking.
+#[derive(Debug, Error)]
+pub enum ServiceError {
+    #[error("failed to start browsing for '{name}' services: {source}")]
+    Discovery {
+        name: String,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    #[error("failed to connect to {host}:{port}: {source}")]
+    Connect {
+        host: String,
+        port: u16,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    #[error("no service named '{name}' is currently available")]
+    NoSuchService { name: String },
+    #[error("failed to send request to '{name}': {source}")]
+    Send {
+        name: String,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    #[error("request to '{name}' timed out after {timeout:?}")]
+    Timeout { name: String, timeout: Duration },
+    #[error("too many requests to '{name}' already in flight ({limit} outstanding)")]
+    TooManyInFlight { name: String, limit: usize },
+}
+
+/// The observed health of a service instance tracked by a `Controller`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Connected and ready to accept requests.
+    Connected,
+    /// Unreachable right now; retrying with exponential backoff.
+    Reconnecting,
+    /// Gave up retrying after exhausting the backoff budget. If the service is rediscovered via
+    /// DNS-SD, connection attempts start over with a fresh budget.
+    Dropped,
+}
+
+/// A service instance's name alongside its current connection health, as reported by
+/// `Controller::list`.
+#[derive(Debug, Clone)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub state: ConnectionState,
+}
+
+/// Advertise a service over DNS-SD, using a 0mq ROUTER socket as the subsequent transport, so
+/// several requests from a peer's multiplexer can be outstanding at once. Each request arrives as
+/// a multipart `[identity, id, payload]` message; the `identity` frame is managed by 0mq, and
+/// `id` must be echoed back verbatim alongside the action's result so the caller can match the
+/// reply to the pending request that sent it.
 pub fn run_service_req_rep<F>(
     ctx: Context,
     name: &str,
     port: u16,
     mut action: F,
-) -> Result<(), Box<dyn Error>>
+) -> Result<(), Box<dyn StdError>>
 where
     F: FnMut(&[u8]) -> Vec<u8>,
 {
     // Open the 0mq socket we'll use to service requests.
-    let socket = ctx.socket(zmq::REP)?;
+    let socket = ctx.socket(zmq::ROUTER)?;
     let addr = format!("tcp://*:{}", port);
     socket.bind(&addr)?;
 
@@ -37,55 +122,445 @@ where
     let _registration = register_extended(&reg_type(name), port, register_data, &core.handle())?;
 
     loop {
-        if let Ok(msg) = socket.recv_bytes(0) {
-            if let Err(e) = socket.send(&action(&msg), 0) {
-                println!("Failed to send response: {}", e);
+        let parts = match socket.recv_multipart(0) {
+            Ok(parts) => parts,
+            Err(e) => {
+                println!("Failed to receive request: {}", e);
+                continue;
             }
+        };
+        if parts.len() != 3 {
+            println!(
+                "Dropped a malformed request with {} parts, expected [identity, id, payload]",
+                parts.len()
+            );
+            continue;
+        }
+        let response = action(&parts[2]);
+        if let Err(e) = socket.send_multipart(
+            [parts[0].as_slice(), parts[1].as_slice(), response.as_slice()],
+            0,
+        ) {
+            println!("Failed to send response: {}", e);
+        }
+    }
+}
+
+/// A request awaiting a reply.
+struct PendingRequest {
+    reply: Sender<Vec<u8>>,
+}
+
+/// One DEALER connection to a service instance, plus the state needed to multiplex many
+/// concurrent requests over it. A dedicated thread owns the socket exclusively and runs `run`,
+/// alternately flushing queued outbound requests and polling for replies; everything else
+/// interacts with the connection only through `pending` and `outbound`.
+struct Connection {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, PendingRequest>>,
+    outbound: Sender<(u64, Vec<u8>)>,
+}
+
+impl Connection {
+    /// Connect a DEALER socket to a service instance and start its poll loop thread.
+    fn open(host: &str, port: u16, ctx: &Context) -> Result<Arc<Self>, ServiceError> {
+        let addr = format!("tcp://{}:{}", host, port);
+        let connect = || -> Result<Socket, zmq::Error> {
+            let socket = ctx.socket(zmq::DEALER)?;
+            socket.connect(&addr)?;
+            Ok(socket)
+        };
+        let socket = connect().map_err(|e| ServiceError::Connect {
+            host: host.to_string(),
+            port,
+            source: Box::new(e),
+        })?;
+
+        let (outbound_send, outbound_recv) = channel();
+        let connection = Arc::new(Self {
+            next_id: AtomicU64::new(0),
+            pending: Mutex::new(HashMap::new()),
+            outbound: outbound_send,
+        });
+
+        let poll_connection = connection.clone();
+        thread::spawn(move || poll_connection.run(socket, outbound_recv));
+
+        Ok(connection)
+    }
+
+    /// Exclusively own `socket`, alternately flushing queued outbound requests and polling for
+    /// replies, until the process exits.
+    fn run(&self, socket: Socket, outbound: Receiver<(u64, Vec<u8>)>) {
+        loop {
+            while let Ok((id, payload)) = outbound.try_recv() {
+                let id_bytes = id.to_le_bytes();
+                if let Err(e) = socket.send_multipart([id_bytes.as_slice(), payload.as_slice()], 0)
+                {
+                    println!("Failed to send request {}: {}", id, e);
+                }
+            }
+
+            let mut items = [socket.as_poll_item(zmq::POLLIN)];
+            if zmq::poll(&mut items, POLL_TIMEOUT_MS).is_err() || !items[0].is_readable() {
+                continue;
+            }
+
+            let parts = match socket.recv_multipart(0) {
+                Ok(parts) => parts,
+                Err(e) => {
+                    println!("Failed to receive reply: {}", e);
+                    continue;
+                }
+            };
+            if parts.len() != 2 {
+                println!(
+                    "Dropped a malformed reply with {} parts, expected [id, payload]",
+                    parts.len()
+                );
+                continue;
+            }
+            let id = match parts[0].as_slice().try_into() {
+                Ok(bytes) => u64::from_le_bytes(bytes),
+                Err(_) => {
+                    println!("Dropped a reply with a malformed id frame");
+                    continue;
+                }
+            };
+            // If the caller already gave up on this request and dropped its receiver, or if the
+            // id doesn't match anything we sent, there's nothing to do with this reply.
+            if let Some(pending) = self.pending.lock().unwrap().remove(&id) {
+                let _ = pending.reply.send(parts[1].clone());
+            }
+        }
+    }
+
+    /// Queue `msg` for sending, returning a receiver that yields the response. If no reply
+    /// arrives within `timeout`, the pending entry is dropped and the receiver disconnects,
+    /// signalling the timeout to the caller.
+    fn send(
+        conn: &Arc<Self>,
+        name: &str,
+        msg: &[u8],
+        timeout: Duration,
+    ) -> Result<Receiver<Vec<u8>>, ServiceError> {
+        let (reply, recv) = channel();
+        let id = {
+            let mut pending = conn.pending.lock().unwrap();
+            if pending.len() >= MAX_IN_FLIGHT {
+                return Err(ServiceError::TooManyInFlight {
+                    name: name.to_string(),
+                    limit: MAX_IN_FLIGHT,
+                });
+            }
+            let id = conn.next_id.fetch_add(1, Ordering::Relaxed);
+            pending.insert(id, PendingRequest { reply });
+            id
+        };
+
+        conn.outbound
+            .send((id, msg.to_vec()))
+            .map_err(|e| ServiceError::Send {
+                name: name.to_string(),
+                source: Box::new(e),
+            })?;
+
+        let timeout_conn = conn.clone();
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            timeout_conn.pending.lock().unwrap().remove(&id);
+        });
+
+        Ok(recv)
+    }
+}
+
+/// Everything we track about one named service instance: where to find it, whatever connection
+/// we've currently got open to it, and the reconnection state machine's progress.
+struct Entry {
+    host: String,
+    port: u16,
+    state: ConnectionState,
+    connection: Option<Arc<Connection>>,
+    /// Delay before the next reconnect attempt, doubling (up to `MAX_RECONNECT_BACKOFF`) each
+    /// time an attempt fails.
+    backoff: Duration,
+    /// When this entry most recently transitioned out of `Connected`, to measure against
+    /// `RECONNECT_BUDGET`.
+    reconnecting_since: Instant,
+}
+
+impl Entry {
+    fn new(host: String, port: u16) -> Self {
+        Self {
+            host,
+            port,
+            state: ConnectionState::Reconnecting,
+            connection: None,
+            backoff: INITIAL_RECONNECT_BACKOFF,
+            reconnecting_since: Instant::now(),
         }
     }
 }
 
 /// Maintain a collection of service instances we can remotely interact with.
-/// Communication is performed via 0mq REQ/REP pairs.
-pub struct Controller(Browser<Socket>);
+/// Communication is multiplexed over 0mq DEALER/ROUTER sockets, so many requests can be
+/// outstanding to the same instance at once rather than one at a time.
+///
+/// A service that deregisters, or whose initial connection attempt fails, isn't dropped outright:
+/// its last-known host and port are kept around and retried with exponential backoff (doubling
+/// from `INITIAL_RECONNECT_BACKOFF` up to `MAX_RECONNECT_BACKOFF`, with jitter so many peers that
+/// drop at once don't all retry in lockstep) until either it reconnects, DNS-SD reports it's back
+/// with fresh connection details, or `RECONNECT_BUDGET` is exhausted and it's marked `Dropped`.
+/// Each service instance backs off independently.
+pub struct Controller {
+    services: Arc<Mutex<HashMap<String, Arc<Mutex<Entry>>>>>,
+    /// Fires the stop signal that lets the browse thread's reactor return and the thread exit,
+    /// rather than running until process exit the way it used to. `None` once `stop` has
+    /// consumed it (or after `Drop` has run).
+    stop: Option<mpsc::Sender<()>>,
+}
 
 impl Controller {
-    /// Start up a new service controller at the given service name.
-    /// Asynchronously browse for new services, and remove them if they deregister.
-    /// For the moment, panic if anything goes wrong during initialization.
-    /// This is acceptable as this action will run once during startup and there's nothing to do
-    /// except bail completely if this process fails.
-    pub fn new(ctx: Context, name: String) -> Self {
-        Self(Browser::new(name, move |service| {
-            req_socket(&service.host_target, service.port, &ctx)
-        }))
-    }
-
-    /// List the services currently available.
-    pub fn list(&self) -> Vec<String> {
-        self.0.list()
-    }
-
-    /// Send a message to one of the services on this controller, returning the response.
-    pub fn send(&self, name: &str, msg: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-        self.0
-            .use_service(name, |socket| {
-                socket.send(msg, 0)?;
-                let response = socket.recv_bytes(0)?;
-                Ok(response)
+    /// Start up a new service controller at the given service name, returning once browsing has
+    /// either started successfully or failed (for example because DNS-SD is unavailable on this
+    /// host), instead of panicking. Asynchronously browses for new services, and begins
+    /// reconnection handling for them if they deregister, for as long as this `Controller` stays
+    /// in scope.
+    pub fn new(ctx: Context, name: String) -> Result<Self, ServiceError> {
+        let services = Arc::new(Mutex::new(HashMap::new()));
+        let (send_started, receive_started) = channel();
+        // Buffer of 1 is enough: `stop` only ever sends a single, idempotent signal.
+        let (stop, stop_received) = mpsc::channel(1);
+
+        let services_appear = services.clone();
+        let ctx_appear = ctx.clone();
+        let services_drop = services.clone();
+        let browse_name = name.clone();
+
+        thread::spawn(move || {
+            browse_forever(
+                &browse_name,
+                send_started,
+                stop_received,
+                move |(service, svc_name)| {
+                    Self::on_service_appear(
+                        &services_appear,
+                        &ctx_appear,
+                        svc_name,
+                        service.host_target,
+                        service.port,
+                    );
+                },
+                move |svc_name| {
+                    Self::on_service_drop(&services_drop, &ctx, svc_name);
+                },
+            );
+        });
+
+        receive_started
+            .recv()
+            .map_err(|e| ServiceError::Discovery {
+                name: name.clone(),
+                source: Box::new(e),
+            })?
+            .map_err(|source| ServiceError::Discovery { name, source })?;
+
+        Ok(Self {
+            services,
+            stop: Some(stop),
+        })
+    }
+
+    /// Stop browsing for new services, idempotently. Already-connected services remain available
+    /// and reconnect attempts already under way carry on, but a service that deregisters after
+    /// this is no longer noticed.
+    pub fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            // The receiving end may already be gone if the browse thread exited on its own
+            // (e.g. a DNS-SD error); either way, there's nothing left to signal.
+            let _ = stop.try_send(());
+        }
+    }
+
+    /// A service instance was discovered (or rediscovered) at `host`:`port`. Record its details
+    /// and kick off a connection attempt, with the backoff clock reset since we just got fresh
+    /// information about it.
+    fn on_service_appear(
+        services: &Arc<Mutex<HashMap<String, Arc<Mutex<Entry>>>>>,
+        ctx: &Context,
+        name: String,
+        host: String,
+        port: u16,
+    ) {
+        let entry = services
+            .lock()
+            .unwrap()
+            .entry(name.clone())
+            .or_insert_with(|| Arc::new(Mutex::new(Entry::new(host.clone(), port))))
+            .clone();
+        {
+            let mut entry = entry.lock().unwrap();
+            entry.host = host;
+            entry.port = port;
+            entry.backoff = INITIAL_RECONNECT_BACKOFF;
+            entry.reconnecting_since = Instant::now();
+        }
+        Self::spawn_reconnect(entry, ctx.clone(), name);
+    }
+
+    /// DNS-SD reported that a service instance deregistered. Rather than discarding what we know
+    /// about it, drop its connection and start retrying against its last-known host and port, on
+    /// the chance this was a transient blip rather than a permanent departure.
+    fn on_service_drop(
+        services: &Arc<Mutex<HashMap<String, Arc<Mutex<Entry>>>>>,
+        ctx: &Context,
+        name: &str,
+    ) {
+        let entry = services.lock().unwrap().get(name).cloned();
+        let Some(entry) = entry else { return };
+        {
+            let mut entry = entry.lock().unwrap();
+            entry.connection = None;
+            entry.state = ConnectionState::Reconnecting;
+            entry.backoff = INITIAL_RECONNECT_BACKOFF;
+            entry.reconnecting_since = Instant::now();
+        }
+        Self::spawn_reconnect(entry, ctx.clone(), name.to_string());
+    }
+
+    /// Attempt to connect to `entry`'s current host and port. On failure, reschedule itself after
+    /// a jittered backoff delay that doubles each time, until `RECONNECT_BUDGET` is exhausted and
+    /// the entry is marked `Dropped` instead.
+    fn spawn_reconnect(entry: Arc<Mutex<Entry>>, ctx: Context, name: String) {
+        thread::spawn(move || loop {
+            let (host, port) = {
+                let entry = entry.lock().unwrap();
+                (entry.host.clone(), entry.port)
+            };
+
+            match Connection::open(&host, port, &ctx) {
+                Ok(connection) => {
+                    let mut entry = entry.lock().unwrap();
+                    entry.connection = Some(connection);
+                    entry.state = ConnectionState::Connected;
+                    println!("Connected to '{name}' at {host}:{port}.");
+                    return;
+                }
+                Err(e) => {
+                    let (backoff, exhausted) = {
+                        let mut entry = entry.lock().unwrap();
+                        let exhausted = entry.reconnecting_since.elapsed() >= RECONNECT_BUDGET;
+                        entry.state = if exhausted {
+                            ConnectionState::Dropped
+                        } else {
+                            ConnectionState::Reconnecting
+                        };
+                        let backoff = entry.backoff;
+                        entry.backoff = (entry.backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                        (backoff, exhausted)
+                    };
+
+                    if exhausted {
+                        println!(
+                            "Giving up on '{name}' after retrying for {:?}: {e}",
+                            RECONNECT_BUDGET
+                        );
+                        return;
+                    }
+
+                    let delay = jittered(backoff);
+                    println!("Failed to connect to '{name}' at {host}:{port}, retrying in {delay:?}: {e}");
+                    thread::sleep(delay);
+                }
+            }
+        });
+    }
+
+    /// List every known service instance and its current connection health.
+    pub fn list(&self) -> Vec<ServiceStatus> {
+        self.services
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, entry)| ServiceStatus {
+                name: name.clone(),
+                state: entry.lock().unwrap().state,
             })
-            .unwrap_or_else(|| bail!(format!("No service named '{}' available.", name)))
+            .collect()
+    }
+
+    /// Send a message to one of the services on this controller, blocking until the response
+    /// arrives or the default timeout expires.
+    pub fn send(&self, name: &str, msg: &[u8]) -> Result<Vec<u8>, ServiceError> {
+        let recv = self.send_async(name, msg)?;
+        recv.recv_timeout(DEFAULT_REQUEST_TIMEOUT)
+            .map_err(|_| ServiceError::Timeout {
+                name: name.to_string(),
+                timeout: DEFAULT_REQUEST_TIMEOUT,
+            })
+    }
+
+    /// Send a message to one of the services on this controller without blocking, returning a
+    /// receiver that yields the response once it arrives, or disconnects if the request times
+    /// out first. This lets many requests to the same (or different) service be outstanding at
+    /// once. Fails if the service isn't currently connected, rather than queuing the request
+    /// against a connection that may not come back for a while.
+    pub fn send_async(&self, name: &str, msg: &[u8]) -> Result<Receiver<Vec<u8>>, ServiceError> {
+        let entry = self.services.lock().unwrap().get(name).cloned();
+        let connection = entry.and_then(|entry| entry.lock().unwrap().connection.clone());
+        let connection = connection.ok_or_else(|| ServiceError::NoSuchService {
+            name: name.to_string(),
+        })?;
+        Connection::send(&connection, name, msg, DEFAULT_REQUEST_TIMEOUT)
+    }
+
+    /// Fan `msg` out to every currently-connected service instance and collect whichever
+    /// responses arrive before the default timeout, keyed by service name. A peer that doesn't
+    /// answer in time is simply absent from the result rather than failing the whole call.
+    pub fn send_all(&self, msg: &[u8]) -> HashMap<String, Vec<u8>> {
+        let pending: Vec<(String, Receiver<Vec<u8>>)> = self
+            .list()
+            .into_iter()
+            .filter(|status| status.state == ConnectionState::Connected)
+            .filter_map(|status| {
+                let recv = self.send_async(&status.name, msg).ok()?;
+                Some((status.name, recv))
+            })
+            .collect();
+
+        pending
+            .into_iter()
+            .filter_map(|(name, recv)| {
+                recv.recv_timeout(DEFAULT_REQUEST_TIMEOUT)
+                    .ok()
+                    .map(|response| (name, response))
+            })
+            .collect()
     }
 }
 
-/// Try to connect a REQ socket at this host and port.
-fn req_socket(host: &str, port: u16, ctx: &Context) -> Result<Socket, Box<dyn Error>> {
-    let addr = format!("tcp://{}:{}", host, port);
+impl Drop for Controller {
+    /// Stop browsing deterministically rather than leaking the browse thread for the life of the
+    /// process. Reconnect threads for already-discovered services are left running, since they
+    /// hold no reference back to the `Controller` and will exit on their own once their backoff
+    /// budget is exhausted.
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
 
-    // Connect a REQ socket.
-    let socket = ctx.socket(zmq::REQ)?;
-    socket.connect(&addr)?;
-    Ok(socket)
+/// Add up to ±20% jitter to a backoff delay, derived from the low bits of the current time, so
+/// many peers that start backing off at the same moment don't all retry in lockstep.
+fn jittered(backoff: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let jitter_percent = (nanos % 41) as i64 - 20;
+    let millis = backoff.as_millis() as i64;
+    let jittered_millis = (millis + millis * jitter_percent / 100).max(0);
+    Duration::from_millis(jittered_millis as u64)
 }
 
 #[cfg(test)]
@@ -113,7 +588,7 @@ mod tests {
         let name = "test";
         let port = 10000;
 
-        let controller = Controller::new(Context::new(), name.to_string());
+        let controller = Controller::new(Context::new(), name.to_string()).unwrap();
 
         // Wait a moment, and assert that we can't see any services.
         sleep(500);
@@ -132,12 +607,40 @@ mod tests {
         // Give the service a moment to get situated.
         sleep(2000);
 
-        let names = controller.list();
-        assert_eq!(1, names.len());
+        let services = controller.list();
+        assert_eq!(1, services.len());
 
         // Test sending a message.
-        let response = controller.send(&names[0], &testbytes()).unwrap();
+        let response = controller.send(&services[0].name, &testbytes()).unwrap();
 
         assert_eq!(deadbeef(), response);
     }
+
+    /// Test that many concurrent requests to the same service instance all get matched up with
+    /// the right reply.
+    #[test]
+    fn test_concurrent_requests() {
+        let name = "test_concurrent";
+        let port = 10001;
+
+        let controller = Controller::new(Context::new(), name.to_string()).unwrap();
+
+        thread::spawn(move || {
+            run_service_req_rep(Context::new(), name, port, |buffer| buffer.to_vec()).unwrap();
+        });
+
+        sleep(2000);
+
+        let services = controller.list();
+        assert_eq!(1, services.len());
+
+        let pending: Vec<_> = (0u8..16)
+            .map(|i| (i, controller.send_async(&services[0].name, &[i]).unwrap()))
+            .collect();
+
+        for (i, recv) in pending {
+            let response = recv.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(vec![i], response);
+        }
+    }
 }